@@ -0,0 +1,252 @@
+//! Responsible for the admin HTTP server, exposing application internals
+//! such as Prometheus metrics to operators, and operations such as model rollouts
+
+use anyhow::{Result, Context};
+use axum::{Router, Json, routing::{get, post}};
+use axum::extract::{Path, Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use prometheus::{Encoder, TextEncoder};
+use serde::Deserialize;
+use std::sync::atomic::Ordering;
+
+// Custom modules
+use crate::inference;
+use crate::source;
+use crate::utils::config::{AppConfig, InferenceModelType};
+use crate::utils::metrics;
+use crate::utils::logging;
+use crate::utils::hot_reload;
+
+/// Starts the admin HTTP server in the background
+pub async fn init_admin_server(app_config: &AppConfig) -> Result<()> {
+    let admin_config = app_config.admin_config();
+    if !admin_config.enabled {
+        tracing::info!("Admin server is disabled");
+        return Ok(())
+    }
+
+    let address = format!("{}:{}", admin_config.host, admin_config.port);
+    let listener = tokio::net::TcpListener::bind(&address)
+        .await
+        .context(format!("Error binding admin server to {}", address))?;
+
+    // Operations changing the application, and the configuration, require the token when configured
+    let protected = Router::new()
+        .route("/config", get(get_config))
+        .route("/models/{model_type}/load", post(load_model))
+        .route("/models/{model_type}/unload", post(unload_model))
+        .route("/models/{model_type}/swap", post(swap_model))
+        .route("/logging/level", post(set_log_level))
+        .route_layer(middleware::from_fn_with_state(admin_config.token.clone(), require_token));
+
+    let router = Router::new()
+        .route("/metrics", get(get_metrics))
+        .route("/stats/sources/{source_id}/recent", get(get_recent_results))
+        .merge(protected);
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            tracing::error!(
+                error=e.to_string(),
+                "Admin server stopped"
+            );
+        }
+    });
+
+    tracing::info!(address=address, "Started admin server");
+
+    Ok(())
+}
+
+/// Rejects requests without the configured bearer token
+async fn require_token(State(token): State<Option<String>>, request: Request, next: Next) -> Response {
+    if !is_authorized(request.headers(), token.as_deref()) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid admin token"
+        ).into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Returns whether the headers carry the given bearer token, any request is authorized without a token
+fn is_authorized(headers: &HeaderMap, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| tokens_match(given, token))
+}
+
+/// Compares tokens without stopping at the first difference, so response times do not reveal matching prefixes
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given
+        .bytes()
+        .zip(expected.bytes())
+        .fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// Returns all application metrics in Prometheus text format
+async fn get_metrics() -> Response {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+
+    match encoder.encode(&metrics::REGISTRY.gather(), &mut buffer) {
+        Ok(_) => (
+            [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+            buffer
+        ).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string()
+        ).into_response()
+    }
+}
+
+/// Returns the effective configuration with secrets redacted, along with the origin of every value
+async fn get_config() -> Response {
+    let effective_config = hot_reload::get_current_config()
+        .and_then(|app_config| app_config.effective_config());
+
+    match effective_config {
+        Ok(effective_config) => Json(effective_config).into_response(),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("{:#}", e)
+        ).into_response()
+    }
+}
+
+/// Returns summaries of the most recent results of a source, oldest first
+async fn get_recent_results(Path(source_id): Path<String>) -> Response {
+    match source::recent_results(&source_id).await {
+        Ok(results) => Json(results.iter().map(|result| result.as_ref()).collect::<Vec<_>>()).into_response(),
+        Err(e) => (
+            StatusCode::NOT_FOUND,
+            format!("{:#}", e)
+        ).into_response()
+    }
+}
+
+/// Body of a model swap request. Fields not given are taken from the current model
+#[derive(Deserialize)]
+struct SwapModelRequest {
+    name: Option<String>,
+    version: Option<u32>,
+    #[serde(default = "SwapModelRequest::default_unload_previous")]
+    unload_previous: bool
+}
+
+impl SwapModelRequest {
+    fn default_unload_previous() -> bool {
+        true
+    }
+}
+
+/// Body of a log level change, the level or RUST_LOG style directives
+#[derive(Deserialize)]
+struct LogLevelRequest {
+    level: String
+}
+
+/// Changes the log level of the application until the next restart
+async fn set_log_level(Json(request): Json<LogLevelRequest>) -> Response {
+    match logging::set_log_level(&request.level) {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            format!("{:#}", e)
+        ).into_response()
+    }
+}
+
+/// Converts the result of an admin operation into a response
+fn operation_response(result: Result<()>) -> Response {
+    match result {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("{:#}", e)
+        ).into_response()
+    }
+}
+
+/// Loads instances of the current model of a given type
+async fn load_model(Path(model_type): Path<InferenceModelType>) -> Response {
+    let result = async {
+        let model = inference::get_inference_model(model_type)?;
+        model.load_model(inference::MODEL_INSTANCES.load(Ordering::Relaxed)).await
+    }.await;
+
+    operation_response(result)
+}
+
+/// Unloads instances of the current model of a given type
+async fn unload_model(Path(model_type): Path<InferenceModelType>) -> Response {
+    let result = async {
+        let model = inference::get_inference_model(model_type)?;
+        model.unload_model().await
+    }.await;
+
+    operation_response(result)
+}
+
+/// Loads a new model for a given type and switches inference to it without downtime
+async fn swap_model(
+    Path(model_type): Path<InferenceModelType>,
+    Json(request): Json<SwapModelRequest>
+) -> Response {
+    let result = async {
+        let mut model_config = inference::get_inference_model(model_type.clone())?
+            .model_config()
+            .clone();
+
+        if let Some(name) = request.name {
+            model_config.name = name;
+        }
+        if let Some(version) = request.version {
+            model_config.version = version;
+        }
+
+        inference::swap_inference_model(model_type, model_config, request.unload_previous).await
+    }.await;
+
+    operation_response(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_str(authorization).unwrap());
+        headers
+    }
+
+    #[test]
+    fn authorizes_matching_bearer_token() {
+        assert!(is_authorized(&headers("Bearer secret"), Some("secret")));
+    }
+
+    #[test]
+    fn rejects_missing_or_wrong_token() {
+        assert!(!is_authorized(&HeaderMap::new(), Some("secret")));
+        assert!(!is_authorized(&headers("Bearer secreT"), Some("secret")));
+        assert!(!is_authorized(&headers("Bearer secret2"), Some("secret")));
+        assert!(!is_authorized(&headers("secret"), Some("secret")));
+    }
+
+    #[test]
+    fn authorizes_any_request_without_token() {
+        assert!(is_authorized(&HeaderMap::new(), None));
+    }
+}
@@ -2,15 +2,62 @@ use anyhow::{Result, Context};
 use libloading::{Library, Symbol};
 use libc::{c_int, c_ulonglong, c_char, c_void};
 use std::slice;
-use once_cell::sync::OnceCell;
-use std::sync::Arc;
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use serde_json::json;
 use std::ffi::CString;
+use std::path::PathBuf;
 
 // Custom modules
 use crate::source;
-use crate::utils::config::AppConfig;
-use crate::processing::{RawFrame, ResultBBOX};
+use crate::utils::config::{AppConfig, ColorOrder, OutputFormat, SourceConfig, StreamConfig};
+use crate::utils::kafka;
+use crate::processing::{self, RawFrame, ResultBBOX, FrameResults};
+
+/// File name of the video client library
+const LIBRARY_NAME: &str = "libclient_video.so";
+
+/// Path used when the library is not found in any of the searched locations
+const LIBRARY_FALLBACK_PATH: &str = "secrets/libclient_video.so";
+
+/// Environment variable pointing to the video client library
+const LIBRARY_PATH_ENV: &str = "CLIENT_VIDEO_LIB_PATH";
+
+/// Searches for a dynamic library in the standard locations, returning the first existing path
+/// 
+/// Locations are checked in the following order:
+/// 1. Path given in the `CLIENT_VIDEO_LIB_PATH` environment variable
+/// 2. Directory of the current executable
+/// 3. `secrets/` directory next to the executable's parent directory
+/// 4. System library directories - /usr/lib, /usr/local/lib
+pub fn search_library_paths(name: &str) -> Option<PathBuf> {
+    let env_path = std::env::var(LIBRARY_PATH_ENV).ok().map(PathBuf::from);
+    let exe_dir = std::env::current_exe().ok().and_then(|exe| exe.parent().map(PathBuf::from));
+
+    library_path_candidates(name, env_path, exe_dir)
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+/// Returns the locations searched for the library, in the order of `search_library_paths`
+fn library_path_candidates(name: &str, env_path: Option<PathBuf>, exe_dir: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    candidates.extend(env_path);
+
+    if let Some(exe_dir) = exe_dir {
+        candidates.push(exe_dir.join(name));
+
+        if let Some(parent_dir) = exe_dir.parent() {
+            candidates.push(parent_dir.join("secrets").join(name));
+        }
+    }
+
+    candidates.push(PathBuf::from("/usr/lib").join(name));
+    candidates.push(PathBuf::from("/usr/local/lib").join(name));
+
+    candidates
+}
 
 /// Client as static global variable
 pub static CLIENT_VIDEO: OnceCell<Arc<ClientVideo>> = OnceCell::new();
@@ -24,14 +71,104 @@ pub fn get_client_video() -> Result<&'static Arc<ClientVideo>> {
     })
 }
 
+/// Integer ids of sources in the video library, translated from configured ids at the FFI boundary
+pub static SOURCE_IDS: Lazy<RwLock<SourceIdMap>> = Lazy::new(|| RwLock::new(SourceIdMap::default()));
+
+/// Bidirectional map between configured source ids and the integer ids of the video library
+///
+/// Library ids are ids of videos in the streaming backend - the `video_id` of a source, or its id when numeric.
+/// Registered ids are kept while the source streams, so they stay stable across configuration reloads
+#[derive(Default)]
+pub struct SourceIdMap {
+    to_library: HashMap<String, c_int>,
+    from_library: HashMap<c_int, String>
+}
+
+impl SourceIdMap {
+    /// Registers the library id of a streamed source, failing when it conflicts with registered sources
+    pub fn register(&mut self, source_id: &str, video_id: Option<i32>) -> Result<c_int> {
+        let library_id = match video_id {
+            Some(video_id) => video_id,
+            None => source_id.parse::<c_int>()
+                .with_context(|| format!("Source {} does not have a numeric id, its video_id must be set", source_id))?
+        };
+
+        if let Some(&registered) = self.to_library.get(source_id) {
+            if registered != library_id {
+                anyhow::bail!("Video id of source {} cannot change from {} to {} while it streams", source_id, registered, library_id);
+            }
+            return Ok(library_id);
+        }
+        if let Some(other) = self.from_library.get(&library_id) {
+            anyhow::bail!("Video {} of source {} is already streamed by source {}", library_id, source_id, other);
+        }
+
+        self.to_library.insert(source_id.to_string(), library_id);
+        self.from_library.insert(library_id, source_id.to_string());
+
+        if library_id.to_string() != source_id {
+            tracing::info!(source_id=source_id, video_id=library_id, "Mapped source to video of the streaming backend");
+        }
+
+        Ok(library_id)
+    }
+
+    /// Removes the library id of a source that stopped streaming
+    pub fn unregister(&mut self, source_id: &str) {
+        if let Some(library_id) = self.to_library.remove(source_id) {
+            self.from_library.remove(&library_id);
+        }
+    }
+
+    /// Returns the library id of a source - its registered id, or its id when numeric
+    pub fn library_id(&self, source_id: &str) -> Result<c_int> {
+        match self.to_library.get(source_id) {
+            Some(&library_id) => Ok(library_id),
+            None => source_id.parse::<c_int>()
+                .with_context(|| format!("Source {} is not streamed by the video client", source_id))
+        }
+    }
+
+    /// Returns the configured id of a library source id, the id itself when it was never registered
+    pub fn source_id(&self, library_id: c_int) -> String {
+        self.from_library
+            .get(&library_id)
+            .cloned()
+            .unwrap_or_else(|| library_id.to_string())
+    }
+}
+
+/// Registers the library id of a streamed source, see `SourceIdMap`
+pub fn register_source_id(source_id: &str, source_config: &SourceConfig) -> Result<c_int> {
+    SOURCE_IDS.write().unwrap().register(source_id, source_config.video_id)
+}
+
+/// Returns the library id of a source, see `SourceIdMap`
+pub fn library_source_id(source_id: &str) -> Result<c_int> {
+    SOURCE_IDS.read().unwrap().library_id(source_id)
+}
+
+/// Returns the configured id of a library source id, see `SourceIdMap`
+pub fn configured_source_id(library_id: c_int) -> String {
+    SOURCE_IDS.read().unwrap().source_id(library_id)
+}
+
 // C Types
 pub type SourceFramesCb = extern "C" fn(source_id: c_int, frame: *const u8, width: c_int, height: c_int, pts: c_ulonglong);
 pub type SourceStoppedCb = extern "C" fn(source_id: c_int);
 pub type SourceNameCb = extern "C" fn(source_id: c_int, source_name: *const c_char);
 pub type SourceStatusCb = extern "C" fn(source_id: c_int, source_status: c_int);
+pub type SourceInfoCb = extern "C" fn(source_id: c_int, info_json: *const c_char);
+pub type SetSourceInfoCallbackFn = extern "C" fn(source_info: SourceInfoCb) -> c_int;
+pub type SourceSubFramesCb = extern "C" fn(source_id: c_int, sub_stream: c_int, frame: *const u8, width: c_int, height: c_int, pts: c_ulonglong);
+pub type SetSubStreamFramesCallbackFn = extern "C" fn(source_sub_frames: SourceSubFramesCb) -> c_int;
 pub type InitMultipleSourcesFn = extern "C" fn(source_ids: *const c_int, size: c_int, log_level: c_int);
 pub type PostResultsFn = extern "C" fn(source_id: c_int, result_json: *const c_char) -> c_int;
 pub type FreeCPtrFn = extern "C" fn(ptr: *const c_void);
+pub type SetStreamOptionsFn = extern "C" fn(source_id: c_int, options_json: *const c_char) -> c_int;
+pub type SetStreamConfigFn = extern "C" fn(config_json: *const c_char) -> c_int;
+pub type ReinitSourceFn = extern "C" fn(source_id: c_int) -> c_int;
+pub type StopSourceFn = extern "C" fn(source_id: c_int) -> c_int;
 pub type SetCallbacksFn = extern "C" fn(
     source_frames: SourceFramesCb,
     source_stopped: SourceStoppedCb,
@@ -54,6 +191,8 @@ pub enum SourceStatus {
     NotFound = 2,
     ConnectionError = 3,
     DecodeError = 4,
+    CircuitOpen = 5,
+    Stalled = 6,
 }
 
 pub struct ClientVideo {
@@ -62,9 +201,19 @@ pub struct ClientVideo {
 
 impl ClientVideo {
     pub fn new() -> Result<Self> {
+        // Resolve library location
+        let library_path = search_library_paths(LIBRARY_NAME)
+            .unwrap_or_else(|| PathBuf::from(LIBRARY_FALLBACK_PATH));
+
+        tracing::info!(
+            path=library_path.display().to_string(),
+            "resolved video client library path"
+        );
+
         // Load dynamic library
         let library = unsafe {
-            Library::new("secrets/libclient_video.so")?
+            Library::new(&library_path)
+                .with_context(|| format!("Error loading video client library from {}", library_path.display()))?
         };
 
         Ok(
@@ -90,7 +239,27 @@ impl ClientVideo {
                     ClientVideo::_source_stopped_callback,
                     ClientVideo::_source_name_callback,
                     ClientVideo::_source_status_callback
-                )
+                );
+
+                // Older libraries do not report stream information
+                match client_video.library().get::<SetSourceInfoCallbackFn>(b"SetSourceInfoCallback") {
+                    Ok(lib_set_source_info_callback) => {
+                        if lib_set_source_info_callback(ClientVideo::_source_info_callback) != 0 {
+                            anyhow::bail!("Video client rejected the source info callback");
+                        }
+                    },
+                    Err(_) => tracing::info!("Video client does not report source stream info")
+                }
+
+                // Older libraries decode only the best stream of a container
+                match client_video.library().get::<SetSubStreamFramesCallbackFn>(b"SetSubStreamFramesCallback") {
+                    Ok(lib_set_sub_stream_frames_callback) => {
+                        if lib_set_sub_stream_frames_callback(ClientVideo::_source_sub_frames_callback) != 0 {
+                            anyhow::bail!("Video client rejected the sub-stream frames callback");
+                        }
+                    },
+                    Err(_) => tracing::info!("Video client does not decode sub-streams")
+                }
             }
 
             Ok(())
@@ -101,16 +270,61 @@ impl ClientVideo {
         Ok(())
     }
 
+    /// Sets the stream configuration of the library, must be called before sources are initiated
+    pub async fn set_stream_config(app_config: &AppConfig) -> Result<()> {
+        let client_video = get_client_video()?;
+        let color_order = app_config.inference_config().color_order;
+        let stream_config = app_config.stream_config().clone();
+
+        let mut config_json = serde_json::to_value(&stream_config)
+            .context("Error serializing stream config")?;
+        config_json["color_order"] = json!(color_order.to_string());
+        let config_json = CString::new(config_json.to_string())
+            .context("Error converting stream config to C string")?;
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            unsafe {
+                let lib_set_stream_config: Symbol<SetStreamConfigFn> = match client_video.library().get(b"SetStreamConfig") {
+                    Ok(symbol) => symbol,
+                    // Older libraries always deliver RGB frames with default stream settings
+                    Err(_) if color_order == ColorOrder::Rgb && stream_config == StreamConfig::default() => return Ok(()),
+                    Err(e) => return Err(e).context("Cannot get 'SetStreamConfig' function, required for BGR frames and stream_config")
+                };
+
+                if lib_set_stream_config(config_json.as_ptr()) != 0 {
+                    anyhow::bail!("Failed to set stream config")
+                }
+            }
+
+            Ok(())
+        }).await
+            .context("Error trying to set stream config in video client")?
+            .context("Error setting stream config in video client")?;
+
+        Ok(())
+    }
+
     pub async fn init_sources(app_config: &AppConfig) -> Result<()> {
         let client_video = get_client_video()?;
 
-        // Get sources ids
-        let source_ids: Vec<c_int> = app_config.sources_config().sources
-            .keys()
-            .filter_map(|k| k.parse::<c_int>().ok())
+        // Get sources ids - shared memory and sub-stream sources are not streamed by the video client.
+        // Ids are assigned in order of the configured ids, so assignments are deterministic
+        let sources = &app_config.sources_config().sources;
+        let mut streamed: Vec<(&String, &SourceConfig)> = sources
+            .iter()
+            .filter(|(_, source_config)| source_config.is_streamed())
             .collect();
+        streamed.sort_by_key(|(source_id, _)| *source_id);
+
+        let source_ids: Vec<c_int> = streamed
+            .into_iter()
+            .map(|(source_id, source_config)| register_source_id(source_id, source_config))
+            .collect::<Result<_>>()?;
 
         if source_ids.len() == 0 {
+            if sources.values().any(|source_config| source_config.shared_memory.is_some()) {
+                return Ok(());
+            }
             anyhow::bail!("No valid sources are avaliable");
         }
 
@@ -136,7 +350,14 @@ impl ClientVideo {
         Ok(())
     }
     
-    pub fn populate_bboxes(source_id: &str, frame: &RawFrame, bboxes: &[ResultBBOX]) -> Result<()> {
+    pub fn populate_bboxes(source_id: &str, frame: &RawFrame, bboxes: &[ResultBBOX], format: OutputFormat) -> Result<()> {
+        if format == OutputFormat::Coco {
+            let detections = bboxes
+                .iter()
+                .map(|bbox| ClientVideo::coco_detection(frame.pts, &bbox.bbox, bbox.class, bbox.score));
+            return ClientVideo::post_results(source_id, ClientVideo::coco_json(detections));
+        }
+
         // Format BBOXes output for sending it back to the client
         let bboxes_json: Vec<_> = bboxes
             .iter()
@@ -159,13 +380,145 @@ impl ClientVideo {
             "bboxes": bboxes_json
         }).to_string();
 
+        ClientVideo::post_results(source_id, bboxes_result_json)
+    }
+
+    /// Sends combined frame results back to the client.
+    /// Embeddings themselves are not sent, only their references in the frame results
+    pub fn populate_results(frame: &RawFrame, results: &FrameResults, format: OutputFormat) -> Result<()> {
+        if format == OutputFormat::Coco {
+            let detections = results.detections
+                .iter()
+                .map(|detection| ClientVideo::coco_detection(results.pts, &detection.bbox, detection.class, detection.score));
+            return ClientVideo::post_results(&results.source_id, ClientVideo::coco_json(detections));
+        }
+
+        let bboxes_json: Vec<_> = results.detections
+            .iter()
+            .map(|detection| {
+                let bbox = ResultBBOX {
+                    bbox: detection.bbox,
+                    class: detection.class,
+                    score: detection.score
+                };
+                let (top_left_corner, bottom_right_corner) = bbox.corners_coordinates(frame);
 
+                json!({
+                    "pts": results.pts,
+                    "top_left_corner": top_left_corner,
+                    "bottom_right_corner": bottom_right_corner,
+                    "class_name": detection.class_name,
+                    "confidence": detection.score,
+                    "detection_index": detection.detection_index,
+                    "embedding_index": detection.embedding_index
+                })
+            })
+            .collect();
+
+        let results_json = json!({
+            "stream_id": results.source_id,
+            "bboxes": bboxes_json
+        }).to_string();
+
+        ClientVideo::post_results(&results.source_id, results_json)
+    }
+
+    /// Formats a single detection as a COCO result
+    fn coco_detection(pts: u64, bbox: &[f32; 4], class: u32, score: f32) -> serde_json::Value {
+        json!({
+            "image_id": pts,
+            "category_id": processing::coco_category_id(class),
+            "bbox": processing::coco_bbox(bbox),
+            "score": score
+        })
+    }
+
+    /// Formats detections as a COCO results list
+    fn coco_json(detections: impl Iterator<Item = serde_json::Value>) -> String {
+        serde_json::Value::Array(detections.collect()).to_string()
+    }
+
+    /// Starts streaming a source added after sources were initiated
+    pub async fn start_source(source_id: &str, source_config: &SourceConfig) -> Result<()> {
+        let client_video = get_client_video()?;
+        let start_source_id = register_source_id(source_id, source_config)?;
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            unsafe {
+                let lib_reinit_source: Symbol<ReinitSourceFn> = client_video.library()
+                    .get(b"ReinitSource")
+                    .context("Cannot get 'ReinitSource' function")?;
+
+                if lib_reinit_source(start_source_id) != 0 {
+                    anyhow::bail!("Failed to start source")
+                }
+            }
+
+            Ok(())
+        }).await
+            .context("Error trying to start source in video client")?
+            .context("Error starting source in video client")?;
+
+        Ok(())
+    }
+
+    /// Stops streaming a source
+    pub async fn stop_source(source_id: &str) -> Result<()> {
+        let client_video = get_client_video()?;
+        let stop_source_id = library_source_id(source_id)?;
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            unsafe {
+                let lib_stop_source: Symbol<StopSourceFn> = client_video.library()
+                    .get(b"StopSource")
+                    .context("Cannot get 'StopSource' function")?;
+
+                if lib_stop_source(stop_source_id) != 0 {
+                    anyhow::bail!("Failed to stop source")
+                }
+            }
+
+            Ok(())
+        }).await
+            .context("Error trying to stop source in video client")?
+            .context("Error stopping source in video client")?;
+
+        // Video of a removed source may be streamed by a source added later
+        SOURCE_IDS.write().unwrap().unregister(source_id);
+
+        Ok(())
+    }
+
+    /// Sets the frame delivery divisor of a source - deliver every Nth frame
+    /// 
+    /// Returns false when the library does not support stream options
+    pub fn set_stream_options(source_id: &str, deliver_every_n: u32) -> Result<bool> {
+        let client_video = get_client_video()?;
+        let options_json = CString::new(json!({ "deliver_every_n": deliver_every_n }).to_string())
+            .context("Error converting stream options to C string")?;
+        let options_source_id = library_source_id(source_id)?;
+
+        unsafe {
+            let lib_set_stream_options: Symbol<SetStreamOptionsFn> = match client_video.library().get(b"SetStreamOptions") {
+                Ok(symbol) => symbol,
+                Err(_) => return Ok(false)
+            };
+
+            if lib_set_stream_options(options_source_id, options_json.as_ptr()) != 0 {
+                anyhow::bail!("Failed to set stream options")
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Posts results JSON of a source to the client library
+    fn post_results(source_id: &str, results_json: String) -> Result<()> {
         // Send back to client
         let client_video = get_client_video()?;
-        let results_bboxes = CString::new(bboxes_result_json)
+        let results_bboxes = CString::new(results_json)
             .context("Error converting bboxes to C string")?;
-        let results_source_id = source_id.parse::<c_int>()
-            .expect("Failed to convert source id to integer");
+        let results_source_id = library_source_id(source_id)?;
 
         unsafe {
             let lib_post_results: Symbol<PostResultsFn> = client_video.library()
@@ -195,7 +548,31 @@ impl ClientVideo {
         height: c_int,
         pts: c_ulonglong,
     ) {
-        let source_id = source_id.to_string();
+        let source_id = configured_source_id(source_id);
+        ClientVideo::process_frame(source_id, None, frame, width, height, pts);
+    }
+
+    extern "C" fn _source_sub_frames_callback(
+        source_id: c_int,
+        sub_stream: c_int,
+        frame: *const u8,
+        width: c_int,
+        height: c_int,
+        pts: c_ulonglong,
+    ) {
+        let source_id = configured_source_id(source_id);
+        ClientVideo::process_frame(source_id, Some(sub_stream as u32), frame, width, height, pts);
+    }
+
+    /// Processes a frame of a source, or of the source reading one of its sub-streams
+    fn process_frame(
+        source_id: String,
+        sub_stream: Option<u32>,
+        frame: *const u8,
+        width: c_int,
+        height: c_int,
+        pts: c_ulonglong,
+    ) {
         let width = width as u32;
         let height = height as u32;
         let frame_size = (width * height * 3) as usize;
@@ -205,7 +582,21 @@ impl ClientVideo {
         if let Ok(rgb_frame) = ClientVideo::get_c_array(frame, frame_size) {
             if let Ok(runtime) = crate::get_tokio_runtime() {
                 runtime.spawn(async move {
-                    match source::get_source_processor(&source_id).await {
+                    let processor = match sub_stream {
+                        Some(index) => source::get_sub_stream_processor(&source_id, index).await,
+                        None => source::get_source_processor(&source_id).await
+                    };
+
+                    match processor {
+                        // Sub-streams no source reads are decoded anyway
+                        Err(e) if sub_stream.is_some() => {
+                            tracing::debug!(
+                                error=e.to_string(),
+                                source_id=source_id,
+                                sub_stream=sub_stream,
+                                "Sub-stream frame is not read by any source"
+                            )
+                        },
                         Err(e) => {
                             tracing::error!(
                                 error=e.to_string(),
@@ -235,13 +626,25 @@ impl ClientVideo {
 
     }
     extern "C" fn _source_stopped_callback(source_id: c_int) {
+        let source_id = configured_source_id(source_id);
         tracing::info!(
             source_id=source_id, 
             "Source stopped!"
         );
+
+        // Detections of a restarted source start a new heatmap
+        if let Ok(runtime) = crate::get_tokio_runtime() {
+            runtime.spawn(async move {
+                let _ = source::reset_heatmap(&source_id).await;
+
+                // Publish results batched for the source
+                let _ = kafka::flush_result_batches(Some(&source_id)).await;
+            });
+        }
     }
 
     extern "C" fn _source_name_callback(source_id: c_int, source_name: *const c_char) {
+        let source_id = configured_source_id(source_id);
         let source_name = ClientVideo::get_c_string(source_name)
             .unwrap_or("UNKNOWN".to_string());
 
@@ -252,13 +655,44 @@ impl ClientVideo {
         );
     }
 
+    extern "C" fn _source_info_callback(source_id: c_int, info_json: *const c_char) {
+        let source_id = configured_source_id(source_id);
+        let stream_info = ClientVideo::get_c_string(info_json)
+            .and_then(|info_json| serde_json::from_str::<source::StreamInfo>(&info_json).context("Error parsing stream info"));
+
+        let stream_info = match stream_info {
+            Ok(stream_info) => stream_info,
+            Err(e) => {
+                tracing::warn!(source_id=source_id, error=format!("{:#}", e), "Got invalid source stream info");
+                return;
+            }
+        };
+
+        tracing::info!(
+            source_id=source_id,
+            width=stream_info.width,
+            height=stream_info.height,
+            fps=stream_info.fps,
+            "Got source stream info"
+        );
+
+        if let Ok(runtime) = crate::get_tokio_runtime() {
+            runtime.spawn(async move {
+                let _ = source::set_stream_info(&source_id, stream_info).await;
+            });
+        }
+    }
+
     extern "C" fn _source_status_callback(source_id: c_int, source_status: c_int) {
+        let source_id = configured_source_id(source_id);
         let source_status = match source_status {
             0 => "OK - Stream is active",
             1 => "ERROR - Not streaming",
             2 => "ERROR - Source not found",
             3 => "ERROR - Connection error",
             4 => "ERROR - Decode error",
+            5 => "ERROR - Source gave up after repeated failures",
+            6 => "ERROR - Stream stopped delivering frames",
             _ => "UNKNOWN status",
         };
 
@@ -326,4 +760,123 @@ impl ClientVideo {
     fn library(&self) -> &Library {
         return &self.library
     }
-}
\ No newline at end of file
+}
+
+/// Writes the detection heatmap of a source to a raw f32 file, returns 0 on success and -1 otherwise
+///
+/// Must not be called from within the application runtime, as it blocks until the heatmap is written
+#[unsafe(no_mangle)]
+pub extern "C" fn ExportHeatmap(source_id: c_int, path: *const c_char) -> c_int {
+    let source_id = configured_source_id(source_id);
+    if path.is_null() {
+        tracing::error!(source_id=source_id, "ExportHeatmap: null path pointer");
+        return -1;
+    }
+
+    let path = unsafe {
+        std::ffi::CStr::from_ptr(path)
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    let result = crate::get_tokio_runtime()
+        .and_then(|runtime| runtime.block_on(source::export_heatmap(&source_id, &path)));
+
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            tracing::error!(
+                error=format!("{:#}", e),
+                source_id=source_id,
+                path=path,
+                "Error exporting detection heatmap"
+            );
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn searches_library_paths_in_order() {
+        let root = std::env::temp_dir().join(format!("client-library-search-{}", std::process::id()));
+        let exe_dir = root.join("bin");
+        std::fs::create_dir_all(&exe_dir).unwrap();
+        std::fs::create_dir_all(root.join("secrets")).unwrap();
+
+        let name = "libclient_video_search_test.so";
+        let find = |env_path: Option<PathBuf>| {
+            library_path_candidates(name, env_path, Some(exe_dir.clone()))
+                .into_iter()
+                .find(|path| path.is_file())
+        };
+
+        let not_found = find(None);
+
+        std::fs::write(root.join("secrets").join(name), "").unwrap();
+        let in_secrets = find(None);
+
+        std::fs::write(exe_dir.join(name), "").unwrap();
+        let next_to_exe = find(None);
+
+        std::fs::write(root.join("custom.so"), "").unwrap();
+        let from_env = find(Some(root.join("custom.so")));
+        let missing_env = find(Some(root.join("missing.so")));
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(not_found, None);
+        assert_eq!(in_secrets, Some(root.join("secrets").join(name)));
+        assert_eq!(next_to_exe, Some(exe_dir.join(name)));
+        assert_eq!(from_env, Some(root.join("custom.so")));
+        assert_eq!(missing_env, Some(exe_dir.join(name)));
+    }
+
+    #[test]
+    fn maps_numeric_and_named_sources() {
+        let mut source_ids = SourceIdMap::default();
+
+        assert_eq!(source_ids.register("7", None).unwrap(), 7);
+        assert_eq!(source_ids.register("lobby-east", Some(12)).unwrap(), 12);
+        assert!(source_ids.register("lobby-west", None).is_err());
+
+        assert_eq!(source_ids.library_id("7").unwrap(), 7);
+        assert_eq!(source_ids.library_id("lobby-east").unwrap(), 12);
+        assert!(source_ids.library_id("lobby-west").is_err());
+
+        assert_eq!(source_ids.source_id(7), "7");
+        assert_eq!(source_ids.source_id(12), "lobby-east");
+        assert_eq!(source_ids.source_id(99), "99");
+    }
+
+    #[test]
+    fn mapping_is_stable_across_reload() {
+        let mut source_ids = SourceIdMap::default();
+        source_ids.register("7", None).unwrap();
+        source_ids.register("lobby-east", Some(12)).unwrap();
+
+        // A reload registers the remaining sources again, along with added ones
+        assert_eq!(source_ids.register("lobby-east", Some(12)).unwrap(), 12);
+        assert_eq!(source_ids.register("garage", Some(3)).unwrap(), 3);
+        assert_eq!(source_ids.register("7", None).unwrap(), 7);
+
+        assert_eq!(source_ids.library_id("lobby-east").unwrap(), 12);
+        assert_eq!(source_ids.source_id(3), "garage");
+        assert!(source_ids.register("lobby-east", Some(13)).is_err());
+    }
+
+    #[test]
+    fn rejects_video_streamed_by_another_source() {
+        let mut source_ids = SourceIdMap::default();
+        source_ids.register("7", None).unwrap();
+
+        assert!(source_ids.register("lobby-east", Some(7)).is_err());
+
+        source_ids.unregister("7");
+        assert_eq!(source_ids.register("lobby-east", Some(7)).unwrap(), 7);
+        assert_eq!(source_ids.source_id(7), "lobby-east");
+    }
+}
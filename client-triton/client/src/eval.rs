@@ -0,0 +1,309 @@
+//! Responsible for validating models against ground truth annotations
+//!
+//! Runs the YOLO model over the images of a COCO annotations file, matches detections
+//! to ground truth by IoU and prints precision, recall and AP per category.
+//! Detections use the thresholds of `sources_config.default` - a low `conf_threshold`
+//! gives a more complete precision/recall curve
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use serde::Deserialize;
+use tokio::time::Instant;
+
+// Custom modules
+use crate::inference;
+use crate::utils;
+use crate::processing::{self, RawFrame};
+use crate::processing::yolo;
+use crate::utils::config::{AppConfig, InferenceModelType};
+
+/// IoU thresholds of COCO mAP - 0.50 to 0.95 in steps of 0.05
+const IOU_THRESHOLDS: [f32; 10] = [0.50, 0.55, 0.60, 0.65, 0.70, 0.75, 0.80, 0.85, 0.90, 0.95];
+
+/// COCO annotations file, only the fields used for evaluation
+#[derive(Deserialize)]
+pub struct CocoAnnotations {
+    pub images: Vec<CocoImage>,
+    pub annotations: Vec<CocoAnnotation>
+}
+
+#[derive(Deserialize)]
+pub struct CocoImage {
+    pub id: u64,
+    pub file_name: String
+}
+
+#[derive(Deserialize)]
+pub struct CocoAnnotation {
+    pub image_id: u64,
+    pub category_id: u32,
+    /// `[x, y, width, height]`
+    pub bbox: [f32; 4],
+    /// Crowd regions are not matched against
+    #[serde(default)]
+    pub iscrowd: u8
+}
+
+/// Single detection or ground truth box of an image, with corners `[x1, y1, x2, y2]`
+#[derive(Clone, Copy)]
+pub struct EvalBox {
+    pub image_id: u64,
+    pub category_id: u32,
+    pub bbox: [f32; 4],
+    pub score: f32
+}
+
+/// Evaluation results of a single category
+pub struct CategoryResult {
+    pub category_id: u32,
+    pub ground_truths: usize,
+    pub detections: usize,
+    /// Precision and recall of all detections at IoU 0.50
+    pub precision: f32,
+    pub recall: f32,
+    pub ap50: f32,
+    /// AP averaged over IoU thresholds 0.50 to 0.95
+    pub ap50_95: f32
+}
+
+/// Accumulates detections and ground truth of a dataset
+#[derive(Default)]
+pub struct Evaluator {
+    ground_truths: Vec<EvalBox>,
+    detections: Vec<EvalBox>
+}
+
+impl Evaluator {
+    pub fn add_ground_truth(&mut self, annotation: &CocoAnnotation) {
+        if annotation.iscrowd != 0 {
+            return;
+        }
+
+        let [x, y, w, h] = annotation.bbox;
+        self.ground_truths.push(EvalBox {
+            image_id: annotation.image_id,
+            category_id: annotation.category_id,
+            bbox: [x, y, x + w, y + h],
+            score: 1.00
+        });
+    }
+
+    pub fn add_detection(&mut self, detection: EvalBox) {
+        self.detections.push(detection);
+    }
+
+    /// Computes the results of every category with ground truth or detections, ordered by category id
+    pub fn evaluate(&self) -> Vec<CategoryResult> {
+        let mut categories: BTreeMap<u32, (Vec<&EvalBox>, Vec<&EvalBox>)> = BTreeMap::new();
+        for ground_truth in self.ground_truths.iter() {
+            categories.entry(ground_truth.category_id).or_default().0.push(ground_truth);
+        }
+        for detection in self.detections.iter() {
+            categories.entry(detection.category_id).or_default().1.push(detection);
+        }
+
+        categories
+            .into_iter()
+            .map(|(category_id, (ground_truths, mut detections))| {
+                detections.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+                let matches: Vec<Vec<bool>> = IOU_THRESHOLDS
+                    .iter()
+                    .map(|&iou_threshold| match_detections(&ground_truths, &detections, iou_threshold))
+                    .collect();
+
+                let true_positives = matches[0].iter().filter(|&&matched| matched).count();
+                let aps: Vec<f32> = matches
+                    .iter()
+                    .map(|matched| average_precision(matched, ground_truths.len()))
+                    .collect();
+
+                CategoryResult {
+                    category_id,
+                    ground_truths: ground_truths.len(),
+                    detections: detections.len(),
+                    precision: ratio(true_positives, detections.len()),
+                    recall: ratio(true_positives, ground_truths.len()),
+                    ap50: aps[0],
+                    ap50_95: aps.iter().sum::<f32>() / aps.len() as f32
+                }
+            })
+            .collect()
+    }
+}
+
+/// Greedily matches detections sorted by score to the unmatched ground truth of their image
+/// with the highest IoU, returning whether each detection is a true positive
+fn match_detections(ground_truths: &[&EvalBox], detections: &[&EvalBox], iou_threshold: f32) -> Vec<bool> {
+    let mut images: HashMap<u64, Vec<(&EvalBox, bool)>> = HashMap::new();
+    for ground_truth in ground_truths {
+        images.entry(ground_truth.image_id).or_default().push((ground_truth, false));
+    }
+
+    detections
+        .iter()
+        .map(|detection| {
+            let Some(image_ground_truths) = images.get_mut(&detection.image_id) else {
+                return false;
+            };
+
+            let best = image_ground_truths
+                .iter_mut()
+                .filter(|(_, matched)| !matched)
+                .map(|(ground_truth, matched)| (yolo::bbox_iou(&ground_truth.bbox, &detection.bbox), matched))
+                .filter(|(iou, _)| *iou >= iou_threshold)
+                .max_by(|a, b| a.0.total_cmp(&b.0));
+
+            match best {
+                Some((_, matched)) => {
+                    *matched = true;
+                    true
+                },
+                None => false
+            }
+        })
+        .collect()
+}
+
+/// Area under the precision/recall curve of detections sorted by score,
+/// with precision interpolated as the maximum precision at any higher recall
+fn average_precision(matched: &[bool], ground_truths: usize) -> f32 {
+    if ground_truths == 0 {
+        return 0.00;
+    }
+
+    let mut true_positives = 0;
+    let curve: Vec<(f32, f32)> = matched
+        .iter()
+        .enumerate()
+        .map(|(index, &is_match)| {
+            true_positives += is_match as usize;
+            (ratio(true_positives, ground_truths), ratio(true_positives, index + 1))
+        })
+        .collect();
+
+    let mut ap = 0.00;
+    let mut max_precision: f32 = 0.00;
+    let mut next_recall = curve.last().map(|(recall, _)| *recall).unwrap_or(0.00);
+    for (recall, precision) in curve.iter().rev() {
+        ap += (next_recall - recall) * max_precision;
+        max_precision = max_precision.max(*precision);
+        next_recall = *recall;
+    }
+    ap + next_recall * max_precision
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 {
+        return 0.00;
+    }
+    numerator as f32 / denominator as f32
+}
+
+/// Runs the YOLO model over the images of the annotations file and prints the results per category.
+/// Images are read from `images_dir`, defaulting to the directory of the annotations file
+pub async fn run_eval(app_config: &AppConfig, annotations_path: &str, images_dir: Option<&str>) -> Result<()> {
+    let contents = std::fs::read_to_string(annotations_path)
+        .with_context(|| format!("Error reading annotations file {}", annotations_path))?;
+    let annotations: CocoAnnotations = serde_json::from_str(&contents)
+        .context("Error parsing COCO annotations")?;
+
+    let images_dir = match images_dir {
+        Some(images_dir) => Path::new(images_dir).to_path_buf(),
+        None => Path::new(annotations_path).parent().unwrap_or(Path::new(".")).to_path_buf()
+    };
+
+    let mut evaluator = Evaluator::default();
+    for annotation in annotations.annotations.iter() {
+        evaluator.add_ground_truth(annotation);
+    }
+
+    let model = inference::get_inference_model(InferenceModelType::YOLO)
+        .context("Evaluation requires a YOLO model")?;
+    let source_config = &app_config.sources_config().default;
+    let eval_start = Instant::now();
+
+    for (index, image) in annotations.images.iter().enumerate() {
+        let path = images_dir.join(&image.file_name);
+        let (data, height, width) = utils::get_image_raw(&path.to_string_lossy())
+            .with_context(|| format!("Error reading image {}", path.display()))?;
+
+        let frame = Arc::new(RawFrame {
+            data,
+            height,
+            width,
+            original_height: height,
+            original_width: width,
+            pts: image.id,
+            added: Instant::now()
+        });
+
+        let (_, bboxes) = yolo::process_frame(model.as_ref(), source_config, frame)
+            .await
+            .with_context(|| format!("Error inferring image {}", path.display()))?;
+
+        for bbox in bboxes {
+            evaluator.add_detection(EvalBox {
+                image_id: image.id,
+                category_id: processing::coco_category_id(bbox.class),
+                bbox: bbox.bbox,
+                score: bbox.score
+            });
+        }
+
+        if (index + 1) % 100 == 0 {
+            tracing::info!(images=index + 1, total=annotations.images.len(), "Evaluation progress");
+        }
+    }
+
+    let results = evaluator.evaluate();
+    print_summary(&results);
+
+    tracing::info!(
+        images=annotations.images.len(),
+        elapsed_secs=eval_start.elapsed().as_secs_f64(),
+        "Evaluation finished"
+    );
+
+    Ok(())
+}
+
+/// Prints results per category, followed by their means over categories with ground truth
+fn print_summary(results: &[CategoryResult]) {
+    println!(
+        "{:>8} {:>8} {:>10} {:>9} {:>9} {:>9} {:>9}",
+        "category", "truths", "detections", "precision", "recall", "AP50", "AP50-95"
+    );
+    for result in results {
+        println!(
+            "{:>8} {:>8} {:>10} {:>9.3} {:>9.3} {:>9.3} {:>9.3}",
+            result.category_id,
+            result.ground_truths,
+            result.detections,
+            result.precision,
+            result.recall,
+            result.ap50,
+            result.ap50_95
+        );
+    }
+
+    let evaluated: Vec<&CategoryResult> = results.iter().filter(|result| result.ground_truths > 0).collect();
+    let mean = |value: fn(&CategoryResult) -> f32| {
+        ratio_f32(evaluated.iter().map(|result| value(result)).sum(), evaluated.len())
+    };
+    println!(
+        "mAP50 {:.3}, mAP50-95 {:.3} over {} categories",
+        mean(|result| result.ap50),
+        mean(|result| result.ap50_95),
+        evaluated.len()
+    );
+}
+
+fn ratio_f32(sum: f32, count: usize) -> f32 {
+    if count == 0 {
+        return 0.00;
+    }
+    sum / count as f32
+}
@@ -8,9 +8,18 @@ use triton_client::Client;
 use triton_client::inference::{ModelInferRequest, RepositoryModelLoadRequest, ModelRepositoryParameter, RepositoryModelUnloadRequest};
 use triton_client::inference::model_infer_request::{InferInputTensor, InferRequestedOutputTensor};
 use triton_client::inference::model_repository_parameter::{ParameterChoice};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hasher;
+use serde::Serialize;
+use std::num::NonZeroUsize;
 use serde_json::json;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use arc_swap::ArcSwap;
+use fnv::FnvHasher;
+use futures::future::BoxFuture;
+use lru::LruCache;
+use once_cell::sync::Lazy;
 use tokio::sync::OnceCell;
 use anyhow::{self, Result, Context};
 use std::time::{Duration, Instant};
@@ -19,36 +28,196 @@ use std::time::{Duration, Instant};
 use crate::utils::{
     self,
     GPUStats,
-    config::{AppConfig, ModelConfig, TritonConfig}
+    config::{AppConfig, ModelConfig, TritonConfig, TritonEndpoint, InferencePrecision},
+    metrics::{self, InferenceErrorKind}
 };
-use crate::utils::config::{InferenceModelType, InferencePrecision};
+use crate::utils::config::InferenceModelType;
+use crate::processing::PreprocessCache;
 
 // Variables
-pub static INFERENCE_MODELS: OnceCell<HashMap<InferenceModelType, Arc<InferenceModel>>> = OnceCell::const_new();
-pub static GPU_STATS_INTERVAL: Duration = Duration::from_secs(200);
+pub static INFERENCE_MODELS: OnceCell<HashMap<InferenceModelType, ArcSwap<InferenceModel>>> = OnceCell::const_new();
+pub static MODEL_INSTANCES: AtomicU32 = AtomicU32::new(0);
+pub static GPU_STATS_INTERVAL_SECS: AtomicU64 = AtomicU64::new(200);
+/// GPUs model instances are placed on, by device index
+pub static GPU_INDICES: OnceCell<Vec<u32>> = OnceCell::const_new();
+
+/// Clients of Triton endpoints by endpoint name, shared by all models served from the same endpoint
+static TRITON_CLIENTS: Lazy<Mutex<HashMap<String, Arc<Client>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Returns the client of a Triton endpoint, created on first use, once its server is ready
+pub async fn connect_triton_endpoint(endpoint: &TritonEndpoint) -> Result<Arc<Client>> {
+    let cached = TRITON_CLIENTS.lock().unwrap().get(&endpoint.name).cloned();
+    let client = match cached {
+        Some(client) => client,
+        None => {
+            let client = Client::new(&endpoint.url, None)
+                .await
+                .with_context(|| format!("Error creating triton client of endpoint '{}'", endpoint.name))?;
+
+            let client = Arc::new(client);
+            TRITON_CLIENTS.lock().unwrap().insert(endpoint.name.clone(), Arc::clone(&client));
+            client
+        }
+    };
+
+    // Check if server is ready
+    let server_ready = client.server_ready()
+        .await
+        .with_context(|| format!("Error getting ready status of endpoint '{}'", endpoint.name))?;
+
+    if !server_ready.ready {
+        anyhow::bail!("Triton server of endpoint '{}' is not ready", endpoint.name);
+    }
+
+    Ok(client)
+}
 
 /// Returns the inference model instance, if initiated
-pub fn get_inference_model(model_type: InferenceModelType) -> Result<&'static Arc<InferenceModel>> {
+/// 
+/// The instance is kept alive by the caller, so inferences started on a
+/// model finish on it even if the model is swapped meanwhile
+pub fn get_inference_model(model_type: InferenceModelType) -> Result<Arc<InferenceModel>> {
     Ok(
-        INFERENCE_MODELS
-            .get()
-            .context("Infernece models are not initiated!")?
-            .get(&model_type)
-            .context("Infernece model is not initiated!")?
+        get_inference_model_slot(&model_type)?.load_full()
     )
 }
 
+fn get_inference_model_slot(model_type: &InferenceModelType) -> Result<&'static ArcSwap<InferenceModel>> {
+    INFERENCE_MODELS
+        .get()
+        .context("Infernece models are not initiated!")?
+        .get(model_type)
+        .context("Infernece model is not initiated!")
+}
+
+/// Model performing inference for a pipeline stage
+pub trait Inference: Send + Sync {
+    fn model_type(&self) -> &InferenceModelType;
+
+    fn model_config(&self) -> &ModelConfig;
+
+    /// Returns the cache of preprocessed frames, if enabled
+    fn preprocess_cache(&self) -> Option<Arc<PreprocessCache>>;
+
+    /// Performs inference on many raw inputs, returning raw model results per sample, per output
+    /// 
+    /// Outputs of each sample are ordered as configured in `output_name`
+    fn infer_outputs(&self, raw_inputs: Vec<Vec<u8>>) -> BoxFuture<'_, Result<Vec<Vec<Vec<u8>>>>>;
+
+    /// Performs inference on many raw inputs, returning the raw first output of the model per sample
+    /// 
+    /// Convenience for single-output models, see `infer_outputs` for models with multiple outputs
+    fn infer(&self, raw_inputs: Vec<Vec<u8>>) -> BoxFuture<'_, Result<Vec<Vec<u8>>>> {
+        Box::pin(async move {
+            let results = self.infer_outputs(raw_inputs).await?;
+
+            Ok(results
+                .into_iter()
+                .map(|sample_outputs| sample_outputs.into_iter().next().unwrap_or_default())
+                .collect())
+        })
+    }
+}
+
+/// Provides the models executing the stages of source pipelines
+pub trait ModelProvider: Send + Sync {
+    /// Returns the current model of the given type
+    fn model(&self, model_type: &InferenceModelType) -> Result<Arc<dyn Inference>>;
+}
+
+/// Models served from Triton Server, as initiated by `init_inference_models`.
+/// Swapped models are returned from the next call on
+pub struct TritonModels;
+
+impl ModelProvider for TritonModels {
+    fn model(&self, model_type: &InferenceModelType) -> Result<Arc<dyn Inference>> {
+        Ok(get_inference_model(model_type.clone())?)
+    }
+}
+
+/// Loads a new model for the given type and atomically switches inference to it
+/// 
+/// The new model is loaded with the same amount of instances as the current one,
+/// and only swapped in once loaded. Optionally unloads the previous model afterwards
+pub async fn swap_inference_model(
+    model_type: InferenceModelType,
+    model_config: ModelConfig,
+    unload_previous: bool
+) -> Result<()> {
+    let model_slot = get_inference_model_slot(&model_type)?;
+    let previous = model_slot.load_full();
+
+    let model = InferenceModel::new(
+        model_type.clone(),
+        previous.triton_config.clone(),
+        model_config
+    )
+        .await
+        .context("Error creating model client")?;
+
+    model.load_model(MODEL_INSTANCES.load(Ordering::Relaxed))
+        .await
+        .context("Error loading new model instances")?;
+
+    let model_name = model.model_config().name.clone();
+    let endpoint_name = model.endpoint().name.clone();
+    model_slot.store(Arc::new(model));
+
+    tracing::info!(
+        model_type=model_type.to_string(),
+        previous_model=&previous.model_config().name,
+        model=&model_name,
+        endpoint=&endpoint_name,
+        "Swapped inference model"
+    );
+
+    // Unload previous model, unless it is the same Triton model
+    if unload_previous && previous.model_config().name != model_name {
+        previous.unload_model()
+            .await
+            .context("Error unloading previous model")?;
+    }
+
+    Ok(())
+}
+
 /// Initiates a single instance of a model for inference
 pub async fn init_inference_models(app_config: &AppConfig) -> Result<()> {
     if let Some(_) = INFERENCE_MODELS.get() {
         anyhow::bail!("Models are already initiated!")
     }
 
+    GPU_STATS_INTERVAL_SECS.store(app_config.gpu_stats_interval_secs().max(1), Ordering::Relaxed);
+    GPU_INDICES.set(app_config.gpu_indices().to_vec())
+        .map_err(|_| anyhow::anyhow!("Error setting GPU indices"))?;
+
+    // Monitor all GPUs once, regardless of the amount of models
+    start_gpu_monitor(app_config.gpu_indices().to_vec());
+
+    // Every endpoint a model may be served from must be reachable, including failover endpoints
+    let triton_config = app_config.triton_config();
+    let mut endpoints: Vec<&TritonEndpoint> = Vec::new();
+    for model_config in app_config.inference_config().models.values() {
+        for endpoint in triton_config.model_endpoints(model_config)? {
+            if !endpoints.contains(&endpoint) {
+                endpoints.push(endpoint);
+            }
+        }
+    }
+    for endpoint in endpoints {
+        connect_triton_endpoint(endpoint)
+            .await
+            .with_context(|| format!("Triton endpoint '{}' at {} is unreachable", endpoint.name, endpoint.url))?;
+
+        tracing::info!(endpoint=endpoint.name, url=endpoint.url, "Connected to Triton endpoint");
+    }
+
     // Create model instances
-    let mut models: HashMap<InferenceModelType, Arc<InferenceModel>> = HashMap::new();
+    let mut models: HashMap<InferenceModelType, ArcSwap<InferenceModel>> = HashMap::new();
     for (model_type, model_config) in app_config.inference_config().models.iter() {
         // Create single instance
         let client_instance = InferenceModel::new(
+            model_type.clone(),
             app_config.triton_config().clone(),
             model_config.clone(),
         )
@@ -57,7 +226,7 @@ pub async fn init_inference_models(app_config: &AppConfig) -> Result<()> {
 
         models.insert(
             model_type.clone(),
-            Arc::new(client_instance)
+            ArcSwap::from_pointee(client_instance)
         );
     }
 
@@ -68,6 +237,47 @@ pub async fn init_inference_models(app_config: &AppConfig) -> Result<()> {
     Ok(())
 }
 
+/// Spawns a single thread reporting statistics of the configured GPUs every interval
+///
+/// Lives for the whole application, independent of the lifetime of models
+fn start_gpu_monitor(gpu_indices: Vec<u32>) {
+    std::thread::spawn(move || {
+        let stats_interval = Duration::from_secs(GPU_STATS_INTERVAL_SECS.load(Ordering::Relaxed));
+
+        // Without a supported GPU (e.g. inference runs remotely) there is nothing to monitor
+        let Some(monitor) = utils::gpu::detect_gpu_monitor() else {
+            tracing::warn!("No supported GPU monitoring backend, GPU utilization is not monitored");
+            return;
+        };
+        tracing::info!(backend=monitor.backend(), "Monitoring GPU utilization");
+
+        loop {
+            let measure_time = Instant::now();
+
+            // Get GPU statistics
+            match monitor.statistics(&gpu_indices) {
+                Ok(gpus_stats) => {
+                    for stats in gpus_stats {
+                        InferenceModel::process_gpu_stats(stats);
+                    }
+                },
+                Err(e) => {
+                    tracing::warn!(
+                        error=e.to_string(),
+                        "Error getting GPU utilization information"
+                    )
+                }
+            };
+
+            // Sleep if time remains
+            let remainder = measure_time.elapsed();
+            if remainder < stats_interval {
+                std::thread::sleep(stats_interval - remainder);
+            }
+        }
+    });
+}
+
 pub async fn start_models_instances(app_config: &AppConfig) -> Result<()> {
     // Calculate total "load units" - how much processing capacity we need
     // Each source contributes fractional load based on its frame rate
@@ -75,6 +285,7 @@ pub async fn start_models_instances(app_config: &AppConfig) -> Result<()> {
         .sources_config()
         .sources
         .len() as u32;
+    MODEL_INSTANCES.store(instances, Ordering::Relaxed);
 
     // Load same amount of instances for each model type
     for model_type in app_config.inference_config().models.keys() {
@@ -95,112 +306,420 @@ pub async fn start_models_instances(app_config: &AppConfig) -> Result<()> {
     Ok(())
 }
 
+/// Latency and throughput of a model at a single batch size
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchmarkResult {
+    pub median_latency_ms: f64,
+    pub throughput_fps: f64
+}
+
+/// Benchmarks every model at its relevant batch sizes - single samples, preferred sizes and the maximum size.
+/// Results are logged as a table and written to a `benchmark_{timestamp}.json` file
+pub async fn run_startup_benchmarks(app_config: &AppConfig) -> Result<()> {
+    let mut all_results: HashMap<String, BTreeMap<usize, BenchmarkResult>> = HashMap::new();
+
+    for model_type in app_config.inference_config().models.keys() {
+        let model = get_inference_model(model_type.clone())?;
+        let model_config = model.model_config();
+
+        let mut batch_sizes: Vec<usize> = std::iter::once(1)
+            .chain(model_config.batch_preferred_sizes.iter().map(|&size| size as usize))
+            .chain(std::iter::once(model_config.batch_max_size as usize))
+            .filter(|&size| size > 0)
+            .collect();
+        batch_sizes.sort();
+        batch_sizes.dedup();
+
+        let results = benchmark_model(&model, &batch_sizes, app_config.benchmark_iterations())
+            .await
+            .with_context(|| format!("Error benchmarking model {}", model_type.to_string()))?;
+        let results: BTreeMap<usize, BenchmarkResult> = results.into_iter().collect();
+
+        // Log as a table
+        let table = results
+            .iter()
+            .map(|(batch_size, result)| format!(
+                "{:>10} | {:>17.2} | {:>15.1}",
+                batch_size, result.median_latency_ms, result.throughput_fps
+            ))
+            .collect::<Vec<_>>()
+            .join("\n");
+        tracing::info!(
+            model_type=model_type.to_string(),
+            "Model benchmark results\n{:>10} | {:>17} | {:>15}\n{}",
+            "batch_size", "median_latency_ms", "throughput_fps", table
+        );
+
+        all_results.insert(model_type.to_string().to_string(), results);
+    }
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let path = format!("benchmark_{}.json", timestamp);
+    let data = serde_json::to_string_pretty(&all_results)
+        .context("Error serializing benchmark results")?;
+    std::fs::write(&path, data)
+        .with_context(|| format!("Error writing benchmark results to {}", path))?;
+
+    tracing::info!(path=path, "Wrote benchmark results");
+
+    Ok(())
+}
+
+/// Measures median latency and throughput of a model for each batch size, using random inputs.
+/// Every batch size runs as many warm-up inferences as measured ones
+pub async fn benchmark_model(
+    model: &InferenceModel,
+    batch_sizes: &[usize],
+    iterations: u32
+) -> Result<HashMap<usize, BenchmarkResult>> {
+    let iterations = iterations.max(1);
+    let input_size = model.model_config().input_size();
+    let precision = model.model_config().precision;
+    let mut results = HashMap::new();
+
+    for &batch_size in batch_sizes {
+        let inputs: Vec<Vec<u8>> = (0..batch_size)
+            .map(|_| random_input(input_size, precision))
+            .collect();
+
+        // Warm-up, skipping the result cache so every request reaches Triton
+        for _ in 0..iterations {
+            model.infer_triton(inputs.clone()).await?;
+        }
+
+        let mut latencies_ms = Vec::with_capacity(iterations as usize);
+        for _ in 0..iterations {
+            let measure_start = Instant::now();
+            model.infer_triton(inputs.clone()).await?;
+            latencies_ms.push(measure_start.elapsed().as_secs_f64() * 1000.0);
+        }
+
+        latencies_ms.sort_by(|a, b| a.total_cmp(b));
+        let median_latency_ms = match latencies_ms.len() % 2 {
+            0 => (latencies_ms[latencies_ms.len() / 2 - 1] + latencies_ms[latencies_ms.len() / 2]) / 2.0,
+            _ => latencies_ms[latencies_ms.len() / 2]
+        };
+        let throughput_fps = if median_latency_ms > 0.0 {
+            batch_size as f64 * 1000.0 / median_latency_ms
+        } else {
+            0.0
+        };
+
+        results.insert(batch_size, BenchmarkResult { median_latency_ms, throughput_fps });
+    }
+
+    Ok(results)
+}
+
+/// Returns a random input of values within [0, 1), as normalized images are
+fn random_input(size: usize, precision: InferencePrecision) -> Vec<u8> {
+    let mut rng = fastrand::Rng::new();
+
+    let mut input = Vec::with_capacity(size);
+    match precision {
+        InferencePrecision::FP32 => {
+            while input.len() < size {
+                input.extend_from_slice(&rng.f32().to_le_bytes());
+            }
+        },
+        InferencePrecision::FP16 => {
+            while input.len() < size {
+                // Exponents below the bias keep half floats finite and below 1
+                let bits = (rng.u16(0..15) << 10) | rng.u16(0..0x400);
+                input.extend_from_slice(&bits.to_le_bytes());
+            }
+        }
+    }
+    input.truncate(size);
+
+    input
+}
+
+/// Starts periodic health checks of models that have them configured
+pub fn start_model_health_checks(app_config: &AppConfig) {
+    for (model_type, model_config) in app_config.inference_config().models.iter() {
+        if let Some(health_checker) = ModelHealthChecker::new(model_type.clone(), model_config) {
+            health_checker.start();
+        }
+    }
+}
+
+/// Periodically validates a model with a warm-up inference on known input data
+///
+/// Triton may keep a model loaded while it returns wrong results. When the output
+/// has an unexpected shape or the inference times out, the model is reloaded
+pub struct ModelHealthChecker {
+    model_type: InferenceModelType,
+    interval: Duration,
+    timeout: Duration
+}
+
+impl ModelHealthChecker {
+    /// Creates a health checker of a model, if health checks are enabled for it
+    pub fn new(model_type: InferenceModelType, model_config: &ModelConfig) -> Option<Self> {
+        let interval_secs = model_config.health_check_interval_secs.filter(|&secs| secs > 0)?;
+
+        Some(Self {
+            model_type,
+            interval: Duration::from_secs(interval_secs),
+            timeout: Duration::from_millis(model_config.health_check_timeout_ms)
+        })
+    }
+
+    /// Runs health checks in a seperate task
+    pub fn start(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            // First tick completes immediately, models were just loaded
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                let Err(e) = self.check().await else {
+                    continue;
+                };
+
+                metrics::HEALTH_CHECK_FAILURES
+                    .with_label_values(&[self.model_type.to_string()])
+                    .inc();
+                tracing::warn!(
+                    model_type=self.model_type.to_string(),
+                    error=format!("{:#}", e),
+                    "Model health check failed, reloading model"
+                );
+
+                if let Err(e) = self.reload().await {
+                    tracing::error!(
+                        model_type=self.model_type.to_string(),
+                        error=format!("{:#}", e),
+                        "Failed to reload unhealthy model"
+                    );
+
+                    if let Err(e) = self.fail_over().await {
+                        tracing::error!(
+                            model_type=self.model_type.to_string(),
+                            error=format!("{:#}", e),
+                            "Failed to fail over unhealthy model"
+                        );
+                    }
+                }
+            }
+        })
+    }
+
+    /// Performs a warm-up inference on a zeroed input, validating the output shape
+    async fn check(&self) -> Result<()> {
+        let model = get_inference_model(self.model_type.clone())?;
+        let model_config = model.model_config();
+
+        let input_size = model_config.input_size();
+        let expected_output_sizes = model_config.output_sizes();
+
+        // Skip the result cache, the request must reach Triton
+        let outputs = tokio::time::timeout(self.timeout, model.infer_triton(vec![vec![0u8; input_size]]))
+            .await
+            .with_context(|| format!("Health check inference timed out after {:?}", self.timeout))?
+            .context("Health check inference failed")?;
+
+        let output_sizes: Vec<usize> = outputs
+            .first()
+            .map(|sample_outputs| sample_outputs.iter().map(|output| output.len()).collect())
+            .unwrap_or_default();
+        if outputs.len() != 1 || output_sizes != expected_output_sizes {
+            anyhow::bail!(
+                "Unexpected health check output. Got {} samples with outputs of {:?} bytes, expected 1 sample with outputs of {:?} bytes (shapes {:?})",
+                outputs.len(),
+                output_sizes,
+                expected_output_sizes,
+                model_config.output_shape
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Unloads and loads the model again, triggering a fresh engine initialization
+    async fn reload(&self) -> Result<()> {
+        let model = get_inference_model(self.model_type.clone())?;
+
+        model.unload_model()
+            .await
+            .context("Error unloading unhealthy model")?;
+        model.load_model(MODEL_INSTANCES.load(Ordering::Relaxed))
+            .await
+            .context("Error loading model instances")?;
+
+        tracing::info!(
+            model_type=self.model_type.to_string(),
+            model=&model.model_config().name,
+            "Reloaded unhealthy model"
+        );
+
+        Ok(())
+    }
+
+    /// Moves the model to the next endpoint able to serve it, trying endpoints following its
+    /// current endpoint in order of preference, then wrapping around
+    async fn fail_over(&self) -> Result<()> {
+        let model_slot = get_inference_model_slot(&self.model_type)?;
+        let model = model_slot.load_full();
+
+        let endpoints = model.triton_config().model_endpoints(model.model_config())?;
+        if endpoints.len() < 2 {
+            anyhow::bail!("Model has no failover endpoints");
+        }
+
+        let current = endpoints
+            .iter()
+            .position(|endpoint| endpoint.name == model.endpoint().name)
+            .unwrap_or(0);
+
+        for endpoint in endpoints.iter().cycle().skip(current + 1).take(endpoints.len() - 1) {
+            let failover_model = InferenceModel::with_endpoint(
+                self.model_type.clone(),
+                model.triton_config().clone(),
+                model.model_config().clone(),
+                (*endpoint).clone()
+            ).await;
+
+            let loaded = match failover_model {
+                Ok(failover_model) => failover_model.load_model(MODEL_INSTANCES.load(Ordering::Relaxed))
+                    .await
+                    .map(|_| failover_model),
+                Err(e) => Err(e)
+            };
+
+            match loaded {
+                Ok(failover_model) => {
+                    model_slot.store(Arc::new(failover_model));
+                    tracing::warn!(
+                        model_type=self.model_type.to_string(),
+                        previous_endpoint=&model.endpoint().name,
+                        endpoint=&endpoint.name,
+                        "Failed over model to another endpoint"
+                    );
+                    return Ok(());
+                },
+                Err(e) => tracing::warn!(
+                    model_type=self.model_type.to_string(),
+                    endpoint=&endpoint.name,
+                    error=format!("{:#}", e),
+                    "Failover endpoint cannot serve the model"
+                )
+            }
+        }
+
+        anyhow::bail!("No failover endpoint could serve the model")
+    }
+}
+
+/// Builds an inference request of a model for a batch of samples, concatenated into a single payload
+/// 
+/// The input shape is derived from the per-sample shape of the model on every call,
+/// and the payload is moved into the request without copying
+fn infer_request(model_config: &ModelConfig, batch_size: usize, payload: Vec<u8>) -> ModelInferRequest {
+    let mut batch_input_shape = Vec::with_capacity(model_config.input_shape.len() + 1);
+    batch_input_shape.push(batch_size as i64);
+    batch_input_shape.extend(&model_config.input_shape);
+
+    ModelInferRequest {
+        model_name: model_config.name.to_string(),
+        model_version: "1".to_string(),
+        id: String::new(),
+        parameters: HashMap::new(),
+        inputs: vec![
+            InferInputTensor {
+                name: model_config.input_name.to_string(),
+                datatype: model_config.precision.to_string(),
+                shape: batch_input_shape,
+                parameters: HashMap::new(),
+                contents: None
+            }
+        ],
+        outputs: model_config.output_name
+            .iter()
+            .map(|output_name| InferRequestedOutputTensor {
+                name: output_name.to_string(),
+                parameters: HashMap::new(),
+            })
+            .collect(),
+        raw_input_contents: vec![payload]
+    }
+}
+
 /// Represents an instance of an inference model
 pub struct InferenceModel {
+    model_type: InferenceModelType,
     client: Arc<Client>,
+    endpoint: TritonEndpoint,
     triton_config: TritonConfig,
     model_config: ModelConfig,
-    base_request: ModelInferRequest,
-    stats_handle: std::thread::JoinHandle<()>
+    result_cache: Option<Mutex<LruCache<u64, Vec<Vec<Vec<u8>>>>>>,
+    cache_hits: AtomicU64,
+    preprocess_cache: Option<Arc<PreprocessCache>>,
+    tensors_dumped: AtomicU32
 }
 
 impl InferenceModel {
-    /// Create new instance of inference model
+    /// Create new instance of inference model, served from the endpoint selected by its configuration
     /// 
-    /// Creates a new Triton Server client for inference
     /// Initiate all values for fast inference, including a pre-made request body for inference
-    /// Reports statistics about GPU utilization
     pub async fn new(
+        model_type: InferenceModelType,
         triton_config: TritonConfig,
         model_config: ModelConfig
     ) -> Result<Self> {
-        //Create client instance
-        let client = Client::new(&triton_config.url, None)
-            .await
-            .context("Error creating triton client instance")?;
-
-        // Check if server is ready
-        let server_ready = client.server_ready()
-            .await
-            .context("Error getting model ready status")?;
-
-        if !server_ready.ready {
-            anyhow::bail!("Triton server is not ready");
-        }
-
-        // Create base inference request
-        let mut batch_input_shape = Vec::with_capacity(&model_config.input_shape.len() + 1);
-        batch_input_shape.extend(&model_config.input_shape);
-
-        let base_request = ModelInferRequest {
-            model_name: model_config.name.to_string(),
-            model_version: "1".to_string(),
-            id: String::new(),
-            parameters: HashMap::new(),
-            inputs: vec![
-                InferInputTensor {
-                    name: model_config.input_name.to_string(),
-                    datatype: model_config.precision.to_string(),
-                    shape: batch_input_shape,
-                    parameters: HashMap::new(),
-                    contents: None
-                }
-            ],
-            outputs: vec![
-                InferRequestedOutputTensor {
-                    name: model_config.output_name.to_string(),
-                    parameters: HashMap::new(),
-                }
-            ],
-            raw_input_contents: Vec::new()
-        };
-
+        let endpoint = triton_config.endpoint(model_config.endpoint.as_deref())?.clone();
 
-        // Spawn seperate task to monitor GPU stats
-        let stats_interval = GPU_STATS_INTERVAL.clone();
-
-        let stats_handle = std::thread::spawn(move || {
-            loop {
-                let measure_time = Instant::now();
+        InferenceModel::with_endpoint(model_type, triton_config, model_config, endpoint).await
+    }
 
-                // Get GPU statistics
-                let stats_result = utils::get_gpu_statistics();
+    /// Create new instance of inference model, served from the given endpoint
+    pub async fn with_endpoint(
+        model_type: InferenceModelType,
+        triton_config: TritonConfig,
+        model_config: ModelConfig,
+        endpoint: TritonEndpoint
+    ) -> Result<Self> {
+        let client = connect_triton_endpoint(&endpoint).await?;
 
-                match stats_result {
-                    Ok(stats) => {
-                        InferenceModel::process_gpu_stats(stats);
-                    },
-                    Err(e) => {
-                        tracing::warn!(
-                            error=e.to_string(),
-                            "Error getting GPU utilization information"
-                        )
-                    }
-                };
+        // Cache of inference results, disabled when capacity is 0
+        let result_cache = NonZeroUsize::new(model_config.max_cache_entries)
+            .map(|capacity| Mutex::new(LruCache::new(capacity)));
 
-                // Sleep if time remains
-                let remainder = measure_time.elapsed();
-                if remainder < stats_interval {
-                    let duration = stats_interval - remainder;
-                    std::thread::sleep(duration);
-                }
-            }
-        });
+        // Cache of preprocessed frames, disabled when capacity is 0
+        let preprocess_cache = PreprocessCache::new(model_config.max_preprocess_cache_entries)
+            .map(Arc::new);
 
         Ok(Self { 
-            client: Arc::new(client),
+            model_type,
+            client,
+            endpoint,
             triton_config,
             model_config,
-            base_request,
-            stats_handle
+            result_cache,
+            cache_hits: AtomicU64::new(0),
+            preprocess_cache,
+            tensors_dumped: AtomicU32::new(0)
         })
     }
 
+    /// Returns the model repository of the model, defaulting to the repository of all models
+    fn repository_name(&self) -> String {
+        self.model_config.models_dir
+            .clone()
+            .unwrap_or_else(|| self.triton_config.models_dir.clone())
+    }
+
     /// Unloads running instances of a given model
     pub async fn unload_model(&self) -> Result<()> {
         // Unload previous instances of model we're about to load
         self.client.repository_model_unload(RepositoryModelUnloadRequest { 
-            repository_name: "".to_string(), 
+            repository_name: self.repository_name(), 
             model_name: self.model_config().name.to_string(), 
             parameters: HashMap::new()
         })
@@ -212,7 +731,17 @@ impl InferenceModel {
     
     /// Loads given amount of instances of a given model
     pub async fn load_model(&self, instances: u32) -> Result<()> {
-        let model_config = json!({
+        let outputs: Vec<_> = self.model_config().output_name
+            .iter()
+            .zip(self.model_config().output_shape.iter())
+            .map(|(output_name, output_shape)| json!({
+                "name": output_name,
+                "data_type": format!("TYPE_{}", &self.model_config().precision.to_string()),
+                "dims": output_shape
+            }))
+            .collect();
+
+        let mut model_config = json!({
             "name": &self.model_config().name,
             "platform": "tensorrt_plan",
             "max_batch_size": &self.model_config().batch_max_size,
@@ -223,25 +752,14 @@ impl InferenceModel {
                     "dims": &self.model_config().input_shape
                 }
             ],
-            "output": [
-                {
-                    "name": &self.model_config().output_name,
-                    "data_type": format!("TYPE_{}", &self.model_config().precision.to_string()),
-                    "dims": &self.model_config().output_shape
-                }
-            ],
+            "output": outputs,
             "instance_group": [
                 {
                     "kind": "KIND_GPU",
                     "count": instances,
-                    "gpus": [0]
+                    "gpus": GPU_INDICES.get().cloned().unwrap_or_else(|| vec![0])
                 }
             ],
-            "dynamic_batching": {
-                "max_queue_delay_microseconds": self.model_config().batch_max_queue_delay,
-                "preferred_batch_size": &self.model_config().batch_preferred_sizes,
-                "preserve_ordering": false
-            },
             "optimization": {
                 "execution_accelerators": {
                 "gpu_execution_accelerator": [
@@ -280,6 +798,15 @@ impl InferenceModel {
             ]
         });
 
+        // Dynamic batching adds queue delay, omitted for per-request execution
+        if self.model_config().enable_dynamic_batching {
+            model_config["dynamic_batching"] = json!({
+                "max_queue_delay_microseconds": self.model_config().batch_max_queue_delay,
+                "preferred_batch_size": &self.model_config().batch_preferred_sizes,
+                "preserve_ordering": self.model_config().preserve_ordering
+            });
+        }
+
         // Define model config
         let mut parameters = HashMap::new();
         parameters.insert("config".to_string(), ModelRepositoryParameter{ 
@@ -288,7 +815,7 @@ impl InferenceModel {
 
         // Load selected model
         self.client.repository_model_load(RepositoryModelLoadRequest { 
-            repository_name: "".to_string(), 
+            repository_name: self.repository_name(), 
             model_name: self.model_config().name.to_string(), 
             parameters: parameters
         })
@@ -298,23 +825,126 @@ impl InferenceModel {
         Ok(())
     }
 
-    /// Performs inference on many raw inputs, returning raw model results
+    /// Performs inference on many raw inputs, returning raw model results per sample, per output
+    /// 
+    /// Outputs of each sample are ordered as configured in `output_name`.
+    /// When caching is enabled, results of recently seen inputs are returned
+    /// without a round-trip to Triton Server
+    pub async fn infer_outputs(&self, raw_inputs: Vec<Vec<u8>>) -> Result<Vec<Vec<Vec<u8>>>> {
+        self.dump_tensors(&raw_inputs);
+
+        let Some(result_cache) = &self.result_cache else {
+            return self.infer_triton(raw_inputs).await;
+        };
+
+        // Check cache for identical inputs
+        let cache_key = InferenceModel::hash_inputs(&raw_inputs);
+        if let Some(results) = result_cache.lock().unwrap().get(&cache_key) {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(results.clone());
+        }
+
+        let results = self.infer_triton(raw_inputs).await?;
+        result_cache.lock().unwrap().put(cache_key, results.clone());
+
+        Ok(results)
+    }
+
+    /// Writes preprocessed inputs to disk until the configured amount of samples is reached
+    ///
+    /// Each sample is written as raw bytes (`.bin`) along with its shape and datatype (`.json`),
+    /// so it can be replayed against Triton as-is. Failures are logged, never failing inference
+    fn dump_tensors(&self, raw_inputs: &[Vec<u8>]) {
+        let Some(dump_config) = &self.model_config.tensor_dump else {
+            return;
+        };
+
+        for raw_input in raw_inputs {
+            let sample = self.tensors_dumped.fetch_add(1, Ordering::Relaxed);
+            if sample >= dump_config.max_samples {
+                return;
+            }
+
+            if let Err(e) = self.dump_tensor(&dump_config.dir, sample, raw_input) {
+                tracing::warn!(
+                    error=format!("{:#}", e),
+                    model_type=self.model_type.to_string(),
+                    "Error dumping preprocessed tensor"
+                );
+            }
+        }
+    }
+
+    fn dump_tensor(&self, dir: &str, sample: u32, raw_input: &[u8]) -> Result<()> {
+        let dir = std::path::Path::new(dir);
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Error creating tensor dump directory {}", dir.display()))?;
+
+        let mut shape = vec![1];
+        shape.extend(&self.model_config.input_shape);
+        let metadata = json!({
+            "model_name": self.model_config.name,
+            "model_version": self.model_config.version,
+            "input_name": self.model_config.input_name,
+            "datatype": self.model_config.precision.to_string(),
+            "shape": shape,
+            "bytes": raw_input.len()
+        });
+
+        let file_stem = format!("{}_{}", self.model_config.name, sample);
+        std::fs::write(dir.join(format!("{}.bin", file_stem)), raw_input)
+            .context("Error writing tensor data")?;
+        std::fs::write(dir.join(format!("{}.json", file_stem)), metadata.to_string())
+            .context("Error writing tensor metadata")?;
+
+        tracing::info!(
+            model_type=self.model_type.to_string(),
+            sample=sample,
+            path=dir.join(&file_stem).display().to_string(),
+            "Dumped preprocessed tensor"
+        );
+
+        Ok(())
+    }
+
+    /// Builds an inference request for a batch of samples, concatenated into a single payload
+    pub fn build_request(&self, batch_size: usize, payload: Vec<u8>) -> ModelInferRequest {
+        infer_request(&self.model_config, batch_size, payload)
+    }
+
+    /// Returns the amount of inference requests served from cache
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the cache of preprocessed frames, if enabled
+    pub fn preprocess_cache(&self) -> Option<Arc<PreprocessCache>> {
+        self.preprocess_cache.clone()
+    }
+
+    /// Hashes preprocessed inputs with FNV, including their lengths to separate input boundaries
+    fn hash_inputs(raw_inputs: &[Vec<u8>]) -> u64 {
+        let mut hasher = FnvHasher::default();
+        for input in raw_inputs {
+            hasher.write_usize(input.len());
+            hasher.write(input);
+        }
+
+        hasher.finish()
+    }
+
+    /// Performs inference on many raw inputs with Triton Server, returning outputs per sample, per output
     /// Automatically batches requests up to max_batch_size and processes batches concurrently
-    pub async fn infer(&self, raw_inputs: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>> {
+    async fn infer_triton(&self, raw_inputs: Vec<Vec<u8>>) -> Result<Vec<Vec<Vec<u8>>>> {
         let max_batch_size = self.model_config.batch_max_size as usize;
         let num_inputs = raw_inputs.len();
         
-        // Calculate output size per sample once
-        let output_size_per_sample: usize = self.model_config.output_shape
-            .iter()
-            .map(|&dim| dim as usize)
-            .product::<usize>() * match self.model_config.precision {
-                InferencePrecision::FP16 => 2,
-                InferencePrecision::FP32 => 4,
-            };
+        // Calculate size per sample of every output once
+        let output_sizes = Arc::new(self.model_config.output_sizes());
+        let output_names = Arc::new(self.model_config.output_name.clone());
         
         // Pre-allocate result slots - direct placement, no sorting
-        let mut all_results: Vec<Vec<u8>> = Vec::with_capacity(num_inputs);
+        let mut all_results: Vec<Vec<Vec<u8>>> = Vec::with_capacity(num_inputs);
         all_results.resize_with(num_inputs, Vec::new);
         
         // Process all batches concurrently (1 batch if num_inputs <= max_batch_size)
@@ -332,33 +962,73 @@ impl InferenceModel {
                     concatenated.extend_from_slice(input);
                 }
                 
-                let mut inference_request = self.base_request.clone();
-                inference_request.inputs[0].shape.insert(0, batch_size as i64);
-                inference_request.raw_input_contents = vec![concatenated];
+                let inference_request = self.build_request(batch_size, concatenated);
                 
                 let client = Arc::clone(&self.client);
-                let output_size = output_size_per_sample;
+                let output_sizes = Arc::clone(&output_sizes);
+                let output_names = Arc::clone(&output_names);
+                let model_type = self.model_type.clone();
                 
                 tokio::spawn(async move {
                     // Network I/O - async
-                    let inference_result = client.model_infer(inference_request)
-                        .await
-                        .context("Error sending triton inference request")?;
+                    let inference_result = match client.model_infer(inference_request).await {
+                        Ok(result) => result,
+                        Err(e) => {
+                            let error = e.to_string();
+                            metrics::inc_inference_error(&model_type, InferenceErrorKind::from_request_error(&error));
+                            anyhow::bail!("Error sending triton inference request: {}", error);
+                        }
+                    };
                     
-                    // CPU work - blocking thread pool
-                    let output_blob = inference_result.raw_output_contents.into_iter().next()
-                        .context("No output from inference")?;
+                    // Order output blobs as configured - matched by name, falling back to response order
+                    let mut output_blobs: Vec<Option<Vec<u8>>> = inference_result.raw_output_contents
+                        .into_iter()
+                        .map(Some)
+                        .collect();
+                    let mut ordered_blobs = Vec::with_capacity(output_names.len());
+
+                    for (output_idx, output_name) in output_names.iter().enumerate() {
+                        let blob_idx = inference_result.outputs
+                            .iter()
+                            .position(|output| &output.name == output_name)
+                            .unwrap_or(output_idx);
+
+                        let Some(output_blob) = output_blobs.get_mut(blob_idx).and_then(|blob| blob.take()) else {
+                            metrics::inc_inference_error(&model_type, InferenceErrorKind::Other);
+                            anyhow::bail!("No output '{}' from inference", output_name);
+                        };
+
+                        // Validate output matches expected shape before slicing it
+                        let expected_size = batch_size * output_sizes[output_idx];
+                        if output_blob.len() != expected_size {
+                            metrics::inc_inference_error(&model_type, InferenceErrorKind::ShapeMismatch);
+                            anyhow::bail!(
+                                "Got unexpected size of inference output '{}'. Got {}, expected {}",
+                                output_name,
+                                output_blob.len(),
+                                expected_size
+                            );
+                        }
+
+                        ordered_blobs.push(output_blob);
+                    }
                     
+                    // CPU work - blocking thread pool
                     let batch_results = tokio::task::spawn_blocking(move || {
-                        // Unsafe pointer slicing for blazing speed
-                        let ptr = output_blob.as_ptr();
-                        let mut results = Vec::with_capacity(batch_size);
-                        
-                        unsafe {
-                            for i in 0..batch_size {
-                                let offset = i * output_size;
-                                let slice = std::slice::from_raw_parts(ptr.add(offset), output_size);
-                                results.push(slice.to_vec());
+                        let mut results: Vec<Vec<Vec<u8>>> = (0..batch_size)
+                            .map(|_| Vec::with_capacity(ordered_blobs.len()))
+                            .collect();
+
+                        for (output_blob, &output_size) in ordered_blobs.iter().zip(output_sizes.iter()) {
+                            // Unsafe pointer slicing for blazing speed
+                            let ptr = output_blob.as_ptr();
+                            
+                            unsafe {
+                                for (i, sample_results) in results.iter_mut().enumerate() {
+                                    let offset = i * output_size;
+                                    let slice = std::slice::from_raw_parts(ptr.add(offset), output_size);
+                                    sample_results.push(slice.to_vec());
+                                }
                             }
                         }
                         
@@ -367,7 +1037,7 @@ impl InferenceModel {
                     .await
                     .context("Failed to split batch results")?;
                     
-                    Ok::<(usize, Vec<Vec<u8>>), anyhow::Error>((start_idx, batch_results))
+                    Ok::<(usize, Vec<Vec<Vec<u8>>>), anyhow::Error>((start_idx, batch_results))
                 })
             })
             .collect();
@@ -375,12 +1045,13 @@ impl InferenceModel {
         // Await all batches and place directly
         let results = futures::future::try_join_all(tasks)
             .await
+            .inspect_err(|_| metrics::inc_inference_error(&self.model_type, InferenceErrorKind::Other))
             .context("Error performing inference on all inputs")?;
         
         for result in results {
             let (start_idx, batch) = result?;
-            for (i, output) in batch.into_iter().enumerate() {
-                all_results[start_idx + i] = output;
+            for (i, outputs) in batch.into_iter().enumerate() {
+                all_results[start_idx + i] = outputs;
             }
         }
         
@@ -388,7 +1059,16 @@ impl InferenceModel {
     }
 
     pub fn process_gpu_stats(stats: GPUStats) {
+        let index = stats.index.to_string();
+        metrics::GPU_UTILIZATION
+            .with_label_values(&[index.as_str(), stats.name.as_str(), stats.uuid.as_str()])
+            .set(stats.util_perc as f64);
+        metrics::GPU_MEMORY_USED
+            .with_label_values(&[index.as_str(), stats.name.as_str(), stats.uuid.as_str()])
+            .set(stats.memory_used as f64);
+
         tracing::info!(
+            index=stats.index,
             name=stats.name,
             uuid=stats.uuid,
             serial=stats.serial,
@@ -402,11 +1082,37 @@ impl InferenceModel {
     }
 }
 
+impl Inference for InferenceModel {
+    fn model_type(&self) -> &InferenceModelType {
+        InferenceModel::model_type(self)
+    }
+
+    fn model_config(&self) -> &ModelConfig {
+        InferenceModel::model_config(self)
+    }
+
+    fn preprocess_cache(&self) -> Option<Arc<PreprocessCache>> {
+        InferenceModel::preprocess_cache(self)
+    }
+
+    fn infer_outputs(&self, raw_inputs: Vec<Vec<u8>>) -> BoxFuture<'_, Result<Vec<Vec<Vec<u8>>>>> {
+        Box::pin(InferenceModel::infer_outputs(self, raw_inputs))
+    }
+}
+
 impl InferenceModel {
+    pub fn model_type(&self) -> &InferenceModelType {
+        &self.model_type
+    }
+
     pub fn client(&self) -> &Client {
         &self.client
     }
 
+    pub fn endpoint(&self) -> &TritonEndpoint {
+        &self.endpoint
+    }
+
     pub fn triton_config(&self) -> &TritonConfig {
         &self.triton_config
     }
@@ -414,12 +1120,55 @@ impl InferenceModel {
     pub fn model_config(&self) -> &ModelConfig {
         &self.model_config
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model_config() -> ModelConfig {
+        serde_yaml::from_str("
+            name: yolo
+            input_shape: [3, 640, 640]
+            output_name: [boxes, scores]
+        ").unwrap()
+    }
+
+    #[test]
+    fn builds_identical_requests_from_per_sample_shape() {
+        let model_config = model_config();
+
+        let first = infer_request(&model_config, 4, vec![0; 16]);
+        let second = infer_request(&model_config, 4, vec![0; 16]);
 
-    pub fn base_request(&self) -> &ModelInferRequest {
-        &self.base_request
+        assert_eq!(first.inputs[0].shape, vec![4, 3, 640, 640]);
+        assert_eq!(first, second);
+        // A previous batch never leaks its batch dimension into the next request
+        assert_eq!(infer_request(&model_config, 2, Vec::new()).inputs[0].shape, vec![2, 3, 640, 640]);
+        assert_eq!(model_config.input_shape, vec![3, 640, 640]);
     }
 
-    pub fn stats_handle(&self) -> &std::thread::JoinHandle<()> {
-        &self.stats_handle
+    #[test]
+    fn creates_random_inputs_within_unit_range() {
+        let input = random_input(4000, InferencePrecision::FP32);
+        assert_eq!(input.len(), 4000);
+        assert!(input.chunks(4).all(|value| (0.0..1.0).contains(&f32::from_le_bytes(value.try_into().unwrap()))));
+
+        // Half floats below 1 have an exponent under the bias of 15
+        let input = random_input(4000, InferencePrecision::FP16);
+        assert_eq!(input.len(), 4000);
+        assert!(input.chunks(2).all(|value| u16::from_le_bytes(value.try_into().unwrap()) >> 10 < 15));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn moves_payload_into_request() {
+        let payload = vec![7u8; 1024];
+        let payload_ptr = payload.as_ptr();
+
+        let request = infer_request(&model_config(), 1, payload);
+
+        assert_eq!(request.raw_input_contents.len(), 1);
+        assert_eq!(request.raw_input_contents[0].as_ptr(), payload_ptr);
+        assert_eq!(request.outputs.iter().map(|output| output.name.as_str()).collect::<Vec<_>>(), vec!["boxes", "scores"]);
+    }
+}
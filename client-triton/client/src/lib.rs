@@ -7,7 +7,14 @@ pub mod utils;
 pub mod inference;
 pub mod processing;
 pub mod client_video;
+pub mod shared_memory;
 pub mod source;
+pub mod admin;
+pub mod eval;
+
+// In-process results subscription
+pub use source::{subscribe_results, recv_results, recent_results};
+pub use processing::FrameResults;
 
 pub static TOKIO_RUNTIME: OnceCell<Handle> = OnceCell::const_new();
 
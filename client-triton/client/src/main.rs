@@ -1,29 +1,111 @@
+use std::sync::Arc;
+use std::time::Duration;
 use anyhow::{Result, Context};
 
 // Custom modules
 use client::inference;
 use client::source;
+use client::admin;
+use client::eval;
+use client::shared_memory;
 use client::utils::{
     kafka,
+    control,
+    hot_reload,
+    persistence,
+    shutdown,
+    stats_sink,
     config::AppConfig
 };
 use client::client_video::ClientVideo;
 
+/// Maximum time to wait for queued Kafka messages on shutdown
+const KAFKA_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Command line arguments of the application
+struct CliArgs {
+    profile: Option<String>,
+    /// Only load and validate the configuration, without connecting to any service
+    check_config: bool,
+    /// COCO annotations to evaluate the model against, instead of processing sources
+    eval_annotations: Option<String>,
+    /// Directory of the evaluated images, defaulting to the directory of the annotations
+    eval_images: Option<String>
+}
+
+impl CliArgs {
+    /// Parses arguments given to the application.
+    /// Profile is taken from `--profile <name>`, falling back to the PROFILE variable
+    fn parse() -> Result<Self> {
+        let mut profile = None;
+        let mut check_config = false;
+        let mut eval_annotations = None;
+        let mut eval_images = None;
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            if arg == "--check-config" {
+                check_config = true;
+            } else if arg == "--profile" {
+                profile = Some(args.next().context("Missing value for --profile")?);
+            } else if let Some(value) = arg.strip_prefix("--profile=") {
+                profile = Some(value.to_string());
+            } else if arg == "--eval" {
+                eval_annotations = Some(args.next().context("Missing value for --eval")?);
+            } else if arg == "--eval-images" {
+                eval_images = Some(args.next().context("Missing value for --eval-images")?);
+            } else {
+                anyhow::bail!("Unknown argument '{}'", arg);
+            }
+        }
+
+        let profile = profile.or_else(|| std::env::var("PROFILE").ok());
+
+        Ok(Self { profile, check_config, eval_annotations, eval_images })
+    }
+}
+
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<()> {
+    let cli_args = CliArgs::parse()
+        .context("Error parsing command line arguments")?;
+
     // Iniaitlize config
-    let app_config = AppConfig::new()
+    let app_config = AppConfig::new(cli_args.profile.as_deref())
         .context("Error loading config")?;
+    let app_config = Arc::new(app_config);
+
+    // Configuration is validated while loading - exits with an error listing all invalid fields
+    if cli_args.check_config {
+        println!("Configuration is valid");
+        return Ok(());
+    }
 
     client::init_tokio_runtime(tokio::runtime::Handle::current())
         .await
         .context("Error initializing tokio runtime")?;
 
+    // Run the registered shutdown steps before exiting on SIGINT/SIGTERM
+    shutdown::init_shutdown_handler();
+
+    // Persist statistics locally for offline analysis
+    stats_sink::init_stats_sink(&app_config)
+        .context("Error initiating stats sink")?;
+
+    // Expose metrics and admin operations
+    admin::init_admin_server(&app_config)
+        .await
+        .context("Error initiating admin server")?;
+
     // Initiate Kafka producer
     kafka::init_kafka_producer(&app_config)
         .await
         .context("Error initiating Kafka producer")?;
 
+    // Publish results still batched, then wait for queued messages to be delivered
+    shutdown::register_shutdown_hook("result_batches", || Box::pin(kafka::flush_result_batches(None)));
+    shutdown::register_shutdown_hook("kafka", || Box::pin(kafka::flush_producer(KAFKA_SHUTDOWN_TIMEOUT)));
+
     // Initiate inference client
     inference::init_inference_models(&app_config)
         .await
@@ -33,19 +115,75 @@ async fn main() -> Result<()> {
         .await
         .context("Error initiating inference model instances")?;
 
+    // Find the optimal batch sizes for the current hardware
+    if app_config.benchmark_on_startup() {
+        inference::run_startup_benchmarks(&app_config)
+            .await
+            .context("Error benchmarking inference models")?;
+    }
+
+    // Validate the model against ground truth, without processing sources
+    if let Some(eval_annotations) = &cli_args.eval_annotations {
+        return eval::run_eval(&app_config, eval_annotations, cli_args.eval_images.as_deref())
+            .await
+            .context("Error evaluating model");
+    }
+
+    // Reload models that stop returning valid results
+    inference::start_model_health_checks(&app_config);
+
     // Initiate sources processors
     source::init_source_processors(&app_config)
         .await
         .context("Error initiating source processors")?;
 
+    // Accept runtime control commands
+    control::start_control_consumer(&app_config)
+        .context("Error initiating control consumer")?;
+
+    // Restore and persist source states across restarts
+    persistence::init_state_persistence(&app_config)
+        .await
+        .context("Error initiating state persistence")?;
+
+    // Apply configuration changes without restarting streams
+    hot_reload::register_apply_hook("sources", |app_config| Box::pin(async move {
+        source::apply_sources_config(&app_config).await
+    }));
+    hot_reload::init_hot_reload(Arc::clone(&app_config))
+        .context("Error initiating configuration hot reload")?;
+
+    // Report sources that stop delivering frames
+    source::start_readiness_monitor(&app_config);
+
+    // Throttle frame delivery of overloaded sources
+    source::start_backpressure_coordinator(&app_config);
+
     // Start receiving frames from sources
-    ClientVideo::set_callbacks()
+    ClientVideo::set_stream_config(&app_config)
         .await
-        .context("Error setting Client Video callbacks")?;
+        .context("Error setting Client Video stream config")?;
 
-    ClientVideo::init_sources(&app_config)
+    ClientVideo::set_callbacks()
         .await
         .context("Error setting Client Video callbacks")?;
 
+    // Shared memory sources are read alongside the video client sources
+    tokio::try_join!(
+        async {
+            ClientVideo::init_sources(&app_config)
+                .await
+                .context("Error setting Client Video callbacks")
+        },
+        async {
+            shared_memory::run_shared_memory_sources(&app_config)
+                .await
+                .context("Error reading shared memory sources")
+        }
+    )?;
+
+    // Sources finished without a shutdown signal
+    shutdown::run_shutdown_hooks().await;
+
     Ok(())
 }
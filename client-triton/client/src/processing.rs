@@ -2,13 +2,21 @@
 //! Performs operations on raw frames/inference results with SIMD optimizations
 
 use anyhow::Result;
-use std::sync::OnceLock;
+use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::hash::Hasher;
+use std::num::NonZeroUsize;
+use fnv::FnvHasher;
+use lru::LruCache;
 use tokio::time::Instant;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // Custom modules
 pub mod yolo;
 pub mod dino;
+pub mod motion;
+pub mod depth;
+pub mod gallery;
 use crate::utils::config::InferencePrecision;
 
 /// Normalization constants
@@ -16,18 +24,42 @@ const IMAGENET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
 const IMAGENET_STD: [f32; 3] = [0.229, 0.224, 0.225];
 const PAD_GRAY_COLOR: usize = 114;
 
+/// COCO category ids of the 80 contiguous class ids the detection model was trained on
+const COCO_CATEGORY_IDS: [u32; 80] = [
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 14, 15, 16, 17, 18, 19, 20, 21,
+    22, 23, 24, 25, 27, 28, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44,
+    46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 65,
+    67, 70, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 84, 85, 86, 87, 88, 89, 90
+];
+
 /// Represents raw frame before performing inference on it
 #[derive(Clone, Debug)]
 pub struct RawFrame {
     pub data: Vec<u8>,
     pub height: u32,
     pub width: u32,
+    /// Dimensions of the frame as received from the source, before any downscale.
+    /// Results are reported in these coordinates
+    pub original_height: u32,
+    pub original_width: u32,
     pub pts: u64,
     pub added: Instant
 }
 
+impl RawFrame {
+    /// Factor from frame to original frame horizontal coordinates
+    pub fn scale_x(&self) -> f32 {
+        self.original_width as f32 / self.width as f32
+    }
+
+    /// Factor from frame to original frame vertical coordinates
+    pub fn scale_y(&self) -> f32 {
+        self.original_height as f32 / self.height as f32
+    }
+}
+
 /// Represents a single bbox instance from the model inference results
-#[derive(Clone, Copy, Serialize)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct ResultBBOX {
     pub bbox: [f32; 4],
     pub class: u32, 
@@ -55,17 +87,37 @@ impl ResultBBOX {
         let y2 = self.bbox[3] as u32;
         
         // Calculate 1D array indices
-        let top_left_corner = y1 * frame.width + x1;
-        let bottom_right_corner = y2 * frame.width + x2;
+        let top_left_corner = y1 * frame.original_width + x1;
+        let bottom_right_corner = y2 * frame.original_width + x2;
 
         return (top_left_corner, bottom_right_corner)
     }
+
+    /// Returns the bbox as COCO `[x, y, width, height]` in original frame coordinates
+    pub fn coco_bbox(&self) -> [f32; 4] {
+        coco_bbox(&self.bbox)
+    }
+}
+
+/// Converts bbox corners `[x1, y1, x2, y2]` to COCO `[x, y, width, height]`
+pub fn coco_bbox(bbox: &[f32; 4]) -> [f32; 4] {
+    [bbox[0], bbox[1], bbox[2] - bbox[0], bbox[3] - bbox[1]]
+}
+
+/// Returns the COCO category id of a class id, classes outside of COCO keep their id
+pub fn coco_category_id(class: u32) -> u32 {
+    COCO_CATEGORY_IDS
+        .get(class as usize)
+        .copied()
+        .unwrap_or(class)
 }
 
 /// Represents embedding output from the model inference results
 #[derive(Clone, Serialize)]
 pub struct ResultEmbedding {
-    pub data: Vec<f32>
+    pub data: Vec<f32>,
+    pub embedding_version: u32,
+    pub model_name: String
 }
 
 impl ResultEmbedding {
@@ -77,6 +129,203 @@ impl ResultEmbedding {
             )
         }.to_vec()
     }
+
+    /// Returns the dot product with another embedding
+    pub fn dot(&self, other: &ResultEmbedding) -> Result<f32> {
+        check_embedding_lengths(&self.data, &other.data)?;
+        Ok(dot_product(&self.data, &other.data))
+    }
+
+    /// Returns the cosine similarity with another embedding, 0 when either has no magnitude
+    pub fn cosine_similarity(&self, other: &ResultEmbedding) -> Result<f32> {
+        check_embedding_lengths(&self.data, &other.data)?;
+        Ok(cosine_similarity(&self.data, &other.data))
+    }
+}
+
+fn check_embedding_lengths(a: &[f32], b: &[f32]) -> Result<()> {
+    if a.len() != b.len() {
+        anyhow::bail!("Embedding length mismatch: {} and {}", a.len(), b.len())
+    }
+
+    Ok(())
+}
+
+/// Returns the cosine similarity of two vectors of the same length, 0 when either has no magnitude
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let magnitude = (dot_product(a, a) * dot_product(b, b)).sqrt();
+    match magnitude > 0.00 {
+        true => dot_product(a, b) / magnitude,
+        false => 0.00
+    }
+}
+
+/// Returns the dot product of two vectors, over the shorter length when they differ.
+/// Uses AVX2 when available on the CPU
+pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+        // SAFETY: the required CPU features were detected above
+        return unsafe { dot_product_avx2(a, b) };
+    }
+
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn dot_product_avx2(a: &[f32], b: &[f32]) -> f32 {
+    use std::arch::x86_64::*;
+
+    let len = a.len().min(b.len());
+    let chunks = len / 8;
+
+    let mut sum = _mm256_setzero_ps();
+    for i in 0..chunks {
+        // SAFETY: the chunk of 8 values is within both slices
+        let (va, vb) = unsafe {
+            (_mm256_loadu_ps(a.as_ptr().add(i * 8)), _mm256_loadu_ps(b.as_ptr().add(i * 8)))
+        };
+        sum = _mm256_fmadd_ps(va, vb, sum);
+    }
+
+    // Sum the 8 lanes, then the remainder
+    let mut lanes = [0.00f32; 8];
+    // SAFETY: the array holds exactly 8 values
+    unsafe { _mm256_storeu_ps(lanes.as_mut_ptr(), sum) };
+    let remainder: f32 = a[chunks * 8..len].iter().zip(&b[chunks * 8..len]).map(|(a, b)| a * b).sum();
+
+    lanes.iter().sum::<f32>() + remainder
+}
+
+/// Represents a single detection along with the embedding of its bbox crop
+#[derive(Clone, Serialize)]
+pub struct DetectionWithEmbedding {
+    /// Index of the detection in the YOLO results of the frame
+    pub detection_index: usize,
+    pub bbox: [f32; 4],
+    pub class: u32,
+    pub class_name: &'static str,
+    pub score: f32,
+    /// Index of the embedding in the DINO results of the frame, if one was produced
+    pub embedding_index: Option<usize>,
+    pub embedding: Option<Vec<f32>>
+}
+
+/// Represents the combined results of all pipeline stages for a single frame
+#[derive(Clone, Serialize)]
+pub struct FrameResults {
+    pub pts: u64,
+    pub source_id: String,
+    pub model_name: Option<String>,
+    pub embedding_version: Option<u32>,
+    pub frame_embedding: Option<Vec<f32>>,
+    pub detections: Vec<DetectionWithEmbedding>
+}
+
+impl FrameResults {
+    /// Combines detections with their embeddings
+    /// 
+    /// DINO results are ordered as the full frame embedding followed by an embedding
+    /// per bbox, in the order of the bboxes - so bbox `i` maps to embedding `i + 1`
+    pub fn new(
+        source_id: &str,
+        frame: &RawFrame,
+        bboxes: &[ResultBBOX],
+        embeddings: Option<&[ResultEmbedding]>
+    ) -> Result<Self> {
+        if let Some(embeddings) = embeddings {
+            if embeddings.len() != bboxes.len() + 1 {
+                anyhow::bail!(
+                    "Got unexpected amount of embeddings. Got {}, expected {}",
+                    embeddings.len(),
+                    bboxes.len() + 1
+                );
+            }
+        }
+
+        let detections = bboxes
+            .iter()
+            .enumerate()
+            .map(|(detection_index, bbox)| {
+                let embedding_index = embeddings.map(|_| detection_index + 1);
+
+                DetectionWithEmbedding {
+                    detection_index,
+                    bbox: bbox.bbox,
+                    class: bbox.class,
+                    class_name: bbox.class_name(),
+                    score: bbox.score,
+                    embedding_index,
+                    embedding: embeddings
+                        .zip(embedding_index)
+                        .map(|(embeddings, index)| embeddings[index].data.clone())
+                }
+            })
+            .collect();
+
+        let first_embedding = embeddings.and_then(|embeddings| embeddings.first());
+
+        Ok(
+            Self {
+                pts: frame.pts,
+                source_id: source_id.to_string(),
+                model_name: first_embedding.map(|e| e.model_name.clone()),
+                embedding_version: first_embedding.map(|e| e.embedding_version),
+                frame_embedding: first_embedding.map(|e| e.data.clone()),
+                detections
+            }
+        )
+    }
+}
+
+/// Cache of preprocessed frames keyed by a hash of the frame, so frames processed multiple times
+/// (e.g. replayed with different postprocess settings) are preprocessed once
+pub struct PreprocessCache {
+    entries: Mutex<LruCache<u64, Vec<u8>>>,
+    hits: AtomicU64
+}
+
+impl PreprocessCache {
+    /// Creates a cache of the given capacity, None when the capacity is 0
+    pub fn new(capacity: usize) -> Option<Self> {
+        NonZeroUsize::new(capacity).map(|capacity| Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicU64::new(0)
+        })
+    }
+
+    /// Returns the cached preprocessed frame, preprocessing and caching it when missing
+    pub fn get_or_insert_with<F>(&self, frame: &RawFrame, preprocess: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce() -> Result<Vec<u8>>
+    {
+        let key = PreprocessCache::hash_frame(frame);
+        if let Some(preprocessed) = self.entries.lock().unwrap().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(preprocessed.clone());
+        }
+
+        let preprocessed = preprocess()?;
+        self.entries.lock().unwrap().put(key, preprocessed.clone());
+
+        Ok(preprocessed)
+    }
+
+    /// Returns the amount of frames served from cache
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Hashes frame pixels with FNV, including dimensions as frames of equal bytes may differ in shape
+    fn hash_frame(frame: &RawFrame) -> u64 {
+        let mut hasher = FnvHasher::default();
+        hasher.write_u32(frame.width);
+        hasher.write_u32(frame.height);
+        hasher.write(&frame.data);
+
+        hasher.finish()
+    }
 }
 
 /// Lookup table for converting values from FP16 to FP32
@@ -257,6 +506,44 @@ pub fn calculate_letterbox(
     }
 }
 
+/// Calculates the source offsets of each output column for nearest-neighbor sampling
+fn nearest_x_offsets(new_width: u32, inv_scale: f32, in_w: u32) -> Vec<u32> {
+    let mut x_offsets: Vec<u32> = Vec::with_capacity(new_width as usize);
+    for x in 0..new_width {
+        x_offsets.push(((x as f32 * inv_scale) as u32).min(in_w - 1) * 3);
+    }
+
+    x_offsets
+}
+
+/// Downscales a raw RGB frame with nearest-neighbor, so its largest dimension fits `max_dimension`
+/// while preserving aspect ratio. Returns the resized frame along with its height and width
+pub fn resize_nearest_rgb(
+    input: &[u8],
+    in_h: u32,
+    in_w: u32,
+    max_dimension: u32,
+) -> (Vec<u8>, u32, u32) {
+    let letterbox = calculate_letterbox(in_h, in_w, max_dimension);
+    let new_h = letterbox.new_height.max(1);
+    let new_w = letterbox.new_width.max(1);
+
+    let x_offsets = nearest_x_offsets(new_w, letterbox.inv_scale, in_w);
+    let mut output = Vec::with_capacity((new_h * new_w * 3) as usize);
+
+    for y in 0..new_h {
+        let src_y = ((y as f32 * letterbox.inv_scale) as u32).min(in_h - 1);
+        let src_row_offset = (src_y * in_w * 3) as usize;
+
+        for &x_offset in x_offsets.iter() {
+            let src_idx = src_row_offset + x_offset as usize;
+            output.extend_from_slice(&input[src_idx..src_idx + 3]);
+        }
+    }
+
+    (output, new_h, new_w)
+}
+
 ///
 /// Performs a single-pass, fused nearest-neighbor resize, letterbox,
 /// and pixel normalization (x / 255.0).
@@ -287,10 +574,7 @@ pub fn resize_letterbox_and_normalize(
     };
 
     // 3. Pre-calculate x-offsets for the source image
-    let mut x_offsets: Vec<u32> = Vec::with_capacity(letterbox.new_width as usize);
-    for x in 0..letterbox.new_width {
-        x_offsets.push(((x as f32 * letterbox.inv_scale) as u32).min(in_w - 1) * 3);
-    }
+    let x_offsets = nearest_x_offsets(letterbox.new_width, letterbox.inv_scale, in_w);
 
     let in_ptr = input.as_ptr();
 
@@ -415,10 +699,7 @@ pub fn resize_letterbox_and_normalize_imagenet(
     let norm_lut_f32 = get_f32_lut(); // u8 -> f32 (0-1)
 
     // 4. Pre-calculate x-offsets for the source image
-    let mut x_offsets: Vec<u32> = Vec::with_capacity(letterbox.new_width as usize);
-    for x in 0..letterbox.new_width {
-        x_offsets.push(((x as f32 * letterbox.inv_scale) as u32).min(in_w - 1) * 3);
-    }
+    let x_offsets = nearest_x_offsets(letterbox.new_width, letterbox.inv_scale, in_w);
 
     let in_ptr = input.as_ptr();
 
@@ -506,4 +787,90 @@ pub fn resize_letterbox_and_normalize_imagenet(
     }
 
     Ok(output)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_frame(pts: u64) -> RawFrame {
+        RawFrame {
+            data: Vec::new(),
+            height: 480,
+            width: 640,
+            original_height: 480,
+            original_width: 640,
+            pts,
+            added: Instant::now()
+        }
+    }
+
+    fn bboxes() -> Vec<ResultBBOX> {
+        vec![
+            ResultBBOX { bbox: [10.0, 20.0, 30.0, 40.0], class: 0, score: 0.9 },
+            ResultBBOX { bbox: [50.0, 60.0, 70.0, 80.0], class: 2, score: 0.6 }
+        ]
+    }
+
+    fn embedding(value: f32) -> ResultEmbedding {
+        ResultEmbedding { data: vec![value; 4], embedding_version: 3, model_name: "dino".to_string() }
+    }
+
+    #[test]
+    fn combines_detections_with_their_embeddings() {
+        let embeddings = vec![embedding(0.0), embedding(1.0), embedding(2.0)];
+
+        let results = FrameResults::new("7", &raw_frame(42), &bboxes(), Some(&embeddings)).unwrap();
+
+        assert_eq!(results.frame_embedding, Some(vec![0.0; 4]));
+        assert_eq!(results.model_name.as_deref(), Some("dino"));
+        assert_eq!(results.embedding_version, Some(3));
+        for (index, detection) in results.detections.iter().enumerate() {
+            assert_eq!(detection.detection_index, index);
+            assert_eq!(detection.embedding_index, Some(index + 1));
+            assert_eq!(detection.embedding, Some(vec![(index + 1) as f32; 4]));
+        }
+        assert_eq!(results.detections[1].bbox, [50.0, 60.0, 70.0, 80.0]);
+        assert_eq!(results.detections[1].class_name, "car");
+    }
+
+    #[test]
+    fn combines_detections_without_embeddings() {
+        let results = FrameResults::new("7", &raw_frame(42), &bboxes(), None).unwrap();
+
+        assert_eq!(results.frame_embedding, None);
+        assert!(results.detections.iter().all(|detection| detection.embedding_index.is_none() && detection.embedding.is_none()));
+    }
+
+    #[test]
+    fn rejects_embeddings_not_matching_detections() {
+        let embeddings = vec![embedding(0.0), embedding(1.0)];
+
+        assert!(FrameResults::new("7", &raw_frame(42), &bboxes(), Some(&embeddings)).is_err());
+    }
+
+    #[test]
+    fn serializes_combined_results_schema() {
+        let embeddings = vec![embedding(0.0), embedding(1.0), embedding(2.0)];
+        let results = FrameResults::new("7", &raw_frame(42), &bboxes()[..1], Some(&embeddings[..2])).unwrap();
+
+        let payload = serde_json::to_value(&results).unwrap();
+
+        assert_eq!(payload, serde_json::json!({
+            "pts": 42,
+            "source_id": "7",
+            "model_name": "dino",
+            "embedding_version": 3,
+            "frame_embedding": [0.0, 0.0, 0.0, 0.0],
+            "detections": [{
+                "detection_index": 0,
+                "bbox": [10.0, 20.0, 30.0, 40.0],
+                "class": 0,
+                "class_name": "person",
+                "score": 0.9f32,
+                "embedding_index": 1,
+                "embedding": [1.0, 1.0, 1.0, 1.0]
+            }]
+        }));
+    }
+}
@@ -0,0 +1,120 @@
+/// Module for filtering detections by distance, using depth frames of a paired depth source
+
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+// Custom modules
+use crate::processing::ResultBBOX;
+use crate::utils::config::DepthFilterConfig;
+
+/// Amount of recent depth frames kept per depth source
+const MAX_DEPTH_FRAMES: usize = 30;
+
+/// Recent depth frames of all depth sources
+pub static DEPTH_FRAMES: Lazy<DepthFrameStore> = Lazy::new(DepthFrameStore::new);
+
+/// Represents a single depth frame
+///
+/// Depth frames arrive as RGB24 frames, carrying a 16 bit raw depth value
+/// per pixel in the first two channels - R holds the high byte, G the low byte
+pub struct DepthFrame {
+    pub data: Vec<u8>,
+    pub height: u32,
+    pub width: u32,
+    pub pts: u64
+}
+
+impl DepthFrame {
+    /// Returns the raw depth value of a pixel
+    pub fn raw_depth(&self, x: u32, y: u32) -> Option<u16> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let idx = ((y * self.width + x) * 3) as usize;
+        let high = *self.data.get(idx)? as u16;
+        let low = *self.data.get(idx + 1)? as u16;
+
+        Some((high << 8) | low)
+    }
+}
+
+/// Keeps recent depth frames of each depth source, keyed by pts
+pub struct DepthFrameStore {
+    frames: Mutex<HashMap<String, VecDeque<Arc<DepthFrame>>>>
+}
+
+impl DepthFrameStore {
+    fn new() -> Self {
+        Self {
+            frames: Mutex::new(HashMap::new())
+        }
+    }
+
+    /// Adds a depth frame of a source, dropping the oldest frame when full
+    pub fn insert(&self, source_id: &str, frame: DepthFrame) {
+        let mut frames = self.frames.lock().unwrap();
+        let source_frames = frames
+            .entry(source_id.to_string())
+            .or_insert_with(|| VecDeque::with_capacity(MAX_DEPTH_FRAMES));
+
+        if source_frames.len() >= MAX_DEPTH_FRAMES {
+            source_frames.pop_front();
+        }
+        source_frames.push_back(Arc::new(frame));
+    }
+
+    /// Returns the depth frame of a source with the closest pts to the given one
+    pub fn get_closest(&self, source_id: &str, pts: u64) -> Option<Arc<DepthFrame>> {
+        self.frames
+            .lock()
+            .unwrap()
+            .get(source_id)?
+            .iter()
+            .min_by_key(|frame| frame.pts.abs_diff(pts))
+            .cloned()
+    }
+}
+
+/// Removes detections further than the configured max distance
+///
+/// Looks up the depth at the centroid of each bbox in the depth frame closest to the given pts.
+/// Depth frames of different resolution are mapped proportionally, as the sensors are co-registered.
+/// Detections are kept when no depth frame or depth value is available.
+/// Returns amount of filtered detections
+pub fn filter_bboxes(
+    config: &DepthFilterConfig,
+    pts: u64,
+    frame_height: u32,
+    frame_width: u32,
+    bboxes: &mut Vec<ResultBBOX>
+) -> usize {
+    let Some(depth_frame) = DEPTH_FRAMES.get_closest(&config.depth_source_id, pts) else {
+        return 0;
+    };
+
+    if frame_height == 0 || frame_width == 0 {
+        return 0;
+    }
+
+    let scale_x = depth_frame.width as f32 / frame_width as f32;
+    let scale_y = depth_frame.height as f32 / frame_height as f32;
+
+    let before = bboxes.len();
+    bboxes.retain(|bbox| {
+        let center_x = (bbox.bbox[0] + bbox.bbox[2]) * 0.5;
+        let center_y = (bbox.bbox[1] + bbox.bbox[3]) * 0.5;
+
+        let depth_x = (center_x.max(0.0) * scale_x) as u32;
+        let depth_y = (center_y.max(0.0) * scale_y) as u32;
+
+        match depth_frame.raw_depth(depth_x, depth_y) {
+            // Raw value of 0 means no depth reading
+            Some(raw) if raw > 0 => raw as f32 * config.depth_scale <= config.max_distance_m,
+            _ => true
+        }
+    });
+
+    before - bboxes.len()
+}
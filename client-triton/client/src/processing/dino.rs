@@ -5,10 +5,11 @@ use std::sync::Arc;
 use std::time::Instant;
 
 // Custom modules
-use crate::inference::InferenceModel;
+use crate::inference::Inference;
 use crate::source::FrameProcessStats;
 use crate::processing::{self, RawFrame, ResultEmbedding, ResultBBOX};
 use crate::utils::config::InferencePrecision;
+use crate::utils::metrics::{self, InferenceErrorKind};
 
 /// Performs pre-processing on raw RGB frame for DINOv3 model
 /// 
@@ -43,10 +44,12 @@ pub fn preprocess(
 /// Performs post-processing on multiple raw inference results from DINOv3 models
 /// 
 /// Takes a Vec of raw Vec<u8> outputs from batch model inference and converts them to 
-/// a Vec of ResultEmbedding containing the feature vectors.
+/// a Vec of ResultEmbedding containing the feature vectors, tagged with the model that produced them.
 pub fn postprocess(
     raw_results: Vec<Vec<u8>>,
     precision: InferencePrecision,
+    model_name: &str,
+    embedding_version: u32,
 ) -> Result<Vec<ResultEmbedding>> {
     let mut embeddings = Vec::with_capacity(raw_results.len());
     
@@ -65,7 +68,7 @@ pub fn postprocess(
                         data.push(processing::get_f16_to_f32_lut(*raw_ptr.add(i)));
                     }
                 }
-                ResultEmbedding { data }
+                data
             }
             InferencePrecision::FP32 => {
                 let raw_ptr = raw_result.as_ptr() as *const f32;
@@ -77,11 +80,17 @@ pub fn postprocess(
                     )
                 };
                 std::mem::forget(raw_result);
-                ResultEmbedding { data }
+                data
             }
         };
         
-        embeddings.push(embedding);
+        embeddings.push(
+            ResultEmbedding {
+                data: embedding,
+                embedding_version,
+                model_name: model_name.to_string()
+            }
+        );
     }
     
     Ok(embeddings)
@@ -99,13 +108,17 @@ pub fn preprocess_bboxes(
     const TARGET_SIZE: u32 = 224;
     
     let mut results = Vec::with_capacity(bboxes.len());
+
+    // BBOXes are in original frame coordinates, map them onto the (possibly downscaled) frame
+    let scale_x = frame.scale_x();
+    let scale_y = frame.scale_y();
     
     for bbox in bboxes {
         // Extract bbox coordinates [x1, y1, x2, y2]
-        let x1 = bbox.bbox[0].max(0.0) as u32;
-        let y1 = bbox.bbox[1].max(0.0) as u32;
-        let x2 = (bbox.bbox[2].min(frame.width as f32)) as u32;
-        let y2 = (bbox.bbox[3].min(frame.height as f32)) as u32;
+        let x1 = (bbox.bbox[0] / scale_x).max(0.0) as u32;
+        let y1 = (bbox.bbox[1] / scale_y).max(0.0) as u32;
+        let x2 = ((bbox.bbox[2] / scale_x).min(frame.width as f32)) as u32;
+        let y2 = ((bbox.bbox[3] / scale_y).min(frame.height as f32)) as u32;
         
         // Calculate bbox dimensions
         let bbox_width = x2.saturating_sub(x1);
@@ -162,7 +175,7 @@ pub fn preprocess_bboxes(
 }
 
 pub async fn process_frame(
-    inference_model: &InferenceModel,
+    inference_model: &dyn Inference,
     frame: Arc<RawFrame>,
     bboxes: Arc<Vec<ResultBBOX>>
 ) -> Result<(FrameProcessStats, Vec<ResultEmbedding>)> {
@@ -173,11 +186,16 @@ pub async fn process_frame(
     let precision = inference_model.model_config().precision;
     let frame_clone = Arc::clone(&frame);
     let bboxes_clone = Arc::clone(&bboxes);
+    let preprocess_cache = inference_model.preprocess_cache();
     
     let pre_inputs = tokio::task::spawn_blocking(move || {
         let mut pre_inputs = Vec::with_capacity(bboxes_clone.len() + 1);
 
-        let pre_frame = preprocess(&frame_clone, precision)
+        // Only the full frame is cached, crops depend on the bboxes of the frame
+        let pre_frame = match preprocess_cache {
+            Some(cache) => cache.get_or_insert_with(&frame_clone, || preprocess(&frame_clone, precision)),
+            None => preprocess(&frame_clone, precision)
+        }
             .context("Error preprocessing image for DinoV3")?;
         pre_inputs.push(pre_frame);
 
@@ -200,11 +218,14 @@ pub async fn process_frame(
 
     // Post process
     let measure_start = Instant::now();
+    let model_name = inference_model.model_config().name.clone();
+    let embedding_version = inference_model.model_config().version;
     let embeddings = tokio::task::spawn_blocking(move || {
-        postprocess(raw_results, precision)
+        postprocess(raw_results, precision, &model_name, embedding_version)
     })
         .await
         .context("Postprocess task failed")?
+        .inspect_err(|_| metrics::inc_inference_error(inference_model.model_type(), InferenceErrorKind::PostprocessFailed))
         .context("Error postprocessing embedding vectors for DinoV3")?;
     let post_proc_time = measure_start.elapsed();
 
@@ -0,0 +1,89 @@
+/// Module for matching embeddings against a small gallery of labeled reference embeddings,
+/// turning embeddings into identities on-device without a separate vector database
+
+use anyhow::Result;
+use std::sync::RwLock;
+
+// Custom modules
+use crate::processing::{self, ResultEmbedding};
+
+/// Represents a single reference embedding of a label
+struct GalleryEntry {
+    label: String,
+    data: Vec<f32>
+}
+
+/// Labeled reference embeddings, which can be added and removed at runtime.
+/// A label may have multiple reference embeddings, e.g. from different angles
+pub struct EmbeddingGallery {
+    entries: RwLock<Vec<GalleryEntry>>
+}
+
+impl EmbeddingGallery {
+    pub fn new() -> Self {
+        Self {
+            entries: RwLock::new(Vec::new())
+        }
+    }
+
+    /// Adds a reference embedding of a label. All embeddings of the gallery must share the same length
+    pub fn add(&self, label: &str, embedding: &ResultEmbedding) -> Result<()> {
+        let mut entries = self.entries.write().unwrap();
+        if let Some(entry) = entries.first() {
+            if entry.data.len() != embedding.data.len() {
+                anyhow::bail!(
+                    "Embedding length mismatch: gallery holds {}, got {}",
+                    entry.data.len(), embedding.data.len()
+                )
+            }
+        }
+
+        entries.push(GalleryEntry {
+            label: label.to_string(),
+            data: embedding.data.clone()
+        });
+
+        Ok(())
+    }
+
+    /// Removes all reference embeddings of a label, returning how many were removed
+    pub fn remove(&self, label: &str) -> usize {
+        let mut entries = self.entries.write().unwrap();
+        let before = entries.len();
+        entries.retain(|entry| entry.label != label);
+
+        before - entries.len()
+    }
+
+    /// Returns the amount of reference embeddings in the gallery
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns up to k labels most similar to the query, by cosine similarity in descending order.
+    /// Labels with multiple reference embeddings are scored by their best match
+    pub fn match_topk(&self, query: &ResultEmbedding, k: usize) -> Vec<(String, f32)> {
+        let entries = self.entries.read().unwrap();
+
+        let mut matches: Vec<(&str, f32)> = Vec::new();
+        for entry in entries.iter().filter(|entry| entry.data.len() == query.data.len()) {
+            let score = processing::cosine_similarity(&entry.data, &query.data);
+            match matches.iter_mut().find(|(label, _)| *label == entry.label) {
+                Some((_, best)) => *best = best.max(score),
+                None => matches.push((&entry.label, score))
+            }
+        }
+
+        matches.sort_by(|a, b| b.1.total_cmp(&a.1));
+        matches.truncate(k);
+
+        matches
+            .into_iter()
+            .map(|(label, score)| (label.to_string(), score))
+            .collect()
+    }
+}
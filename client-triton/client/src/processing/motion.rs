@@ -0,0 +1,248 @@
+/// Module for cheap motion detection on raw frames, used to skip inference on static scenes
+
+use std::time::{Duration, Instant};
+
+// Custom modules
+use crate::utils::config::MotionGateConfig;
+
+/// Size of the grayscale thumbnail frames are compared on
+pub const THUMBNAIL_SIZE: u32 = 64;
+
+/// Creates a small grayscale thumbnail of a raw RGB frame
+/// 
+/// Samples the frame with nearest-neighbor on a fixed grid, converting each
+/// sampled pixel to grayscale with integer luma weights.
+pub fn grayscale_thumbnail(frame: &[u8], height: u32, width: u32) -> Vec<u8> {
+    let mut thumbnail = Vec::with_capacity((THUMBNAIL_SIZE * THUMBNAIL_SIZE) as usize);
+    if height == 0 || width == 0 || frame.len() < (height * width * 3) as usize {
+        return thumbnail;
+    }
+
+    for y in 0..THUMBNAIL_SIZE {
+        let src_y = y * height / THUMBNAIL_SIZE;
+        let row_offset = (src_y * width * 3) as usize;
+
+        for x in 0..THUMBNAIL_SIZE {
+            let src_x = x * width / THUMBNAIL_SIZE;
+            let idx = row_offset + (src_x * 3) as usize;
+
+            let luma = (frame[idx] as u32 * 77 + frame[idx + 1] as u32 * 150 + frame[idx + 2] as u32 * 29) >> 8;
+            thumbnail.push(luma as u8);
+        }
+    }
+
+    thumbnail
+}
+
+/// Returns the mean absolute difference between two thumbnails, in pixel intensity (0-255)
+pub fn mean_abs_diff(a: &[u8], b: &[u8]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return f32::MAX;
+    }
+
+    // Simple loop over u8 pairs - vectorized by the compiler
+    let total: u64 = a.iter()
+        .zip(b.iter())
+        .map(|(&x, &y)| x.abs_diff(y) as u64)
+        .sum();
+
+    total as f32 / a.len() as f32
+}
+
+/// Decides whether frames carry enough motion to be worth inference
+/// 
+/// Compares each frame against the last frame that passed the gate. Frames pass
+/// when the difference exceeds the configured threshold, or when no frame has
+/// passed for the max idle interval - so static scenes still get a periodic heartbeat.
+pub struct MotionGate {
+    config: MotionGateConfig,
+    reference: Option<Vec<u8>>,
+    last_passed: Option<Instant>
+}
+
+impl MotionGate {
+    pub fn new(config: MotionGateConfig) -> Self {
+        Self {
+            config,
+            reference: None,
+            last_passed: None
+        }
+    }
+
+    /// Returns whether the frame with the given thumbnail should be sent to inference
+    pub fn check(&mut self, thumbnail: &[u8]) -> bool {
+        let max_idle = Duration::from_secs(self.config.max_idle_secs);
+        let idle_expired = self.last_passed
+            .map_or(true, |last| last.elapsed() >= max_idle);
+
+        let has_motion = match &self.reference {
+            Some(reference) => mean_abs_diff(reference, thumbnail) >= self.config.threshold,
+            None => true
+        };
+
+        if has_motion || idle_expired {
+            self.reference = Some(thumbnail.to_vec());
+            self.last_passed = Some(Instant::now());
+            return true;
+        }
+
+        false
+    }
+}
+
+/// Detects frames that are near-identical to the last processed frame
+///
+/// Consecutive duplicates can be limited, so static scenes still get periodic inference
+pub struct FrameDeduplicator {
+    threshold: f32,
+    max_consecutive_skips: Option<u32>,
+    consecutive_skips: u32,
+    last_processed: Option<Vec<u8>>
+}
+
+impl FrameDeduplicator {
+    pub fn new(threshold: f32, max_consecutive_skips: Option<u32>) -> Self {
+        Self {
+            threshold,
+            max_consecutive_skips,
+            consecutive_skips: 0,
+            last_processed: None
+        }
+    }
+
+    /// Returns whether the frame with the given thumbnail should be skipped as a duplicate
+    /// of the last processed frame, counting consecutive skips
+    pub fn is_duplicate(&mut self, thumbnail: &[u8]) -> bool {
+        let duplicate = match &self.last_processed {
+            Some(last) => mean_abs_diff(last, thumbnail) < self.threshold,
+            None => false
+        };
+
+        if !duplicate {
+            return false;
+        }
+
+        if let Some(max_skips) = self.max_consecutive_skips {
+            if self.consecutive_skips >= max_skips {
+                return false;
+            }
+        }
+
+        self.consecutive_skips += 1;
+        true
+    }
+
+    /// Stores the thumbnail of a frame that was sent to inference
+    pub fn set_processed(&mut self, thumbnail: Vec<u8>) {
+        self.consecutive_skips = 0;
+        self.last_processed = Some(thumbnail);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 128;
+    const HEIGHT: u32 = 96;
+
+    /// Gray RGB frame with a white square of the given size at the given position
+    fn frame_with_square(x: u32, y: u32, size: u32) -> Vec<u8> {
+        let mut frame = vec![64; (WIDTH * HEIGHT * 3) as usize];
+        for row in y..(y + size).min(HEIGHT) {
+            for column in x..(x + size).min(WIDTH) {
+                let offset = ((row * WIDTH + column) * 3) as usize;
+                frame[offset..offset + 3].fill(255);
+            }
+        }
+        frame
+    }
+
+    fn thumbnail(frame: &[u8]) -> Vec<u8> {
+        grayscale_thumbnail(frame, HEIGHT, WIDTH)
+    }
+
+    fn motion_gate() -> MotionGate {
+        MotionGate::new(MotionGateConfig { threshold: 2.0, max_idle_secs: 60 })
+    }
+
+    #[test]
+    fn thumbnails_are_grayscale_and_fixed_size() {
+        let thumbnail = thumbnail(&frame_with_square(0, 0, 0));
+
+        assert_eq!(thumbnail.len(), (THUMBNAIL_SIZE * THUMBNAIL_SIZE) as usize);
+        assert!(thumbnail.iter().all(|&pixel| pixel == 64));
+        assert!(grayscale_thumbnail(&[0; 3], HEIGHT, WIDTH).is_empty());
+    }
+
+    #[test]
+    fn measures_mean_abs_diff() {
+        assert_eq!(mean_abs_diff(&[10, 20], &[10, 20]), 0.0);
+        assert_eq!(mean_abs_diff(&[10, 20], &[20, 0]), 15.0);
+        assert_eq!(mean_abs_diff(&[10], &[10, 20]), f32::MAX);
+    }
+
+    #[test]
+    fn gates_static_scene() {
+        let mut gate = motion_gate();
+        let static_frame = thumbnail(&frame_with_square(10, 10, 16));
+
+        assert!(gate.check(&static_frame), "first frame has no reference");
+        for _ in 0..10 {
+            assert!(!gate.check(&static_frame));
+        }
+    }
+
+    #[test]
+    fn passes_moving_scene() {
+        let mut gate = motion_gate();
+
+        for step in 0..5 {
+            assert!(gate.check(&thumbnail(&frame_with_square(step * 20, 20, 32))));
+        }
+    }
+
+    #[test]
+    fn passes_static_scene_once_idle() {
+        let mut gate = motion_gate();
+        let static_frame = thumbnail(&frame_with_square(10, 10, 16));
+        assert!(gate.check(&static_frame));
+        assert!(!gate.check(&static_frame));
+
+        gate.last_passed = Some(Instant::now() - Duration::from_secs(61));
+
+        assert!(gate.check(&static_frame), "heartbeat frame passes without motion");
+        assert!(!gate.check(&static_frame));
+    }
+
+    #[test]
+    fn skips_duplicates_without_limit() {
+        let mut deduplicator = FrameDeduplicator::new(1.0, None);
+        let static_frame = thumbnail(&frame_with_square(10, 10, 16));
+
+        assert!(!deduplicator.is_duplicate(&static_frame), "first frame has no reference");
+        deduplicator.set_processed(static_frame.clone());
+
+        for _ in 0..100 {
+            assert!(deduplicator.is_duplicate(&static_frame));
+        }
+        assert!(!deduplicator.is_duplicate(&thumbnail(&frame_with_square(60, 40, 32))));
+    }
+
+    #[test]
+    fn limits_consecutive_duplicate_skips() {
+        let mut deduplicator = FrameDeduplicator::new(1.0, Some(3));
+        let static_frame = thumbnail(&frame_with_square(10, 10, 16));
+        deduplicator.set_processed(static_frame.clone());
+
+        for _ in 0..2 {
+            for _ in 0..3 {
+                assert!(deduplicator.is_duplicate(&static_frame));
+            }
+            assert!(!deduplicator.is_duplicate(&static_frame), "frame after the limit goes to inference");
+
+            // Processing the frame restarts the count
+            deduplicator.set_processed(static_frame.clone());
+        }
+    }
+}
@@ -3,11 +3,12 @@ use std::time::Instant;
 use std::sync::Arc;
 
 // Custom modules
-use crate::inference::InferenceModel;
+use crate::inference::Inference;
 use crate::source::FrameProcessStats;
 use crate::processing::{self, RawFrame, ResultBBOX};
-use crate::utils::config::SourceConfig;
+use crate::utils::config::{SourceConfig, NmsMode};
 use crate::utils::config::InferencePrecision;
+use crate::utils::metrics::{self, InferenceErrorKind};
 
 /// Performs pre-processing on raw RGB frame for YOLO models
 /// 
@@ -44,8 +45,10 @@ pub fn preprocess(
 }
 
 /// Perform NMS reduction of bboxes
+/// 
+/// When class agnostic, overlapping bboxes suppress each other regardless of their classes
 #[inline(never)] // Don't inline to keep instruction cache hot for main loop
-fn bbox_nms(detections: &mut Vec<ResultBBOX>, nms_threshold: f32) {
+fn bbox_nms(detections: &mut Vec<ResultBBOX>, nms_threshold: f32, class_agnostic: bool) {
     let len = detections.len();
     if len <= 1 {
         return;
@@ -67,7 +70,7 @@ fn bbox_nms(detections: &mut Vec<ResultBBOX>, nms_threshold: f32) {
             let kept = unsafe { detections.get_unchecked(j) };
             
             // Skip different classes
-            if kept.class != detection_i.class {
+            if !class_agnostic && kept.class != detection_i.class {
                 continue;
             }
             
@@ -102,22 +105,97 @@ fn bbox_nms(detections: &mut Vec<ResultBBOX>, nms_threshold: f32) {
     detections.truncate(write_idx);
 }
 
+/// Default Gaussian decay of soft NMS
+const DEFAULT_NMS_SOFT_SIGMA: f32 = 0.5;
+
+/// Settings of NMS reduction of a source
+#[derive(Clone, Copy, Debug)]
+pub struct NmsOptions {
+    pub iou_threshold: f32,
+    pub class_agnostic: bool,
+    pub mode: NmsMode,
+    pub soft_sigma: f32
+}
+
+impl NmsOptions {
+    pub fn from_config(source_config: &SourceConfig) -> Self {
+        Self {
+            iou_threshold: source_config.nms_iou_threshold,
+            class_agnostic: source_config.class_agnostic_nms,
+            mode: source_config.nms_mode,
+            soft_sigma: source_config.nms_soft_sigma.unwrap_or(DEFAULT_NMS_SOFT_SIGMA)
+        }
+    }
+}
+
+/// Returns the IoU of two bboxes in (x1, y1, x2, y2) format
+#[inline(always)]
+pub fn bbox_iou(a: &[f32; 4], b: &[f32; 4]) -> f32 {
+    let x1_max = a[0].max(b[0]);
+    let y1_max = a[1].max(b[1]);
+    let x2_min = a[2].min(b[2]);
+    let y2_min = a[3].min(b[3]);
+
+    if x1_max >= x2_min || y1_max >= y2_min {
+        return 0.0;
+    }
+
+    let intersection = (x2_min - x1_max) * (y2_min - y1_max);
+    let area_a = (a[2] - a[0]) * (a[3] - a[1]);
+    let area_b = (b[2] - b[0]) * (b[3] - b[1]);
+
+    intersection / (area_a + area_b - intersection)
+}
+
+/// Perform Soft-NMS reduction of bboxes, with Gaussian decay
+/// 
+/// Repeatedly keeps the highest scored bbox and decays scores of the bboxes overlapping it
+/// by `exp(-IoU^2 / sigma)`. Bboxes decayed below the confidence threshold are dropped
+#[inline(never)]
+fn bbox_soft_nms(detections: &mut Vec<ResultBBOX>, sigma: f32, conf_threshold: f32, class_agnostic: bool) {
+    let mut remaining = std::mem::take(detections);
+    detections.reserve(remaining.len());
+
+    while !remaining.is_empty() {
+        let best_idx = remaining
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.score.total_cmp(&b.score))
+            .map(|(idx, _)| idx)
+            .unwrap_or(0);
+        let best = remaining.swap_remove(best_idx);
+
+        for detection in remaining.iter_mut() {
+            if !class_agnostic && detection.class != best.class {
+                continue;
+            }
+
+            let iou = bbox_iou(&best.bbox, &detection.bbox);
+            detection.score *= (-(iou * iou) / sigma).exp();
+        }
+
+        remaining.retain(|detection| detection.score >= conf_threshold);
+        detections.push(best);
+    }
+}
+
 /// Performs post-processing on inference results for YOLO models
 /// 
 /// Including the following steps of processing:
 /// 1. Convert BBOX coordinates from (x, y, w, h) to (x1, y1, x2, y2) together
-/// with restoring the letterbox padding applied during pre-processing
+/// with restoring the letterbox padding applied during pre-processing, and any
+/// downscale applied to the frame before it was queued
 /// 2. Finds out the class id with the max probability - making it the 
 /// class for the bbox along with its probabiliy
 /// 3. Filter BBOXes on a given confidence threshold, before applying NMS(boosts performance significantly)
-/// 4. Perform NMS on left over BBOXes
+/// 4. Perform NMS on left over BBOXes - hard, or soft with a Gaussian decay
 pub fn postprocess(
     results: &[u8],
     original_frame: &RawFrame,
     output_shape: &[i64],
     precision: InferencePrecision,
     pred_conf_threshold: f32,
-    nms_iou_threshold: f32,
+    nms: NmsOptions,
 ) -> Result<Vec<ResultBBOX>> {
     // Validate model output shape
     if output_shape.len() != 2 {
@@ -157,6 +235,10 @@ pub fn postprocess(
         original_frame.width, 
         TARGET_SIZE
     );
+
+    // Include the downscale applied at enqueue time, so bboxes are in original frame coordinates
+    let inv_scale_x = letterbox.inv_scale * original_frame.scale_x();
+    let inv_scale_y = letterbox.inv_scale * original_frame.scale_y();
     
     // Pre-allocate with exact capacity estimate (typically ~100-200 detections)
     let mut detections = Vec::with_capacity(256);
@@ -185,10 +267,10 @@ pub fn postprocess(
                     // Fused bbox transformation
                     let half_w = w * 0.5;
                     let half_h = h * 0.5;
-                    let x1 = (x - half_w - letterbox.pad_x as f32) * letterbox.inv_scale;
-                    let y1 = (y - half_h - letterbox.pad_y as f32) * letterbox.inv_scale;
-                    let x2 = (x + half_w - letterbox.pad_x as f32) * letterbox.inv_scale;
-                    let y2 = (y + half_h - letterbox.pad_y as f32) * letterbox.inv_scale;
+                    let x1 = (x - half_w - letterbox.pad_x as f32) * inv_scale_x;
+                    let y1 = (y - half_h - letterbox.pad_y as f32) * inv_scale_y;
+                    let x2 = (x + half_w - letterbox.pad_x as f32) * inv_scale_x;
+                    let y2 = (y + half_h - letterbox.pad_y as f32) * inv_scale_y;
                     
                     // Find max class with unrolled loop for common cases
                     let mut max_score: f32 = 0.0;
@@ -240,10 +322,10 @@ pub fn postprocess(
                     // Fused bbox transformation
                     let half_w = w * 0.5;
                     let half_h = h * 0.5;
-                    let x1 = (x - half_w - letterbox.pad_x as f32) * letterbox.inv_scale;
-                    let y1 = (y - half_h - letterbox.pad_y as f32) * letterbox.inv_scale;
-                    let x2 = (x + half_w - letterbox.pad_x as f32) * letterbox.inv_scale;
-                    let y2 = (y + half_h - letterbox.pad_y as f32) * letterbox.inv_scale;
+                    let x1 = (x - half_w - letterbox.pad_x as f32) * inv_scale_x;
+                    let y1 = (y - half_h - letterbox.pad_y as f32) * inv_scale_y;
+                    let x2 = (x + half_w - letterbox.pad_x as f32) * inv_scale_x;
+                    let y2 = (y + half_h - letterbox.pad_y as f32) * inv_scale_y;
                     
                     // Find max class with unrolling
                     let mut max_score: f32 = 0.0;
@@ -276,7 +358,10 @@ pub fn postprocess(
     
     // Fast NMS only if needed
     if detections.len() > 1 {
-        bbox_nms(&mut detections, nms_iou_threshold);
+        match nms.mode {
+            NmsMode::Hard => bbox_nms(&mut detections, nms.iou_threshold, nms.class_agnostic),
+            NmsMode::Soft => bbox_soft_nms(&mut detections, nms.soft_sigma, pred_conf_threshold, nms.class_agnostic)
+        }
     }
     
     Ok(detections)
@@ -284,7 +369,7 @@ pub fn postprocess(
 
 /// Performs operations on a given frame, including pre/post processing, inference on the given frame
 pub async fn process_frame(
-    inference_model: &InferenceModel, 
+    inference_model: &dyn Inference, 
     source_config: &SourceConfig,
     frame: Arc<RawFrame>
 ) -> Result<(FrameProcessStats, Vec<ResultBBOX>)> {
@@ -294,8 +379,12 @@ pub async fn process_frame(
     let measure_start = Instant::now();
     let precision = inference_model.model_config().precision;
     let frame_clone = Arc::clone(&frame);
+    let preprocess_cache = inference_model.preprocess_cache();
     let pre_frame = tokio::task::spawn_blocking(move || {
-        preprocess(&frame_clone, precision)
+        match preprocess_cache {
+            Some(cache) => cache.get_or_insert_with(&frame_clone, || preprocess(&frame_clone, precision)),
+            None => preprocess(&frame_clone, precision)
+        }
     })
         .await
         .context("Preprocess task failed")?
@@ -316,9 +405,9 @@ pub async fn process_frame(
 
     // Post process
     let measure_start = Instant::now();
-    let post_output_shape = inference_model.model_config().output_shape.clone();
+    let post_output_shape = inference_model.model_config().output_shape[0].clone();
     let post_conf_threshold = source_config.conf_threshold;
-    let post_nms_iou_threshold = source_config.nms_iou_threshold;
+    let post_nms = NmsOptions::from_config(source_config);
     
     let bboxes = tokio::task::spawn_blocking(move || {
         postprocess(
@@ -327,11 +416,12 @@ pub async fn process_frame(
             &post_output_shape,
             precision,
             post_conf_threshold,
-            post_nms_iou_threshold
+            post_nms
         )
     })
         .await
         .context("Postprocess task failed")?
+        .inspect_err(|_| metrics::inc_inference_error(inference_model.model_type(), InferenceErrorKind::PostprocessFailed))
         .context("Error postprocessing BBOXes for YOLO")?;
     let post_proc_time = measure_start.elapsed();
 
@@ -343,4 +433,154 @@ pub async fn process_frame(
     stats.processing = processing_start.elapsed().as_micros() as u64;
 
     Ok((stats, bboxes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NMS: NmsOptions = NmsOptions {
+        iou_threshold: 0.5,
+        class_agnostic: false,
+        mode: NmsMode::Hard,
+        soft_sigma: 0.5
+    };
+
+    /// FP32 output of a single anchor and class, as (x, y, w, h) in letterboxed 640x640 coordinates
+    fn single_anchor_output(x: f32, y: f32, w: f32, h: f32, score: f32) -> Vec<u8> {
+        [x, y, w, h, score].iter().flat_map(|value| value.to_ne_bytes()).collect()
+    }
+
+    fn frame(height: u32, width: u32, original_height: u32, original_width: u32) -> RawFrame {
+        RawFrame {
+            data: Vec::new(),
+            height,
+            width,
+            original_height,
+            original_width,
+            pts: 0,
+            added: tokio::time::Instant::now()
+        }
+    }
+
+    fn postprocess_single(output: &[u8], frame: &RawFrame) -> Vec<ResultBBOX> {
+        postprocess(output, frame, &[5, 1], InferencePrecision::FP32, 0.5, NMS).unwrap()
+    }
+
+    fn assert_bbox(actual: [f32; 4], expected: [f32; 4]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 0.01, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn projects_bboxes_to_original_frame() {
+        // 1280x720 frame letterboxed to 640x360, padded by 140 rows
+        let output = single_anchor_output(240.0, 275.0, 160.0, 90.0, 0.9);
+
+        let bboxes = postprocess_single(&output, &frame(720, 1280, 720, 1280));
+
+        assert_eq!(bboxes.len(), 1);
+        assert_bbox(bboxes[0].bbox, [320.0, 180.0, 640.0, 360.0]);
+    }
+
+    #[test]
+    fn projects_bboxes_through_queue_downscale() {
+        // 3840x2160 frame downscaled to 1280x720 when queued, then letterboxed to 640x360
+        let output = single_anchor_output(240.0, 275.0, 160.0, 90.0, 0.9);
+
+        let bboxes = postprocess_single(&output, &frame(720, 1280, 2160, 3840));
+
+        assert_bbox(bboxes[0].bbox, [960.0, 540.0, 1920.0, 1080.0]);
+    }
+
+    #[test]
+    fn downscale_keeps_original_frame_coordinates() {
+        let (data, height, width) = processing::resize_nearest_rgb(&vec![0; 3840 * 2160 * 3], 2160, 3840, 1280);
+        let output = single_anchor_output(320.0, 320.0, 100.0, 50.0, 0.9);
+
+        let direct = postprocess_single(&output, &frame(2160, 3840, 2160, 3840));
+        let downscaled = postprocess_single(&output, &frame(height, width, 2160, 3840));
+
+        assert_eq!((height, width), (720, 1280));
+        assert_eq!(data.len(), (height * width * 3) as usize);
+        assert_bbox(downscaled[0].bbox, direct[0].bbox);
+    }
+
+    fn detection(x1: f32, class: u32, score: f32) -> ResultBBOX {
+        ResultBBOX { bbox: [x1, 0.0, x1 + 100.0, 100.0], class, score }
+    }
+
+    #[test]
+    fn measures_bbox_iou() {
+        let bbox = [0.0, 0.0, 100.0, 100.0];
+
+        assert_eq!(bbox_iou(&bbox, &bbox), 1.0);
+        assert_eq!(bbox_iou(&bbox, &[100.0, 0.0, 200.0, 100.0]), 0.0);
+        assert!((bbox_iou(&bbox, &[50.0, 0.0, 150.0, 100.0]) - 1.0 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn hard_nms_suppresses_overlapping_bboxes_of_same_class() {
+        let mut detections = vec![detection(10.0, 0, 0.8), detection(0.0, 0, 0.9), detection(0.0, 2, 0.7)];
+
+        bbox_nms(&mut detections, 0.5, false);
+
+        let kept: Vec<(u32, f32)> = detections.iter().map(|d| (d.class, d.score)).collect();
+        assert_eq!(kept, vec![(0, 0.9), (2, 0.7)]);
+    }
+
+    #[test]
+    fn hard_nms_suppresses_other_classes_when_class_agnostic() {
+        // Overlapping car and person, e.g. a person detected inside a car
+        let mut class_aware = vec![detection(0.0, 2, 0.9), detection(10.0, 0, 0.8), detection(300.0, 0, 0.6)];
+        let mut class_agnostic = class_aware.clone();
+
+        bbox_nms(&mut class_aware, 0.5, false);
+        bbox_nms(&mut class_agnostic, 0.5, true);
+
+        let kept = |detections: &[ResultBBOX]| -> Vec<(u32, f32)> {
+            detections.iter().map(|d| (d.class, d.score)).collect()
+        };
+        assert_eq!(kept(&class_aware), vec![(2, 0.9), (0, 0.8), (0, 0.6)]);
+        assert_eq!(kept(&class_agnostic), vec![(2, 0.9), (0, 0.6)], "disjoint bboxes are kept");
+    }
+
+    #[test]
+    fn soft_nms_decays_overlapping_scores() {
+        let mut detections = vec![detection(50.0, 0, 0.8), detection(0.0, 0, 0.9), detection(300.0, 0, 0.6)];
+
+        bbox_soft_nms(&mut detections, 0.5, 0.25, false);
+
+        // IoU of 1/3 decays the score by exp(-(1/3)^2 / 0.5)
+        let decayed = 0.8 * (-(1.0f32 / 9.0) / 0.5).exp();
+        let scores: Vec<f32> = detections.iter().map(|d| d.score).collect();
+        assert_eq!(scores.len(), 3);
+        assert_eq!(scores[0], 0.9);
+        assert!((scores[1] - decayed).abs() < 1e-5, "{} != {}", scores[1], decayed);
+        assert_eq!(scores[2], 0.6, "disjoint bboxes keep their score");
+    }
+
+    #[test]
+    fn soft_nms_drops_bboxes_decayed_below_threshold() {
+        let mut detections = vec![detection(0.0, 0, 0.9), detection(1.0, 0, 0.5)];
+
+        bbox_soft_nms(&mut detections, 0.5, 0.25, false);
+
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].score, 0.9);
+    }
+
+    #[test]
+    fn soft_nms_decays_other_classes_only_when_class_agnostic() {
+        let mut class_aware = vec![detection(0.0, 0, 0.9), detection(1.0, 2, 0.5)];
+        let mut class_agnostic = class_aware.clone();
+
+        bbox_soft_nms(&mut class_aware, 0.5, 0.25, false);
+        bbox_soft_nms(&mut class_agnostic, 0.5, 0.25, true);
+
+        assert_eq!(class_aware.len(), 2);
+        assert_eq!(class_aware[1].score, 0.5);
+        assert_eq!(class_agnostic.len(), 1);
+    }
 }
\ No newline at end of file
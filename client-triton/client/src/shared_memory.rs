@@ -0,0 +1,235 @@
+//! Responsible for reading decoded frames written to shared memory by co-located capture processes,
+//! bypassing both decoding and the video client library
+//!
+//! A region holds a header followed by a single RGB frame, all fields in native byte order:
+//!
+//! | Offset | Type  | Field                                         |
+//! |--------|-------|-----------------------------------------------|
+//! | 0      | u32   | state - 0 empty, 1 frame ready, 2 closed      |
+//! | 4      | u32   | frame width                                   |
+//! | 8      | u32   | frame height                                  |
+//! | 12     | u32   | reserved                                      |
+//! | 16     | u64   | frame pts                                     |
+//! | 64     | bytes | RGB frame of `width * height * 3` bytes       |
+//!
+//! The capturer writes a frame only while the state is empty, then sets it to ready.
+//! The reader copies the frame out and sets the state back to empty, handing the buffer back to the capturer.
+//! Setting the state to closed stops the reader
+
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::sync::atomic::{AtomicU32, Ordering};
+use anyhow::{Result, Context};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+// Custom modules
+use crate::source;
+use crate::utils::config::{AppConfig, SharedMemoryConfig};
+use crate::utils::kafka;
+
+/// Size of the header preceding the frame, keeping the frame aligned to a cache line
+pub const HEADER_SIZE: usize = 64;
+
+/// Region holds no frame, the capturer may write the next one
+pub const STATE_EMPTY: u32 = 0;
+/// Region holds a frame not yet read
+pub const STATE_READY: u32 = 1;
+/// Capturer stopped writing frames to the region
+pub const STATE_CLOSED: u32 = 2;
+
+/// Header at the start of the region, layout shared with the capturer
+#[repr(C)]
+struct FrameHeader {
+    state: AtomicU32,
+    width: u32,
+    height: u32,
+    _reserved: u32,
+    pts: u64
+}
+
+/// Result of checking the region for a new frame
+pub enum SharedFrame {
+    /// No frame was written since the last read
+    Pending,
+    Ready {
+        data: Vec<u8>,
+        width: u32,
+        height: u32,
+        pts: u64
+    },
+    Closed
+}
+
+/// Shared memory region mapped into the process
+pub struct SharedMemoryRegion {
+    ptr: *mut u8,
+    len: usize,
+    _file: File
+}
+
+// The mapping is owned by the region and only accessed through the header state protocol
+unsafe impl Send for SharedMemoryRegion {}
+unsafe impl Sync for SharedMemoryRegion {}
+
+impl SharedMemoryRegion {
+    /// Maps the shared memory file at the given path, created and sized by the capturer
+    pub fn open(path: &str) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Error opening shared memory file {}", path))?;
+
+        let len = file.metadata()
+            .context("Error reading shared memory file size")?
+            .len() as usize;
+        if len < HEADER_SIZE {
+            anyhow::bail!("Shared memory file {} is {} bytes, smaller than the {} bytes header", path, len, HEADER_SIZE);
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Error mapping shared memory file {}", path));
+        }
+
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+            _file: file
+        })
+    }
+
+    fn header(&self) -> *const FrameHeader {
+        self.ptr as *const FrameHeader
+    }
+
+    /// Takes the frame written by the capturer, if any. The frame is copied straight out of the mapping,
+    /// as the buffer is handed back to the capturer right after
+    pub fn take_frame(&self) -> Result<SharedFrame> {
+        let header = self.header();
+        let state = unsafe { &(*header).state };
+
+        match state.load(Ordering::Acquire) {
+            STATE_READY => {},
+            STATE_CLOSED => return Ok(SharedFrame::Closed),
+            _ => return Ok(SharedFrame::Pending)
+        }
+
+        // Fields are written by another process, before the state was set to ready
+        let (width, height, pts) = unsafe {
+            (
+                std::ptr::read_volatile(&raw const (*header).width),
+                std::ptr::read_volatile(&raw const (*header).height),
+                std::ptr::read_volatile(&raw const (*header).pts)
+            )
+        };
+
+        let frame_size = width as usize * height as usize * 3;
+        let result = if frame_size == 0 || HEADER_SIZE + frame_size > self.len {
+            Err(anyhow::anyhow!(
+                "Frame of {}x{} does not fit the {} bytes shared memory region",
+                width, height, self.len
+            ))
+        } else {
+            let data = unsafe {
+                std::slice::from_raw_parts(self.ptr.add(HEADER_SIZE), frame_size).to_vec()
+            };
+            Ok(SharedFrame::Ready { data, width, height, pts })
+        };
+
+        // Invalid frames are released as well, so the capturer is not stuck
+        state.store(STATE_EMPTY, Ordering::Release);
+
+        result
+    }
+}
+
+impl Drop for SharedMemoryRegion {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// Reads frames of all shared memory sources, until their capturers close the regions
+pub async fn run_shared_memory_sources(app_config: &AppConfig) -> Result<()> {
+    let readers: Vec<JoinHandle<()>> = app_config.sources_config().sources
+        .iter()
+        .filter_map(|(source_id, source_config)| {
+            let shared_memory = source_config.shared_memory.clone()?;
+            Some(spawn_reader(source_id.clone(), shared_memory))
+        })
+        .collect();
+
+    for reader in readers {
+        reader.await
+            .context("Error joining shared memory reader")?;
+    }
+
+    Ok(())
+}
+
+/// Starts reading frames of a shared memory source, stopping once the capturer closes the region
+/// or the source is removed
+pub fn spawn_reader(source_id: String, config: SharedMemoryConfig) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Err(e) = read_frames(&source_id, &config).await {
+            tracing::error!(
+                source_id=source_id,
+                path=config.path,
+                error=format!("{:#}", e),
+                "Error reading frames from shared memory"
+            );
+        }
+    })
+}
+
+async fn read_frames(source_id: &str, config: &SharedMemoryConfig) -> Result<()> {
+    let region = SharedMemoryRegion::open(&config.path)?;
+    tracing::info!(source_id=source_id, path=config.path, "Reading frames from shared memory");
+
+    let mut interval = interval(Duration::from_millis(config.poll_interval_ms.max(1)));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+
+        let (data, width, height, pts) = match region.take_frame() {
+            Ok(SharedFrame::Pending) => continue,
+            Ok(SharedFrame::Closed) => break,
+            Ok(SharedFrame::Ready { data, width, height, pts }) => (data, width, height, pts),
+            Err(e) => {
+                tracing::warn!(source_id=source_id, error=e.to_string(), "Invalid frame in shared memory");
+                continue;
+            }
+        };
+
+        let Ok(processor) = source::get_source_processor(source_id).await else {
+            tracing::info!(source_id=source_id, "Source was removed, stopped reading shared memory");
+            return Ok(());
+        };
+        processor.process_frame(data, height, width, pts).await;
+    }
+
+    tracing::info!(source_id=source_id, "Shared memory region was closed by the capturer");
+
+    // Same as a stopped video client source
+    let _ = source::reset_heatmap(source_id).await;
+    kafka::flush_result_batches(Some(source_id))
+        .await
+        .context("Error publishing batched results")?;
+
+    Ok(())
+}
@@ -1,25 +1,43 @@
 //! Responsible for handling video stream frames, sending them to inference
 //! and populating results to third party systems
 
-use std::sync::Arc;
-use std::sync::atomic::{Ordering, AtomicU64};
-use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{Ordering, AtomicBool, AtomicU64};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use anyhow::{Result, Context};
+use arc_swap::ArcSwap;
 use tokio::time::{Duration, interval, Instant};
-use tokio::sync::{RwLock, Semaphore, OnceCell};
+use tokio::sync::{RwLock, Semaphore, OnceCell, broadcast};
 
 // Custom modules
-use crate::inference;
-use crate::utils::queue::FixedSizeQueue;
-use crate::processing::{self, RawFrame, ResultBBOX, ResultEmbedding};
-use crate::utils::config::{AppConfig, SourceConfig, InferenceModelType, InferenceTask};
-use crate::utils::kafka::Kafka;
+use crate::inference::{self, ModelProvider};
+use crate::utils::queue::{FixedSizeQueue, OverflowPolicy};
+use crate::processing::{self, RawFrame, ResultBBOX, ResultEmbedding, FrameResults};
+use crate::processing::motion::{self, MotionGate, FrameDeduplicator};
+use crate::processing::depth::{self, DepthFrame};
+use crate::utils::persistence::SourceState;
+use crate::utils::histogram::{LatencyHistogram, HistogramSnapshot};
+use crate::utils::config::{AppConfig, SourceConfig, SourceTopics, DebounceConfig, OutputsConfig, BackpressureConfig, InferenceModelType};
+use crate::utils::kafka::{self, Kafka};
+use crate::utils::metrics;
+use crate::utils::stats_sink;
+use crate::utils::rate_limiter::RateLimiter;
+use crate::utils::backpressure::BackpressureController;
 use crate::client_video::ClientVideo;
+use crate::shared_memory;
 
 // Variables
 pub static PROCESSORS: OnceCell<RwLock<HashMap<String, Arc<SourceProcessor>>>> = OnceCell::const_new();
+pub static RESULTS_BROADCAST: OnceCell<broadcast::Sender<Arc<FrameResults>>> = OnceCell::const_new();
+pub static OVERLAP_DEDUPLICATOR: OnceCell<OverlapDeduplicator> = OnceCell::const_new();
 pub static MAX_QUEUE_FRAMES: usize = 15;
 pub static SOURCE_STATS_INTERVAL: Duration = Duration::from_secs(1);
+pub static MAX_DROP_RECORDS: usize = 64;
+pub static DROP_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+pub static MAX_RECENT_TOP_SCORES: usize = 5;
 
 /// Returns a source processor instance by given stream ID
 pub async fn get_source_processor(stream_id: &str) -> Result<Arc<SourceProcessor>> {
@@ -33,17 +51,51 @@ pub async fn get_source_processor(stream_id: &str) -> Result<Arc<SourceProcessor
         .context("Error getting stream source processor")
 }
 
+/// Returns the processor of the source reading a sub-stream of a streamed source
+pub async fn get_sub_stream_processor(source_id: &str, index: u32) -> Result<Arc<SourceProcessor>> {
+    PROCESSORS
+        .get()
+        .context("Source processors not initiated")?
+        .read()
+        .await
+        .values()
+        .find(|processor| {
+            processor.source_config
+                .load()
+                .sub_stream
+                .as_ref()
+                .is_some_and(|sub_stream| sub_stream.source == source_id && sub_stream.index == index)
+        })
+        .cloned()
+        .context("No source reads the sub-stream")
+}
+
 /// Initiates source processors for given list of sources
 pub async fn init_source_processors(app_config: &AppConfig) -> Result<()> {
     let mut processors: HashMap<String, Arc<SourceProcessor>> = HashMap::new();
+
+    // Channel of results of all sources, for in-process subscribers
+    RESULTS_BROADCAST.get_or_init(|| async {
+        let (sender, _) = broadcast::channel(app_config.outputs_config().subscription_capacity.max(1));
+        sender
+    }).await;
     
+    // Deduplication of detections across sources with overlapping views
+    if let Some(overlap_window_ms) = app_config.overlap_window_ms() {
+        OVERLAP_DEDUPLICATOR.get_or_init(|| async {
+            OverlapDeduplicator::new(overlap_window_ms, app_config.overlap_similarity())
+        }).await;
+    }
+
     for (source_id, source_config) in app_config.sources_config().sources.iter() {
         // Start processor
         let processor = Arc::new(
             SourceProcessor::new(
                 source_id.to_string(),
                 source_config.clone(),
-                app_config.inference_config().task
+                app_config.outputs_config().clone(),
+                app_config.kafka_config().source_topics(source_id, source_config.topic_override.as_ref()),
+                app_config.backpressure_config()
             )
         );
         
@@ -61,7 +113,292 @@ pub async fn init_source_processors(app_config: &AppConfig) -> Result<()> {
     Ok(())
 }
 
+/// Subscribes to the results of a single source, or of all sources when no source is given
+///
+/// Subscribers receive the results of every inferred frame. Subscribers that fall behind
+/// miss the oldest results, see [`recv_results`]
+pub async fn subscribe_results(source_id: Option<&str>) -> Result<broadcast::Receiver<Arc<FrameResults>>> {
+    match source_id {
+        Some(source_id) => {
+            let processor = get_source_processor(source_id).await?;
+            Ok(processor.outputs.sender.subscribe())
+        },
+        None => {
+            let sender = RESULTS_BROADCAST
+                .get()
+                .context("Source processors not initiated")?;
+            Ok(sender.subscribe())
+        }
+    }
+}
+
+/// Applies a reloaded sources configuration to running source processors
+/// 
+/// Changed sources are updated in place when possible, otherwise recreated.
+/// New sources are started and deleted sources are stopped, along with their streams
+pub async fn apply_sources_config(app_config: &AppConfig) -> Result<()> {
+    let sources = &app_config.sources_config().sources;
+    let mut processors = PROCESSORS
+        .get()
+        .context("Source processors not initiated")?
+        .write()
+        .await;
+
+    // Stop deleted sources
+    let deleted: Vec<String> = processors
+        .keys()
+        .filter(|source_id| !sources.contains_key(*source_id))
+        .cloned()
+        .collect();
+
+    for source_id in deleted {
+        // Shared memory readers stop once their source is removed, sub-streams stay with their source's stream
+        let Some(processor) = processors.remove(&source_id) else {
+            continue;
+        };
+        if !processor.source_config.load().is_streamed() {
+            tracing::info!(source_id=source_id, "Removed source processor");
+            continue;
+        }
+
+        if let Err(e) = ClientVideo::stop_source(&source_id).await {
+            tracing::warn!(
+                source_id=source_id,
+                error=e.to_string(),
+                "Error stopping stream of deleted source"
+            );
+        }
+        tracing::info!(source_id=source_id, "Removed source processor");
+    }
+
+    for (source_id, source_config) in sources.iter() {
+        match processors.get(source_id) {
+            Some(processor) if **processor.source_config.load() == *source_config => continue,
+            Some(processor) if processor.can_update_config(source_config) => {
+                processor.update_config(source_config.clone());
+                tracing::info!(source_id=source_id, "Updated source processor configuration");
+            },
+            existing => {
+                let processor = Arc::new(
+                    SourceProcessor::new(
+                        source_id.to_string(),
+                        source_config.clone(),
+                        app_config.outputs_config().clone(),
+                        app_config.kafka_config().source_topics(source_id, source_config.topic_override.as_ref()),
+                        app_config.backpressure_config()
+                    )
+                );
+
+                // Stream of a recreated source keeps running, new sources start streaming
+                let is_new = existing.is_none();
+                processors.insert(source_id.to_string(), processor);
+
+                if is_new && let Some(shared_memory) = source_config.shared_memory.clone() {
+                    shared_memory::spawn_reader(source_id.to_string(), shared_memory);
+                    tracing::info!(source_id=source_id, "Added source processor");
+                } else if is_new && source_config.sub_stream.is_some() {
+                    tracing::info!(source_id=source_id, "Added source processor");
+                } else if is_new {
+                    ClientVideo::start_source(source_id, source_config)
+                        .await
+                        .with_context(|| format!("Error starting stream of source {}", source_id))?;
+                    tracing::info!(source_id=source_id, "Added source processor");
+                } else {
+                    tracing::info!(source_id=source_id, "Recreated source processor to apply configuration");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the detection heatmap of a source to a file, normalized by the amount of inferred frames
+pub async fn export_heatmap(source_id: &str, path: &str) -> Result<()> {
+    let processor = get_source_processor(source_id).await?;
+    processor.outputs.heatmap.export_heatmap(path)
+}
+
+/// Clears the detection heatmap of a source, as its stream restarted
+pub async fn reset_heatmap(source_id: &str) -> Result<()> {
+    let processor = get_source_processor(source_id).await?;
+    processor.outputs.heatmap.reset();
+    Ok(())
+}
+
+/// Records stream information of a source as reported by the video client, attached to its bbox outputs
+pub async fn set_stream_info(source_id: &str, stream_info: StreamInfo) -> Result<()> {
+    let processor = get_source_processor(source_id).await?;
+    *processor.outputs.stream_info.lock().unwrap() = Some(stream_info);
+    Ok(())
+}
+
+/// Returns summaries of the most recent results of a source, oldest first
+pub async fn recent_results(source_id: &str) -> Result<Vec<Arc<RecentResult>>> {
+    let processor = get_source_processor(source_id).await?;
+    Ok(processor.outputs.recent.snapshot())
+}
+
+/// Receives the next results of a subscription, returns None once the subscription is closed
+///
+/// Results missed due to lagging behind are skipped and counted
+pub async fn recv_results(receiver: &mut broadcast::Receiver<Arc<FrameResults>>) -> Option<Arc<FrameResults>> {
+    loop {
+        match receiver.recv().await {
+            Ok(results) => return Some(results),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                metrics::RESULTS_LAGGED.inc_by(missed);
+            },
+            Err(broadcast::error::RecvError::Closed) => return None
+        }
+    }
+}
+
+/// Reports ready sources as not ready once they stop delivering frames for the readiness timeout
+pub fn start_readiness_monitor(app_config: &AppConfig) {
+    let silence_timeout = Duration::from_secs(app_config.readiness_config().silence_timeout_secs.max(1));
+
+    tokio::spawn(async move {
+        let mut interval = interval(SOURCE_STATS_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let Some(rwlock) = PROCESSORS.get() else {
+                continue;
+            };
+
+            for (source_id, processor) in rwlock.read().await.iter() {
+                if !processor.readiness.check_silence(silence_timeout) {
+                    continue;
+                }
+
+                metrics::SOURCE_READY.with_label_values(&[source_id.as_str()]).set(0);
+                tracing::warn!(
+                    source_id=source_id,
+                    event="source_not_ready",
+                    silence_timeout_secs=silence_timeout.as_secs(),
+                    "Source is not ready, no frames received"
+                );
+            }
+        }
+    });
+}
+
+/// Starts pushing delivery divisors of overloaded sources to the video layer
+///
+/// Only changed divisors are pushed. Stops when the video library does not support stream options
+pub fn start_backpressure_coordinator(app_config: &AppConfig) {
+    let backpressure_config = app_config.backpressure_config();
+    if !backpressure_config.enabled {
+        return;
+    }
+
+    let push_interval = Duration::from_secs(backpressure_config.push_interval_secs.max(1));
+
+    tokio::spawn(async move {
+        let mut interval = interval(push_interval);
+        let mut pushed_divisors: HashMap<String, u32> = HashMap::new();
+
+        loop {
+            interval.tick().await;
+
+            let Some(rwlock) = PROCESSORS.get() else {
+                continue;
+            };
+
+            // Collect changed divisors without holding the processors lock during FFI calls
+            let changed: Vec<(String, u32)> = rwlock
+                .read()
+                .await
+                .iter()
+                .filter_map(|(source_id, processor)| {
+                    // Frames in shared memory or of sub-streams are not throttled through the video client
+                    if !processor.source_config.load().is_streamed() {
+                        return None;
+                    }
+
+                    let divisor = processor.delivery_divisor()?;
+                    let pushed = pushed_divisors.get(source_id).copied().unwrap_or(1);
+                    (divisor != pushed).then(|| (source_id.clone(), divisor))
+                })
+                .collect();
+
+            for (source_id, divisor) in changed {
+                let push_source_id = source_id.clone();
+                let push_result = tokio::task::spawn_blocking(move || {
+                    ClientVideo::set_stream_options(&push_source_id, divisor)
+                }).await;
+
+                match push_result {
+                    Ok(Ok(true)) => {
+                        tracing::info!(
+                            source_id=source_id,
+                            deliver_every_n=divisor,
+                            "Updated source frame delivery"
+                        );
+                        pushed_divisors.insert(source_id, divisor);
+                    },
+                    Ok(Ok(false)) => {
+                        tracing::warn!("Video library does not support stream options, backpressure is disabled");
+                        return;
+                    },
+                    Ok(Err(e)) => {
+                        tracing::warn!(
+                            source_id=source_id,
+                            error=e.to_string(),
+                            "Failed to update source frame delivery"
+                        );
+                    },
+                    Err(e) => {
+                        tracing::warn!(
+                            source_id=source_id,
+                            error=e.to_string(),
+                            "Update source frame delivery task failed"
+                        );
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Returns the last known state of every source that processed a frame
+pub async fn get_source_states() -> HashMap<String, SourceState> {
+    let Some(rwlock) = PROCESSORS.get() else {
+        return HashMap::new();
+    };
+
+    rwlock
+        .read()
+        .await
+        .iter()
+        .filter_map(|(source_id, processor)| {
+            processor.last_state().map(|state| (source_id.clone(), state))
+        })
+        .collect()
+}
+
+/// Restores previously saved states of sources, returns amount of restored sources
+pub async fn restore_source_states(states: HashMap<String, SourceState>) -> usize {
+    let Some(rwlock) = PROCESSORS.get() else {
+        return 0;
+    };
+
+    let processors = rwlock.read().await;
+    let mut restored = 0;
+    for (source_id, state) in states {
+        if let Some(processor) = processors.get(&source_id) {
+            processor.restore_state(state);
+            restored += 1;
+        }
+    }
+
+    restored
+}
+
 /// Responsible for giving information about times at specific parts of inference
+#[derive(Clone, Serialize)]
 pub struct FrameProcessStats {
     pub queue: u64,
     pub pre_processing: u64,
@@ -100,12 +437,21 @@ pub struct SourceStats {
     pub frames_expected: AtomicU64,
     pub frames_success: AtomicU64,
     pub frames_failed: AtomicU64,
-    pub total_queue_time: AtomicU64,
-    pub total_pre_proc_time: AtomicU64,
-    pub total_inference_time: AtomicU64,
-    pub total_post_proc_time: AtomicU64,
-    pub total_results_time: AtomicU64,
-    pub total_processing_time: AtomicU64
+    pub frames_gated: AtomicU64,
+    pub frames_deduplicated: AtomicU64,
+    pub frames_rate_limited: AtomicU64,
+    /// Frames dropped from a full queue, also counted as failed
+    pub frames_dropped: AtomicU64,
+    /// Frames replaced by a newer frame in a latest only queue, not counted as failed
+    pub frames_superseded: AtomicU64,
+    /// Most recent frames dropped from a full queue in the current interval
+    pub dropped_frames: Mutex<DroppedFrames>,
+    pub queue_time: LatencyHistogram,
+    pub pre_proc_time: LatencyHistogram,
+    pub inference_time: LatencyHistogram,
+    pub post_proc_time: LatencyHistogram,
+    pub results_time: LatencyHistogram,
+    pub processing_time: LatencyHistogram
 }
 
 impl SourceStats {
@@ -115,38 +461,463 @@ impl SourceStats {
             frames_expected: AtomicU64::new(0),
             frames_success: AtomicU64::new(0),
             frames_failed: AtomicU64::new(0),
-            total_queue_time: AtomicU64::new(0),
-            total_pre_proc_time: AtomicU64::new(0),
-            total_inference_time: AtomicU64::new(0),
-            total_post_proc_time: AtomicU64::new(0),
-            total_results_time: AtomicU64::new(0),
-            total_processing_time: AtomicU64::new(0)
+            frames_gated: AtomicU64::new(0),
+            frames_deduplicated: AtomicU64::new(0),
+            frames_rate_limited: AtomicU64::new(0),
+            frames_dropped: AtomicU64::new(0),
+            frames_superseded: AtomicU64::new(0),
+            dropped_frames: Mutex::new(DroppedFrames::new(MAX_DROP_RECORDS)),
+            queue_time: LatencyHistogram::new(),
+            pre_proc_time: LatencyHistogram::new(),
+            inference_time: LatencyHistogram::new(),
+            post_proc_time: LatencyHistogram::new(),
+            results_time: LatencyHistogram::new(),
+            processing_time: LatencyHistogram::new()
+        }
+    }
+
+    pub fn accumulate(&self, stats: &FrameProcessStats) {
+        self.queue_time.record(stats.queue);
+        self.pre_proc_time.record(stats.pre_processing);
+        self.inference_time.record(stats.inference);
+        self.post_proc_time.record(stats.post_processing);
+        self.results_time.record(stats.results);
+        self.processing_time.record(stats.processing);
+    }
+}
+
+/// Whether a source is delivering frames, independent of its connection status
+pub struct SourceReadiness {
+    ready: AtomicBool,
+    started: Instant,
+    /// Milliseconds since `started` of the most recent frame
+    last_frame_ms: AtomicU64
+}
+
+impl SourceReadiness {
+    pub fn new() -> Self {
+        Self {
+            ready: AtomicBool::new(false),
+            started: Instant::now(),
+            last_frame_ms: AtomicU64::new(0)
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    /// Records a received frame, returns whether the source just became ready
+    fn frame_received(&self) -> bool {
+        self.last_frame_ms.store(self.started.elapsed().as_millis() as u64, Ordering::Relaxed);
+        !self.ready.swap(true, Ordering::Relaxed)
+    }
+
+    /// Marks a ready source without frames for the timeout as not ready, returns whether it just became not ready
+    fn check_silence(&self, timeout: Duration) -> bool {
+        if !self.is_ready() {
+            return false;
+        }
+
+        let last_frame = Duration::from_millis(self.last_frame_ms.load(Ordering::Relaxed));
+        let silence = self.started.elapsed().saturating_sub(last_frame);
+        silence >= timeout && self.ready.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Aggregates of frames dropped from the queue over an interval
+pub struct DropSummary {
+    pub min_pts: u64,
+    pub max_pts: u64,
+    /// Longest time a dropped frame spent in the queue
+    pub oldest_age: Duration
+}
+
+/// Ring buffer of the most recent frames dropped from the queue - their pts and time spent in queue
+/// 
+/// Used to tell bunched drops (e.g. encoder bursts) from spread out drops
+pub struct DroppedFrames {
+    records: VecDeque<(u64, Duration)>,
+    capacity: usize
+}
+
+impl DroppedFrames {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: VecDeque::with_capacity(capacity),
+            capacity
+        }
+    }
+
+    /// Records a dropped frame, evicting the oldest record when full
+    pub fn record(&mut self, frame: &RawFrame) {
+        if self.records.len() >= self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back((frame.pts, frame.added.elapsed()));
+    }
+
+    /// Summarizes recorded drops and clears them for the next interval
+    pub fn drain(&mut self) -> Option<DropSummary> {
+        let summary = self.records.iter().fold(None, |summary: Option<DropSummary>, &(pts, age)| {
+            Some(match summary {
+                None => DropSummary { min_pts: pts, max_pts: pts, oldest_age: age },
+                Some(summary) => DropSummary {
+                    min_pts: summary.min_pts.min(pts),
+                    max_pts: summary.max_pts.max(pts),
+                    oldest_age: summary.oldest_age.max(age)
+                }
+            })
+        });
+        self.records.clear();
+
+        summary
+    }
+}
+
+/// Suppresses rapid re-triggering of detections within the same area of the frame
+///
+/// The frame is divided into a grid of cells. Once a detection centroid falls into a cell,
+/// further detections in that cell are suppressed until the debounce time passes
+pub struct SpatialDebouncer {
+    grid_size: u32,
+    debounce: Duration,
+    last_trigger_time: HashMap<(u32, u32), Instant>
+}
+
+impl SpatialDebouncer {
+    pub fn new(config: &DebounceConfig) -> Self {
+        Self {
+            grid_size: config.grid_size.max(1),
+            debounce: Duration::from_millis(config.debounce_ms),
+            last_trigger_time: HashMap::new()
+        }
+    }
+
+    /// Returns the grid cell containing the centroid of a bbox
+    fn cell(&self, frame: &RawFrame, bbox: &[f32; 4]) -> (u32, u32) {
+        let center_x = (bbox[0] + bbox[2]) * 0.5;
+        let center_y = (bbox[1] + bbox[3]) * 0.5;
+
+        let cell_x = (center_x.max(0.0) / frame.original_width.max(1) as f32 * self.grid_size as f32) as u32;
+        let cell_y = (center_y.max(0.0) / frame.original_height.max(1) as f32 * self.grid_size as f32) as u32;
+
+        (cell_x.min(self.grid_size - 1), cell_y.min(self.grid_size - 1))
+    }
+
+    /// Returns whether each given bbox should be published.
+    /// All detections of an armed cell within the same frame are published, triggering the cell
+    pub fn check<'a>(&mut self, frame: &RawFrame, bboxes: impl IntoIterator<Item = &'a [f32; 4]>) -> Vec<bool> {
+        let now = Instant::now();
+        let mut triggered = Vec::new();
+
+        let publish = bboxes
+            .into_iter()
+            .map(|bbox| {
+                let cell = self.cell(frame, bbox);
+                let armed = self.last_trigger_time
+                    .get(&cell)
+                    .map_or(true, |last| now.duration_since(*last) >= self.debounce);
+
+                if armed {
+                    triggered.push(cell);
+                }
+                armed
+            })
+            .collect();
+
+        for cell in triggered {
+            self.last_trigger_time.insert(cell, now);
+        }
+
+        publish
+    }
+}
+
+/// Summary of the results of a single frame, kept for debugging without the frame itself
+#[derive(Serialize)]
+pub struct RecentResult {
+    pub pts: u64,
+    pub timestamp_ms: u64,
+    pub detections: usize,
+    pub class_counts: BTreeMap<&'static str, usize>,
+    /// Highest detection scores, in descending order
+    pub top_scores: Vec<f32>,
+    /// Timings of the frame in microseconds
+    pub stats: FrameProcessStats
+}
+
+impl RecentResult {
+    pub fn new(frame: &RawFrame, bboxes: &[ResultBBOX], stats: &FrameProcessStats) -> Self {
+        let mut class_counts = BTreeMap::new();
+        for bbox in bboxes {
+            *class_counts.entry(bbox.class_name()).or_insert(0) += 1;
+        }
+
+        let mut top_scores: Vec<f32> = bboxes.iter().map(|bbox| bbox.score).collect();
+        top_scores.sort_unstable_by(|a, b| b.total_cmp(a));
+        top_scores.truncate(MAX_RECENT_TOP_SCORES);
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        Self {
+            pts: frame.pts,
+            timestamp_ms,
+            detections: bboxes.len(),
+            class_counts,
+            top_scores,
+            stats: stats.clone()
+        }
+    }
+}
+
+/// Bounded buffer of the most recent result summaries of a source, evicting the oldest
+/// 
+/// Summaries are built before the lock is taken, so it is only held to push or clone pointers
+pub struct RecentResults {
+    results: Mutex<VecDeque<Arc<RecentResult>>>,
+    capacity: usize
+}
+
+impl RecentResults {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            results: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity
+        }
+    }
+
+    pub fn push(&self, result: RecentResult) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let result = Arc::new(result);
+        let mut results = self.results.lock().unwrap();
+        if results.len() >= self.capacity {
+            results.pop_front();
+        }
+        results.push_back(result);
+    }
+
+    /// Returns the kept summaries, oldest first
+    pub fn snapshot(&self) -> Vec<Arc<RecentResult>> {
+        self.results.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Accumulates where detections of a source occur over time, revealing high-traffic zones
+///
+/// Each cell of the grid accumulates the scores of detections whose centroid falls within it
+pub struct DetectionHeatmap {
+    grid: Arc<Mutex<Vec<f32>>>,
+    grid_w: usize,
+    grid_h: usize,
+    frames: AtomicU64
+}
+
+impl DetectionHeatmap {
+    pub fn new(grid_w: usize, grid_h: usize) -> Self {
+        Self {
+            grid: Arc::new(Mutex::new(vec![0.0; grid_w * grid_h])),
+            grid_w,
+            grid_h,
+            frames: AtomicU64::new(0)
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.grid_w > 0 && self.grid_h > 0
+    }
+
+    /// Accumulates centroids of the detections of a single frame, in coordinates of the given frame size
+    pub fn update_heatmap(&self, bboxes: &[ResultBBOX], frame_width: u32, frame_height: u32) {
+        if !self.is_enabled() || frame_width == 0 || frame_height == 0 {
+            return;
+        }
+
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        if bboxes.is_empty() {
+            return;
+        }
+
+        let mut grid = self.grid.lock().unwrap();
+        for bbox in bboxes {
+            let [x1, y1, x2, y2] = bbox.bbox;
+            let center_x = ((x1 + x2) / 2.0 / frame_width as f32).clamp(0.0, 1.0);
+            let center_y = ((y1 + y2) / 2.0 / frame_height as f32).clamp(0.0, 1.0);
+
+            let column = ((center_x * self.grid_w as f32) as usize).min(self.grid_w - 1);
+            let row = ((center_y * self.grid_h as f32) as usize).min(self.grid_h - 1);
+            grid[row * self.grid_w + column] += bbox.score;
         }
     }
 
+    /// Writes the grid as raw little-endian f32 values, row by row, normalized by the amount of frames
+    pub fn export_heatmap(&self, path: &str) -> Result<()> {
+        if !self.is_enabled() {
+            anyhow::bail!("Detection heatmap is disabled");
+        }
+
+        let frames = self.frames.load(Ordering::Relaxed).max(1) as f32;
+        let data: Vec<u8> = self.grid
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|cell| (cell / frames).to_le_bytes())
+            .collect();
+
+        std::fs::write(path, data)
+            .with_context(|| format!("Error writing heatmap to '{}'", path))?;
+
+        Ok(())
+    }
+
     pub fn reset(&self) {
-        self.frames_total.store(0, Ordering::Relaxed);
-        self.frames_expected.store(0, Ordering::Relaxed);
-        self.frames_success.store(0, Ordering::Relaxed);
-        self.frames_failed.store(0, Ordering::Relaxed);
-        self.total_queue_time.store(0, Ordering::Relaxed);
-        self.total_pre_proc_time.store(0, Ordering::Relaxed);
-        self.total_inference_time.store(0, Ordering::Relaxed);
-        self.total_post_proc_time.store(0, Ordering::Relaxed);
-        self.total_results_time.store(0, Ordering::Relaxed);
-        self.total_processing_time.store(0, Ordering::Relaxed);
+        self.grid.lock().unwrap().fill(0.0);
+        self.frames.store(0, Ordering::Relaxed);
     }
+}
 
-    pub fn accumulate(&self, stats: &FrameProcessStats) {
-        self.total_queue_time.fetch_add(stats.queue, Ordering::Relaxed);
-        self.total_pre_proc_time.fetch_add(stats.pre_processing, Ordering::Relaxed);
-        self.total_inference_time.fetch_add(stats.inference, Ordering::Relaxed);
-        self.total_post_proc_time.fetch_add(stats.post_processing, Ordering::Relaxed);
-        self.total_results_time.fetch_add(stats.results, Ordering::Relaxed);
-        self.total_processing_time.fetch_add(stats.processing, Ordering::Relaxed);
+/// Stream information of a source as reported by the video client
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct StreamInfo {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f64
+}
+
+/// Destinations of the results of a single source
+pub struct ResultsOutputs {
+    config: OutputsConfig,
+    topics: SourceTopics,
+    sender: broadcast::Sender<Arc<FrameResults>>,
+    recent: RecentResults,
+    heatmap: DetectionHeatmap,
+    /// Known once the stream of the source is active
+    stream_info: Mutex<Option<StreamInfo>>
+}
+
+impl ResultsOutputs {
+    fn new(config: OutputsConfig, topics: SourceTopics) -> Self {
+        let (sender, _) = broadcast::channel(config.subscription_capacity.max(1));
+        let recent = RecentResults::new(config.recent_results_capacity);
+        let heatmap = DetectionHeatmap::new(config.heatmap_grid_w, config.heatmap_grid_h);
+
+        Self {
+            config,
+            topics,
+            sender,
+            recent,
+            heatmap,
+            stream_info: Mutex::new(None)
+        }
+    }
+
+    /// Returns whether any in-process subscriber receives the results of the source
+    fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+            || RESULTS_BROADCAST.get().is_some_and(|sender| sender.receiver_count() > 0)
+    }
+
+    /// Publishes results to in-process subscribers of the source and of all sources
+    fn broadcast(&self, results: &Arc<FrameResults>) {
+        // Sending only fails when there are no subscribers
+        let _ = self.sender.send(Arc::clone(results));
+        if let Some(sender) = RESULTS_BROADCAST.get() {
+            let _ = sender.send(Arc::clone(results));
+        }
+    }
+}
+
+/// Suppresses detections already published by other sources within a time window,
+/// as cameras with overlapping views detect the same objects simultaneously
+///
+/// Detections match when they are of the same class and the cosine similarity of their
+/// embeddings reaches the threshold, detections without an embedding are never suppressed
+pub struct OverlapDeduplicator {
+    window: Duration,
+    similarity: f32,
+    published: Mutex<VecDeque<PublishedDetection>>
+}
+
+/// Detection published by a source, kept for the window of the deduplicator
+struct PublishedDetection {
+    source_id: String,
+    class: u32,
+    embedding: Vec<f32>,
+    published_at: Instant
+}
+
+impl OverlapDeduplicator {
+    pub fn new(window_ms: u64, similarity: f32) -> Self {
+        Self {
+            window: Duration::from_millis(window_ms),
+            similarity,
+            published: Mutex::new(VecDeque::new())
+        }
+    }
+
+    /// Returns whether each given detection - its class and embedding - should be published,
+    /// registering the published ones
+    pub fn check<'a>(
+        &self,
+        source_id: &str,
+        detections: impl IntoIterator<Item = (u32, Option<&'a [f32]>)>
+    ) -> Vec<bool> {
+        self.check_at(source_id, detections, Instant::now())
+    }
+
+    fn check_at<'a>(
+        &self,
+        source_id: &str,
+        detections: impl IntoIterator<Item = (u32, Option<&'a [f32]>)>,
+        now: Instant
+    ) -> Vec<bool> {
+        let mut published = self.published.lock().unwrap();
+
+        // Forget detections outside of the window, kept in order of publishing
+        while published.front().is_some_and(|detection| now.duration_since(detection.published_at) >= self.window) {
+            published.pop_front();
+        }
+
+        detections
+            .into_iter()
+            .map(|(class, embedding)| {
+                let Some(embedding) = embedding else {
+                    return true;
+                };
+
+                let duplicate = published.iter().any(|detection| {
+                    detection.source_id != source_id
+                        && detection.class == class
+                        && processing::cosine_similarity(&detection.embedding, embedding) >= self.similarity
+                });
+                if duplicate {
+                    return false;
+                }
+
+                published.push_back(PublishedDetection {
+                    source_id: source_id.to_string(),
+                    class,
+                    embedding: embedding.to_vec(),
+                    published_at: now
+                });
+                true
+            })
+            .collect()
     }
 }
 
+/// Keeps only the items marked for publishing
+fn retain_published<T>(items: &mut Vec<T>, publish: Vec<bool>) {
+    let mut publish = publish.into_iter();
+    items.retain(|_| publish.next().unwrap_or(true));
+}
+
 /// Responsible for managing inference/processing for each source
 /// 
 /// Performs inference for each source seperately. Allows us to control 
@@ -166,9 +937,16 @@ pub struct SourceProcessor {
 
     // Source specific settings
     source_id: Arc<String>,
-    source_config: Arc<SourceConfig>,
+    source_config: Arc<ArcSwap<SourceConfig>>,
     source_stats: Arc<SourceStats>,
-    inference_task: InferenceTask
+    motion_gate: Option<Mutex<MotionGate>>,
+    frame_deduplicator: Option<Mutex<FrameDeduplicator>>,
+    rate_limiter: Option<RateLimiter>,
+    backpressure: Option<Arc<Mutex<BackpressureController>>>,
+    last_state: Arc<Mutex<Option<SourceState>>>,
+    outputs: Arc<ResultsOutputs>,
+    readiness: SourceReadiness,
+    paused: AtomicBool
 }
 
 impl SourceProcessor {
@@ -182,21 +960,75 @@ impl SourceProcessor {
     pub fn new(
         source_id: String,
         source_config: SourceConfig,
-        inference_task: InferenceTask
+        outputs_config: OutputsConfig,
+        topics: SourceTopics,
+        backpressure_config: &BackpressureConfig
+    ) -> Self {
+        SourceProcessor::with_models(
+            source_id,
+            source_config,
+            outputs_config,
+            topics,
+            backpressure_config,
+            Arc::new(inference::TritonModels)
+        )
+    }
+
+    /// Creates a new instance of source processor, executing its pipeline stages with the given models
+    pub fn with_models(
+        source_id: String,
+        source_config: SourceConfig,
+        mut outputs_config: OutputsConfig,
+        topics: SourceTopics,
+        backpressure_config: &BackpressureConfig,
+        models: Arc<dyn ModelProvider>
     ) -> Self {
+        // Results of shared memory sources have no video client stream to go back to
+        if source_config.shared_memory.is_some() {
+            outputs_config.client_video = false;
+        }
+
+        // Kafka output is skipped entirely when Kafka is disabled, rather than probing the producer per frame
+        outputs_config.kafka &= kafka::is_kafka_enabled();
+
         // Create global counters
         let source_id = Arc::new(source_id);
         let source_stats = Arc::new(SourceStats::new());
-        let source_config = Arc::new(source_config);
+        let source_config = Arc::new(ArcSwap::from_pointee(source_config));
+        let initial_config = source_config.load_full();
+        let last_state = Arc::new(Mutex::new(None));
+        let outputs = Arc::new(ResultsOutputs::new(outputs_config, topics));
+
+        // Debouncer of Kafka output, used to avoid re-triggering on stationary objects
+        let debouncer = initial_config.debounce
+            .as_ref()
+            .map(|config| Arc::new(Mutex::new(SpatialDebouncer::new(config))));
         
         // Create a queue for frames. We set a maximum number of frames possible to be in queue at a given time
-        // When the limit reaches, it drops the oldest frame in the queue, making it possible for new frames
-        // to be added to the queue and be processed.
+        // When the limit reaches, the overflow policy decides - by default it drops the oldest frame in the queue,
+        // making it possible for new frames to be added to the queue and be processed.
+        // Latest only sources keep a single frame, where replacing it is intended rather than a failure
+        let latest_only = initial_config.latest_only;
+        let (queue_capacity, overflow_policy) = match latest_only {
+            true => (1, OverflowPolicy::DropOldest),
+            false => (MAX_QUEUE_FRAMES, initial_config.queue_overflow_policy)
+        };
         let queue_stats = Arc::clone(&source_stats);
-        let queue_drop_callback = move |_: Arc<RawFrame>| {
+        let queue_drop_callback = move |frame: Arc<RawFrame>| {
+            if latest_only {
+                queue_stats.frames_superseded.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
             queue_stats.frames_failed.fetch_add(1, Ordering::Relaxed);
+            queue_stats.frames_dropped.fetch_add(1, Ordering::Relaxed);
+            queue_stats.dropped_frames.lock().unwrap().record(&frame);
         };
-        let source_queue = Arc::new(FixedSizeQueue::<Arc<RawFrame>>::new(MAX_QUEUE_FRAMES, Some(queue_drop_callback)));
+        let source_queue = Arc::new(FixedSizeQueue::<Arc<RawFrame>>::new(
+            queue_capacity,
+            overflow_policy,
+            Some(queue_drop_callback)
+        ));
         let queue_semaphore = Arc::new(Semaphore::new(MAX_QUEUE_FRAMES));
         
         // Create a seperate task for handling frames - performing inference
@@ -205,6 +1037,10 @@ impl SourceProcessor {
         let process_source_id = Arc::clone(&source_id);
         let process_source_config = Arc::clone(&source_config);
         let process_source_stats = Arc::clone(&source_stats);
+        let process_last_state = Arc::clone(&last_state);
+        let process_debouncer = debouncer;
+        let process_outputs = Arc::clone(&outputs);
+        let process_models = models;
 
         let process_handle = tokio::spawn(async move {
             let frame_process: Result<()> = async {
@@ -217,8 +1053,13 @@ impl SourceProcessor {
                                 // Move values to the new thread
                                 let process_source_id_ext = Arc::clone(&process_source_id);
                                 let process_source_id_int = Arc::clone(&process_source_id);
-                                let process_source_config = Arc::clone(&process_source_config);
+                                // Configuration is read per frame, so updates apply from the next frame
+                                let process_source_config = process_source_config.load_full();
                                 let process_source_stats = Arc::clone(&process_source_stats);
+                                let process_last_state = Arc::clone(&process_last_state);
+                                let process_debouncer = process_debouncer.clone();
+                                let process_outputs = Arc::clone(&process_outputs);
+                                let process_models = Arc::clone(&process_models);
                                 let process_frame = Arc::clone(&frame);
 
                                 // Spawn processing in a new thread with permit
@@ -229,8 +1070,11 @@ impl SourceProcessor {
                                     let process_result = SourceProcessor::process_frame_internal(
                                         process_source_id_int,
                                         &process_source_config,
-                                        process_frame,
-                                        inference_task
+                                        process_models.as_ref(),
+                                        &process_last_state,
+                                        process_debouncer.as_deref(),
+                                        &process_outputs,
+                                        process_frame
                                     ).await;
 
                                     // Count processing statistics
@@ -242,6 +1086,12 @@ impl SourceProcessor {
 
                                             // Add inference statistics to counters
                                             process_source_stats.accumulate(&stats);
+                                            metrics::QUEUE_TIME
+                                                .with_label_values(&[process_source_id_ext.as_str()])
+                                                .observe(metrics::micros_to_secs(stats.queue));
+                                            metrics::PROCESSING_TIME
+                                                .with_label_values(&[process_source_id_ext.as_str()])
+                                                .observe(metrics::micros_to_secs(stats.processing));
                                         },
                                         Err(_) => {
                                             process_source_stats.frames_failed.fetch_add(1, Ordering::Relaxed);
@@ -279,30 +1129,70 @@ impl SourceProcessor {
             }
         });
 
+        // Backpressure, used to throttle frame delivery when the source is overloaded
+        let backpressure = backpressure_config.enabled
+            .then(|| Arc::new(Mutex::new(BackpressureController::new(backpressure_config.clone()))));
+
         // Create a seperate task for printing source statistics
+        let stats_backpressure = backpressure.clone();
         let stats_source_id = source_id.clone();
         let stats_source_config = source_config.clone();
         let stats_source_stats = Arc::clone(&source_stats);
+        let stats_source_queue = Arc::clone(&source_queue);
         let stats_interval = SOURCE_STATS_INTERVAL.clone();
 
         let stats_handle = tokio::spawn(async move {
             let mut interval = interval(stats_interval);
+            let mut last_drop_warning: Option<Instant> = None;
             
             loop {
                 interval.tick().await;
 
-                Self::process_stats_internal(
-                    &stats_source_id, 
-                    &stats_source_config,
-                    &stats_source_stats
-                );
+                let queue_len = stats_source_queue.len().await;
+                metrics::QUEUE_DEPTH
+                    .with_label_values(&[stats_source_id.as_str()])
+                    .set(queue_len as i64);
+
+                // Update delivery divisor with the load of the interval, before stats are drained
+                let frames_dropped = stats_source_stats.frames_dropped.swap(0, Ordering::Relaxed);
+                if let Some(backpressure) = &stats_backpressure {
+                    let frames_processed = stats_source_stats.frames_expected.load(Ordering::Relaxed);
+                    let frames_queued = frames_dropped + frames_processed;
 
-                // Reset statistics
-                stats_source_stats.reset();
+                    let occupancy = queue_len as f32 / stats_source_queue.capacity() as f32;
+                    let drop_rate = match frames_queued {
+                        0 => 0.00,
+                        _ => frames_dropped as f32 / frames_queued as f32
+                    };
+
+                    backpressure.lock().unwrap().update(occupancy, drop_rate);
+                }
+
+                // Statistics are drained - reset for the next interval
+                Self::process_stats_internal(
+                    &stats_source_id, 
+                    &stats_source_config.load(),
+                    &stats_source_stats,
+                    frames_dropped,
+                    &mut last_drop_warning
+                );
 
             }
         });
 
+        // Motion gate, used to skip inference on static scenes
+        let motion_gate = initial_config.motion_gate
+            .clone()
+            .map(|config| Mutex::new(MotionGate::new(config)));
+
+        // Frame deduplication, used to skip near-identical frames
+        let frame_deduplicator = initial_config.dedup_threshold
+            .map(|threshold| Mutex::new(FrameDeduplicator::new(threshold, initial_config.dedup_max_skips)));
+
+        // Rate limiter, used to cap inferences per second regardless of the source frame rate
+        let rate_limiter = initial_config.max_inferences_per_sec
+            .map(RateLimiter::new);
+
         tracing::info!(
             source_id=&*source_id,
             "initiated client processing"
@@ -316,22 +1206,129 @@ impl SourceProcessor {
             source_id,
             source_config,
             source_stats,
-            inference_task
+            motion_gate,
+            frame_deduplicator,
+            rate_limiter,
+            backpressure,
+            last_state,
+            outputs,
+            readiness: SourceReadiness::new(),
+            paused: AtomicBool::new(false)
         }
     }
 
     /// Sends inference requests to a seperate thread pool
     pub async fn process_frame(&self, raw_frame: Vec<u8>, height: u32, width: u32, pts: u64) {
+        // Delivering frames makes the source ready, also while paused
+        if self.readiness.frame_received() {
+            metrics::SOURCE_READY.with_label_values(&[self.source_id.as_str()]).set(1);
+            tracing::info!(
+                source_id=&*self.source_id,
+                event="source_ready",
+                width=width,
+                height=height,
+                pts=pts,
+                "Source is ready, receiving frames"
+            );
+        }
+
+        // Frames of paused sources are ignored until resumed
+        if self.paused.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let source_config = self.source_config.load_full();
+
+        // Depth sources only provide frames for other sources
+        if source_config.is_depth_source {
+            depth::DEPTH_FRAMES.insert(
+                &self.source_id,
+                DepthFrame {
+                    data: raw_frame,
+                    height,
+                    width,
+                    pts
+                }
+            );
+            return;
+        }
+
         let frames_total = self.source_stats.frames_total.load(Ordering::Relaxed);
 
         // Send inference results on every N frame
-        if (frames_total + 1) % (self.source_config.inf_frame as u64) == 0 {
+        if (frames_total + 1) % (source_config.inf_frame as u64) == 0 {
+            // Skip near-identical frames and frames without motion
+            let thumbnail = if self.frame_deduplicator.is_some() || self.motion_gate.is_some() {
+                let thumbnail = motion::grayscale_thumbnail(&raw_frame, height, width);
+
+                if let Some(frame_deduplicator) = &self.frame_deduplicator {
+                    if frame_deduplicator.lock().unwrap().is_duplicate(&thumbnail) {
+                        self.source_stats.frames_total.fetch_add(1, Ordering::Relaxed);
+                        self.source_stats.frames_deduplicated.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                }
+
+                if let Some(motion_gate) = &self.motion_gate {
+                    if !motion_gate.lock().unwrap().check(&thumbnail) {
+                        self.source_stats.frames_total.fetch_add(1, Ordering::Relaxed);
+                        self.source_stats.frames_gated.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                }
+
+                Some(thumbnail)
+            } else {
+                None
+            };
+
+            // Skip frames above the max inferences per second, after the gates so
+            // skipped frames do not consume permits of the following ones
+            if let Some(rate_limiter) = &self.rate_limiter {
+                if !rate_limiter.try_acquire() {
+                    self.source_stats.frames_total.fetch_add(1, Ordering::Relaxed);
+                    self.source_stats.frames_rate_limited.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+
+            // Near-identical frames are compared with the last frame sent for inference
+            if let (Some(frame_deduplicator), Some(thumbnail)) = (&self.frame_deduplicator, thumbnail) {
+                frame_deduplicator.lock().unwrap().set_processed(thumbnail);
+            }
+
+            // Downscale large frames to bound memory held by the queue
+            let (frame_data, frame_height, frame_width) = match source_config.queue_max_dimension {
+                Some(max_dimension) if height.max(width) > max_dimension => {
+                    let resize_result = tokio::task::spawn_blocking(move || {
+                        processing::resize_nearest_rgb(&raw_frame, height, width, max_dimension)
+                    }).await;
+
+                    match resize_result {
+                        Ok(resized) => resized,
+                        Err(e) => {
+                            tracing::error!(
+                                source_id=&*self.source_id,
+                                error=e.to_string(),
+                                "error downscaling frame"
+                            );
+                            self.source_stats.frames_total.fetch_add(1, Ordering::Relaxed);
+                            self.source_stats.frames_failed.fetch_add(1, Ordering::Relaxed);
+                            return;
+                        }
+                    }
+                },
+                _ => (raw_frame, height, width)
+            };
+
             // Create new frame object
             let frame = Arc::new(
                 RawFrame {
-                    data: raw_frame,
-                    height,
-                    width,
+                    data: frame_data,
+                    height: frame_height,
+                    width: frame_width,
+                    original_height: height,
+                    original_width: width,
                     pts,
                     added: Instant::now()
                 }
@@ -345,138 +1342,343 @@ impl SourceProcessor {
         }
     }
 
+    /// Returns whether a frame is sampled for debugging, with the given probability
+    fn debug_sampled(sample_rate: f32) -> bool {
+        if sample_rate <= 0.00 {
+            return false;
+        }
+
+        fastrand::f32() < sample_rate
+    }
+
     /// Used to perform inference on a raw frame and return stats about timing
-    #[allow(unreachable_patterns)]
     async fn process_frame_internal(
         source_id: Arc<String>,
         source_config: &SourceConfig,
-        frame: Arc<RawFrame>, 
-        inference_task: InferenceTask
+        models: &dyn ModelProvider,
+        last_state: &Mutex<Option<SourceState>>,
+        debouncer: Option<&Mutex<SpatialDebouncer>>,
+        outputs: &ResultsOutputs,
+        frame: Arc<RawFrame>
     ) -> Result<FrameProcessStats> {
         let frame_queue_time = frame.added.elapsed();
-        
-        // Perform inference on raw frame and populate results
-        let mut stats = match inference_task {
-            InferenceTask::ObjectDetection => {
-                // Get BBOXes for frame
-                let bboxes_model = inference::get_inference_model(InferenceModelType::YOLO)?;
-                let bboxes_frame = Arc::clone(&frame);
-                let (mut bboxes_stats, bboxes) = processing::yolo::process_frame(
-                    &bboxes_model,
-                    &source_config,
-                    bboxes_frame
-                ).await?;
-
-                // Populate BBOXes if we have any
-                if bboxes.len() > 0 {
-                    let measure_start = Instant::now();
-
-                    // Populate BBOXes to third party services
-                    let results_source_id = Arc::clone(&source_id);
-                    let results_frame = Arc::clone(&frame);
-                    let results_arc = Arc::new(bboxes);
-                    SourceProcessor::populate_bboxes(
-                        results_source_id, 
-                        results_frame, 
-                        results_arc
-                    ).await;
-
-                    // Update results time
-                    let results_time = measure_start.elapsed();
-                    bboxes_stats.results += results_time.as_micros() as u64;
+
+        // Perform inference on raw frame - executing the source pipeline stages in order
+        let mut stats = FrameProcessStats::default();
+        let mut bboxes: Option<Arc<Vec<ResultBBOX>>> = None;
+        let mut embeddings: Option<Arc<Vec<ResultEmbedding>>> = None;
+
+        for model_type in source_config.pipeline.iter() {
+            match model_type {
+                InferenceModelType::YOLO => {
+                    // Get BBOXes for frame
+                    let bboxes_model = models.model(model_type)?;
+                    let bboxes_frame = Arc::clone(&frame);
+                    let (bboxes_stats, mut stage_bboxes) = processing::yolo::process_frame(
+                        bboxes_model.as_ref(),
+                        &source_config,
+                        bboxes_frame
+                    ).await?;
+
+                    // Filter detections that are too far away
+                    if let Some(depth_filter) = &source_config.depth_filter {
+                        let filtered = depth::filter_bboxes(
+                            depth_filter,
+                            frame.pts,
+                            frame.original_height,
+                            frame.original_width,
+                            &mut stage_bboxes
+                        );
+
+                        if filtered > 0 {
+                            tracing::debug!(
+                                source_id=&*source_id,
+                                filtered=filtered,
+                                "filtered distant detections"
+                            );
+                        }
+                    }
+                    SourceProcessor::observe_model_stats(&source_id, model_type, &bboxes_stats);
+
+                    stats.accumulate(&bboxes_stats);
+
+                    // Remember last known detections of source
+                    *last_state.lock().unwrap() = Some(SourceState::new(frame.pts, stage_bboxes.clone()));
+
+                    outputs.heatmap.update_heatmap(&stage_bboxes, frame.original_width, frame.original_height);
+
+                    // Log full detections of a sample of frames
+                    if SourceProcessor::debug_sampled(source_config.debug_sample_rate) {
+                        tracing::debug!(
+                            source_id=source_id.as_str(),
+                            pts=frame.pts,
+                            bboxes=serde_json::to_string(&stage_bboxes).unwrap_or_default(),
+                            "Sampled frame detections"
+                        );
+                    }
+
+                    bboxes = Some(Arc::new(stage_bboxes));
+                },
+                InferenceModelType::DINO => {
+                    // Get embeddings for frame and bboxes of previous stage
+                    let embedding_bboxes = bboxes
+                        .clone()
+                        .context("DINO stage requires BBOXes from a previous YOLO stage")?;
+                    let embedding_model = models.model(model_type)?;
+                    let embedding_frame = Arc::clone(&frame);
+                    let (embedding_stats, stage_embeddings): (FrameProcessStats, Vec<ResultEmbedding>) = processing::dino::process_frame(
+                        embedding_model.as_ref(),
+                        embedding_frame,
+                        embedding_bboxes
+                    ).await?;
+                    SourceProcessor::observe_model_stats(&source_id, model_type, &embedding_stats);
+
+                    stats.accumulate(&embedding_stats);
+                    embeddings = Some(Arc::new(stage_embeddings));
                 }
+            }
+        }
+
+        // Populate results to third party services
+        let recent_bboxes = bboxes.clone();
+        let measure_start = Instant::now();
+        let results = match &bboxes {
+            Some(bboxes) if source_config.combined_results || outputs.has_subscribers() => {
+                let results = FrameResults::new(
+                    &source_id,
+                    &frame,
+                    bboxes,
+                    embeddings.as_deref().map(|embeddings| embeddings.as_slice())
+                )
+                    .context("Error combining frame results")?;
 
-                bboxes_stats
+                Some(Arc::new(results))
             },
-            InferenceTask::Embedding => {
-                // Get BBOXes for frame
-                let bboxes_model = inference::get_inference_model(InferenceModelType::YOLO)?;
-                let bboxes_frame = Arc::clone(&frame);
-                let (bboxes_stats, bboxes) = processing::yolo::process_frame(
-                    &bboxes_model,
-                    &source_config,
-                    bboxes_frame
-                ).await?;
-                let bboxes = Arc::new(bboxes);
-
-                // Get embeddings for frame and bboxes
-                let embedding_model = inference::get_inference_model(InferenceModelType::DINO)?;
-                let embedding_bboxes = Arc::clone(&bboxes);
-                let embedding_frame = Arc::clone(&frame);
-                let (mut embedding_stats, embeddings): (FrameProcessStats, Vec<ResultEmbedding>) = processing::dino::process_frame(
-                    &embedding_model,
-                    embedding_frame,
-                    embedding_bboxes
-                ).await?;
-                let embeddings = Arc::new(embeddings);
-
-                // Populate embeddings if we have any
-                if embeddings.len() > 0 {
-                    let measure_start = Instant::now();
-
-                    // Populate embeddings to third party services
-                    let results_source_id = Arc::clone(&source_id);
-                    let results_frame = Arc::clone(&frame);
-                    let results_embeddings = Arc::clone(&embeddings);
-                    SourceProcessor::populate_embeddings(
-                        results_source_id, 
-                        results_frame, 
-                        results_embeddings
-                    ).await;
-
-                    // Update results time
-                    let results_time = measure_start.elapsed();
-                    embedding_stats.results += results_time.as_micros() as u64;
-                }
+            _ => None
+        };
+
+        // In-process subscribers receive results of every frame, including empty ones
+        if let Some(results) = &results {
+            outputs.broadcast(results);
+        }
 
-                // Combine statistics
-                let mut final_stats = FrameProcessStats::default();
-                final_stats.accumulate(&bboxes_stats);
-                final_stats.accumulate(&embedding_stats);
+        if source_config.combined_results {
+            // Single publish of bboxes along with their embeddings
+            if let Some(results) = results.filter(|results| results.detections.len() > 0) {
 
-                final_stats
+                // Suppress debounced and cross-source duplicate detections from Kafka output only
+                let overlap_deduplicator = OVERLAP_DEDUPLICATOR.get();
+                let kafka_results = if debouncer.is_some() || overlap_deduplicator.is_some() {
+                    let mut kafka_results = (*results).clone();
+
+                    if let Some(debouncer) = debouncer {
+                        let publish = debouncer
+                            .lock()
+                            .unwrap()
+                            .check(&frame, kafka_results.detections.iter().map(|detection| &detection.bbox));
+                        retain_published(&mut kafka_results.detections, publish);
+                    }
+
+                    if let Some(overlap_deduplicator) = overlap_deduplicator {
+                        let publish = overlap_deduplicator.check(
+                            source_id.as_str(),
+                            kafka_results.detections
+                                .iter()
+                                .map(|detection| (detection.class, detection.embedding.as_deref()))
+                        );
+                        let duplicates = publish.iter().filter(|&&publish| !publish).count();
+                        metrics::CROSS_SOURCE_DUPLICATES
+                            .with_label_values(&[source_id.as_str()])
+                            .inc_by(duplicates as u64);
+                        retain_published(&mut kafka_results.detections, publish);
+                    }
+
+                    Arc::new(kafka_results)
+                } else {
+                    Arc::clone(&results)
+                };
+
+                SourceProcessor::populate_results(
+                    outputs,
+                    Arc::clone(&frame),
+                    results,
+                    kafka_results
+                ).await;
             }
-            _ => anyhow::bail!("Model task is not supported for processing!")
-        };
+        } else {
+            // Deprecated - results of the final stage only
+            match source_config.pipeline.last() {
+                Some(InferenceModelType::YOLO) => {
+                    if let Some(bboxes) = bboxes.filter(|bboxes| bboxes.len() > 0) {
+                        // Suppress debounced and cross-source duplicate detections from Kafka output only
+                        let overlap_deduplicator = OVERLAP_DEDUPLICATOR.get();
+                        let kafka_bboxes = if debouncer.is_some() || overlap_deduplicator.is_some() {
+                            let mut publish = vec![true; bboxes.len()];
+
+                            if let Some(debouncer) = debouncer {
+                                publish = debouncer
+                                    .lock()
+                                    .unwrap()
+                                    .check(&frame, bboxes.iter().map(|bbox| &bbox.bbox));
+                            }
+
+                            // Embedding of bbox `i` is `i + 1`, as in FrameResults
+                            let bbox_embeddings = embeddings
+                                .as_deref()
+                                .filter(|embeddings| embeddings.len() == bboxes.len() + 1);
+                            if let Some(overlap_deduplicator) = overlap_deduplicator {
+                                let not_duplicate = overlap_deduplicator.check(
+                                    source_id.as_str(),
+                                    bboxes
+                                        .iter()
+                                        .enumerate()
+                                        .filter(|(index, _)| publish[*index])
+                                        .map(|(index, bbox)| (
+                                            bbox.class,
+                                            bbox_embeddings.map(|embeddings| embeddings[index + 1].data.as_slice())
+                                        ))
+                                );
+                                let duplicates = not_duplicate.iter().filter(|&&publish| !publish).count();
+                                metrics::CROSS_SOURCE_DUPLICATES
+                                    .with_label_values(&[source_id.as_str()])
+                                    .inc_by(duplicates as u64);
+
+                                let mut not_duplicate = not_duplicate.into_iter();
+                                for publish in publish.iter_mut().filter(|publish| **publish) {
+                                    *publish = not_duplicate.next().unwrap_or(true);
+                                }
+                            }
+
+                            Arc::new(
+                                bboxes
+                                    .iter()
+                                    .zip(publish)
+                                    .filter_map(|(bbox, publish)| publish.then_some(*bbox))
+                                    .collect()
+                            )
+                        } else {
+                            Arc::clone(&bboxes)
+                        };
+
+                        SourceProcessor::populate_bboxes(
+                            outputs,
+                            Arc::clone(&source_id),
+                            Arc::clone(&frame),
+                            bboxes,
+                            kafka_bboxes
+                        ).await;
+                    }
+                },
+                Some(InferenceModelType::DINO) => {
+                    if let Some(embeddings) = embeddings.filter(|embeddings| embeddings.len() > 0) {
+                        SourceProcessor::populate_embeddings(
+                            outputs,
+                            Arc::clone(&source_id),
+                            Arc::clone(&frame),
+                            embeddings
+                        ).await;
+                    }
+                },
+                None => anyhow::bail!("Source pipeline has no stages!")
+            }
+        }
+        stats.results += measure_start.elapsed().as_micros() as u64;
 
         // Return statistics
         stats.queue = frame_queue_time.as_micros() as u64;
         stats.processing += frame_queue_time.as_micros() as u64;
+
+        // Keep a summary of the frame for debugging
+        if let Some(bboxes) = &recent_bboxes {
+            outputs.recent.push(RecentResult::new(&frame, bboxes, &stats));
+        }
+
         Ok(stats)
     }
 
+    /// Records timing statistics of a single model stage to the metrics registry
+    fn observe_model_stats(source_id: &str, model_type: &InferenceModelType, stats: &FrameProcessStats) {
+        let labels = [source_id, model_type.to_string()];
+        metrics::PRE_PROC_TIME
+            .with_label_values(&labels)
+            .observe(metrics::micros_to_secs(stats.pre_processing));
+        metrics::INFERENCE_TIME
+            .with_label_values(&labels)
+            .observe(metrics::micros_to_secs(stats.inference));
+        metrics::POST_PROC_TIME
+            .with_label_values(&labels)
+            .observe(metrics::micros_to_secs(stats.post_processing));
+    }
+
     /// Reports inference statistics for the given source processor
     fn process_stats_internal(
         source_id: &str,
         source_config: &SourceConfig,
-        source_stats: &SourceStats
+        source_stats: &SourceStats,
+        frames_dropped: u64,
+        last_drop_warning: &mut Option<Instant>
     ) {
-        let mut avg_queue: f64 = 0.00;
-        let mut avg_pre_proc: f64 = 0.00;
-        let mut avg_inference: f64 = 0.00;
-        let mut avg_post_proc: f64 = 0.00;
-        let mut avg_results: f64 = 0.00;
-        let mut avg_processing: f64 = 0.00;
-
-        // Extract values of statistics
-        let frames_total = source_stats.frames_total.load(Ordering::Relaxed) as u64;
-        let frames_expected = source_stats.frames_expected.load(Ordering::Relaxed) as u64;
-        let frames_success = source_stats.frames_success.load(Ordering::Relaxed) as u64;
-        let frames_failed = source_stats.frames_failed.load(Ordering::Relaxed) as u64;
-        let total_queue_time = source_stats.total_queue_time.load(Ordering::Relaxed) as u64;
-        let total_pre_proc_time = source_stats.total_pre_proc_time.load(Ordering::Relaxed) as u64;
-        let total_inference_time = source_stats.total_inference_time.load(Ordering::Relaxed) as u64;
-        let total_post_proc_time = source_stats.total_post_proc_time.load(Ordering::Relaxed) as u64;
-        let total_results_time = source_stats.total_results_time.load(Ordering::Relaxed) as u64;
-        let total_processing_time = source_stats.total_processing_time.load(Ordering::Relaxed) as u64;
+        // Drain values of statistics - values recorded meanwhile are kept for the next interval
+        let frames_total = source_stats.frames_total.swap(0, Ordering::Relaxed);
+        let frames_expected = source_stats.frames_expected.swap(0, Ordering::Relaxed);
+        let frames_success = source_stats.frames_success.swap(0, Ordering::Relaxed);
+        let frames_failed = source_stats.frames_failed.swap(0, Ordering::Relaxed);
+        let frames_gated = source_stats.frames_gated.swap(0, Ordering::Relaxed);
+        let frames_deduplicated = source_stats.frames_deduplicated.swap(0, Ordering::Relaxed);
+        let frames_rate_limited = source_stats.frames_rate_limited.swap(0, Ordering::Relaxed);
+        let frames_superseded = source_stats.frames_superseded.swap(0, Ordering::Relaxed);
+        let dropped = source_stats.dropped_frames.lock().unwrap().drain();
+        let queue = source_stats.queue_time.drain();
+        let pre_proc = source_stats.pre_proc_time.drain();
+        let inference = source_stats.inference_time.drain();
+        let post_proc = source_stats.post_proc_time.drain();
+        let results = source_stats.results_time.drain();
+        let processing = source_stats.processing_time.drain();
         
-        if frames_success > 0 {
-            avg_queue = (total_queue_time as f64) / (frames_success as f64);
-            avg_pre_proc = (total_pre_proc_time as f64) / (frames_success as f64);
-            avg_inference = (total_inference_time as f64) / (frames_success as f64);
-            avg_post_proc = (total_post_proc_time as f64) / (frames_success as f64);
-            avg_results = (total_results_time as f64) / (frames_success as f64);
-            avg_processing = (total_processing_time as f64) / (frames_success as f64);
+        // Export interval counters
+        metrics::FRAMES_TOTAL.with_label_values(&[source_id]).inc_by(frames_total);
+        metrics::FRAMES_SUCCESS.with_label_values(&[source_id]).inc_by(frames_success);
+        metrics::FRAMES_FAILED.with_label_values(&[source_id]).inc_by(frames_failed);
+        metrics::FRAMES_GATED.with_label_values(&[source_id]).inc_by(frames_gated);
+        metrics::FRAMES_DEDUPLICATED.with_label_values(&[source_id]).inc_by(frames_deduplicated);
+        metrics::FRAMES_RATE_LIMITED.with_label_values(&[source_id]).inc_by(frames_rate_limited);
+        metrics::FRAMES_DROPPED.with_label_values(&[source_id]).inc_by(frames_dropped);
+        metrics::FRAMES_SUPERSEDED.with_label_values(&[source_id]).inc_by(frames_superseded);
+
+        // Warn when drops exceed the allowed share of frames arriving to the queue, at most once per warning interval
+        let frames_queued = frames_dropped + frames_expected;
+        if let (Some(drop_warn_percent), Some(dropped)) = (source_config.drop_warn_percent, &dropped) {
+            let drop_percent = frames_dropped as f32 / frames_queued.max(1) as f32 * 100.00;
+            let warning_due = last_drop_warning.is_none_or(|last| last.elapsed() >= DROP_WARNING_INTERVAL);
+
+            if drop_percent > drop_warn_percent && warning_due {
+                tracing::warn!(
+                    source_id=source_id,
+                    frames_dropped=frames_dropped,
+                    frames_queued=frames_queued,
+                    drop_percent=drop_percent,
+                    drop_warn_percent=drop_warn_percent,
+                    min_dropped_pts=dropped.min_pts,
+                    max_dropped_pts=dropped.max_pts,
+                    oldest_dropped_age=dropped.oldest_age.as_micros() as u64,
+                    "Source is dropping frames from a full queue"
+                );
+                *last_drop_warning = Some(Instant::now());
+            }
+        }
+
+        // Inferences per second over the interval
+        let inference_rate = frames_expected as f64 / SOURCE_STATS_INTERVAL.as_secs_f64();
+
+        // Export interval percentiles
+        for (stage, snapshot) in [
+            ("queue", &queue),
+            ("pre_proc", &pre_proc),
+            ("inference", &inference),
+            ("post_proc", &post_proc),
+            ("processing", &processing)
+        ] {
+            SourceProcessor::export_percentiles(source_id, stage, snapshot);
         }
 
         tracing::info!(
@@ -486,55 +1688,230 @@ impl SourceProcessor {
             frames_expected=frames_expected,
             frames_success=frames_success,
             frames_failed=frames_failed,
-            avg_queue=avg_queue,
-            avg_pre_proc=avg_pre_proc,
-            avg_inference=avg_inference,
-            avg_post_proc=avg_post_proc,
-            avg_results=avg_results,
-            avg_processing=avg_processing,
+            frames_gated=frames_gated,
+            frames_deduplicated=frames_deduplicated,
+            frames_rate_limited=frames_rate_limited,
+            frames_dropped=frames_dropped,
+            frames_superseded=frames_superseded,
+            min_dropped_pts=dropped.as_ref().map(|d| d.min_pts),
+            max_dropped_pts=dropped.as_ref().map(|d| d.max_pts),
+            oldest_dropped_age=dropped.as_ref().map(|d| d.oldest_age.as_micros() as u64),
+            inference_rate=inference_rate,
+            max_inferences_per_sec=source_config.max_inferences_per_sec,
+            avg_queue=queue.mean(),
+            avg_pre_proc=pre_proc.mean(),
+            avg_inference=inference.mean(),
+            avg_post_proc=post_proc.mean(),
+            avg_results=results.mean(),
+            avg_processing=processing.mean(),
+            p50_queue=queue.percentile(50.00),
+            p95_queue=queue.percentile(95.00),
+            p99_queue=queue.percentile(99.00),
+            max_queue=queue.max(),
+            p50_pre_proc=pre_proc.percentile(50.00),
+            p95_pre_proc=pre_proc.percentile(95.00),
+            p99_pre_proc=pre_proc.percentile(99.00),
+            max_pre_proc=pre_proc.max(),
+            p50_inference=inference.percentile(50.00),
+            p95_inference=inference.percentile(95.00),
+            p99_inference=inference.percentile(99.00),
+            max_inference=inference.max(),
+            p50_post_proc=post_proc.percentile(50.00),
+            p95_post_proc=post_proc.percentile(95.00),
+            p99_post_proc=post_proc.percentile(99.00),
+            max_post_proc=post_proc.max(),
+            p50_processing=processing.percentile(50.00),
+            p95_processing=processing.percentile(95.00),
+            p99_processing=processing.percentile(99.00),
+            max_processing=processing.max(),
             "inference statistics"
         );
+
+        // Persist the same statistics for offline analysis
+        if let Some(sink) = stats_sink::get_stats_sink() {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+
+            let mut record = json!({
+                "timestamp_ms": timestamp,
+                "source_id": source_id,
+                "inference_every_n": source_config.inf_frame,
+                "frames_total": frames_total,
+                "frames_expected": frames_expected,
+                "frames_success": frames_success,
+                "frames_failed": frames_failed,
+                "frames_gated": frames_gated,
+                "frames_deduplicated": frames_deduplicated,
+                "frames_rate_limited": frames_rate_limited,
+                "frames_dropped": frames_dropped,
+                "frames_superseded": frames_superseded,
+                "min_dropped_pts": dropped.as_ref().map(|d| d.min_pts),
+                "max_dropped_pts": dropped.as_ref().map(|d| d.max_pts),
+                "oldest_dropped_age": dropped.as_ref().map(|d| d.oldest_age.as_micros() as u64),
+                "inference_rate": inference_rate,
+                "max_inferences_per_sec": source_config.max_inferences_per_sec,
+                "avg_results": results.mean()
+            });
+
+            for (stage, snapshot) in [
+                ("queue", &queue),
+                ("pre_proc", &pre_proc),
+                ("inference", &inference),
+                ("post_proc", &post_proc),
+                ("processing", &processing)
+            ] {
+                record[format!("avg_{}", stage)] = json!(snapshot.mean());
+                record[format!("p50_{}", stage)] = json!(snapshot.percentile(50.00));
+                record[format!("p95_{}", stage)] = json!(snapshot.percentile(95.00));
+                record[format!("p99_{}", stage)] = json!(snapshot.percentile(99.00));
+                record[format!("max_{}", stage)] = json!(snapshot.max());
+            }
+
+            sink.record(record);
+        }
+    }
+
+    /// Exports percentiles of a processing stage over the last interval to Prometheus
+    fn export_percentiles(source_id: &str, stage: &str, snapshot: &HistogramSnapshot) {
+        if snapshot.count() == 0 {
+            return;
+        }
+
+        for (quantile, value) in [
+            ("0.5", snapshot.percentile(50.00)),
+            ("0.95", snapshot.percentile(95.00)),
+            ("0.99", snapshot.percentile(99.00)),
+            ("1", snapshot.max())
+        ] {
+            metrics::LATENCY_PERCENTILES
+                .with_label_values(&[source_id, stage, quantile])
+                .set(metrics::micros_to_secs(value));
+        }
+    }
+
+    /// Returns the desired frame delivery divisor of the source, if backpressure is enabled
+    pub fn delivery_divisor(&self) -> Option<u32> {
+        self.backpressure
+            .as_ref()
+            .map(|backpressure| backpressure.lock().unwrap().divisor())
+    }
+
+    /// Replaces the configuration of the source, taking effect from the next frame
+    /// 
+    /// Settings backing stateful components are only read on creation, see `can_update_config`
+    pub fn update_config(&self, source_config: SourceConfig) {
+        self.source_config.store(Arc::new(source_config));
+    }
+
+    /// Replaces the confidence threshold of the source, taking effect from the next frame
+    pub fn set_conf_threshold(&self, conf_threshold: f32) -> Result<()> {
+        if !(0.0..=1.0).contains(&conf_threshold) {
+            anyhow::bail!("Confidence threshold must be within 0-1, got {}", conf_threshold);
+        }
+
+        let mut source_config = (**self.source_config.load()).clone();
+        source_config.conf_threshold = conf_threshold;
+        self.update_config(source_config);
+
+        Ok(())
+    }
+
+    /// Stops or resumes inference of the source, the stream itself keeps running
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether a running processor can apply a new configuration in place.
+    /// Otherwise the processor must be recreated, as its motion gate, deduplicator,
+    /// rate limiter, debouncer, topics and queue are built from the configuration on creation.
+    /// Changes to the depth filter or depth source role recreate it too, clearing state gathered under the previous role.
+    ///
+    /// Settings the stream was started with - video_id, shared_memory and sub_stream - cannot change at all,
+    /// reloads changing them are rejected by `AppConfig::immutable_changes`
+    pub fn can_update_config(&self, source_config: &SourceConfig) -> bool {
+        let current = self.source_config.load();
+
+        current.motion_gate == source_config.motion_gate
+            && current.dedup_threshold == source_config.dedup_threshold
+            && current.dedup_max_skips == source_config.dedup_max_skips
+            && current.max_inferences_per_sec == source_config.max_inferences_per_sec
+            && current.debounce == source_config.debounce
+            && current.topic_override == source_config.topic_override
+            && current.queue_overflow_policy == source_config.queue_overflow_policy
+            && current.latest_only == source_config.latest_only
+            && current.depth_filter == source_config.depth_filter
+            && current.is_depth_source == source_config.is_depth_source
+    }
+
+    /// Returns the last known state of the source
+    pub fn last_state(&self) -> Option<SourceState> {
+        self.last_state.lock().unwrap().clone()
+    }
+
+    /// Restores the last known state of the source, unless frames were already processed
+    pub fn restore_state(&self, state: SourceState) {
+        let mut last_state = self.last_state.lock().unwrap();
+        if last_state.is_none() {
+            *last_state = Some(state);
+        }
     }
 
     /// Populates BBOXes to third party services
+    ///
+    /// Kafka receives its own set of bboxes, as detections may be debounced from it
     pub async fn populate_bboxes(
+        outputs: &ResultsOutputs,
         source_id: Arc<String>, 
         frame: Arc<RawFrame>, 
-        bboxes: Arc<Vec<ResultBBOX>>
+        bboxes: Arc<Vec<ResultBBOX>>,
+        kafka_bboxes: Arc<Vec<ResultBBOX>>
     ) {
-        let bboxes = Arc::new(bboxes);
-
         // Send to client video
-        let client_source_id = Arc::clone(&source_id);
-        let client_frame = Arc::clone(&frame);
-        let client_bboxes = Arc::clone(&bboxes);
-
-        if let Err(e) = tokio::task::spawn_blocking(move || {
-            ClientVideo::populate_bboxes(
-                &client_source_id,
-                &client_frame,
-                &client_bboxes
-            )
-        }).await {
-            tracing::warn!(
-                source_id=&*source_id,
-                error=e.to_string(),
-                "Failed to populate bboxes to client video"
-            );
-        };
+        if outputs.config.client_video {
+            let client_source_id = Arc::clone(&source_id);
+            let client_frame = Arc::clone(&frame);
+            let client_bboxes = Arc::clone(&bboxes);
+            let client_format = outputs.config.format;
 
+            if let Err(e) = tokio::task::spawn_blocking(move || {
+                ClientVideo::populate_bboxes(
+                    &client_source_id,
+                    &client_frame,
+                    &client_bboxes,
+                    client_format
+                )
+            }).await {
+                tracing::warn!(
+                    source_id=&*source_id,
+                    error=e.to_string(),
+                    "Failed to populate bboxes to client video"
+                );
+            };
+        }
 
         // Send to Kafka - don't wait for results
         // Will run in a seperate task
+        if !outputs.config.kafka || kafka_bboxes.is_empty() {
+            return;
+        }
+        let kafka_topic = outputs.topics.bboxes.clone();
         let kafka_source_id = Arc::clone(&source_id);
         let kafka_frame = Arc::clone(&frame);
-        let kafka_bboxes = Arc::clone(&bboxes);
+        let kafka_stream_info = *outputs.stream_info.lock().unwrap();
 
         tokio::task::spawn(async move {
             if let Err(e) = Kafka::populate_bboxes(
+                &kafka_topic,
                 &kafka_source_id,
                 &kafka_frame,
-                &kafka_bboxes
+                &kafka_bboxes,
+                kafka_stream_info.as_ref()
             ).await {
                 // tracing::warn!(
                 //     source_id=&*kafka_source_id,
@@ -545,20 +1922,72 @@ impl SourceProcessor {
         });
     }
 
+    /// Populates combined frame results to third party services
+    pub async fn populate_results(
+        outputs: &ResultsOutputs,
+        frame: Arc<RawFrame>,
+        results: Arc<FrameResults>,
+        kafka_results: Arc<FrameResults>
+    ) {
+        // Send to client video
+        if outputs.config.client_video {
+            let client_frame = Arc::clone(&frame);
+            let client_results = Arc::clone(&results);
+            let client_format = outputs.config.format;
+
+            if let Err(e) = tokio::task::spawn_blocking(move || {
+                ClientVideo::populate_results(
+                    &client_frame,
+                    &client_results,
+                    client_format
+                )
+            }).await {
+                tracing::warn!(
+                    source_id=&results.source_id,
+                    error=e.to_string(),
+                    "Failed to populate results to client video"
+                );
+            };
+        }
+
+        // Send to Kafka - don't wait for results
+        // Will run in a seperate task
+        if !outputs.config.kafka || kafka_results.detections.is_empty() {
+            return;
+        }
+        let kafka_topic = outputs.topics.results.clone();
+
+        tokio::task::spawn(async move {
+            if let Err(e) = Kafka::populate_results(&kafka_topic, Arc::clone(&kafka_results)).await {
+                tracing::warn!(
+                    source_id=&kafka_results.source_id,
+                    error=e.to_string(),
+                    "Failed to populate results to Kafka"
+                );
+            };
+        });
+    }
+
     /// Populates embedding to third party services
     pub async fn populate_embeddings(
+        outputs: &ResultsOutputs,
         source_id: Arc<String>, 
         frame: Arc<RawFrame>, 
         embeddings: Arc<Vec<ResultEmbedding>>
     ) {
         // Send to Kafka - don't wait for results
         // Will run in a seperate task
+        if !outputs.config.kafka {
+            return;
+        }
+        let kafka_topic = outputs.topics.embedding.clone();
         let kafka_source_id = Arc::clone(&source_id);
         let kafka_frame = Arc::clone(&frame);
         let kafka_embeddings = Arc::clone(&embeddings);
 
         tokio::task::spawn(async move {
             if let Err(e) = Kafka::populate_embeddings(
+                &kafka_topic,
                 &kafka_source_id,
                 &kafka_frame,
                 &kafka_embeddings
@@ -579,4 +2008,292 @@ impl Drop for SourceProcessor {
         self.process_handle.abort();
         self.stats_handle.abort();
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::BoxFuture;
+    use crate::inference::Inference;
+    use crate::processing::PreprocessCache;
+    use crate::utils::config::ModelConfig;
+
+    fn frame_results(pts: u64) -> Arc<FrameResults> {
+        Arc::new(FrameResults {
+            pts,
+            source_id: "1".to_string(),
+            model_name: None,
+            embedding_version: None,
+            frame_embedding: None,
+            detections: Vec::new()
+        })
+    }
+
+    fn raw_frame(pts: u64, age: Duration) -> RawFrame {
+        RawFrame {
+            data: Vec::new(),
+            height: 0,
+            width: 0,
+            original_height: 0,
+            original_width: 0,
+            pts,
+            added: Instant::now() - age
+        }
+    }
+
+    fn outputs() -> ResultsOutputs {
+        let topics = SourceTopics {
+            bboxes: "bboxes".to_string(),
+            embedding: "embedding".to_string(),
+            results: "results".to_string()
+        };
+        ResultsOutputs::new(OutputsConfig::default(), topics)
+    }
+
+    #[tokio::test]
+    async fn broadcasts_results_to_source_subscribers() {
+        let outputs = outputs();
+        assert!(!outputs.has_subscribers());
+
+        let mut receiver = outputs.sender.subscribe();
+        assert!(outputs.has_subscribers());
+
+        outputs.broadcast(&frame_results(7));
+
+        assert_eq!(recv_results(&mut receiver).await.unwrap().pts, 7);
+    }
+
+    #[tokio::test]
+    async fn skips_results_missed_by_lagging_subscribers() {
+        let (sender, mut receiver) = broadcast::channel(1);
+        let lagged = metrics::RESULTS_LAGGED.get();
+
+        for pts in 1..=3 {
+            assert!(sender.send(frame_results(pts)).is_ok());
+        }
+
+        assert_eq!(recv_results(&mut receiver).await.unwrap().pts, 3);
+        assert!(metrics::RESULTS_LAGGED.get() >= lagged + 2);
+
+        drop(sender);
+        assert!(recv_results(&mut receiver).await.is_none());
+    }
+
+    #[test]
+    fn summarizes_dropped_frames() {
+        let mut dropped = DroppedFrames::new(8);
+        assert!(dropped.drain().is_none());
+
+        for (pts, age) in [(40, 30), (10, 120), (25, 60)] {
+            dropped.record(&raw_frame(pts, Duration::from_millis(age)));
+        }
+        let summary = dropped.drain().unwrap();
+
+        assert_eq!((summary.min_pts, summary.max_pts), (10, 40));
+        assert!(summary.oldest_age >= Duration::from_millis(120));
+        assert!(dropped.drain().is_none(), "draining clears the interval");
+    }
+
+    #[test]
+    fn keeps_most_recent_dropped_frames() {
+        let mut dropped = DroppedFrames::new(2);
+
+        for pts in 1..=5 {
+            dropped.record(&raw_frame(pts, Duration::ZERO));
+        }
+        let summary = dropped.drain().unwrap();
+
+        assert_eq!((summary.min_pts, summary.max_pts), (4, 5));
+    }
+
+    fn bbox(class: u32, score: f32) -> ResultBBOX {
+        ResultBBOX { bbox: [0.0, 0.0, 10.0, 10.0], class, score }
+    }
+
+    #[test]
+    fn summarizes_recent_results() {
+        let bboxes: Vec<ResultBBOX> = [0.4, 0.9, 0.5, 0.7, 0.6, 0.8]
+            .iter()
+            .enumerate()
+            .map(|(index, &score)| bbox(if index < 4 { 0 } else { 2 }, score))
+            .collect();
+
+        let result = RecentResult::new(&raw_frame(12, Duration::ZERO), &bboxes, &FrameProcessStats::default());
+
+        assert_eq!(result.pts, 12);
+        assert_eq!(result.detections, 6);
+        assert_eq!(result.class_counts, BTreeMap::from([("person", 4), ("car", 2)]));
+        assert_eq!(result.top_scores, vec![0.9, 0.8, 0.7, 0.6, 0.5]);
+    }
+
+    #[test]
+    fn keeps_most_recent_results() {
+        let recent = RecentResults::new(2);
+        for pts in 1..=3 {
+            recent.push(RecentResult::new(&raw_frame(pts, Duration::ZERO), &[], &FrameProcessStats::default()));
+        }
+
+        let pts: Vec<u64> = recent.snapshot().iter().map(|result| result.pts).collect();
+        assert_eq!(pts, vec![2, 3]);
+
+        let disabled = RecentResults::new(0);
+        disabled.push(RecentResult::new(&raw_frame(1, Duration::ZERO), &[], &FrameProcessStats::default()));
+        assert!(disabled.snapshot().is_empty());
+    }
+
+    #[test]
+    fn suppresses_similar_detections_of_other_sources() {
+        let deduplicator = OverlapDeduplicator::new(500, 0.95);
+        let car = vec![0.60, 0.80, 0.00, 0.10];
+        let same_car = vec![0.61, 0.79, 0.01, 0.10];
+        let other_car = vec![0.00, 0.10, 0.90, 0.40];
+        let now = Instant::now();
+
+        assert_eq!(deduplicator.check_at("1", [(2, Some(car.as_slice())), (0, None)], now), vec![true, true]);
+
+        // Near-identical embedding seen by another camera within the window
+        let later = now + Duration::from_millis(100);
+        let detections = [
+            (2, Some(same_car.as_slice())),
+            (0, Some(same_car.as_slice())),
+            (2, Some(other_car.as_slice()))
+        ];
+        assert_eq!(deduplicator.check_at("2", detections, later), vec![false, true, true]);
+
+        // The source that published it is not suppressed by itself
+        assert_eq!(deduplicator.check_at("1", [(2, Some(same_car.as_slice()))], later), vec![true]);
+
+        // Published detections expire after the window
+        let expired = now + Duration::from_millis(700);
+        assert_eq!(deduplicator.check_at("2", [(2, Some(same_car.as_slice()))], expired), vec![true]);
+    }
+
+    /// Model returning the same outputs for every input, in place of Triton Server
+    struct StubModel {
+        model_type: InferenceModelType,
+        model_config: ModelConfig,
+        output: Vec<u8>
+    }
+
+    impl Inference for StubModel {
+        fn model_type(&self) -> &InferenceModelType {
+            &self.model_type
+        }
+
+        fn model_config(&self) -> &ModelConfig {
+            &self.model_config
+        }
+
+        fn preprocess_cache(&self) -> Option<Arc<PreprocessCache>> {
+            None
+        }
+
+        fn infer_outputs(&self, raw_inputs: Vec<Vec<u8>>) -> BoxFuture<'_, Result<Vec<Vec<Vec<u8>>>>> {
+            Box::pin(async move {
+                Ok(raw_inputs.iter().map(|_| vec![self.output.clone()]).collect())
+            })
+        }
+    }
+
+    struct StubModels(HashMap<InferenceModelType, Arc<StubModel>>);
+
+    impl ModelProvider for StubModels {
+        fn model(&self, model_type: &InferenceModelType) -> Result<Arc<dyn Inference>> {
+            let model = self.0.get(model_type).context("Stub model is not configured")?;
+            Ok(Arc::clone(model) as Arc<dyn Inference>)
+        }
+    }
+
+    fn f32_bytes(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_le_bytes()).collect()
+    }
+
+    /// YOLO detecting a single object of the given score, followed by DINO producing the given embedding
+    fn stub_models(score: f32, embedding: &[f32]) -> Arc<StubModels> {
+        let yolo = StubModel {
+            model_type: InferenceModelType::YOLO,
+            model_config: serde_yaml::from_str("{name: yolo, input_shape: [3, 640, 640], output_shape: [5, 1]}").unwrap(),
+            output: f32_bytes(&[320.0, 320.0, 100.0, 100.0, score])
+        };
+        let dino = StubModel {
+            model_type: InferenceModelType::DINO,
+            model_config: serde_yaml::from_str("{name: dino, version: 3, input_shape: [3, 224, 224]}").unwrap(),
+            output: f32_bytes(embedding)
+        };
+
+        Arc::new(StubModels(HashMap::from([
+            (InferenceModelType::YOLO, Arc::new(yolo)),
+            (InferenceModelType::DINO, Arc::new(dino))
+        ])))
+    }
+
+    fn stub_processor(source_config: SourceConfig, models: Arc<StubModels>) -> SourceProcessor {
+        let outputs_config = OutputsConfig {
+            kafka: false,
+            client_video: false,
+            ..OutputsConfig::default()
+        };
+
+        SourceProcessor::with_models(
+            "stub".to_string(),
+            source_config,
+            outputs_config,
+            outputs().topics.clone(),
+            &BackpressureConfig::default(),
+            models
+        )
+    }
+
+    async fn next_results(receiver: &mut broadcast::Receiver<Arc<FrameResults>>) -> Arc<FrameResults> {
+        tokio::time::timeout(Duration::from_secs(10), recv_results(receiver))
+            .await
+            .expect("Frame results were not published")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn publishes_results_of_frames_through_pipeline() {
+        let source_config = SourceConfig {
+            pipeline: vec![InferenceModelType::YOLO, InferenceModelType::DINO],
+            combined_results: true,
+            ..SourceConfig::default()
+        };
+        let processor = stub_processor(source_config, stub_models(0.9, &[0.6, 0.8]));
+        let mut receiver = processor.outputs.sender.subscribe();
+
+        processor.process_frame(vec![0; 64 * 64 * 3], 64, 64, 42).await;
+        let results = next_results(&mut receiver).await;
+
+        assert_eq!((results.source_id.as_str(), results.pts), ("stub", 42));
+        assert_eq!((results.model_name.as_deref(), results.embedding_version), (Some("dino"), Some(3)));
+        assert_eq!(results.detections.len(), 1);
+        assert_eq!(results.detections[0].score, 0.9);
+        assert_eq!(results.detections[0].embedding_index, Some(1));
+        assert_eq!(results.detections[0].embedding, Some(vec![0.6, 0.8]));
+        assert_eq!(processor.last_state().map(|state| state.pts), Some(42));
+    }
+
+    #[tokio::test]
+    async fn filters_next_frame_with_updated_conf_threshold() {
+        let source_config = SourceConfig {
+            pipeline: vec![InferenceModelType::YOLO],
+            conf_threshold: 0.25,
+            ..SourceConfig::default()
+        };
+        let processor = stub_processor(source_config.clone(), stub_models(0.5, &[]));
+        let mut receiver = processor.outputs.sender.subscribe();
+
+        processor.process_frame(vec![0; 64 * 64 * 3], 64, 64, 1).await;
+        assert_eq!(next_results(&mut receiver).await.detections.len(), 1);
+
+        processor.set_conf_threshold(0.6).unwrap();
+        processor.process_frame(vec![0; 64 * 64 * 3], 64, 64, 2).await;
+        let results = next_results(&mut receiver).await;
+        assert_eq!((results.pts, results.detections.len()), (2, 0));
+
+        processor.update_config(SourceConfig { conf_threshold: 0.4, ..source_config });
+        processor.process_frame(vec![0; 64 * 64 * 3], 64, 64, 3).await;
+        let results = next_results(&mut receiver).await;
+        assert_eq!((results.pts, results.detections.len()), (3, 1));
+    }
 }
\ No newline at end of file
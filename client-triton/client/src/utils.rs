@@ -1,14 +1,27 @@
 use image::{ImageReader, GenericImageView};
 use anyhow::{Result, Context};
-use nvml_wrapper::Nvml;
 
 // Custom modules
 pub mod config;
 pub mod kafka;
+pub mod metrics;
+pub mod histogram;
+pub mod persistence;
+pub mod stats_sink;
 pub mod queue;
+pub mod rate_limiter;
+pub mod backpressure;
+pub mod protobuf;
+pub mod gpu;
+pub mod control;
+pub mod hot_reload;
+pub mod logging;
+pub mod shutdown;
 
 /// Represents GPU statistics that are reported by the application
 pub struct GPUStats {
+    /// Index of the GPU within its backend
+    pub index: u32,
     pub name: String,
     pub uuid: String,
     pub serial: String,
@@ -36,63 +49,14 @@ pub fn get_image_raw(path: &str) -> Result<(Vec<u8>, u32, u32)> {
     Ok((img_rgb8.into_raw(), height, width))
 }
 
-/// Returns the name of the NVIDIA GPU installed on the machine
-pub fn get_gpu_name() -> Result<String> {
-    let nvml = Nvml::init()
-        .context("Error initiating NVML wrapper")?;
-    let device = nvml.device_by_index(0)
-        .context("Error getting GPU ID 0 device")?;
-    Ok(
-        device.name()
-            .context("Error getting GPU ID 0 Name")?
-    )
-}
-
-
-/// Returns statistics about the NVIDIA GPU installed on the machine
-pub fn get_gpu_statistics() -> Result<GPUStats> {
-    let nvml = Nvml::init()
-        .context("Error initiating NVML wrapper")?;
-    let device = nvml.device_by_index(0)
-        .context("Error getting GPU ID 0 device")?;
-
-    // GPU general information
-    let gpu_name = device.name()
-        .context("Error getting GPU name")?;
-    let gpu_uuid = device.uuid()
-        .unwrap_or("".to_string());
-    let gpu_serial = device.serial()
-        .unwrap_or("".to_string());
-
+/// Returns the name of the first of the given GPUs, NVIDIA or AMD, failing when any of them is not installed.
+/// Returns nothing without a supported GPU backend
+pub fn get_gpu_name(gpu_indices: &[u32]) -> Result<Option<String>> {
+    let Some(monitor) = gpu::detect_gpu_monitor() else {
+        return Ok(None);
+    };
+    monitor.validate_indices(gpu_indices)?;
 
-    // GPU memory information
-    let memory_info = device.memory_info()
-        .context("Error getting GPU memory information")?;
-    let gpu_memory_total = memory_info.total / 1024 / 1024;
-    let gpu_memory_used = memory_info.used / 1024 / 1024;
-    let gpu_memory_free = memory_info.free / 1024 / 1024;
-    let mut gpu_memory: u32 = 0;
-
-    if gpu_memory_total > 0 {
-        gpu_memory = (gpu_memory_used as f32 * 100.0 / gpu_memory_total as f32) as u32;
-    }
-
-    // GPU performance information
-    let utilization = device.utilization_rates()
-        .context("Error getting GPU utilization information")?;
-    let gpu_util = utilization.gpu;
-
-
-    Ok(
-        GPUStats {
-            name: gpu_name,
-            uuid: gpu_uuid,
-            serial: gpu_serial,
-            memory_total: gpu_memory_total,
-            memory_used: gpu_memory_used,
-            memory_free: gpu_memory_free,
-            util_perc: gpu_util,
-            memory_perc: gpu_memory
-        }
-    )
-}
\ No newline at end of file
+    let index = gpu_indices.first().copied().unwrap_or(0);
+    Ok(Some(monitor.gpu_name(index)?))
+}
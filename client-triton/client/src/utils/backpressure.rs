@@ -0,0 +1,133 @@
+//! Responsible for computing how often a source should deliver frames,
+//! so overloaded sources are throttled at the video layer instead of dropping frames
+
+// Custom modules
+use crate::utils::config::BackpressureConfig;
+
+/// Computes the delivery divisor of a source - deliver every Nth frame - from its load
+///
+/// The divisor doubles after consecutive intervals under pressure (queue mostly full or
+/// frames dropped), and halves after consecutive intervals of relief (queue mostly empty
+/// without drops). Loads in between keep the divisor, so it does not oscillate every interval
+pub struct BackpressureController {
+    config: BackpressureConfig,
+    divisor: u32,
+    pressure_intervals: u32,
+    relief_intervals: u32
+}
+
+impl BackpressureController {
+    pub fn new(config: BackpressureConfig) -> Self {
+        Self {
+            config,
+            divisor: 1,
+            pressure_intervals: 0,
+            relief_intervals: 0
+        }
+    }
+
+    /// Updates the divisor with the load of the last interval, returns the new divisor
+    ///
+    /// Occupancy is the fraction of the queue in use, drop rate is the fraction
+    /// of frames dropped from the queue out of all queued frames
+    pub fn update(&mut self, occupancy: f32, drop_rate: f32) -> u32 {
+        if occupancy >= self.config.high_occupancy || drop_rate > self.config.max_drop_rate {
+            self.pressure_intervals += 1;
+            self.relief_intervals = 0;
+        } else if occupancy <= self.config.low_occupancy && drop_rate == 0.00 {
+            self.relief_intervals += 1;
+            self.pressure_intervals = 0;
+        } else {
+            self.pressure_intervals = 0;
+            self.relief_intervals = 0;
+        }
+
+        let hysteresis_intervals = self.config.hysteresis_intervals.max(1);
+
+        if self.pressure_intervals >= hysteresis_intervals {
+            self.divisor = (self.divisor * 2).min(self.config.max_divisor.max(1));
+            self.pressure_intervals = 0;
+        } else if self.relief_intervals >= hysteresis_intervals {
+            self.divisor = (self.divisor / 2).max(1);
+            self.relief_intervals = 0;
+        }
+
+        self.divisor
+    }
+
+    pub fn divisor(&self) -> u32 {
+        self.divisor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> BackpressureController {
+        BackpressureController::new(BackpressureConfig {
+            enabled: true,
+            max_divisor: 4,
+            high_occupancy: 0.8,
+            low_occupancy: 0.2,
+            max_drop_rate: 0.05,
+            hysteresis_intervals: 2,
+            push_interval_secs: 5
+        })
+    }
+
+    #[test]
+    fn doubles_divisor_after_consecutive_pressure() {
+        let mut controller = controller();
+
+        assert_eq!(controller.update(0.9, 0.00), 1);
+        assert_eq!(controller.update(0.5, 0.10), 2, "drops alone are pressure");
+        assert_eq!(controller.update(0.9, 0.00), 2);
+        assert_eq!(controller.update(0.9, 0.00), 4);
+    }
+
+    #[test]
+    fn caps_divisor_at_max() {
+        let mut controller = controller();
+
+        for _ in 0..10 {
+            controller.update(1.0, 0.50);
+        }
+
+        assert_eq!(controller.divisor(), 4);
+    }
+
+    #[test]
+    fn halves_divisor_after_consecutive_relief() {
+        let mut controller = controller();
+        for _ in 0..4 {
+            controller.update(1.0, 0.00);
+        }
+
+        assert_eq!(controller.update(0.1, 0.00), 4);
+        assert_eq!(controller.update(0.1, 0.00), 2);
+        assert_eq!(controller.update(0.1, 0.00), 2);
+        assert_eq!(controller.update(0.1, 0.00), 1);
+        assert_eq!(controller.update(0.0, 0.00), 1);
+        assert_eq!(controller.update(0.0, 0.00), 1);
+    }
+
+    #[test]
+    fn keeps_divisor_under_moderate_or_alternating_load() {
+        let mut controller = controller();
+
+        for _ in 0..5 {
+            assert_eq!(controller.update(0.5, 0.00), 1);
+        }
+        for _ in 0..5 {
+            assert_eq!(controller.update(0.9, 0.00), 1);
+            assert_eq!(controller.update(0.5, 0.00), 1);
+        }
+
+        // Relief requires no drops, even with an empty queue
+        controller.update(1.0, 0.00);
+        controller.update(1.0, 0.00);
+        assert_eq!(controller.update(0.1, 0.01), 2);
+        assert_eq!(controller.update(0.1, 0.01), 2);
+    }
+}
@@ -2,257 +2,3146 @@
 //! for easy access and setting format for same variables
 
 use std::path::{Path};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use anyhow::{self, Result, Context};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, fmt};
-use tracing_appender::rolling::{RollingFileAppender, Rotation};
-use serde_yaml;
-use serde::Deserialize;
+use serde_yaml::{self, Value};
+use serde::{Deserialize, Serialize};
 
 // Custom modules
 use crate::utils;
+use crate::utils::metrics;
+use crate::utils::logging::{self, LoggingGuard};
+use crate::utils::queue::OverflowPolicy;
 
-/// Represents the local environment the codebase is on
-/// 
-/// Used mostly to read an environment variables file and
-/// use it in the application rather than external variables
-#[derive(Clone, Copy, Debug, Deserialize)]
+/// Configuration file path, relative to the working directory
+pub const CONFIG_PATH: &str = "secrets/config.yaml";
+
+/// Deprecated top-level configuration fields, with their suggested replacements.
+/// Deprecated fields are removed after 2 major versions
+pub const DEPRECATED_FIELDS: &[(&str, &str)] = &[
+    ("log_format", "logging_config.format"),
+    ("triton_config.url", "triton_config.endpoints")
+];
+
+/// Setter of a configuration field from the value of an environment variable
+type EnvOverrideSetter = fn(&mut AppConfig, &str) -> Result<()>;
+
+/// Environment variables overriding fields of the configuration file, with the path of the field and
+/// the expected type of their value. Names follow the path of the field, separated by double underscores
+pub const ENV_OVERRIDES: &[(&str, &str, &str, EnvOverrideSetter)] = &[
+    ("APP__LOCAL", "local", "boolean", |c, v| { c.local = v.parse()?; Ok(()) }),
+    ("APP__TRITON__URL", "triton_config.endpoints.0.url", "string", |c, v| { c.triton_config.set_primary_url(v); Ok(()) }),
+    ("APP__TRITON__MODELS_DIR", "triton_config.models_dir", "string", |c, v| { c.triton_config.models_dir = v.to_string(); Ok(()) }),
+    ("APP__KAFKA__ENABLED", "kafka_config.enabled", "boolean", |c, v| { c.kafka_config.enabled = v.parse()?; Ok(()) }),
+    ("APP__KAFKA__BROKERS", "kafka_config.brokers", "string", |c, v| { c.kafka_config.brokers = v.to_string(); Ok(()) }),
+    ("APP__KAFKA__SCHEMA_REGISTRY_URL", "kafka_config.schema_registry_url", "string", |c, v| { c.kafka_config.schema_registry_url = Some(v.to_string()); Ok(()) }),
+    ("APP__KAFKA__SASL_USERNAME", "kafka_config.sasl_username", "string", |c, v| { c.kafka_config.sasl_username = Some(v.to_string()); Ok(()) }),
+    ("APP__KAFKA__SASL_PASSWORD", "kafka_config.sasl_password", "string", |c, v| { c.kafka_config.sasl_password = Some(v.to_string()); Ok(()) }),
+    ("APP__ADMIN__ENABLED", "admin_config.enabled", "boolean", |c, v| { c.admin_config.enabled = v.parse()?; Ok(()) }),
+    ("APP__ADMIN__HOST", "admin_config.host", "string", |c, v| { c.admin_config.host = v.to_string(); Ok(()) }),
+    ("APP__ADMIN__PORT", "admin_config.port", "integer (0-65535)", |c, v| { c.admin_config.port = v.parse()?; Ok(()) }),
+    ("APP__ADMIN__TOKEN", "admin_config.token", "string", |c, v| { c.admin_config.token = Some(v.to_string()); Ok(()) }),
+    ("APP__SOURCES__DEFAULT__INF_FRAME", "sources_config.default.inf_frame", "integer", |c, v| { c.sources_config.default.inf_frame = v.parse()?; Ok(()) }),
+    ("APP__SOURCES__DEFAULT__CONF_THRESHOLD", "sources_config.default.conf_threshold", "float", |c, v| { c.sources_config.default.conf_threshold = v.parse()?; Ok(()) }),
+    ("APP__SOURCES__DEFAULT__NMS_IOU_THRESHOLD", "sources_config.default.nms_iou_threshold", "float", |c, v| { c.sources_config.default.nms_iou_threshold = v.parse()?; Ok(()) }),
+];
+
+/// Accessor of a configuration field whose value may reference secrets
+type SecretField = fn(&mut AppConfig) -> Option<&mut String>;
+
+/// Configuration fields that may reference secrets with `${env:VAR_NAME}` or `${file:/path}`,
+/// resolved after the configuration is parsed. Each is serialized with `redact_secret`
+pub const SECRET_FIELDS: &[(&str, SecretField)] = &[
+    ("kafka_config.sasl_password", |c| c.kafka_config.sasl_password.as_mut()),
+    ("admin_config.token", |c| c.admin_config.token.as_mut()),
+];
+
+/// Replaces every `${env:VAR_NAME}` and `${file:/path}` reference of a value with the content of
+/// the environment variable or file, leaving the rest of the value untouched.
+/// A single trailing newline of files is removed
+pub fn resolve_secret_references(value: &str) -> Result<String> {
+    let mut resolved = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        resolved.push_str(&rest[..start]);
+
+        let reference = &rest[start + 2..];
+        let end = reference.find('}')
+            .context("Secret reference is missing its closing '}'")?;
+        let (scheme, source) = reference[..end]
+            .split_once(':')
+            .with_context(|| format!("Secret reference '{}' must be of the form ${{env:VAR_NAME}} or ${{file:/path}}", &reference[..end]))?;
+
+        let secret = match scheme {
+            "env" => std::env::var(source)
+                .with_context(|| format!("Environment variable {} is not set", source))?,
+            "file" => {
+                let contents = std::fs::read_to_string(source)
+                    .with_context(|| format!("Error reading secret file {}", source))?;
+                let contents = contents.strip_suffix('\n').unwrap_or(&contents);
+                contents.strip_suffix('\r').unwrap_or(contents).to_string()
+            }
+            _ => anyhow::bail!("Unknown secret reference scheme '{}', expected env or file", scheme)
+        };
+
+        resolved.push_str(&secret);
+        rest = &reference[end + 1..];
+    }
+
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+/// Placeholder of secret values in serialized configuration
+pub const REDACTED: &str = "<redacted>";
+
+/// Serializes a secret field as `REDACTED` when set, so configuration dumps never hold credentials
+fn redact_secret<S>(value: &Option<String>, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer
+{
+    value.as_ref().map(|_| REDACTED).serialize(serializer)
+}
+
+/// Returns dot separated paths of every leaf of a JSON document, array items by their index
+fn leaf_paths(value: &serde_json::Value, path: String, paths: &mut Vec<String>) {
+    let join = |key: &dyn std::fmt::Display| match path.is_empty() {
+        true => key.to_string(),
+        false => format!("{}.{}", path, key)
+    };
+
+    match value {
+        serde_json::Value::Object(object) if !object.is_empty() => {
+            for (key, value) in object {
+                leaf_paths(value, join(key), paths);
+            }
+        }
+        serde_json::Value::Array(array) if !array.is_empty() => {
+            for (index, value) in array.iter().enumerate() {
+                leaf_paths(value, join(&index), paths);
+            }
+        }
+        _ => paths.push(path)
+    }
+}
+
+/// Represents the environment the application is deployed to, written as `dev`, `staging` or `prod`.
+/// The former `NonProduction` and `Production` values are still accepted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Environment {
-    Production,
-    NonProduction
+    #[serde(alias = "NonProduction")]
+    Dev,
+    Staging,
+    #[default]
+    #[serde(alias = "Production")]
+    Prod
+}
+
+/// Represents the format of console logs
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum LogFormat {
+    Json,
+    Pretty,
+    Compact
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ModelConfig {
     pub name: String,
+
+    /// Version of the model outputs, bumped whenever outputs change meaning
+    /// (e.g. embedding dimensions). Published with results for consumers
+    #[serde(default = "ModelConfig::default_version")]
+    pub version: u32,
+
+    /// Precision of the model inputs, FP32 by default
+    #[serde(default)]
     pub precision: InferencePrecision,
+
+    /// Name of the model input, `images` by default
+    #[serde(default = "ModelConfig::default_input_name")]
     pub input_name: String,
+
+    /// Per-sample shape of the model input, defaults to the shape of the model type when empty
+    #[serde(default)]
     pub input_shape: Vec<i64>,
-    pub output_name: String,
-    pub output_shape: Vec<i64>,
+
+    /// Names of the model outputs, a single name or a list for models with multiple outputs.
+    /// `output` by default
+    #[serde(default = "ModelConfig::default_output_name", deserialize_with = "one_or_many")]
+    pub output_name: Vec<String>,
+
+    /// Per-sample shapes of the model outputs, in the same order as output names.
+    /// Defaults to the shapes of the model type when empty
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub output_shape: Vec<Vec<i64>>,
+
+    /// Maximum batch size, 8 by default
+    #[serde(default = "ModelConfig::default_batch_max_size")]
     pub batch_max_size: u32,
+
+    /// Microseconds requests wait to be batched, 1000 by default
+    #[serde(default = "ModelConfig::default_batch_max_queue_delay")]
     pub batch_max_queue_delay: u32,
-    pub batch_preferred_sizes: Vec<u32>
+
+    /// Batch sizes Triton prefers to create, defaults to powers of two up to `batch_max_size` when empty
+    #[serde(default)]
+    pub batch_preferred_sizes: Vec<u32>,
+
+    /// Maximum amount of cached inference results, keyed by the preprocessed inputs. 0 disables caching
+    #[serde(default)]
+    pub max_cache_entries: usize,
+
+    /// Maximum amount of cached preprocessed frames, keyed by a hash of the frame. 0 disables caching.
+    /// Useful when replaying the same frames, e.g. while tuning postprocess settings
+    #[serde(default)]
+    pub max_preprocess_cache_entries: usize,
+
+    /// Whether Triton batches requests dynamically. When disabled, each request is executed on its own
+    #[serde(default = "ModelConfig::default_enable_dynamic_batching")]
+    pub enable_dynamic_batching: bool,
+
+    /// Whether dynamically batched responses are returned in request order
+    #[serde(default)]
+    pub preserve_ordering: bool,
+
+    /// Seconds between warm-up inferences validating the model, disabled when not set
+    #[serde(default)]
+    pub health_check_interval_secs: Option<u64>,
+
+    /// Milliseconds after which a health check inference is considered failed
+    #[serde(default = "ModelConfig::default_health_check_timeout_ms")]
+    pub health_check_timeout_ms: u64,
+
+    /// Name of the Triton endpoint serving the model, the first endpoint when not set
+    #[serde(default)]
+    pub endpoint: Option<String>,
+
+    /// Endpoints also hosting the model, in order of preference. The model fails over to them
+    /// when a health check fails and it cannot be reloaded, requiring `health_check_interval_secs`
+    #[serde(default)]
+    pub failover_endpoints: Vec<String>,
+
+    /// Model repository of the model, overriding `triton_config.models_dir`
+    #[serde(default)]
+    pub models_dir: Option<String>,
+
+    /// Writes the first preprocessed inputs to disk for replaying against Triton, disabled when not set
+    #[serde(default)]
+    pub tensor_dump: Option<TensorDumpConfig>
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TensorDumpConfig {
+    /// Directory receiving raw input tensors along with their metadata
+    pub dir: String,
+    /// Amount of input samples written before dumping stops
+    #[serde(default = "TensorDumpConfig::default_max_samples")]
+    pub max_samples: u32
+}
+
+impl TensorDumpConfig {
+    fn default_max_samples() -> u32 {
+        5
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SourcesConfig {
     #[serde(default)]
     pub sources: HashMap<String, SourceConfig>,
 
+    /// Ids of the sources, written as strings or numbers
+    #[serde(deserialize_with = "source_ids")]
     pub ids: Vec<String>,
+    #[serde(default)]
     pub default: SourceConfig,
+    #[serde(default, deserialize_with = "source_id_keys")]
     pub custom: HashMap<String, SourceConfigOptional>
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SourceConfig {
+    /// Every Nth frame is inferred, 1 by default
+    #[serde(default = "SourceConfig::default_inf_frame")]
     pub inf_frame: u32,
+
+    /// Detections scored below it are dropped, 0.25 by default
+    #[serde(default = "SourceConfig::default_conf_threshold")]
     pub conf_threshold: f32,
-    pub nms_iou_threshold: f32
+
+    /// IoU above which overlapping detections are suppressed, used by hard NMS only. 0.45 by default
+    #[serde(default = "SourceConfig::default_nms_iou_threshold")]
+    pub nms_iou_threshold: f32,
+
+    /// Suppress overlapping detections regardless of their classes
+    #[serde(default)]
+    pub class_agnostic_nms: bool,
+
+    /// How overlapping detections are reduced
+    #[serde(default)]
+    pub nms_mode: NmsMode,
+
+    /// Gaussian decay of soft NMS, overlapping scores decay as `score * exp(-IoU^2 / sigma)`.
+    /// Lower values decay more aggressively - typically 0.5 for pedestrians, 1.0 for vehicles.
+    /// Decayed scores are filtered again by conf_threshold, so detections above the threshold
+    /// before NMS may still be dropped. Used by soft NMS only, defaults to 0.5 when not set
+    #[serde(default)]
+    pub nms_soft_sigma: Option<f32>,
+
+    /// Skips inference on frames without motion, disabled when not set
+    #[serde(default)]
+    pub motion_gate: Option<MotionGateConfig>,
+
+    /// Mean absolute difference of grayscale pixels (0-255) from the last processed
+    /// frame, below which a frame is skipped as a duplicate. Disabled when not set
+    #[serde(default)]
+    pub dedup_threshold: Option<f32>,
+
+    /// Maximum consecutive frames skipped as duplicates, so static scenes still get periodic inference.
+    /// Unlimited when not set
+    #[serde(default)]
+    pub dedup_max_skips: Option<u32>,
+
+    /// Hard cap of inferences per second, applied on top of inf_frame. Unlimited when not set
+    #[serde(default)]
+    pub max_inferences_per_sec: Option<f64>,
+
+    /// Percentage (0-100) of frames arriving to the queue that may be dropped before warning.
+    /// Disabled when not set
+    #[serde(default)]
+    pub drop_warn_percent: Option<f32>,
+
+    /// Probability (0-1) of logging the full detections of a frame at debug level, for inspecting live outputs.
+    /// Disabled by default
+    #[serde(default)]
+    pub debug_sample_rate: f32,
+
+    /// Models executed in order for each frame, derived from the inference task when empty
+    #[serde(default)]
+    pub pipeline: Vec<InferenceModelType>,
+
+    /// Publish bboxes and embeddings together as a single message per frame.
+    /// The separate bboxes/embeddings messages are deprecated, kept as default for compatibility
+    #[serde(default)]
+    pub combined_results: bool,
+
+    /// Filters detections by distance using a paired depth source, disabled when not set
+    #[serde(default)]
+    pub depth_filter: Option<DepthFilterConfig>,
+
+    /// Whether the source provides depth frames for other sources instead of being inferred.
+    /// Derived from the depth filters of other sources
+    #[serde(skip)]
+    pub is_depth_source: bool,
+
+    /// Frames with a larger dimension are downscaled before being queued, bounding memory per source
+    #[serde(default)]
+    pub queue_max_dimension: Option<u32>,
+
+    /// What happens to frames arriving to a full queue, dropping the oldest by default
+    #[serde(default)]
+    pub queue_overflow_policy: OverflowPolicy,
+
+    /// Keeps only the newest frame in queue for lowest latency. Superseded frames are counted as skipped,
+    /// not failed, and `queue_overflow_policy` is ignored
+    #[serde(default)]
+    pub latest_only: bool,
+
+    /// Suppresses repeated detections within the same area of the frame from Kafka output, disabled when not set
+    #[serde(default)]
+    pub debounce: Option<DebounceConfig>,
+
+    /// Topics of the source replacing the topics of `kafka_config`, e.g. for restricted cameras
+    #[serde(default)]
+    pub topic_override: Option<TopicOverride>,
+
+    /// Reads decoded frames written to shared memory by a co-located capturer instead of
+    /// streaming through the video client, disabled when not set
+    #[serde(default)]
+    pub shared_memory: Option<SharedMemoryConfig>,
+
+    /// Receives frames of one video stream in the container of another source instead of
+    /// streaming itself, requires `stream_config.decode_all_streams`. Disabled when not set
+    #[serde(default)]
+    pub sub_stream: Option<SubStreamConfig>,
+
+    /// Id of the streamed video in the streaming backend, required for sources whose id is not numeric.
+    /// Defaults to the source id
+    #[serde(default)]
+    pub video_id: Option<i32>
+}
+
+impl SourcesConfig {
+    /// Resolves the configuration of every source - custom values override defaults, pipelines default
+    /// to the models of the inference task and must reference configured models
+    pub fn resolve_sources(&self, inference_config: &InferenceConfig) -> Result<HashMap<String, SourceConfig>> {
+        let mut sources: HashMap<String, SourceConfig> = HashMap::new();
+        for source_id in self.ids.iter() {
+            // Custom values override defaults if exist
+            let mut source_config = match self.custom.get(source_id) {
+                Some(custom_config) => self.default.merged(custom_config)
+                    .with_context(|| format!("Error merging custom configuration of source {}", source_id))?,
+                None => self.default.clone()
+            };
+
+            if source_config.pipeline.is_empty() {
+                source_config.pipeline = inference_config.task.default_pipeline();
+            }
+            AppConfig::validate_pipeline(&source_config.pipeline, inference_config)
+                .with_context(|| format!("Invalid pipeline of source {}", source_id))?;
+
+            sources.insert(
+                source_id.clone(), 
+                source_config
+            );
+        }
+
+        // Streamed sources are identified by the video they stream in the streaming backend
+        let mut video_ids: Vec<(i32, &String)> = Vec::new();
+        for (source_id, source_config) in sources.iter().filter(|(_, source_config)| source_config.is_streamed()) {
+            let video_id = match source_config.video_id {
+                Some(video_id) => video_id,
+                None => source_id.parse::<i32>()
+                    .with_context(|| format!("Source {} does not have a numeric id, its video_id must be set", source_id))?
+            };
+            video_ids.push((video_id, source_id));
+        }
+        video_ids.sort();
+        for pair in video_ids.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                anyhow::bail!("Sources {} and {} stream the same video {}", pair[0].1, pair[1].1, pair[0].0);
+            }
+        }
+
+        // Sub-streams are decoded from the container of a streamed source
+        let mut sub_streams: Vec<(&String, &SubStreamConfig)> = sources
+            .iter()
+            .filter_map(|(source_id, source_config)| Some((source_id, source_config.sub_stream.as_ref()?)))
+            .collect();
+        sub_streams.sort_by_key(|(source_id, _)| *source_id);
+
+        for (index, (source_id, sub_stream)) in sub_streams.iter().enumerate() {
+            let parent = sources
+                .get(&sub_stream.source)
+                .with_context(|| format!("Source {} of sub-stream source {} is not a configured source", sub_stream.source, source_id))?;
+
+            if !parent.is_streamed() {
+                anyhow::bail!("Source {} of sub-stream source {} is not streamed by the video client", sub_stream.source, source_id);
+            }
+            if sub_streams[..index].iter().any(|(_, other)| other == sub_stream) {
+                anyhow::bail!("Sub-stream {} of source {} is read by more than one source", sub_stream.index, sub_stream.source);
+            }
+        }
+
+        // Mark sources providing depth frames
+        let depth_source_ids: Vec<String> = sources
+            .values()
+            .filter_map(|source_config| source_config.depth_filter.as_ref())
+            .map(|depth_filter| depth_filter.depth_source_id.clone())
+            .collect();
+
+        for depth_source_id in depth_source_ids {
+            let depth_source = sources
+                .get_mut(&depth_source_id)
+                .with_context(|| format!("Depth source {} is not a configured source", depth_source_id))?;
+
+            if depth_source.depth_filter.is_some() {
+                anyhow::bail!("Depth source {} cannot have a depth filter itself", depth_source_id);
+            }
+            depth_source.is_depth_source = true;
+        }
+
+        Ok(sources)
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+/// Per-source overrides of `SourceConfig`, every field set overrides its default counterpart.
+/// Fields are named exactly as in `SourceConfig`, so they are merged without listing them
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SourceConfigOptional {
     pub inf_frame: Option<u32>,
     pub conf_threshold: Option<f32>,
-    pub nms_iou_threshold: Option<f32>
+    pub nms_iou_threshold: Option<f32>,
+    pub class_agnostic_nms: Option<bool>,
+    pub nms_mode: Option<NmsMode>,
+    pub nms_soft_sigma: Option<f32>,
+    pub motion_gate: Option<MotionGateConfig>,
+    pub dedup_threshold: Option<f32>,
+    pub dedup_max_skips: Option<u32>,
+    pub max_inferences_per_sec: Option<f64>,
+    pub drop_warn_percent: Option<f32>,
+    pub debug_sample_rate: Option<f32>,
+    pub pipeline: Option<Vec<InferenceModelType>>,
+    pub combined_results: Option<bool>,
+    pub depth_filter: Option<DepthFilterConfig>,
+    pub queue_max_dimension: Option<u32>,
+    pub queue_overflow_policy: Option<OverflowPolicy>,
+    pub latest_only: Option<bool>,
+    pub debounce: Option<DebounceConfig>,
+    pub topic_override: Option<TopicOverride>,
+    pub shared_memory: Option<SharedMemoryConfig>,
+    pub sub_stream: Option<SubStreamConfig>,
+    pub video_id: Option<i32>
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct TritonConfig {
-    pub url: String,
-    pub models_dir: String
+impl Default for SourceConfig {
+    fn default() -> Self {
+        defaults_of()
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct KafkaConfig {
-    pub brokers: String,
-    pub topic_bboxes: String,
-    pub topic_embedding: String
+impl SourceConfig {
+    fn default_inf_frame() -> u32 {
+        1
+    }
+
+    fn default_conf_threshold() -> f32 {
+        0.25
+    }
+
+    fn default_nms_iou_threshold() -> f32 {
+        0.45
+    }
+
+    /// Whether frames of the source are streamed by the video client, rather than read from
+    /// shared memory or taken from the container of another source
+    pub fn is_streamed(&self) -> bool {
+        self.shared_memory.is_none() && self.sub_stream.is_none()
+    }
+
+    /// Returns the configuration with every field set in `custom` replacing its counterpart.
+    /// Merged through their YAML representation, so new fields need no merge logic of their own
+    pub fn merged(&self, custom: &SourceConfigOptional) -> Result<SourceConfig> {
+        let mut merged = serde_yaml::to_value(self)?;
+        let Value::Mapping(overrides) = serde_yaml::to_value(custom)? else {
+            anyhow::bail!("Custom source configuration is not a mapping");
+        };
+
+        let base = merged
+            .as_mapping_mut()
+            .context("Source configuration is not a mapping")?;
+        for (field, value) in overrides.into_iter().filter(|(_, value)| !value.is_null()) {
+            base.insert(field, value);
+        }
+
+        Ok(serde_yaml::from_value(merged)?)
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct InferenceConfig {
-    pub models: HashMap<InferenceModelType, ModelConfig>,
-    pub task: InferenceTask
+/// Topics replacing the configured topics for a single source, templated like them
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TopicOverride {
+    pub bboxes: Option<String>,
+    pub embedding: Option<String>,
+    pub results: Option<String>
 }
 
-/// Represents the inference model precision type
-#[derive(PartialEq, Eq, Clone, Copy, Debug, Deserialize)]
-pub enum InferencePrecision {
-    FP32,
-    FP16
+/// Concrete topics the results of a single source are produced to
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceTopics {
+    pub bboxes: String,
+    pub embedding: String,
+    pub results: String
 }
 
-impl InferencePrecision {
-    pub fn to_string(&self) -> String {
-        match self {
-            InferencePrecision::FP32 => "FP32",
-            InferencePrecision::FP16 => "FP16",
-        }.to_string()
-    }
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SharedMemoryConfig {
+    /// Shared memory file written by the capturer, e.g. /dev/shm/camera-1 or /proc/<pid>/fd/<n> of a memfd
+    pub path: String,
+    /// Milliseconds between checks for a new frame
+    #[serde(default = "SharedMemoryConfig::default_poll_interval_ms")]
+    pub poll_interval_ms: u64
 }
 
-/// Represents type of inference model
-#[derive(PartialEq, Eq, Hash, Clone, Debug, Deserialize)]
-pub enum InferenceModelType {
-    YOLO,
-    DINO,
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubStreamConfig {
+    /// Streamed source whose container carries the video stream
+    pub source: String,
+    /// Index of the video stream among the video streams of the container, starting at 0
+    pub index: u32
 }
 
-impl InferenceModelType {
-    pub fn to_string(&self) -> &'static str {
-        match self {
-            InferenceModelType::YOLO => "YOLO",
-            InferenceModelType::DINO => "DINO",
-        }
+impl SharedMemoryConfig {
+    fn default_poll_interval_ms() -> u64 {
+        2
     }
 }
 
-/// Represents type of inference model
-#[derive(Copy, Clone, Debug, Deserialize)]
-pub enum InferenceTask {
-    ObjectDetection,
-    Embedding
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DepthFilterConfig {
+    /// Source providing depth frames co-registered with this source
+    pub depth_source_id: String,
+    /// Detections further than this distance are filtered
+    pub max_distance_m: f32,
+    /// Meters per raw depth unit
+    pub depth_scale: f32
 }
 
-/// Represents all the configuation variables used by the application
-#[derive(Debug, Deserialize)]
-pub struct AppConfig {
-    local: bool,
-    environment: Environment,
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DebounceConfig {
+    /// Frame is divided into grid_size x grid_size cells
+    pub grid_size: u32,
+    /// Milliseconds after a detection in a cell, during which further detections in it are suppressed
+    pub debounce_ms: u64
+}
 
-    #[serde(default)]
-    gpu_name: String,
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MotionGateConfig {
+    /// Mean absolute difference of grayscale pixels (0-255) from the last
+    /// inferred frame, above which the scene is considered changed. Must be greater than 0,
+    /// as every frame passes a gate of 0
+    pub threshold: f32,
+    /// Seconds after which a frame is inferred even without motion, at least 1
+    pub max_idle_secs: u64
+}
 
-    sources_config: SourcesConfig,
-    kafka_config: KafkaConfig,
-    triton_config: TritonConfig,
-    inference_config: InferenceConfig
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResultsBatchingConfig {
+    /// Milliseconds after the first frame of a batch at which the batch is published
+    pub max_batch_ms: u64,
+    /// Frames at which a batch is published right away, bounding the frames held per source
+    pub max_batch_frames: usize
 }
 
-impl AppConfig {
-    /// Creates a new instance of the configuration object
-    pub fn new() -> Result<Self> {
-        let mut config: AppConfig = AppConfig::load_config_file()
-            .context("Error loading configuation file")?;
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TritonConfig {
+    /// Triton servers models are served from
+    #[serde(default)]
+    pub endpoints: Vec<TritonEndpoint>,
 
-        // Initiate app logging
-        AppConfig::init_logging(config.local);
+    /// Single server of older configurations, becomes an endpoint named `default`
+    #[serde(default)]
+    pub url: Option<String>,
 
-        // GPU information
-        config.gpu_name = utils::get_gpu_name()
-            .context("Error getting GPU name")?;
+    /// Model repository of all models, the repositories Triton was started with when empty
+    #[serde(default)]
+    pub models_dir: String
+}
 
-        // Parse sources
-        let mut sources: HashMap<String, SourceConfig> = HashMap::new();
-        for source_id in config.sources_config().ids.iter() {
-            // Get source preferred config
-            let mut source_config = config.sources_config().default.clone();
-            let custom_config = config.sources_config().custom.get(source_id);
-
-            // Assign custom values - override defaults if exist
-            source_config.inf_frame = custom_config
-                .and_then(|o| o.inf_frame)
-                .filter(|&x| x >= 1 && x <= 30)
-                .unwrap_or(source_config.inf_frame);
-
-            source_config.conf_threshold = custom_config
-                .and_then(|o| o.conf_threshold)
-                .filter(|&x| x >= 0.00 && x <= 1.00)
-                .unwrap_or(source_config.conf_threshold);
-
-            source_config.nms_iou_threshold = custom_config
-                .and_then(|o| o.nms_iou_threshold)
-                .filter(|&x| x >= 0.00 && x <= 1.00)
-                .unwrap_or(source_config.nms_iou_threshold);
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TritonEndpoint {
+    pub name: String,
+    pub url: String
+}
 
-            sources.insert(
-                source_id.clone(), 
-                source_config
-            );
+impl TritonConfig {
+    /// Name of the endpoint created from `url`
+    pub const DEFAULT_ENDPOINT: &str = "default";
+
+    /// Moves the deprecated single url into the endpoints, unless endpoints are configured
+    fn normalize_endpoints(&mut self) {
+        let Some(url) = self.url.take() else {
+            return;
+        };
+
+        if self.endpoints.is_empty() {
+            self.endpoints.push(TritonEndpoint {
+                name: TritonConfig::DEFAULT_ENDPOINT.to_string(),
+                url
+            });
         }
-        config.sources_config.sources = sources;
+    }
 
-        Ok(config)
+    /// Replaces the url of the first endpoint, or the single url when no endpoints are configured
+    fn set_primary_url(&mut self, url: &str) {
+        match self.endpoints.first_mut() {
+            Some(endpoint) => endpoint.url = url.to_string(),
+            None => self.url = Some(url.to_string())
+        }
     }
 
-    /// Loads environment variables from a local .env file
-    fn load_config_file() -> Result<AppConfig> {
-        // Path relative to cwd
-        let config_file = "secrets/config.yaml".to_string();
-        let config_path = Path::new(&config_file);
-        
-        // Load configuration file
-        let contents = std::fs::read_to_string(config_path)
-            .context("Error locating configuration file")?;
+    /// Returns the endpoint with the given name, or the first endpoint when no name is given
+    pub fn endpoint(&self, name: Option<&str>) -> Result<&TritonEndpoint> {
+        match name {
+            Some(name) => self.endpoints
+                .iter()
+                .find(|endpoint| endpoint.name == name)
+                .with_context(|| format!("Triton endpoint '{}' is not configured", name)),
+            None => self.endpoints
+                .first()
+                .context("No Triton endpoints are configured")
+        }
+    }
 
-        let config_file: AppConfig = serde_yaml::from_str(&contents)
-            .context("Error parsing configuration file")?;
+    /// Returns the endpoints of a model in order of preference - its endpoint followed by its failover endpoints
+    pub fn model_endpoints(&self, model_config: &ModelConfig) -> Result<Vec<&TritonEndpoint>> {
+        let mut endpoints = vec![self.endpoint(model_config.endpoint.as_deref())?];
+        for name in model_config.failover_endpoints.iter() {
+            let endpoint = self.endpoint(Some(name))?;
+            if !endpoints.contains(&endpoint) {
+                endpoints.push(endpoint);
+            }
+        }
 
-        Ok(config_file)
+        Ok(endpoints)
     }
+}
 
-    /// Initiates structured logging
-    fn init_logging(local: bool) {
-        let file_appender = RollingFileAppender::new(Rotation::NEVER, "logs", "app.log");
-        let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-
-        // Append logging to local file
-        let file_layer = if local {
-            Some(
-                tracing_subscriber::fmt::layer()
-                    .json()
-                    .with_timer(fmt::time::UtcTime::rfc_3339())
-                    .with_writer(non_blocking)
-            )
-        } else {
-            None
-        };
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KafkaConfig {
+    /// Whether results are published to Kafka - enabled by default when the section is set,
+    /// disabled when it is missing
+    #[serde(default = "KafkaConfig::default_enabled")]
+    pub enabled: bool,
 
-        tracing_subscriber::registry()
-            .with(EnvFilter::from_default_env())
-            .with(
-                // Console layer - pretty format
-                tracing_subscriber::fmt::layer()
-                    .json()
-                    .with_timer(fmt::time::UtcTime::rfc_3339())
-                    .with_writer(std::io::stdout)
-            )
-            .with(file_layer)
-            .init();
+    /// Startup behavior when brokers cannot be reached
+    #[serde(default)]
+    pub on_unavailable: KafkaUnavailablePolicy,
 
-        std::mem::forget(_guard);
-    }
-}
+    /// Comma separated brokers, `localhost:9092` by default
+    #[serde(default = "KafkaConfig::default_brokers")]
+    pub brokers: String,
 
-impl AppConfig {
-    pub fn is_local(&self) -> bool {
-        self.local
-    }
+    /// Topics of bboxes, embeddings and combined results - `bboxes`, `embedding` and `results` by default.
+    /// Templated with `{source_id}`, e.g. `detections.{source_id}`, to produce each source to its own topic
+    #[serde(default = "KafkaConfig::default_topic_bboxes")]
+    pub topic_bboxes: String,
+    #[serde(default = "KafkaConfig::default_topic_embedding")]
+    pub topic_embedding: String,
 
-    pub fn environment(&self) -> Environment {
-        self.environment
-    }
+    /// Topic for combined frame results, used when sources publish combined results
+    #[serde(default = "KafkaConfig::default_topic_results")]
+    pub topic_results: String,
 
-    pub fn gpu_name(&self) -> &str {
-        &self.gpu_name
-    }
+    /// Topic of runtime control commands, the control consumer is disabled when not set
+    #[serde(default)]
+    pub topic_control: Option<String>,
 
-    pub fn sources_config(&self) -> &SourcesConfig {
-        &self.sources_config
-    }
+    /// Topic receiving acknowledgements of control commands, not acknowledged when not set
+    #[serde(default)]
+    pub topic_control_responses: Option<String>,
 
-    pub fn kafka_config(&self) -> &KafkaConfig {
-        &self.kafka_config
-    }
+    /// Consumer group of the control consumer
+    #[serde(default = "KafkaConfig::default_control_group_id")]
+    pub control_group_id: String,
 
-    pub fn triton_config(&self) -> &TritonConfig {
-        &self.triton_config
-    }
+    /// Encoding of published messages
+    #[serde(default)]
+    pub serialization: Serialization,
 
-    pub fn inference_config(&self) -> &InferenceConfig {
-        &self.inference_config
-    }
-}
\ No newline at end of file
+    /// Confluent compatible schema registry, protobuf schemas are registered to it when set
+    #[serde(default)]
+    pub schema_registry_url: Option<String>,
+
+    /// How messages are assigned to partitions of detection topics
+    #[serde(default)]
+    pub partitioning: Partitioning,
+
+    /// Retries of the producer itself before a message is considered failed (`message.send.max.retries`)
+    #[serde(default = "KafkaConfig::default_send_max_retries")]
+    pub send_max_retries: u32,
+
+    /// Messages held in the producer queue (`queue.buffering.max.messages`)
+    #[serde(default = "KafkaConfig::default_queue_buffering_max_messages")]
+    pub queue_buffering_max_messages: u32,
+
+    /// Kilobytes held in the producer queue (`queue.buffering.max.kbytes`)
+    #[serde(default = "KafkaConfig::default_queue_buffering_max_kbytes")]
+    pub queue_buffering_max_kbytes: u32,
+
+    /// Milliseconds messages wait in the producer queue to be batched (`queue.buffering.max.ms`),
+    /// also accepted as `linger_ms`
+    #[serde(default = "KafkaConfig::default_queue_buffering_max_ms", alias = "linger_ms")]
+    pub queue_buffering_max_ms: u32,
+
+    /// Maximum messages batched into a single request (`batch.num.messages`)
+    #[serde(default = "KafkaConfig::default_batch_num_messages")]
+    pub batch_num_messages: u32,
+
+    /// Compression codec of message batches (`compression.codec`)
+    #[serde(default)]
+    pub compression: Compression,
+
+    /// Acknowledgements required from brokers before a message is delivered (`acks`) - all, or a number of brokers
+    #[serde(default = "KafkaConfig::default_acks")]
+    pub acks: String,
+
+    /// Whether brokers deduplicate messages resent by the producer retries (`enable.idempotence`), requires `acks: all`
+    #[serde(default)]
+    pub enable_idempotence: bool,
+
+    /// Additional rdkafka producer properties, applied last so they override any of the above
+    #[serde(default)]
+    pub extra_properties: HashMap<String, String>,
+
+    /// Bytes of failed messages kept locally for retrying, oldest are evicted above it. 0 disables retrying
+    #[serde(default = "KafkaConfig::default_retry_buffer_max_bytes")]
+    pub retry_buffer_max_bytes: usize,
+
+    /// Initial milliseconds between retries of failed messages, doubled on every failure
+    #[serde(default = "KafkaConfig::default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+
+    /// Maximum milliseconds between retries of failed messages
+    #[serde(default = "KafkaConfig::default_retry_backoff_max_ms")]
+    pub retry_backoff_max_ms: u64,
+
+    /// Maximum bytes of a message (`message.max.bytes`), larger messages are dead-lettered up front
+    #[serde(default = "KafkaConfig::default_message_max_bytes")]
+    pub message_max_bytes: usize,
+
+    /// Failed retries of a buffered message before it is dead-lettered. 0 retries forever
+    #[serde(default = "KafkaConfig::default_dead_letter_max_attempts")]
+    pub dead_letter_max_attempts: u32,
+
+    /// Topic receiving dead-lettered messages, along with their error as headers
+    #[serde(default)]
+    pub dead_letter_topic: Option<String>,
+
+    /// Directory of newline-delimited JSON files receiving dead-lettered messages,
+    /// used when there is no dead-letter topic or producing to it fails
+    #[serde(default)]
+    pub dead_letter_dir: Option<String>,
+
+    /// Whether oversized JSON messages are still produced without their embeddings, so detections survive
+    #[serde(default = "KafkaConfig::default_dead_letter_strip_embeddings")]
+    pub dead_letter_strip_embeddings: bool,
+
+    /// Seconds between delivery statistics summaries, also the rdkafka statistics interval. 0 disables both
+    #[serde(default = "KafkaConfig::default_stats_interval_secs")]
+    pub stats_interval_secs: u64,
+
+    /// Percentage of failed produce attempts within a statistics interval above which a warning is logged
+    #[serde(default = "KafkaConfig::default_failure_warn_percent")]
+    pub failure_warn_percent: f32,
+
+    /// Protocol used to communicate with brokers
+    #[serde(default)]
+    pub security_protocol: SecurityProtocol,
+
+    /// SASL mechanism, e.g. PLAIN, SCRAM-SHA-256 or SCRAM-SHA-512
+    #[serde(default)]
+    pub sasl_mechanism: Option<String>,
+
+    #[serde(default)]
+    pub sasl_username: Option<String>,
+
+    /// SASL password - prefer a `${env:VAR_NAME}` or `${file:/path}` reference to keep it out of the configuration file
+    #[serde(default, serialize_with = "redact_secret")]
+    pub sasl_password: Option<String>,
+
+    /// CA certificate verifying the brokers, required for SSL protocols
+    #[serde(default)]
+    pub ssl_ca_location: Option<String>,
+
+    /// Client certificate, for brokers requiring mutual TLS
+    #[serde(default)]
+    pub ssl_certificate_location: Option<String>,
+
+    /// Private key of the client certificate
+    #[serde(default)]
+    pub ssl_key_location: Option<String>,
+
+    /// Publishes combined results of multiple frames of a source as a single message.
+    /// Each frame is published as its own message when not set
+    #[serde(default)]
+    pub results_batching: Option<ResultsBatchingConfig>
+}
+
+impl Default for KafkaConfig {
+    /// Used when the section is missing, for sites without Kafka
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ..defaults_of()
+        }
+    }
+}
+
+impl KafkaConfig {
+    /// Resolves the concrete topics of a source - its overrides take precedence over the configured topics,
+    /// then `{source_id}` is expanded in either
+    pub fn source_topics(&self, source_id: &str, topic_override: Option<&TopicOverride>) -> SourceTopics {
+        let resolve = |configured: &String, overridden: Option<&String>| {
+            overridden
+                .unwrap_or(configured)
+                .replace("{source_id}", source_id)
+        };
+
+        SourceTopics {
+            bboxes: resolve(&self.topic_bboxes, topic_override.and_then(|o| o.bboxes.as_ref())),
+            embedding: resolve(&self.topic_embedding, topic_override.and_then(|o| o.embedding.as_ref())),
+            results: resolve(&self.topic_results, topic_override.and_then(|o| o.results.as_ref()))
+        }
+    }
+
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_brokers() -> String {
+        "localhost:9092".to_string()
+    }
+
+    fn default_topic_bboxes() -> String {
+        "bboxes".to_string()
+    }
+
+    fn default_topic_embedding() -> String {
+        "embedding".to_string()
+    }
+
+    fn default_topic_results() -> String {
+        "results".to_string()
+    }
+
+    /// Returns rdkafka properties of the security settings, making sure their combination is coherent
+    pub fn security_properties(&self) -> Result<Vec<(&'static str, String)>> {
+        let protocol = self.security_protocol;
+        let mut properties = vec![("security.protocol", protocol.as_str().to_string())];
+
+        // SASL credentials
+        let sasl_configured = self.sasl_mechanism.is_some() || self.sasl_username.is_some() || self.sasl_password.is_some();
+
+        if protocol.uses_sasl() {
+            let (Some(mechanism), Some(username), Some(password)) = (&self.sasl_mechanism, &self.sasl_username, &self.sasl_password) else {
+                anyhow::bail!("Security protocol {} requires sasl_mechanism, sasl_username and a SASL password", protocol.as_str())
+            };
+
+            properties.push(("sasl.mechanism", mechanism.clone()));
+            properties.push(("sasl.username", username.clone()));
+            properties.push(("sasl.password", password.clone()));
+        } else if sasl_configured {
+            anyhow::bail!("SASL settings require a SASL_PLAINTEXT or SASL_SSL security protocol")
+        }
+
+        // TLS certificates
+        let ssl_configured = self.ssl_ca_location.is_some()
+            || self.ssl_certificate_location.is_some()
+            || self.ssl_key_location.is_some();
+
+        if protocol.uses_ssl() {
+            let ca_location = self.ssl_ca_location
+                .as_ref()
+                .with_context(|| format!("Security protocol {} requires ssl_ca_location", protocol.as_str()))?;
+            properties.push(("ssl.ca.location", ca_location.clone()));
+
+            match (&self.ssl_certificate_location, &self.ssl_key_location) {
+                (Some(certificate), Some(key)) => {
+                    properties.push(("ssl.certificate.location", certificate.clone()));
+                    properties.push(("ssl.key.location", key.clone()));
+                },
+                (None, None) => {},
+                _ => anyhow::bail!("ssl_certificate_location and ssl_key_location must be set together")
+            }
+        } else if ssl_configured {
+            anyhow::bail!("SSL settings require an SSL or SASL_SSL security protocol")
+        }
+
+        Ok(properties)
+    }
+
+    fn default_stats_interval_secs() -> u64 {
+        10
+    }
+
+    fn default_failure_warn_percent() -> f32 {
+        5.00
+    }
+
+    fn default_control_group_id() -> String {
+        "client-control".to_string()
+    }
+
+    fn default_send_max_retries() -> u32 {
+        3
+    }
+
+    fn default_queue_buffering_max_messages() -> u32 {
+        100_000
+    }
+
+    fn default_queue_buffering_max_kbytes() -> u32 {
+        1_048_576
+    }
+
+    fn default_queue_buffering_max_ms() -> u32 {
+        5
+    }
+
+    fn default_batch_num_messages() -> u32 {
+        10_000
+    }
+
+    fn default_acks() -> String {
+        "all".to_string()
+    }
+
+    fn default_retry_buffer_max_bytes() -> usize {
+        64 * 1024 * 1024
+    }
+
+    fn default_retry_backoff_ms() -> u64 {
+        500
+    }
+
+    fn default_retry_backoff_max_ms() -> u64 {
+        30_000
+    }
+
+    fn default_message_max_bytes() -> usize {
+        1_000_000
+    }
+
+    fn default_dead_letter_max_attempts() -> u32 {
+        10
+    }
+
+    fn default_dead_letter_strip_embeddings() -> bool {
+        true
+    }
+}
+
+/// Represents how messages are assigned to partitions
+///
+/// - `BySource` keys messages by source id - messages of a source share a partition and keep their order
+/// - `Fixed` produces all messages to a single partition (`!Fixed 0`) - all messages keep their order
+/// - `RoundRobin` produces messages without a key, spreading them across partitions - no ordering is guaranteed
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Partitioning {
+    #[default]
+    BySource,
+    Fixed(u32),
+    RoundRobin
+}
+
+/// Represents the compression codec of Kafka message batches, unknown codecs are rejected when parsing
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Lz4,
+    Zstd
+}
+
+impl Compression {
+    /// Returns the codec name as expected by rdkafka
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Lz4 => "lz4",
+            Compression::Zstd => "zstd"
+        }
+    }
+}
+
+/// Represents the startup behavior when Kafka is enabled but its brokers cannot be reached
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum KafkaUnavailablePolicy {
+    /// Exit with an error
+    Fail,
+    /// Keep running, messages are buffered and retried until the brokers are reachable
+    #[default]
+    Degrade
+}
+
+/// Represents the protocol used to communicate with Kafka brokers (`security.protocol`)
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SecurityProtocol {
+    #[default]
+    Plaintext,
+    Ssl,
+    SaslPlaintext,
+    SaslSsl
+}
+
+impl SecurityProtocol {
+    /// Returns the protocol name as expected by rdkafka
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecurityProtocol::Plaintext => "plaintext",
+            SecurityProtocol::Ssl => "ssl",
+            SecurityProtocol::SaslPlaintext => "sasl_plaintext",
+            SecurityProtocol::SaslSsl => "sasl_ssl"
+        }
+    }
+
+    pub fn uses_sasl(&self) -> bool {
+        matches!(self, SecurityProtocol::SaslPlaintext | SecurityProtocol::SaslSsl)
+    }
+
+    pub fn uses_ssl(&self) -> bool {
+        matches!(self, SecurityProtocol::Ssl | SecurityProtocol::SaslSsl)
+    }
+}
+
+/// Represents the format of detections posted back to the client video library
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum OutputFormat {
+    /// Frame corners as pixel indexes of the flattened frame, along with class names
+    #[default]
+    Native,
+    /// COCO results - a list of detections with `image_id` set to the frame pts,
+    /// `category_id`, `bbox` as `[x, y, width, height]` and `score`, readable by pycocotools
+    Coco
+}
+
+/// Represents the encoding of messages published to Kafka
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Serialization {
+    #[default]
+    Json,
+    Protobuf
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    pub enabled: bool,
+    /// Only reachable from the same host by default
+    pub host: String,
+    pub port: u16,
+    /// Bearer token required by operations changing the application and by the configuration dump.
+    /// Required when listening on other than a loopback address
+    #[serde(serialize_with = "redact_secret")]
+    pub token: Option<String>
+}
+
+impl AdminConfig {
+    /// Returns whether the server is only reachable from the same host
+    pub fn is_loopback(&self) -> bool {
+        self.host == "localhost" || self.host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|address| address.is_loopback())
+    }
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            host: "127.0.0.1".to_string(),
+            port: 9100,
+            token: None
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputsConfig {
+    /// Publish results to Kafka
+    pub kafka: bool,
+    /// Publish results to the client video library
+    pub client_video: bool,
+    /// Format of detections published to the client video library
+    pub format: OutputFormat,
+    /// Results buffered per in-process subscription before lagging subscribers miss results
+    pub subscription_capacity: usize,
+    /// Result summaries kept per source for debugging, 0 disables keeping them
+    pub recent_results_capacity: usize,
+    /// Columns of the detection heatmap grid per source, 0 disables the heatmap
+    pub heatmap_grid_w: usize,
+    /// Rows of the detection heatmap grid per source, 0 disables the heatmap
+    pub heatmap_grid_h: usize
+}
+
+impl Default for OutputsConfig {
+    fn default() -> Self {
+        Self {
+            kafka: true,
+            client_video: true,
+            format: OutputFormat::Native,
+            subscription_capacity: 64,
+            recent_results_capacity: 30,
+            heatmap_grid_w: 0,
+            heatmap_grid_h: 0
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistenceConfig {
+    pub enabled: bool,
+    pub backend: StateBackend,
+    /// Location of the state, meaning depends on the backend
+    pub path: String,
+    pub interval_secs: u64
+}
+
+impl Default for PersistenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: StateBackend::File,
+            path: "state/sources.json".to_string(),
+            interval_secs: 30
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackpressureConfig {
+    /// Throttle frame delivery of overloaded sources at the video layer
+    pub enabled: bool,
+    /// Maximum delivery divisor - deliver every Nth frame
+    pub max_divisor: u32,
+    /// Queue occupancy (0-1) at or above which a source is under pressure
+    pub high_occupancy: f32,
+    /// Queue occupancy (0-1) at or below which a source without drops is relieved
+    pub low_occupancy: f32,
+    /// Fraction of queued frames dropped above which a source is under pressure
+    pub max_drop_rate: f32,
+    /// Consecutive stats intervals of pressure or relief before the divisor changes
+    pub hysteresis_intervals: u32,
+    /// Seconds between pushes of changed divisors to the video layer
+    pub push_interval_secs: u64
+}
+
+/// Stream settings of the video library, set once before sources are initiated
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamConfig {
+    /// Consecutive failures of the same kind after which a source stops retrying, until it is re-initiated
+    pub circuit_breaker_threshold: u32,
+    /// Decode every video stream of source containers, delivering them to sources configured with `sub_stream`
+    pub decode_all_streams: bool,
+    /// Frames larger than this are downscaled while decoding, keeping their aspect ratio. Unlimited when not set
+    pub max_decode_resolution: Option<Resolution>,
+    /// Seconds without decoded frames after which a connected stream is reconnected. 0 disables the watchdog
+    pub frame_timeout_secs: u64,
+    /// Seconds between checks of the backend that a consumed stream is still active
+    pub keepalive_interval_secs: u64,
+    /// Draw the source id, time and latest posted detections onto frames before they are delivered.
+    /// Sub-stream frames are not annotated
+    pub annotate_frames: bool
+}
+
+/// Frame dimensions in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadinessConfig {
+    /// Seconds without frames after which a ready source is reported as not ready
+    pub silence_timeout_secs: u64
+}
+
+/// Represents when the log file is rotated
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    #[default]
+    Daily,
+    /// Once the file reaches `max_file_bytes`
+    Size
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Log level or RUST_LOG style directives, e.g. `info,client=debug`. Overrides RUST_LOG when set
+    pub level: Option<String>,
+    /// Console log format, defaults to Pretty when running locally and Json otherwise
+    pub format: Option<LogFormat>,
+    /// Log to the console
+    pub console: bool,
+    /// Log to a JSON file, defaults to running locally
+    pub file: Option<bool>,
+    pub file_path: String,
+    pub rotation: LogRotation,
+    /// Size of the log file at which it is rotated, used by size rotation only
+    pub max_file_bytes: u64,
+    /// Rotated log files retained, older files are deleted
+    pub max_files: usize
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: None,
+            format: None,
+            console: true,
+            file: None,
+            file_path: "logs/app.log".to_string(),
+            rotation: LogRotation::Daily,
+            max_file_bytes: 104_857_600,
+            max_files: 7
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotReloadConfig {
+    /// Reload the configuration file whenever it changes
+    pub enabled: bool,
+    /// Seconds between checks of the configuration file modification time
+    pub poll_interval_secs: u64
+}
+
+impl Default for HotReloadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_interval_secs: 2
+        }
+    }
+}
+
+impl Default for BackpressureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_divisor: 8,
+            high_occupancy: 0.80,
+            low_occupancy: 0.30,
+            max_drop_rate: 0.05,
+            hysteresis_intervals: 3,
+            push_interval_secs: 5
+        }
+    }
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            circuit_breaker_threshold: 5,
+            decode_all_streams: false,
+            max_decode_resolution: None,
+            frame_timeout_secs: 10,
+            keepalive_interval_secs: 2,
+            annotate_frames: false
+        }
+    }
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            silence_timeout_secs: 10
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsSinkConfig {
+    pub enabled: bool,
+    /// Directory of the statistics files
+    pub directory: String,
+    /// Size after which the statistics file is rotated
+    pub max_file_bytes: u64,
+    /// Amount of rotated files retained
+    pub max_files: usize
+}
+
+impl Default for StatsSinkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: "stats".to_string(),
+            max_file_bytes: 10 * 1024 * 1024,
+            max_files: 5
+        }
+    }
+}
+
+/// Represents the store used for persisting source states
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum StateBackend {
+    File
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InferenceConfig {
+    pub models: HashMap<InferenceModelType, ModelConfig>,
+
+    /// Inference task of the pipeline, ObjectDetection by default
+    #[serde(default)]
+    pub task: InferenceTask,
+
+    /// Channel order of frames delivered by the video client, matching the order models were trained on
+    #[serde(default)]
+    pub color_order: ColorOrder
+}
+
+impl ModelConfig {
+    fn default_version() -> u32 {
+        1
+    }
+
+    fn default_enable_dynamic_batching() -> bool {
+        true
+    }
+
+    fn default_input_name() -> String {
+        "images".to_string()
+    }
+
+    fn default_output_name() -> Vec<String> {
+        vec!["output".to_string()]
+    }
+
+    fn default_batch_max_size() -> u32 {
+        8
+    }
+
+    fn default_batch_max_queue_delay() -> u32 {
+        1000
+    }
+
+    /// Fills shapes and preferred batch sizes left empty with the defaults of the model type
+    fn fill_defaults(&mut self, model_type: &InferenceModelType) {
+        if self.input_shape.is_empty() {
+            self.input_shape = model_type.default_input_shape();
+        }
+        if self.output_shape.is_empty() {
+            self.output_shape = model_type.default_output_shape();
+        }
+        if self.batch_preferred_sizes.is_empty() {
+            self.batch_preferred_sizes = std::iter::successors(Some(2), |size| Some(size * 2))
+                .take_while(|size| *size <= self.batch_max_size)
+                .collect();
+        }
+    }
+
+    fn default_health_check_timeout_ms() -> u64 {
+        5000
+    }
+
+    /// Returns the per-sample size in bytes of the input
+    pub fn input_size(&self) -> usize {
+        self.input_shape
+            .iter()
+            .map(|&dim| dim as usize)
+            .product::<usize>() * self.precision.bytes()
+    }
+
+    /// Returns the per-sample size in bytes of every output, in configured order
+    pub fn output_sizes(&self) -> Vec<usize> {
+        self.output_shape
+            .iter()
+            .map(|shape| shape.iter().map(|&dim| dim as usize).product::<usize>() * self.precision.bytes())
+            .collect()
+    }
+
+    /// Sorts and deduplicates preferred batch sizes, which are validated against the max batch size
+    /// beforehand, as Triton rejects invalid values with unclear errors
+    fn normalize_batch_preferred_sizes(&mut self) {
+        let original = self.batch_preferred_sizes.clone();
+        self.batch_preferred_sizes.sort_unstable();
+        self.batch_preferred_sizes.dedup();
+
+        if self.batch_preferred_sizes != original {
+            tracing::warn!(
+                model=&self.name,
+                original=format!("{:?}", original),
+                normalized=format!("{:?}", self.batch_preferred_sizes),
+                "Sorted and deduplicated preferred batch sizes"
+            );
+        }
+    }
+}
+
+/// Represents the channel order of frames, applied at the video client scaler
+/// 
+/// Preprocessing copies channels in the order they are received, so this is the single
+/// place controlling which order models see. Applies to every model of the pipeline.
+/// Ultralytics YOLO and torchvision/DINO pipelines train on RGB. Pipelines reading
+/// images with OpenCV (`cv2.imread`) without conversion, such as Caffe-era models, train on BGR.
+/// ImageNet normalization constants are applied in RGB channel positions regardless
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum ColorOrder {
+    #[default]
+    Rgb,
+    Bgr
+}
+
+impl ColorOrder {
+    pub fn to_string(&self) -> String {
+        match self {
+            ColorOrder::Rgb => "Rgb",
+            ColorOrder::Bgr => "Bgr",
+        }.to_string()
+    }
+}
+
+/// Represents how overlapping detections are reduced
+/// 
+/// Hard NMS drops detections overlapping a higher scored one above the IoU threshold.
+/// Soft NMS decays their scores by overlap instead, keeping occluded objects
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum NmsMode {
+    #[default]
+    Hard,
+    Soft
+}
+
+/// Represents the inference model precision type
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum InferencePrecision {
+    #[default]
+    FP32,
+    FP16
+}
+
+impl InferencePrecision {
+    pub fn to_string(&self) -> String {
+        match self {
+            InferencePrecision::FP32 => "FP32",
+            InferencePrecision::FP16 => "FP16",
+        }.to_string()
+    }
+
+    /// Returns the size of a single value in bytes
+    pub fn bytes(&self) -> usize {
+        match self {
+            InferencePrecision::FP32 => 4,
+            InferencePrecision::FP16 => 2,
+        }
+    }
+}
+
+/// Represents type of inference model
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Serialize, Deserialize)]
+pub enum InferenceModelType {
+    YOLO,
+    DINO,
+}
+
+impl InferenceModelType {
+    /// Input shape of the model type as exported by default - 640x640 YOLO, 224x224 DINO
+    pub fn default_input_shape(&self) -> Vec<i64> {
+        match self {
+            InferenceModelType::YOLO => vec![3, 640, 640],
+            InferenceModelType::DINO => vec![3, 224, 224],
+        }
+    }
+
+    /// Output shapes of the model type as exported by default - 80 COCO classes YOLO, ViT-B DINO
+    pub fn default_output_shape(&self) -> Vec<Vec<i64>> {
+        match self {
+            InferenceModelType::YOLO => vec![vec![84, 8400]],
+            InferenceModelType::DINO => vec![vec![768]],
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            InferenceModelType::YOLO => "YOLO",
+            InferenceModelType::DINO => "DINO",
+        }
+    }
+}
+
+/// Represents type of inference model
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum InferenceTask {
+    #[default]
+    ObjectDetection,
+    Embedding
+}
+
+impl InferenceTask {
+    /// Returns the models executed for the task, used when a source has no pipeline configured
+    pub fn default_pipeline(&self) -> Vec<InferenceModelType> {
+        match self {
+            InferenceTask::ObjectDetection => vec![InferenceModelType::YOLO],
+            InferenceTask::Embedding => vec![InferenceModelType::YOLO, InferenceModelType::DINO]
+        }
+    }
+}
+
+/// Represents all the configuation variables used by the application
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppConfig {
+    /// Running on a developer machine, false by default
+    #[serde(default)]
+    local: bool,
+
+    #[serde(default)]
+    environment: Environment,
+
+    #[serde(default)]
+    profile: Option<String>,
+
+    /// Deprecated, replaced by `logging_config.format`
+    #[serde(default)]
+    log_format: Option<LogFormat>,
+
+    #[serde(default)]
+    logging_config: LoggingConfig,
+
+    /// Keeps file logging running while the configuration the application started with is alive
+    #[serde(skip)]
+    logging_guard: Option<LoggingGuard>,
+
+    #[serde(default)]
+    gpu_name: String,
+
+    sources_config: SourcesConfig,
+
+    #[serde(default)]
+    kafka_config: KafkaConfig,
+
+    triton_config: TritonConfig,
+    inference_config: InferenceConfig,
+
+    #[serde(default)]
+    admin_config: AdminConfig,
+
+    #[serde(default)]
+    persistence_config: PersistenceConfig,
+
+    #[serde(default)]
+    outputs_config: OutputsConfig,
+
+    #[serde(default)]
+    stats_sink_config: StatsSinkConfig,
+
+    #[serde(default)]
+    backpressure_config: BackpressureConfig,
+
+    #[serde(default)]
+    readiness_config: ReadinessConfig,
+
+    #[serde(default)]
+    stream_config: StreamConfig,
+
+    #[serde(default)]
+    hot_reload_config: HotReloadConfig,
+
+    /// Milliseconds during which a detection published by any source suppresses
+    /// matching detections of other sources. Disabled when not set
+    #[serde(default)]
+    overlap_window_ms: Option<u64>,
+
+    /// Minimal cosine similarity of embeddings for detections of the same class
+    /// to be considered the same object across sources
+    #[serde(default = "AppConfig::default_overlap_similarity")]
+    overlap_similarity: f32,
+
+    /// Seconds between GPU utilization reports
+    #[serde(default = "AppConfig::default_gpu_stats_interval_secs")]
+    gpu_stats_interval_secs: u64,
+
+    /// GPUs model instances run on and whose utilization is reported, by device index
+    #[serde(default = "AppConfig::default_gpu_indices")]
+    gpu_indices: Vec<u32>,
+
+    /// Benchmark batch sizes of every model before starting inference
+    #[serde(default)]
+    benchmark_on_startup: bool,
+
+    /// Measured inferences per batch size when benchmarking, preceded by as many warm-up inferences
+    #[serde(default = "AppConfig::default_benchmark_iterations")]
+    benchmark_iterations: u32,
+
+    /// Additional deprecated top-level fields, mapped to their suggested replacements
+    #[serde(default)]
+    deprecated_fields: Option<HashMap<String, String>>,
+
+    /// Deprecated fields found in the configuration file, with their suggested replacements
+    #[serde(skip)]
+    deprecations: Vec<(String, String)>,
+
+    /// Environment variables applied over the configuration file
+    #[serde(skip)]
+    env_overrides: Vec<&'static str>,
+
+    /// Fields of `SECRET_FIELDS` whose value referenced secrets, never to be printed
+    #[serde(skip)]
+    resolved_secrets: Vec<&'static str>,
+
+    /// Paths of the fields written in the configuration file, after applying the profile
+    #[serde(skip)]
+    file_fields: HashSet<String>
+}
+
+impl AppConfig {
+    /// Creates a new instance of the configuration object
+    /// 
+    /// When a profile is given, its overrides from the `profiles` section
+    /// are deep-merged on top of the base configuration
+    pub fn new(profile: Option<&str>) -> Result<Self> {
+        let config = AppConfig::build(profile, true)?;
+
+        // Logged once, so the configuration a site runs with is never reconstructed from scattered logs
+        let effective_config = config.effective_config()
+            .context("Error dumping effective configuration")?;
+        tracing::info!(config=effective_config.to_string(), "Effective configuration");
+
+        Ok(config)
+    }
+
+    /// Returns the effective configuration with secrets redacted, along with the origin of
+    /// every value - `env` override, configuration `file`, or `default` when neither set it,
+    /// which includes values derived from other fields
+    pub fn effective_config(&self) -> Result<serde_json::Value> {
+        let config = serde_json::to_value(self)
+            .context("Error serializing configuration")?;
+
+        let env_fields: Vec<&str> = ENV_OVERRIDES
+            .iter()
+            .filter(|(variable, ..)| self.env_overrides.contains(variable))
+            .map(|(_, path, ..)| *path)
+            .collect();
+
+        // Values of deprecated fields are moved to their replacements
+        let from_file = |path: &str| {
+            self.file_fields.contains(path) || self.deprecations
+                .iter()
+                .any(|(_, replacement)| path == replacement || path.starts_with(&format!("{}.", replacement)))
+        };
+
+        let mut paths = Vec::new();
+        leaf_paths(&config, String::new(), &mut paths);
+        let origins: serde_json::Map<String, serde_json::Value> = paths
+            .into_iter()
+            .map(|path| {
+                let origin = match (env_fields.contains(&path.as_str()), from_file(&path)) {
+                    (true, _) => "env",
+                    (false, true) => "file",
+                    (false, false) => "default"
+                };
+                (path, origin.into())
+            })
+            .collect();
+
+        Ok(serde_json::json!({
+            "config": config,
+            "origins": origins
+        }))
+    }
+
+    /// Loads the configuration file again at runtime, logging is already initiated
+    pub fn reload(profile: Option<&str>) -> Result<Self> {
+        AppConfig::build(profile, false)
+    }
+
+    fn build(profile: Option<&str>, init_logging: bool) -> Result<Self> {
+        let mut config: AppConfig = AppConfig::load_config_file(profile)
+            .context("Error loading configuation file")?;
+        config.profile = profile.map(|p| p.to_string());
+
+        // Initiate app logging
+        if init_logging {
+            let file_enabled = config.logging_config.file.unwrap_or(config.local);
+            config.logging_guard = Some(
+                logging::init_logging(&config.logging_config, config.log_format(), file_enabled)
+                    .context("Error initiating logging")?
+            );
+        }
+
+        if let Some(profile) = &config.profile {
+            tracing::info!(profile=profile, "Applied configuration profile");
+        }
+
+        // Warn about deprecated fields, once logging is available
+        for (deprecated, replacement) in config.deprecations.iter() {
+            tracing::warn!("Config field '{}' is deprecated, use '{}' instead", deprecated, replacement);
+        }
+        metrics::CONFIG_DEPRECATIONS.set(config.deprecations.len() as i64);
+
+        // Values are not logged, as overrides commonly hold credentials
+        for variable in config.env_overrides.iter() {
+            tracing::info!(variable=variable, "Applied environment override to configuration");
+        }
+        for field in config.resolved_secrets.iter() {
+            tracing::info!(field=field, "Resolved secret reference of configuration");
+        }
+
+        // Configurations with a single url have a single endpoint
+        config.triton_config.normalize_endpoints();
+
+        // Models without shapes take the shapes of their model type
+        for (model_type, model_config) in config.inference_config.models.iter_mut() {
+            model_config.fill_defaults(model_type);
+        }
+
+        // Report every invalid field at once, before anything is started
+        config.validate()?;
+
+        // GPU information - inference may run remotely, so a missing local GPU is not fatal,
+        // but configured GPUs missing from the local backend are
+        let gpu_name = utils::get_gpu_name(&config.gpu_indices)
+            .context("Invalid gpu_indices")?;
+        config.gpu_name = match gpu_name {
+            Some(gpu_name) => gpu_name,
+            None => {
+                tracing::warn!("No local GPU is available, proceeding without GPU information");
+                "unknown".to_string()
+            }
+        };
+
+        // Normalize models
+        for model_config in config.inference_config.models.values_mut() {
+            if model_config.enable_dynamic_batching {
+                model_config.normalize_batch_preferred_sizes();
+            }
+        }
+
+        // Parse sources
+        let sources = config.sources_config.resolve_sources(&config.inference_config)
+            .context("Error resolving sources configuration")?;
+        config.sources_config.sources = sources;
+
+        Ok(config)
+    }
+
+    /// Checks every constraint of the configuration, returning all violations at once along with
+    /// the YAML path of the offending field
+    pub fn validate(&self) -> Result<()> {
+        let mut violations = Violations::default();
+
+        // Sources - nothing can run without them
+        let ids = &self.sources_config.ids;
+        violations.check(!ids.is_empty(), "sources_config.ids", "must list at least one source");
+        for (index, source_id) in ids.iter().enumerate() {
+            let path = format!("sources_config.ids[{}]", index);
+            violations.check(!source_id.trim().is_empty(), &path, "must not be empty");
+            violations.check(!ids[..index].contains(source_id), &path, format!("source '{}' is listed more than once", source_id));
+        }
+
+        let default = &self.sources_config.default;
+        violations.check_ranges("sources_config.default", &source_ranges(default));
+        let default_pipeline = match default.pipeline.is_empty() {
+            true => self.inference_config.task.default_pipeline(),
+            false => default.pipeline.clone()
+        };
+        if let Err(e) = AppConfig::validate_pipeline(&default_pipeline, &self.inference_config) {
+            violations.add("sources_config.default.pipeline", e);
+        }
+
+        let mut custom_ids: Vec<&String> = self.sources_config.custom.keys().collect();
+        custom_ids.sort();
+        for source_id in custom_ids {
+            let custom = &self.sources_config.custom[source_id];
+            let path = format!("sources_config.custom.{}", source_id);
+            violations.check(ids.contains(source_id), &path, "source is not listed in sources_config.ids");
+            violations.check_ranges(&path, &custom_source_ranges(custom));
+
+            if let Some(pipeline) = custom.pipeline.as_ref().filter(|pipeline| !pipeline.is_empty()) {
+                if let Err(e) = AppConfig::validate_pipeline(pipeline, &self.inference_config) {
+                    violations.add(format!("{}.pipeline", path), e);
+                }
+            }
+        }
+
+        // Models
+        let mut model_types: Vec<&InferenceModelType> = self.inference_config.models.keys().collect();
+        model_types.sort_by_key(|model_type| model_type.to_string());
+        for model_type in model_types {
+            let model = &self.inference_config.models[model_type];
+            let path = format!("inference_config.models.{}", model_type.to_string());
+
+            violations.check(!model.name.is_empty(), format!("{}.name", path), "must not be empty");
+            violations.check(
+                !model.input_shape.is_empty() && model.input_shape.iter().all(|&dim| dim > 0),
+                format!("{}.input_shape", path),
+                "must be non-empty with positive dimensions"
+            );
+            violations.check(!model.output_name.is_empty(), format!("{}.output_name", path), "at least one output is required");
+            violations.check(
+                model.output_name.len() == model.output_shape.len(),
+                format!("{}.output_shape", path),
+                format!("got {} output names and {} output shapes, expected one shape per output", model.output_name.len(), model.output_shape.len())
+            );
+            for (index, shape) in model.output_shape.iter().enumerate() {
+                violations.check(
+                    !shape.is_empty() && shape.iter().all(|&dim| dim > 0),
+                    format!("{}.output_shape[{}]", path, index),
+                    "must be non-empty with positive dimensions"
+                );
+            }
+            violations.check(model.batch_max_size >= 1, format!("{}.batch_max_size", path), "must be at least 1");
+
+            if model.enable_dynamic_batching {
+                violations.check(
+                    !model.batch_preferred_sizes.is_empty(),
+                    format!("{}.batch_preferred_sizes", path),
+                    "at least one preferred batch size is required"
+                );
+                for (index, &size) in model.batch_preferred_sizes.iter().enumerate() {
+                    violations.check(
+                        size >= 1 && size <= model.batch_max_size,
+                        format!("{}.batch_preferred_sizes[{}]", path, index),
+                        format!("{} must be between 1 and batch_max_size ({})", size, model.batch_max_size)
+                    );
+                }
+            }
+        }
+
+        // Triton - UNIX domain socket transport is not implemented, triton_client::Client
+        // builds its own TCP channel from the url and accepts no custom connector
+        let endpoints = &self.triton_config.endpoints;
+        violations.check(!endpoints.is_empty(), "triton_config.endpoints", "must list at least one endpoint");
+        for (index, endpoint) in endpoints.iter().enumerate() {
+            let path = format!("triton_config.endpoints[{}]", index);
+            violations.check(!endpoint.name.trim().is_empty(), format!("{}.name", path), "must not be empty");
+            violations.check(
+                !endpoints[..index].iter().any(|other| other.name == endpoint.name),
+                format!("{}.name", path),
+                format!("endpoint '{}' is listed more than once", endpoint.name)
+            );
+            violations.check(!endpoint.url.is_empty(), format!("{}.url", path), "must not be empty");
+            violations.check(
+                !endpoint.url.starts_with("unix://"),
+                format!("{}.url", path),
+                "UNIX domain socket transport is not implemented, use a http(s):// url of the server"
+            );
+        }
+        for (model_type, model_config) in self.inference_config.models.iter() {
+            if let Err(e) = self.triton_config.model_endpoints(model_config) {
+                violations.add(format!("inference_config.models.{}", model_type.to_string()), e);
+            }
+        }
+
+        // Video library streams
+        violations.check(self.stream_config.circuit_breaker_threshold > 0, "stream_config.circuit_breaker_threshold", "must be at least 1");
+        violations.check(self.stream_config.keepalive_interval_secs > 0, "stream_config.keepalive_interval_secs", "must be at least 1");
+        if let Some(resolution) = self.stream_config.max_decode_resolution {
+            violations.check(resolution.width > 0 && resolution.height > 0, "stream_config.max_decode_resolution", "width and height must be at least 1");
+        }
+        let reads_sub_streams = self.sources_config.default.sub_stream.is_some()
+            || self.sources_config.custom.values().any(|custom| custom.sub_stream.is_some());
+        violations.check(
+            self.stream_config.decode_all_streams || !reads_sub_streams,
+            "stream_config.decode_all_streams",
+            "must be enabled when sources read sub-streams"
+        );
+
+        // Outputs
+        violations.check(self.outputs_config.subscription_capacity > 0, "outputs_config.subscription_capacity", "must be at least 1");
+
+        // Cross-source deduplication
+        violations.check(
+            self.overlap_similarity > 0.00 && self.overlap_similarity <= 1.00,
+            "overlap_similarity",
+            format!("{} must be greater than 0 and at most 1", self.overlap_similarity)
+        );
+
+        // GPUs
+        violations.check(!self.gpu_indices.is_empty(), "gpu_indices", "must list at least one GPU");
+        for (index, gpu_index) in self.gpu_indices.iter().enumerate() {
+            violations.check(
+                !self.gpu_indices[..index].contains(gpu_index),
+                format!("gpu_indices[{}]", index),
+                format!("GPU {} is listed more than once", gpu_index)
+            );
+        }
+
+        // Kafka
+        if self.outputs_config.kafka && self.kafka_config.enabled {
+            let kafka = &self.kafka_config;
+            for (field, value) in [
+                ("brokers", &kafka.brokers),
+                ("topic_bboxes", &kafka.topic_bboxes),
+                ("topic_embedding", &kafka.topic_embedding),
+                ("topic_results", &kafka.topic_results)
+            ] {
+                violations.check(!value.trim().is_empty(), format!("kafka_config.{}", field), "must not be empty when Kafka output is enabled");
+            }
+            violations.check(
+                !kafka.enable_idempotence || kafka.acks == "all",
+                "kafka_config.acks",
+                format!("{} must be all when enable_idempotence is set", kafka.acks)
+            );
+        }
+        if let (true, Err(e)) = (self.kafka_config.enabled, self.kafka_config.security_properties()) {
+            violations.add("kafka_config.security_protocol", e);
+        }
+
+        // Admin server - operations must not be open to the network
+        let admin = &self.admin_config;
+        if admin.enabled {
+            violations.check(
+                admin.is_loopback() || admin.token.is_some(),
+                "admin_config.token",
+                format!("is required when listening on {}, which is not a loopback address", admin.host)
+            );
+            violations.check(
+                admin.token.as_ref().is_none_or(|token| !token.trim().is_empty()),
+                "admin_config.token",
+                "must not be empty"
+            );
+        }
+
+        violations.into_result()
+    }
+
+    /// Validates that every stage of a pipeline references a configured model,
+    /// and that stages relying on detections run after a detection stage
+    fn validate_pipeline(pipeline: &[InferenceModelType], inference_config: &InferenceConfig) -> Result<()> {
+        for (stage, model_type) in pipeline.iter().enumerate() {
+            if !inference_config.models.contains_key(model_type) {
+                anyhow::bail!("Model {} is not configured under inference_config.models", model_type.to_string());
+            }
+
+            if *model_type == InferenceModelType::DINO && !pipeline[..stage].contains(&InferenceModelType::YOLO) {
+                anyhow::bail!("Model DINO requires a YOLO stage before it");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns YAML paths of the fields differing from the given configuration, that cannot be
+    /// applied without a restart - models, inference task and service connections
+    pub fn immutable_changes(&self, other: &AppConfig) -> Vec<String> {
+        let mut changes: Vec<String> = Vec::new();
+        let mut check = |changed: bool, path: String| {
+            if changed {
+                changes.push(path);
+            }
+        };
+
+        check(self.inference_config.task != other.inference_config.task, "inference_config.task".to_string());
+        check(self.inference_config.color_order != other.inference_config.color_order, "inference_config.color_order".to_string());
+        check(self.stream_config != other.stream_config, "stream_config".to_string());
+
+        let mut model_types: Vec<&InferenceModelType> = self.inference_config.models.keys()
+            .chain(other.inference_config.models.keys())
+            .collect();
+        model_types.sort_by_key(|model_type| model_type.to_string());
+        model_types.dedup();
+        for model_type in model_types {
+            let path = format!("inference_config.models.{}", model_type.to_string());
+            let (Some(current), Some(new)) = (
+                self.inference_config.models.get(model_type),
+                other.inference_config.models.get(model_type)
+            ) else {
+                check(true, path);
+                continue;
+            };
+
+            check(current.name != new.name, format!("{}.name", path));
+            check(current.version != new.version, format!("{}.version", path));
+            check(current.precision != new.precision, format!("{}.precision", path));
+            check(current.input_name != new.input_name, format!("{}.input_name", path));
+            check(current.input_shape != new.input_shape, format!("{}.input_shape", path));
+            check(current.output_name != new.output_name, format!("{}.output_name", path));
+            check(current.output_shape != new.output_shape, format!("{}.output_shape", path));
+            check(current.endpoint != new.endpoint, format!("{}.endpoint", path));
+            check(current.failover_endpoints != new.failover_endpoints, format!("{}.failover_endpoints", path));
+        }
+
+        // Streams of sources keep running across reloads, so the settings they were started with cannot change
+        let mut source_ids: Vec<&String> = self.sources_config.sources
+            .keys()
+            .filter(|source_id| other.sources_config.sources.contains_key(*source_id))
+            .collect();
+        source_ids.sort();
+        for source_id in source_ids {
+            let path = format!("sources_config.sources.{}", source_id);
+            let (current, new) = (&self.sources_config.sources[source_id], &other.sources_config.sources[source_id]);
+
+            check(current.video_id != new.video_id, format!("{}.video_id", path));
+            check(current.shared_memory != new.shared_memory, format!("{}.shared_memory", path));
+            check(current.sub_stream != new.sub_stream, format!("{}.sub_stream", path));
+        }
+
+        check(self.overlap_window_ms != other.overlap_window_ms, "overlap_window_ms".to_string());
+        check(self.overlap_similarity != other.overlap_similarity, "overlap_similarity".to_string());
+        check(self.gpu_indices != other.gpu_indices, "gpu_indices".to_string());
+        check(self.triton_config.endpoints != other.triton_config.endpoints, "triton_config.endpoints".to_string());
+        check(self.triton_config.models_dir != other.triton_config.models_dir, "triton_config.models_dir".to_string());
+        check(self.kafka_config.enabled != other.kafka_config.enabled, "kafka_config.enabled".to_string());
+        check(self.kafka_config.brokers != other.kafka_config.brokers, "kafka_config.brokers".to_string());
+
+        changes
+    }
+
+    /// Loads environment variables from a local .env file
+    fn load_config_file(profile: Option<&str>) -> Result<AppConfig> {
+        let config_path = Path::new(CONFIG_PATH);
+        
+        // Load configuration file
+        let contents = std::fs::read_to_string(config_path)
+            .context("Error locating configuration file")?;
+
+        let mut config_value: Value = serde_yaml::from_str(&contents)
+            .context("Error parsing configuration file")?;
+
+        AppConfig::apply_profile(&mut config_value, profile)
+            .context("Error applying configuration profile")?;
+
+        let mut config_file: AppConfig = serde_yaml::from_value(config_value.clone())
+            .context("Error parsing configuration file")?;
+        config_file.deprecations = AppConfig::find_deprecations(
+            &config_value,
+            config_file.deprecated_fields.as_ref()
+        );
+
+        // Keys that are not strings, e.g. numeric source ids, are written as strings like in the effective configuration
+        let mut file_fields = Vec::new();
+        leaf_paths(&serde_json::to_value(&config_value).unwrap_or_default(), String::new(), &mut file_fields);
+        config_file.file_fields = file_fields.into_iter().collect();
+
+        // Environment variables take precedence over the file
+        AppConfig::apply_env_overrides(&mut config_file, |variable| std::env::var(variable).ok())?;
+
+        // Secrets may also be referenced by environment overrides
+        AppConfig::resolve_secrets(&mut config_file)?;
+
+        Ok(config_file)
+    }
+
+    /// Resolves secret references of the fields of `SECRET_FIELDS`
+    fn resolve_secrets(config: &mut AppConfig) -> Result<()> {
+        for (path, field) in SECRET_FIELDS {
+            let Some(value) = field(config) else {
+                continue;
+            };
+            if !value.contains("${") {
+                continue;
+            }
+
+            *value = resolve_secret_references(value)
+                .with_context(|| format!("Error resolving secret of {}", path))?;
+            config.resolved_secrets.push(path);
+        }
+
+        Ok(())
+    }
+
+    /// Applies the environment variables of `ENV_OVERRIDES` that are set, as read by `get_var`
+    fn apply_env_overrides<F>(config: &mut AppConfig, get_var: F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<String>
+    {
+        for (variable, _, expected_type, setter) in ENV_OVERRIDES {
+            let Some(value) = get_var(variable) else {
+                continue;
+            };
+
+            setter(config, &value)
+                .with_context(|| format!("Invalid value '{}' of environment variable {}, expected {}", value, variable, expected_type))?;
+            config.env_overrides.push(variable);
+        }
+
+        Ok(())
+    }
+
+    /// Returns fields of the configuration matching deprecated fields, with their replacements.
+    /// Nested fields are given as dot separated paths, e.g. `triton_config.url`
+    fn find_deprecations(config: &Value, deprecated_fields: Option<&HashMap<String, String>>) -> Vec<(String, String)> {
+        let contains_path = |path: &str| {
+            path.split('.')
+                .try_fold(config, |value, key| value.as_mapping()?.get(key))
+                .is_some()
+        };
+
+        let known = DEPRECATED_FIELDS
+            .iter()
+            .map(|(deprecated, replacement)| (deprecated.to_string(), replacement.to_string()));
+        let configured = deprecated_fields
+            .into_iter()
+            .flatten()
+            .map(|(deprecated, replacement)| (deprecated.clone(), replacement.clone()));
+
+        let mut deprecations: Vec<(String, String)> = known
+            .chain(configured)
+            .filter(|(deprecated, _)| contains_path(deprecated))
+            .collect();
+        deprecations.sort();
+        deprecations.dedup_by(|a, b| a.0 == b.0);
+
+        deprecations
+    }
+
+    /// Merges the overrides of the selected profile on top of the base configuration.
+    /// The `profiles` section itself is always removed from the configuration
+    fn apply_profile(config: &mut Value, profile: Option<&str>) -> Result<()> {
+        let profiles = config
+            .as_mapping_mut()
+            .context("Configuration file is not a mapping")?
+            .remove("profiles");
+
+        let Some(profile) = profile else {
+            return Ok(())
+        };
+
+        let overrides = profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(profile))
+            .cloned()
+            .with_context(|| format!("Profile '{}' is not defined under 'profiles'", profile))?;
+
+        AppConfig::merge_values(config, overrides);
+
+        Ok(())
+    }
+
+    /// Deep-merges YAML values - mappings are merged key by key, anything else is replaced
+    fn merge_values(base: &mut Value, overrides: Value) {
+        match (base, overrides) {
+            (Value::Mapping(base_map), Value::Mapping(overrides_map)) => {
+                for (key, value) in overrides_map {
+                    match base_map.get_mut(&key) {
+                        Some(base_value) => AppConfig::merge_values(base_value, value),
+                        None => {
+                            base_map.insert(key, value);
+                        }
+                    }
+                }
+            },
+            (base, overrides) => *base = overrides
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn is_local(&self) -> bool {
+        self.local
+    }
+
+    pub fn environment(&self) -> Environment {
+        self.environment
+    }
+
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    pub fn log_format(&self) -> LogFormat {
+        self.logging_config.format
+            .or(self.log_format)
+            .unwrap_or(if self.local { LogFormat::Pretty } else { LogFormat::Json })
+    }
+
+    pub fn logging_config(&self) -> &LoggingConfig {
+        &self.logging_config
+    }
+
+    pub fn gpu_name(&self) -> &str {
+        &self.gpu_name
+    }
+
+    pub fn sources_config(&self) -> &SourcesConfig {
+        &self.sources_config
+    }
+
+    pub fn kafka_config(&self) -> &KafkaConfig {
+        &self.kafka_config
+    }
+
+    pub fn triton_config(&self) -> &TritonConfig {
+        &self.triton_config
+    }
+
+    pub fn inference_config(&self) -> &InferenceConfig {
+        &self.inference_config
+    }
+
+    pub fn admin_config(&self) -> &AdminConfig {
+        &self.admin_config
+    }
+
+    pub fn persistence_config(&self) -> &PersistenceConfig {
+        &self.persistence_config
+    }
+
+    pub fn outputs_config(&self) -> &OutputsConfig {
+        &self.outputs_config
+    }
+
+    pub fn stats_sink_config(&self) -> &StatsSinkConfig {
+        &self.stats_sink_config
+    }
+
+    pub fn backpressure_config(&self) -> &BackpressureConfig {
+        &self.backpressure_config
+    }
+
+    pub fn readiness_config(&self) -> &ReadinessConfig {
+        &self.readiness_config
+    }
+
+    pub fn stream_config(&self) -> &StreamConfig {
+        &self.stream_config
+    }
+
+    pub fn hot_reload_config(&self) -> &HotReloadConfig {
+        &self.hot_reload_config
+    }
+
+    pub fn overlap_window_ms(&self) -> Option<u64> {
+        self.overlap_window_ms
+    }
+
+    pub fn overlap_similarity(&self) -> f32 {
+        self.overlap_similarity
+    }
+
+    fn default_overlap_similarity() -> f32 {
+        0.95
+    }
+
+    pub fn gpu_stats_interval_secs(&self) -> u64 {
+        self.gpu_stats_interval_secs
+    }
+
+    fn default_gpu_stats_interval_secs() -> u64 {
+        200
+    }
+
+    pub fn gpu_indices(&self) -> &[u32] {
+        &self.gpu_indices
+    }
+
+    fn default_gpu_indices() -> Vec<u32> {
+        vec![0]
+    }
+
+    pub fn benchmark_on_startup(&self) -> bool {
+        self.benchmark_on_startup
+    }
+
+    pub fn benchmark_iterations(&self) -> u32 {
+        self.benchmark_iterations
+    }
+
+    fn default_benchmark_iterations() -> u32 {
+        20
+    }
+}
+
+/// Either a single value or a list of values in configuration
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>)
+}
+
+/// Source id written as a string or a number in configuration
+#[derive(PartialEq, Eq, Hash, Deserialize)]
+#[serde(untagged)]
+enum SourceIdValue {
+    Text(String),
+    Number(i64)
+}
+
+impl SourceIdValue {
+    fn into_string(self) -> String {
+        match self {
+            SourceIdValue::Text(id) => id,
+            SourceIdValue::Number(id) => id.to_string()
+        }
+    }
+}
+
+/// Deserializes source ids written as strings or numbers
+fn source_ids<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>
+{
+    Ok(
+        Vec::<SourceIdValue>::deserialize(deserializer)?
+            .into_iter()
+            .map(SourceIdValue::into_string)
+            .collect()
+    )
+}
+
+/// Deserializes a mapping keyed by source ids written as strings or numbers
+fn source_id_keys<'de, D, T>(deserializer: D) -> std::result::Result<HashMap<String, T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>
+{
+    Ok(
+        HashMap::<SourceIdValue, T>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(id, value)| (id.into_string(), value))
+            .collect()
+    )
+}
+
+/// Returns a configuration section with every field set to its serde default,
+/// keeping `Default` in line with the defaults of omitted fields
+fn defaults_of<T: serde::de::DeserializeOwned>() -> T {
+    serde_yaml::from_value(Value::Mapping(serde_yaml::Mapping::new()))
+        .expect("Configuration section has a default for every field")
+}
+
+/// Deserializes a single value or a list of values into a list,
+/// keeping single-value configurations valid as lists grow
+fn one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>
+{
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values
+    })
+}
+
+/// Range of allowed values of a numeric field - its name, value if set, and inclusive bounds
+type FieldRange = (&'static str, Option<f64>, f64, f64);
+
+/// Ranges of the numeric fields of a source configuration
+fn source_ranges(source: &SourceConfig) -> Vec<FieldRange> {
+    vec![
+        ("inf_frame", Some(source.inf_frame as f64), 1.00, 30.00),
+        ("conf_threshold", Some(source.conf_threshold as f64), 0.00, 1.00),
+        ("nms_iou_threshold", Some(source.nms_iou_threshold as f64), 0.00, 1.00),
+        ("nms_soft_sigma", source.nms_soft_sigma.map(f64::from), f64::MIN_POSITIVE, f64::MAX),
+        ("motion_gate.threshold", source.motion_gate.as_ref().map(|gate| gate.threshold as f64), f64::MIN_POSITIVE, 255.00),
+        ("motion_gate.max_idle_secs", source.motion_gate.as_ref().map(|gate| gate.max_idle_secs as f64), 1.00, f64::MAX),
+        ("dedup_threshold", source.dedup_threshold.map(f64::from), 0.00, 255.00),
+        ("max_inferences_per_sec", source.max_inferences_per_sec, f64::MIN_POSITIVE, f64::MAX),
+        ("drop_warn_percent", source.drop_warn_percent.map(f64::from), 0.00, 100.00),
+        ("debug_sample_rate", Some(source.debug_sample_rate as f64), 0.00, 1.00),
+        ("queue_max_dimension", source.queue_max_dimension.map(f64::from), 1.00, f64::MAX),
+        ("debounce.grid_size", source.debounce.as_ref().map(|debounce| debounce.grid_size as f64), 1.00, f64::MAX),
+        ("depth_filter.max_distance_m", source.depth_filter.as_ref().map(|filter| filter.max_distance_m as f64), f64::MIN_POSITIVE, f64::MAX),
+        ("depth_filter.depth_scale", source.depth_filter.as_ref().map(|filter| filter.depth_scale as f64), f64::MIN_POSITIVE, f64::MAX),
+        ("shared_memory.poll_interval_ms", source.shared_memory.as_ref().map(|shm| shm.poll_interval_ms as f64), 1.00, f64::MAX)
+    ]
+}
+
+/// Ranges of the numeric fields set by a custom source configuration
+fn custom_source_ranges(source: &SourceConfigOptional) -> Vec<FieldRange> {
+    vec![
+        ("inf_frame", source.inf_frame.map(f64::from), 1.00, 30.00),
+        ("conf_threshold", source.conf_threshold.map(f64::from), 0.00, 1.00),
+        ("nms_iou_threshold", source.nms_iou_threshold.map(f64::from), 0.00, 1.00),
+        ("nms_soft_sigma", source.nms_soft_sigma.map(f64::from), f64::MIN_POSITIVE, f64::MAX),
+        ("motion_gate.threshold", source.motion_gate.as_ref().map(|gate| gate.threshold as f64), f64::MIN_POSITIVE, 255.00),
+        ("motion_gate.max_idle_secs", source.motion_gate.as_ref().map(|gate| gate.max_idle_secs as f64), 1.00, f64::MAX),
+        ("dedup_threshold", source.dedup_threshold.map(f64::from), 0.00, 255.00),
+        ("max_inferences_per_sec", source.max_inferences_per_sec, f64::MIN_POSITIVE, f64::MAX),
+        ("drop_warn_percent", source.drop_warn_percent.map(f64::from), 0.00, 100.00),
+        ("debug_sample_rate", source.debug_sample_rate.map(f64::from), 0.00, 1.00),
+        ("queue_max_dimension", source.queue_max_dimension.map(f64::from), 1.00, f64::MAX),
+        ("debounce.grid_size", source.debounce.as_ref().map(|debounce| debounce.grid_size as f64), 1.00, f64::MAX),
+        ("depth_filter.max_distance_m", source.depth_filter.as_ref().map(|filter| filter.max_distance_m as f64), f64::MIN_POSITIVE, f64::MAX),
+        ("depth_filter.depth_scale", source.depth_filter.as_ref().map(|filter| filter.depth_scale as f64), f64::MIN_POSITIVE, f64::MAX),
+        ("shared_memory.poll_interval_ms", source.shared_memory.as_ref().map(|shm| shm.poll_interval_ms as f64), 1.00, f64::MAX)
+    ]
+}
+
+/// Violations of configuration constraints, each prefixed with the YAML path of its field
+#[derive(Default)]
+struct Violations(Vec<String>);
+
+impl Violations {
+    fn add(&mut self, path: impl std::fmt::Display, message: impl std::fmt::Display) {
+        self.0.push(format!("{}: {:#}", path, message));
+    }
+
+    /// Adds a violation unless the constraint holds
+    fn check(&mut self, valid: bool, path: impl std::fmt::Display, message: impl std::fmt::Display) {
+        if !valid {
+            self.add(path, message);
+        }
+    }
+
+    /// Adds a violation for every set field outside of its range
+    fn check_ranges(&mut self, path: &str, ranges: &[FieldRange]) {
+        for (field, value, min, max) in ranges {
+            let Some(value) = value else {
+                continue;
+            };
+
+            let bounds = match (*min == f64::MIN_POSITIVE, *max == f64::MAX) {
+                (true, true) => "greater than 0".to_string(),
+                (false, true) => format!("at least {}", min),
+                (true, false) => format!("greater than 0 and at most {}", max),
+                _ => format!("between {} and {}", min, max)
+            };
+            // Values of f32 fields are shown as written, e.g. 0.1 rather than 0.10000000149011612
+            let shown = match (*value as f32) as f64 == *value {
+                true => (*value as f32).to_string(),
+                false => value.to_string()
+            };
+            self.check(value >= min && value <= max, format!("{}.{}", path, field), format!("{} must be {}", shown, bounds));
+        }
+    }
+
+    fn into_result(self) -> Result<()> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        anyhow::bail!(
+            "Found {} invalid configuration fields:\n  - {}",
+            self.0.len(),
+            self.0.join("\n  - ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inference_config() -> InferenceConfig {
+        serde_yaml::from_str("models: { YOLO: { name: yolo }, DINO: { name: dino } }").unwrap()
+    }
+
+    fn sources_config(yaml: &str) -> SourcesConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const BASE_CONFIG: &str = "
+        sources_config: { ids: [1] }
+        triton_config: { url: 'http://localhost:8001' }
+        inference_config: { models: { YOLO: { name: yolo }, DINO: { name: dino } } }
+    ";
+
+    /// Parses a configuration as loaded from a file, with the given YAML merged over `BASE_CONFIG`
+    fn app_config(overrides: &str) -> AppConfig {
+        let mut value: Value = serde_yaml::from_str(BASE_CONFIG).unwrap();
+        AppConfig::merge_values(&mut value, serde_yaml::from_str(overrides).unwrap());
+
+        let mut config: AppConfig = serde_yaml::from_value(value).unwrap();
+        config.triton_config.normalize_endpoints();
+        for (model_type, model_config) in config.inference_config.models.iter_mut() {
+            model_config.fill_defaults(model_type);
+        }
+        config
+    }
+
+    /// Writes a secret file unique to the test, returning its path
+    fn secret_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("client-secret-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn resolves_env_secret_references() {
+        let path = std::env::var("PATH").unwrap();
+
+        assert_eq!(resolve_secret_references("${env:PATH}").unwrap(), path);
+    }
+
+    #[test]
+    fn resolves_file_secret_references() {
+        let file = secret_file("file", "kafka-password\n");
+        let resolved = resolve_secret_references(&format!("${{file:{}}}", file.display()));
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(resolved.unwrap(), "kafka-password");
+    }
+
+    #[test]
+    fn resolves_references_nested_in_plain_strings() {
+        let file = secret_file("nested", "secret");
+        let resolved = resolve_secret_references(&format!("user:${{file:{}}}@${{env:PATH}}/", file.display()));
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(resolved.unwrap(), format!("user:secret@{}/", std::env::var("PATH").unwrap()));
+        assert_eq!(resolve_secret_references("no references").unwrap(), "no references");
+    }
+
+    #[test]
+    fn reports_missing_secret_sources() {
+        let error = resolve_secret_references("${env:CLIENT_TEST_MISSING_SECRET}").unwrap_err();
+        assert!(error.to_string().contains("CLIENT_TEST_MISSING_SECRET"));
+
+        let error = resolve_secret_references("${file:/nonexistent/client-secret}").unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/client-secret"));
+    }
+
+    #[test]
+    fn names_field_of_unresolved_secret() {
+        let mut config = app_config("kafka_config: { sasl_password: '${env:CLIENT_TEST_MISSING_SECRET}' }");
+
+        let error = AppConfig::resolve_secrets(&mut config).unwrap_err();
+
+        assert!(format!("{:#}", error).contains("kafka_config.sasl_password"));
+    }
+
+    #[test]
+    fn rejects_malformed_secret_references() {
+        assert!(resolve_secret_references("${env:PATH").is_err());
+        assert!(resolve_secret_references("${PATH}").is_err());
+        assert!(resolve_secret_references("${vault:kafka}").is_err());
+    }
+
+    #[test]
+    fn effective_config_redacts_secrets() {
+        let file = secret_file("redacted", "file-admin-token");
+        let mut config = app_config(&format!(
+            "{{ kafka_config: {{ sasl_password: inline-kafka-password }}, admin_config: {{ token: '${{file:{}}}' }} }}",
+            file.display()
+        ));
+        let resolved = AppConfig::resolve_secrets(&mut config);
+        std::fs::remove_file(&file).unwrap();
+        resolved.unwrap();
+
+        AppConfig::apply_env_overrides(&mut config, |variable| {
+            (variable == "APP__KAFKA__SASL_PASSWORD").then(|| "env-kafka-password".to_string())
+        }).unwrap();
+
+        let effective_config = config.effective_config().unwrap();
+        let dump = effective_config.to_string();
+
+        for secret in ["inline-kafka-password", "file-admin-token", "env-kafka-password"] {
+            assert!(!dump.contains(secret), "{} is not redacted", secret);
+        }
+        assert_eq!(effective_config["config"]["kafka_config"]["sasl_password"], REDACTED);
+        assert_eq!(effective_config["config"]["admin_config"]["token"], REDACTED);
+        assert_eq!(effective_config["origins"]["kafka_config.sasl_password"], "env");
+    }
+
+    fn kafka_config(yaml: &str) -> KafkaConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn builds_plaintext_security_properties() {
+        let properties = kafka_config("security_protocol: PLAINTEXT").security_properties().unwrap();
+
+        assert_eq!(properties, vec![("security.protocol", "plaintext".to_string())]);
+    }
+
+    #[test]
+    fn builds_sasl_security_properties() {
+        let properties = kafka_config("
+            security_protocol: SASL_PLAINTEXT
+            sasl_mechanism: SCRAM-SHA-512
+            sasl_username: detections
+            sasl_password: kafka-password
+        ").security_properties().unwrap();
+
+        assert_eq!(properties, vec![
+            ("security.protocol", "sasl_plaintext".to_string()),
+            ("sasl.mechanism", "SCRAM-SHA-512".to_string()),
+            ("sasl.username", "detections".to_string()),
+            ("sasl.password", "kafka-password".to_string())
+        ]);
+    }
+
+    #[test]
+    fn builds_ssl_security_properties() {
+        let properties = kafka_config("
+            security_protocol: SSL
+            ssl_ca_location: /certs/ca.pem
+        ").security_properties().unwrap();
+
+        assert_eq!(properties, vec![
+            ("security.protocol", "ssl".to_string()),
+            ("ssl.ca.location", "/certs/ca.pem".to_string())
+        ]);
+    }
+
+    #[test]
+    fn builds_sasl_ssl_security_properties_with_client_certificate() {
+        let properties = kafka_config("
+            security_protocol: SASL_SSL
+            sasl_mechanism: PLAIN
+            sasl_username: detections
+            sasl_password: kafka-password
+            ssl_ca_location: /certs/ca.pem
+            ssl_certificate_location: /certs/client.pem
+            ssl_key_location: /certs/client.key
+        ").security_properties().unwrap();
+
+        assert_eq!(properties, vec![
+            ("security.protocol", "sasl_ssl".to_string()),
+            ("sasl.mechanism", "PLAIN".to_string()),
+            ("sasl.username", "detections".to_string()),
+            ("sasl.password", "kafka-password".to_string()),
+            ("ssl.ca.location", "/certs/ca.pem".to_string()),
+            ("ssl.certificate.location", "/certs/client.pem".to_string()),
+            ("ssl.key.location", "/certs/client.key".to_string())
+        ]);
+    }
+
+    #[test]
+    fn rejects_incoherent_security_settings() {
+        for (yaml, message) in [
+            ("{ security_protocol: SASL_SSL, sasl_mechanism: PLAIN, sasl_username: detections, ssl_ca_location: /ca.pem }", "requires sasl_mechanism"),
+            ("{ security_protocol: PLAINTEXT, sasl_username: detections }", "SASL settings require"),
+            ("{ security_protocol: SSL }", "requires ssl_ca_location"),
+            ("{ security_protocol: SSL, ssl_ca_location: /ca.pem, ssl_key_location: /client.key }", "must be set together"),
+            ("{ security_protocol: SASL_PLAINTEXT, sasl_mechanism: PLAIN, sasl_username: a, sasl_password: b, ssl_ca_location: /ca.pem }", "SSL settings require")
+        ] {
+            let error = kafka_config(yaml).security_properties().unwrap_err();
+            assert!(error.to_string().contains(message), "{}: {}", yaml, error);
+        }
+    }
+
+    #[test]
+    fn resolves_sasl_password_references() {
+        let mut config = app_config("
+            kafka_config:
+              security_protocol: SASL_PLAINTEXT
+              sasl_mechanism: PLAIN
+              sasl_username: detections
+              sasl_password: '${env:PATH}'
+        ");
+
+        AppConfig::resolve_secrets(&mut config).unwrap();
+        let properties = config.kafka_config.security_properties().unwrap();
+
+        assert!(properties.contains(&("sasl.password", std::env::var("PATH").unwrap())));
+    }
+
+    /// Reads environment variables from the given pairs instead of the process environment
+    fn env_vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |variable| vars.get(variable).cloned()
+    }
+
+    #[test]
+    fn env_overrides_take_precedence_over_file() {
+        let mut config = app_config("
+            triton_config: { url: 'http://file-triton:8001', models_dir: /file-models }
+            kafka_config: { brokers: file-kafka:9092, enabled: true }
+            sources_config: { default: { conf_threshold: 0.4, inf_frame: 2 } }
+        ");
+
+        AppConfig::apply_env_overrides(&mut config, env_vars(&[
+            ("APP__TRITON__URL", "http://env-triton:8001"),
+            ("APP__KAFKA__BROKERS", "env-kafka:9092"),
+            ("APP__KAFKA__ENABLED", "false"),
+            ("APP__SOURCES__DEFAULT__CONF_THRESHOLD", "0.6")
+        ])).unwrap();
+
+        // Overridden fields
+        assert_eq!(config.triton_config.endpoints[0].url, "http://env-triton:8001");
+        assert_eq!(config.kafka_config.brokers, "env-kafka:9092");
+        assert!(!config.kafka_config.enabled);
+        assert_eq!(config.sources_config.default.conf_threshold, 0.6);
+
+        // Fields of the file without overrides
+        assert_eq!(config.triton_config.models_dir, "/file-models");
+        assert_eq!(config.sources_config.default.inf_frame, 2);
+    }
+
+    #[test]
+    fn keeps_file_without_env_overrides() {
+        let mut config = app_config("kafka_config: { brokers: file-kafka:9092 }");
+
+        AppConfig::apply_env_overrides(&mut config, env_vars(&[])).unwrap();
+
+        assert_eq!(config.kafka_config.brokers, "file-kafka:9092");
+    }
+
+    #[test]
+    fn rejects_invalid_env_override_values() {
+        for (variable, value, expected_type) in [
+            ("APP__KAFKA__ENABLED", "yes", "boolean"),
+            ("APP__ADMIN__PORT", "70000", "integer (0-65535)"),
+            ("APP__SOURCES__DEFAULT__INF_FRAME", "-1", "integer"),
+            ("APP__SOURCES__DEFAULT__CONF_THRESHOLD", "high", "float")
+        ] {
+            let mut config = app_config("{}");
+
+            let error = AppConfig::apply_env_overrides(&mut config, env_vars(&[(variable, value)])).unwrap_err();
+
+            let error = error.to_string();
+            assert!(error.contains(variable), "{}", error);
+            assert!(error.contains(&format!("expected {}", expected_type)), "{}", error);
+            assert!(error.contains(&format!("'{}'", value)), "{}", error);
+        }
+    }
+
+    #[test]
+    fn parses_environments() {
+        for (value, environment) in [
+            ("dev", Environment::Dev),
+            ("staging", Environment::Staging),
+            ("prod", Environment::Prod),
+            ("NonProduction", Environment::Dev),
+            ("Production", Environment::Prod)
+        ] {
+            assert_eq!(app_config(&format!("environment: {}", value)).environment(), environment);
+        }
+
+        assert_eq!(app_config("{}").environment(), Environment::Prod);
+        assert!(serde_yaml::from_str::<Environment>("qa").is_err());
+    }
+
+    #[test]
+    fn merges_selected_profile_over_base() {
+        let mut config: Value = serde_yaml::from_str("
+            environment: dev
+            triton_config: { url: 'http://localhost:8001', models_dir: /models }
+            profiles:
+              prod:
+                environment: prod
+                triton_config: { url: 'http://triton:8001' }
+        ").unwrap();
+
+        AppConfig::apply_profile(&mut config, Some("prod")).unwrap();
+
+        let expected: Value = serde_yaml::from_str("
+            environment: prod
+            triton_config: { url: 'http://triton:8001', models_dir: /models }
+        ").unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn removes_profiles_without_selected_profile() {
+        let mut config: Value = serde_yaml::from_str("
+            environment: dev
+            profiles: { prod: { environment: prod } }
+        ").unwrap();
+
+        AppConfig::apply_profile(&mut config, None).unwrap();
+
+        assert_eq!(config, serde_yaml::from_str::<Value>("environment: dev").unwrap());
+    }
+
+    #[test]
+    fn rejects_unknown_profile() {
+        let mut config: Value = serde_yaml::from_str("profiles: { prod: { environment: prod } }").unwrap();
+
+        let error = AppConfig::apply_profile(&mut config, Some("qa")).unwrap_err();
+
+        assert!(error.to_string().contains("'qa'"));
+    }
+
+    #[test]
+    fn accepts_base_config() {
+        app_config("{}").validate().unwrap();
+    }
+
+    #[test]
+    fn fills_defaults_of_minimal_config() {
+        let mut config: AppConfig = serde_yaml::from_str("
+            sources_config:
+              ids: [1]
+            triton_config:
+              url: 'http://localhost:8001'
+            inference_config: { models: { YOLO: { name: yolo } } }
+        ").unwrap();
+        config.triton_config.normalize_endpoints();
+        for (model_type, model_config) in config.inference_config.models.iter_mut() {
+            model_config.fill_defaults(model_type);
+        }
+        config.validate().unwrap();
+
+        let sources = config.sources_config.resolve_sources(&config.inference_config).unwrap();
+        let source = &sources["1"];
+        assert_eq!(source.inf_frame, 1);
+        assert_eq!(source.conf_threshold, 0.25);
+        assert_eq!(source.nms_iou_threshold, 0.45);
+        assert_eq!(source.pipeline, vec![InferenceModelType::YOLO]);
+        assert!(!source.combined_results);
+
+        let model = &config.inference_config.models[&InferenceModelType::YOLO];
+        assert_eq!(model.version, 1);
+        assert_eq!(model.input_name, "images");
+        assert_eq!(model.input_shape, InferenceModelType::YOLO.default_input_shape());
+        assert_eq!((model.batch_max_size, model.batch_max_queue_delay), (8, 1000));
+        assert_eq!(model.batch_preferred_sizes, vec![2, 4, 8]);
+
+        assert_eq!(config.triton_config.endpoints[0].url, "http://localhost:8001");
+        assert_eq!(config.kafka_config.brokers, "localhost:9092");
+        assert_eq!(config.kafka_config.acks, "all");
+        assert_eq!(config.kafka_config.dead_letter_max_attempts, 10);
+        assert_eq!(config.gpu_indices, vec![0]);
+    }
+
+    #[test]
+    fn reports_every_invalid_field_at_once() {
+        let error = app_config("
+            sources_config:
+              ids: ['1', ' ', '1']
+              default: { conf_threshold: 1.5, nms_iou_threshold: -0.1, inf_frame: 0 }
+              custom: { 1: { conf_threshold: -0.2 } }
+            inference_config: { models: { YOLO: { name: yolo, batch_max_size: 0 } } }
+            outputs_config: { subscription_capacity: 0 }
+            kafka_config: { topic_results: '' }
+            overlap_similarity: 1.5
+        ").validate().unwrap_err();
+        let error = format!("{:#}", error);
+
+        let expected = [
+            "sources_config.ids[1]: must not be empty",
+            "sources_config.ids[2]: source '1' is listed more than once",
+            "sources_config.default.inf_frame: 0 must be between 1 and 30",
+            "sources_config.default.conf_threshold: 1.5 must be between 0 and 1",
+            "sources_config.default.nms_iou_threshold: -0.1 must be between 0 and 1",
+            "sources_config.custom.1.conf_threshold: -0.2 must be between 0 and 1",
+            "inference_config.models.YOLO.batch_max_size: must be at least 1",
+            // Preferred sizes are derived from the maximum size
+            "inference_config.models.YOLO.batch_preferred_sizes: at least one preferred batch size is required",
+            "outputs_config.subscription_capacity: must be at least 1",
+            "kafka_config.topic_results: must not be empty when Kafka output is enabled",
+            "overlap_similarity: 1.5 must be greater than 0 and at most 1"
+        ];
+        for violation in expected {
+            assert!(error.contains(violation), "missing '{}' in {}", violation, error);
+        }
+        assert!(error.contains(&format!("Found {} invalid configuration fields", expected.len())), "{}", error);
+    }
+
+    #[test]
+    fn admin_server_defaults_to_loopback() {
+        let config = app_config("{}");
+
+        assert!(config.admin_config().is_loopback());
+        assert!(config.admin_config().token.is_none());
+    }
+
+    #[test]
+    fn requires_admin_token_outside_loopback() {
+        let error = app_config("admin_config: { host: 0.0.0.0 }").validate().unwrap_err();
+        assert!(format!("{:#}", error).contains("admin_config.token"));
+
+        app_config("admin_config: { host: 0.0.0.0, token: secret }").validate().unwrap();
+        app_config("admin_config: { host: 0.0.0.0, enabled: false }").validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_motion_gates_passing_every_frame() {
+        let error = app_config("
+            sources_config:
+              default: { motion_gate: { threshold: 0, max_idle_secs: 5 } }
+              custom: { 1: { motion_gate: { threshold: 300, max_idle_secs: 0 } } }
+        ").validate().unwrap_err();
+        let error = format!("{:#}", error);
+
+        assert!(error.contains("sources_config.default.motion_gate.threshold"));
+        assert!(error.contains("sources_config.custom.1.motion_gate.threshold"));
+        assert!(error.contains("sources_config.custom.1.motion_gate.max_idle_secs"));
+
+        app_config("sources_config: { default: { motion_gate: { threshold: 4.5, max_idle_secs: 5 } } }")
+            .validate()
+            .unwrap();
+    }
+
+    #[test]
+    fn rejects_unix_socket_triton_urls() {
+        let error = app_config("triton_config: { url: 'unix:///run/triton.sock' }")
+            .validate()
+            .unwrap_err();
+
+        assert!(format!("{:#}", error).contains("triton_config.endpoints[0].url"));
+    }
+
+    #[test]
+    fn reloads_sources_without_restart() {
+        let current = app_config("{}");
+        let reloaded = app_config("sources_config: { ids: [1, 2], default: { conf_threshold: 0.7 }, custom: { 2: { conf_threshold: 0.4 } } }");
+
+        assert!(current.immutable_changes(&reloaded).is_empty());
+
+        let sources = reloaded.sources_config().resolve_sources(reloaded.inference_config()).unwrap();
+        assert_eq!(sources["1"].conf_threshold, 0.7);
+        assert_eq!(sources["2"].conf_threshold, 0.4);
+    }
+
+    #[test]
+    fn lists_changes_requiring_restart() {
+        let current = app_config("{}");
+        let reloaded = app_config("
+            triton_config: { url: 'http://triton:8001' }
+            inference_config: { models: { YOLO: { name: yolo-v2 } } }
+            gpu_indices: [1]
+        ");
+
+        assert_eq!(current.immutable_changes(&reloaded), vec![
+            "inference_config.models.YOLO.name",
+            "gpu_indices",
+            "triton_config.endpoints"
+        ]);
+    }
+
+    #[test]
+    fn lists_stream_changes_of_running_sources() {
+        let with_sources = |overrides: &str| {
+            let mut config = app_config(overrides);
+            config.sources_config.sources = config.sources_config.resolve_sources(&config.inference_config).unwrap();
+            config
+        };
+        let current = with_sources("sources_config: { ids: [1, 2, 3] }");
+        let reloaded = with_sources("
+            sources_config:
+              ids: [1, 2, 3, 4]
+              custom:
+                1: { video_id: 9 }
+                2: { shared_memory: { path: /dev/shm/camera-2 } }
+                3: { conf_threshold: 0.5, depth_filter: { depth_source_id: '4', max_distance_m: 10.0, depth_scale: 0.001 } }
+        ");
+
+        assert_eq!(current.immutable_changes(&reloaded), vec![
+            "sources_config.sources.1.video_id",
+            "sources_config.sources.2.shared_memory"
+        ]);
+    }
+
+    #[test]
+    fn rejects_empty_and_duplicate_gpu_indices() {
+        let error = app_config("gpu_indices: []").validate().unwrap_err();
+        assert!(format!("{:#}", error).contains("gpu_indices: must list at least one GPU"));
+
+        let error = app_config("gpu_indices: [0, 1, 0]").validate().unwrap_err();
+        assert!(format!("{:#}", error).contains("gpu_indices[2]: GPU 0 is listed more than once"));
+    }
+
+    #[test]
+    fn rejects_config_without_sources() {
+        let error = app_config("sources_config: { ids: [] }").validate().unwrap_err();
+
+        assert!(format!("{:#}", error).contains("sources_config.ids: must list at least one source"));
+    }
+
+    const ENDPOINTS: &str = "triton_config: { url: null, endpoints: [{ name: a, url: 'http://a:8001' }, { name: b, url: 'http://b:8001' }] }";
+
+    #[test]
+    fn moves_single_url_into_default_endpoint() {
+        let triton_config = &app_config("{}").triton_config;
+
+        assert_eq!(triton_config.endpoints, vec![TritonEndpoint {
+            name: TritonConfig::DEFAULT_ENDPOINT.to_string(),
+            url: "http://localhost:8001".to_string()
+        }]);
+        assert_eq!(triton_config.url, None);
+    }
+
+    #[test]
+    fn overrides_url_of_primary_endpoint() {
+        let mut triton_config = app_config(ENDPOINTS).triton_config;
+
+        triton_config.set_primary_url("http://override:8001");
+
+        assert_eq!(triton_config.endpoints[0].url, "http://override:8001");
+        assert_eq!(triton_config.endpoints[1].url, "http://b:8001");
+    }
+
+    #[test]
+    fn orders_model_endpoints_by_preference() {
+        let config = app_config(&format!(
+            "{}\ninference_config: {{ models: {{ YOLO: {{ endpoint: b, failover_endpoints: [b, a] }} }} }}",
+            ENDPOINTS
+        ));
+        let endpoint_names = |model_type: InferenceModelType| -> Vec<String> {
+            let model_config = &config.inference_config.models[&model_type];
+            config.triton_config.model_endpoints(model_config)
+                .unwrap()
+                .iter()
+                .map(|endpoint| endpoint.name.clone())
+                .collect()
+        };
+
+        assert_eq!(endpoint_names(InferenceModelType::YOLO), vec!["b", "a"]);
+        assert_eq!(endpoint_names(InferenceModelType::DINO), vec!["a"], "first endpoint by default");
+    }
+
+    #[test]
+    fn reports_unknown_model_endpoints() {
+        let error = app_config(&format!(
+            "{}\ninference_config: {{ models: {{ DINO: {{ failover_endpoints: [c] }} }} }}",
+            ENDPOINTS
+        )).validate().unwrap_err();
+
+        assert!(format!("{:#}", error).contains("inference_config.models.DINO: Triton endpoint 'c' is not configured"));
+    }
+
+    #[test]
+    fn finds_nested_deprecated_fields() {
+        let config: Value = serde_yaml::from_str(BASE_CONFIG).unwrap();
+
+        let deprecations = AppConfig::find_deprecations(&config, None);
+
+        assert_eq!(deprecations, vec![("triton_config.url".to_string(), "triton_config.endpoints".to_string())]);
+    }
+
+    #[test]
+    fn resolves_numeric_and_named_sources() {
+        let sources = sources_config("
+            ids: [7, lobby-east]
+            custom: { lobby-east: { video_id: 12 } }
+        ").resolve_sources(&inference_config()).unwrap();
+
+        assert_eq!(sources["7"].video_id, None);
+        assert_eq!(sources["lobby-east"].video_id, Some(12));
+    }
+
+    #[test]
+    fn named_sources_require_video_id() {
+        let error = sources_config("ids: [7, lobby-east]")
+            .resolve_sources(&inference_config())
+            .unwrap_err();
+
+        assert!(error.to_string().contains("lobby-east"));
+    }
+
+    #[test]
+    fn accepts_single_and_two_stage_pipelines() {
+        use InferenceModelType::{DINO, YOLO};
+
+        AppConfig::validate_pipeline(&[YOLO], &inference_config()).unwrap();
+        AppConfig::validate_pipeline(&[YOLO, DINO], &inference_config()).unwrap();
+    }
+
+    #[test]
+    fn rejects_misconfigured_pipelines() {
+        use InferenceModelType::{DINO, YOLO};
+
+        let error = AppConfig::validate_pipeline(&[DINO], &inference_config()).unwrap_err();
+        assert!(error.to_string().contains("requires a YOLO stage"));
+
+        let error = AppConfig::validate_pipeline(&[DINO, YOLO], &inference_config()).unwrap_err();
+        assert!(error.to_string().contains("requires a YOLO stage"));
+
+        let yolo_only: InferenceConfig = serde_yaml::from_str("models: { YOLO: { name: yolo } }").unwrap();
+        let error = AppConfig::validate_pipeline(&[YOLO, DINO], &yolo_only).unwrap_err();
+        assert!(error.to_string().contains("DINO is not configured"));
+    }
+
+    #[test]
+    fn resolves_pipelines_per_source() {
+        let sources = sources_config("
+            ids: [1, 2, 3]
+            default: { pipeline: [YOLO] }
+            custom: { 2: { pipeline: [YOLO, DINO] }, 3: { pipeline: [] } }
+        ").resolve_sources(&inference_config()).unwrap();
+
+        assert_eq!(sources["1"].pipeline, vec![InferenceModelType::YOLO]);
+        assert_eq!(sources["2"].pipeline, vec![InferenceModelType::YOLO, InferenceModelType::DINO]);
+        assert_eq!(sources["3"].pipeline, vec![InferenceModelType::YOLO]);
+    }
+
+    #[test]
+    fn resolves_dedup_max_skips_per_source() {
+        let sources = sources_config("
+            ids: [1, 2]
+            default: { dedup_max_skips: 25 }
+            custom: { 2: { dedup_max_skips: 5 } }
+        ").resolve_sources(&inference_config()).unwrap();
+
+        assert_eq!(sources["1"].dedup_max_skips, Some(25));
+        assert_eq!(sources["2"].dedup_max_skips, Some(5));
+    }
+
+    #[test]
+    fn reports_misconfigured_source_pipeline() {
+        let error = app_config("sources_config: { ids: [1], custom: { 1: { pipeline: [DINO] } } }")
+            .validate()
+            .unwrap_err();
+
+        assert!(format!("{:#}", error).contains("sources_config.custom.1.pipeline"));
+    }
+
+    #[test]
+    fn rejects_sources_streaming_the_same_video() {
+        let result = sources_config("
+            ids: [7, lobby-east]
+            custom: { lobby-east: { video_id: 7 } }
+        ").resolve_sources(&inference_config());
+
+        assert!(result.is_err());
+    }
+}
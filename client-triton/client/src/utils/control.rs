@@ -0,0 +1,247 @@
+//! Responsible for runtime control of the application through a Kafka topic
+//!
+//! Commands are JSON messages, e.g. `{"cmd":"pause","source_id":"7"}`.
+//! Every applied command is logged with its offset and acknowledged to the responses topic, when set.
+//! Unknown or malformed commands are ignored with a warning
+
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::Message;
+use serde::Deserialize;
+use serde_json::json;
+use anyhow::{Result, Context};
+
+// Custom modules
+use crate::source;
+use crate::utils::config::AppConfig;
+use crate::utils::kafka;
+use crate::utils::hot_reload;
+
+/// Commands accepted on the control topic
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum ControlCommand {
+    Pause { source_id: String },
+    Resume { source_id: String },
+    SetConfThreshold { source_id: String, value: f32 },
+    ReloadConfig
+}
+
+impl ControlCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControlCommand::Pause { .. } => "pause",
+            ControlCommand::Resume { .. } => "resume",
+            ControlCommand::SetConfThreshold { .. } => "set_conf_threshold",
+            ControlCommand::ReloadConfig => "reload_config"
+        }
+    }
+
+    pub fn source_id(&self) -> Option<&str> {
+        match self {
+            ControlCommand::Pause { source_id }
+            | ControlCommand::Resume { source_id }
+            | ControlCommand::SetConfThreshold { source_id, .. } => Some(source_id),
+            ControlCommand::ReloadConfig => None
+        }
+    }
+
+    /// Applies the command to the running application
+    pub async fn apply(&self, profile: Option<&str>) -> Result<()> {
+        match self {
+            ControlCommand::Pause { source_id } => {
+                source::get_source_processor(source_id).await?.set_paused(true);
+            },
+            ControlCommand::Resume { source_id } => {
+                source::get_source_processor(source_id).await?.set_paused(false);
+            },
+            ControlCommand::SetConfThreshold { source_id, value } => {
+                source::get_source_processor(source_id).await?.set_conf_threshold(*value)?;
+            },
+            ControlCommand::ReloadConfig => {
+                hot_reload::reload_config(profile).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Starts consuming the control topic in the background, when configured
+pub fn start_control_consumer(app_config: &AppConfig) -> Result<()> {
+    let kafka_config = app_config.kafka_config();
+    if !kafka_config.enabled {
+        return Ok(())
+    }
+    let Some(topic_control) = kafka_config.topic_control.clone() else {
+        return Ok(())
+    };
+
+    // Only commands sent while running are applied
+    let mut client_config = ClientConfig::new();
+    client_config
+        .set("bootstrap.servers", &kafka_config.brokers)
+        .set("group.id", &kafka_config.control_group_id)
+        .set("enable.auto.commit", "true")
+        .set("auto.offset.reset", "latest");
+
+    let security_properties = kafka_config.security_properties()
+        .context("Invalid Kafka security settings")?;
+    for (key, value) in security_properties {
+        client_config.set(key, value);
+    }
+
+    let consumer: StreamConsumer = client_config
+        .create()
+        .context("Failed to create Kafka control consumer")?;
+
+    consumer.subscribe(&[&topic_control])
+        .with_context(|| format!("Failed to subscribe to control topic '{}'", topic_control))?;
+
+    let profile = app_config.profile().map(|profile| profile.to_string());
+    let topic_responses = kafka_config.topic_control_responses.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (payload, partition, offset) = match consumer.recv().await {
+                Ok(message) => (
+                    message.payload().map(|payload| payload.to_vec()),
+                    message.partition(),
+                    message.offset()
+                ),
+                Err(e) => {
+                    tracing::warn!(
+                        error=e.to_string(),
+                        "Error receiving control command"
+                    );
+                    continue;
+                }
+            };
+
+            let command = match payload.as_deref().map(serde_json::from_slice::<ControlCommand>) {
+                Some(Ok(command)) => command,
+                Some(Err(e)) => {
+                    tracing::warn!(
+                        error=e.to_string(),
+                        partition=partition,
+                        offset=offset,
+                        "Ignoring unknown or malformed control command"
+                    );
+                    continue;
+                },
+                None => continue
+            };
+
+            let result = command.apply(profile.as_deref()).await;
+            match &result {
+                Ok(_) => tracing::info!(
+                    cmd=command.name(),
+                    source_id=command.source_id(),
+                    partition=partition,
+                    offset=offset,
+                    "Applied control command"
+                ),
+                Err(e) => tracing::warn!(
+                    cmd=command.name(),
+                    source_id=command.source_id(),
+                    partition=partition,
+                    offset=offset,
+                    error=format!("{:#}", e),
+                    "Failed to apply control command"
+                )
+            }
+
+            if let Some(topic_responses) = &topic_responses {
+                if let Err(e) = acknowledge(topic_responses, &command, partition, offset, &result).await {
+                    tracing::warn!(
+                        error=format!("{:#}", e),
+                        "Failed to acknowledge control command"
+                    );
+                }
+            }
+        }
+    });
+
+    tracing::info!(topic=topic_control, "Consuming control commands");
+
+    Ok(())
+}
+
+/// Produces the outcome of a command to the responses topic
+async fn acknowledge(
+    topic: &str,
+    command: &ControlCommand,
+    partition: i32,
+    offset: i64,
+    result: &Result<()>
+) -> Result<()> {
+    let ack = json!({
+        "cmd": command.name(),
+        "source_id": command.source_id(),
+        "partition": partition,
+        "offset": offset,
+        "success": result.is_ok(),
+        "error": result.as_ref().err().map(|e| format!("{:#}", e))
+    });
+
+    kafka::get_kafka_producer()?
+        .produce(topic, command.name(), &ack.to_string())
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(payload: &str) -> serde_json::Result<ControlCommand> {
+        serde_json::from_str(payload)
+    }
+
+    #[test]
+    fn parses_commands() {
+        let command = parse(r#"{"cmd": "pause", "source_id": "1"}"#).unwrap();
+        assert!(matches!(&command, ControlCommand::Pause { source_id } if source_id == "1"));
+        assert_eq!((command.name(), command.source_id()), ("pause", Some("1")));
+
+        let command = parse(r#"{"cmd": "resume", "source_id": "lobby"}"#).unwrap();
+        assert!(matches!(&command, ControlCommand::Resume { source_id } if source_id == "lobby"));
+        assert_eq!((command.name(), command.source_id()), ("resume", Some("lobby")));
+
+        let command = parse(r#"{"cmd": "set_conf_threshold", "source_id": "1", "value": 0.4}"#).unwrap();
+        assert!(matches!(&command, ControlCommand::SetConfThreshold { source_id, value } if source_id == "1" && *value == 0.4));
+        assert_eq!((command.name(), command.source_id()), ("set_conf_threshold", Some("1")));
+
+        let command = parse(r#"{"cmd": "reload_config"}"#).unwrap();
+        assert!(matches!(command, ControlCommand::ReloadConfig));
+        assert_eq!((command.name(), command.source_id()), ("reload_config", None));
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        assert!(parse(r#"{"cmd": "restart", "source_id": "1"}"#).is_err());
+        assert!(parse(r#"{"cmd": "Pause", "source_id": "1"}"#).is_err());
+        assert!(parse(r#"{"source_id": "1"}"#).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_commands() {
+        for payload in [
+            r#"{"cmd": "pause"}"#,
+            r#"{"cmd": "pause", "source_id": 1}"#,
+            r#"{"cmd": "set_conf_threshold", "source_id": "1"}"#,
+            r#"{"cmd": "set_conf_threshold", "source_id": "1", "value": "high"}"#,
+            r#"{"cmd": "pause", "source_id": null}"#,
+            "pause 1",
+            ""
+        ] {
+            assert!(parse(payload).is_err(), "{}", payload);
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_commands_of_unknown_sources() {
+        let command = parse(r#"{"cmd": "pause", "source_id": "missing"}"#).unwrap();
+
+        assert!(command.apply(None).await.is_err());
+    }
+}
@@ -0,0 +1,341 @@
+//! Responsible for reading GPU statistics from the available vendor backend
+//!
+//! NVIDIA GPUs are read through NVML, AMD GPUs through the amdgpu sysfs interface
+//! used by rocm-smi. The backend is selected at runtime by what the machine provides
+
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use nvml_wrapper::Nvml;
+
+// Custom modules
+use crate::utils::GPUStats;
+
+/// Directory of DRM devices exposed by the kernel
+const DRM_DEVICES_DIR: &str = "/sys/class/drm";
+
+/// PCI vendor id of AMD devices
+const AMD_VENDOR_ID: &str = "0x1002";
+
+/// Source of statistics of all GPUs of a single vendor
+pub trait GpuMonitor: Send {
+    /// Name of the backend, for logging
+    fn backend(&self) -> &'static str;
+
+    /// Returns the amount of GPUs of the backend, indexed from 0
+    fn device_count(&self) -> Result<u32>;
+
+    /// Returns statistics about a single GPU by its index
+    fn device_statistics(&self, index: u32) -> Result<GPUStats>;
+
+    /// Returns statistics about the GPUs with the given indices
+    fn statistics(&self, indices: &[u32]) -> Result<Vec<GPUStats>> {
+        indices
+            .iter()
+            .map(|&index| self.device_statistics(index))
+            .collect()
+    }
+
+    /// Returns the name of a GPU by its index
+    fn gpu_name(&self, index: u32) -> Result<String> {
+        Ok(self.device_statistics(index)?.name)
+    }
+
+    /// Fails when any of the indices has no GPU, listing the available GPUs
+    fn validate_indices(&self, indices: &[u32]) -> Result<()> {
+        let device_count = self.device_count()?;
+        let missing: Vec<String> = indices
+            .iter()
+            .filter(|&&index| index >= device_count)
+            .map(|index| index.to_string())
+            .collect();
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let available: Vec<String> = (0..device_count)
+            .map(|index| match self.gpu_name(index) {
+                Ok(name) => format!("{} ({})", index, name),
+                Err(_) => index.to_string()
+            })
+            .collect();
+
+        anyhow::bail!(
+            "GPU indices {} are not available, {} GPUs are available: [{}]",
+            missing.join(", "),
+            device_count,
+            available.join(", ")
+        )
+    }
+}
+
+/// Returns the monitor of the first available backend - NVML first, then ROCm
+pub fn detect_gpu_monitor() -> Option<Box<dyn GpuMonitor>> {
+    match NvmlMonitor::new() {
+        Ok(monitor) => return Some(Box::new(monitor)),
+        Err(e) => {
+            tracing::debug!(error=format!("{:#}", e), "NVML GPU monitoring is unavailable");
+        }
+    }
+
+    match RocmMonitor::new() {
+        Ok(monitor) => return Some(Box::new(monitor)),
+        Err(e) => {
+            tracing::debug!(error=format!("{:#}", e), "ROCm GPU monitoring is unavailable");
+        }
+    }
+
+    None
+}
+
+/// Returns memory usage in percent of the total memory
+fn memory_percent(used: u64, total: u64) -> u32 {
+    if total > 0 {
+        (used as f32 * 100.0 / total as f32) as u32
+    } else {
+        0
+    }
+}
+
+/// Statistics of NVIDIA GPUs, read through NVML
+pub struct NvmlMonitor {
+    nvml: Nvml
+}
+
+impl NvmlMonitor {
+    pub fn new() -> Result<Self> {
+        let nvml = Nvml::init()
+            .context("Error initiating NVML wrapper")?;
+
+        Ok(Self { nvml })
+    }
+}
+
+impl GpuMonitor for NvmlMonitor {
+    fn backend(&self) -> &'static str {
+        "nvml"
+    }
+
+    fn device_count(&self) -> Result<u32> {
+        self.nvml.device_count()
+            .context("Error getting GPU device count")
+    }
+
+    fn device_statistics(&self, index: u32) -> Result<GPUStats> {
+        let device = self.nvml.device_by_index(index)
+            .with_context(|| format!("Error getting GPU ID {} device", index))?;
+
+        // GPU general information
+        let gpu_name = device.name()
+            .context("Error getting GPU name")?;
+        let gpu_uuid = device.uuid()
+            .unwrap_or("".to_string());
+        let gpu_serial = device.serial()
+            .unwrap_or("".to_string());
+
+        // GPU memory information
+        let memory_info = device.memory_info()
+            .context("Error getting GPU memory information")?;
+        let gpu_memory_total = memory_info.total / 1024 / 1024;
+        let gpu_memory_used = memory_info.used / 1024 / 1024;
+        let gpu_memory_free = memory_info.free / 1024 / 1024;
+
+        // GPU performance information
+        let utilization = device.utilization_rates()
+            .context("Error getting GPU utilization information")?;
+
+        Ok(
+            GPUStats {
+                index,
+                name: gpu_name,
+                uuid: gpu_uuid,
+                serial: gpu_serial,
+                memory_total: gpu_memory_total,
+                memory_used: gpu_memory_used,
+                memory_free: gpu_memory_free,
+                util_perc: utilization.gpu,
+                memory_perc: memory_percent(gpu_memory_used, gpu_memory_total)
+            }
+        )
+    }
+}
+
+/// Statistics of AMD GPUs, read from the amdgpu sysfs interface
+pub struct RocmMonitor {
+    devices: Vec<PathBuf>
+}
+
+impl RocmMonitor {
+    /// Finds AMD GPUs exposing utilization information, failing when there are none
+    pub fn new() -> Result<Self> {
+        let entries = std::fs::read_dir(DRM_DEVICES_DIR)
+            .with_context(|| format!("Error reading {}", DRM_DEVICES_DIR))?;
+
+        let mut devices: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                // Cards only, not their connectors (e.g. card0-DP-1)
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                name.len() > 4 && name.starts_with("card") && name[4..].chars().all(|c| c.is_ascii_digit())
+            })
+            .map(|entry| entry.path().join("device"))
+            .filter(|device| {
+                RocmMonitor::read_value(device, "vendor").is_ok_and(|vendor| vendor == AMD_VENDOR_ID)
+                    && device.join("gpu_busy_percent").is_file()
+            })
+            .collect();
+
+        if devices.is_empty() {
+            anyhow::bail!("No AMD GPU devices found");
+        }
+        devices.sort();
+
+        Ok(Self { devices })
+    }
+
+    /// Reads a single trimmed value of a device file
+    fn read_value(device: &Path, file: &str) -> Result<String> {
+        let value = std::fs::read_to_string(device.join(file))
+            .with_context(|| format!("Error reading {}", device.join(file).display()))?;
+
+        Ok(value.trim().to_string())
+    }
+
+    /// Reads a numeric value of a device file
+    fn read_number(device: &Path, file: &str) -> Result<u64> {
+        RocmMonitor::read_value(device, file)?
+            .parse()
+            .with_context(|| format!("Invalid number in {}", device.join(file).display()))
+    }
+
+    /// Returns statistics about a single AMD GPU by its index and sysfs device directory
+    fn read_statistics(index: u32, device: &Path) -> Result<GPUStats> {
+        // GPU general information - product name is only exposed on some devices
+        let gpu_name = RocmMonitor::read_value(device, "product_name")
+            .ok()
+            .filter(|name| !name.is_empty())
+            .unwrap_or("AMD GPU".to_string());
+        let gpu_uuid = RocmMonitor::read_value(device, "unique_id")
+            .unwrap_or("".to_string());
+        let gpu_serial = RocmMonitor::read_value(device, "serial_number")
+            .unwrap_or("".to_string());
+
+        // GPU memory information, reported in bytes
+        let gpu_memory_total = RocmMonitor::read_number(device, "mem_info_vram_total")
+            .context("Error getting GPU memory information")? / 1024 / 1024;
+        let gpu_memory_used = RocmMonitor::read_number(device, "mem_info_vram_used")
+            .context("Error getting GPU memory information")? / 1024 / 1024;
+
+        // GPU performance information
+        let gpu_util = RocmMonitor::read_number(device, "gpu_busy_percent")
+            .context("Error getting GPU utilization information")?;
+
+        Ok(
+            GPUStats {
+                index,
+                name: gpu_name,
+                uuid: gpu_uuid,
+                serial: gpu_serial,
+                memory_total: gpu_memory_total,
+                memory_used: gpu_memory_used,
+                memory_free: gpu_memory_total.saturating_sub(gpu_memory_used),
+                util_perc: gpu_util as u32,
+                memory_perc: memory_percent(gpu_memory_used, gpu_memory_total)
+            }
+        )
+    }
+}
+
+impl GpuMonitor for RocmMonitor {
+    fn backend(&self) -> &'static str {
+        "rocm"
+    }
+
+    /// Cards are indexed in order of their DRM card number
+    fn device_count(&self) -> Result<u32> {
+        Ok(self.devices.len() as u32)
+    }
+
+    fn device_statistics(&self, index: u32) -> Result<GPUStats> {
+        let device = self.devices
+            .get(index as usize)
+            .with_context(|| format!("Error getting GPU ID {} device", index))?;
+
+        RocmMonitor::read_statistics(index, device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Backend with the given amount of GPUs, named by their index
+    struct FakeMonitor(u32);
+
+    impl GpuMonitor for FakeMonitor {
+        fn backend(&self) -> &'static str {
+            "fake"
+        }
+
+        fn device_count(&self) -> Result<u32> {
+            Ok(self.0)
+        }
+
+        fn device_statistics(&self, index: u32) -> Result<GPUStats> {
+            anyhow::ensure!(index < self.0, "Error getting GPU ID {} device", index);
+
+            Ok(GPUStats {
+                index,
+                name: format!("GPU {}", index),
+                uuid: String::new(),
+                serial: String::new(),
+                memory_total: 0,
+                memory_used: 0,
+                memory_free: 0,
+                util_perc: 0,
+                memory_perc: 0
+            })
+        }
+    }
+
+    #[test]
+    fn reads_statistics_of_selected_gpus() {
+        let stats = FakeMonitor(4).statistics(&[3, 1]).unwrap();
+
+        assert_eq!(stats.iter().map(|stats| stats.index).collect::<Vec<_>>(), vec![3, 1]);
+        assert_eq!(FakeMonitor(4).gpu_name(2).unwrap(), "GPU 2");
+    }
+
+    #[test]
+    fn lists_available_gpus_of_missing_indices() {
+        FakeMonitor(2).validate_indices(&[0, 1]).unwrap();
+
+        let error = FakeMonitor(2).validate_indices(&[1, 2, 5]).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "GPU indices 2, 5 are not available, 2 GPUs are available: [0 (GPU 0), 1 (GPU 1)]"
+        );
+    }
+
+    #[test]
+    fn reads_amd_statistics_from_sysfs() {
+        let device = std::env::temp_dir().join(format!("client-amdgpu-{}", std::process::id()));
+        std::fs::create_dir_all(&device).unwrap();
+        for (file, value) in [
+            ("mem_info_vram_total", "8589934592\n"),
+            ("mem_info_vram_used", "2147483648\n"),
+            ("gpu_busy_percent", "37\n"),
+            ("unique_id", "abc123\n")
+        ] {
+            std::fs::write(device.join(file), value).unwrap();
+        }
+
+        let stats = RocmMonitor::read_statistics(1, &device).unwrap();
+
+        assert_eq!((stats.index, stats.name.as_str(), stats.uuid.as_str()), (1, "AMD GPU", "abc123"));
+        assert_eq!((stats.memory_total, stats.memory_used, stats.memory_free), (8192, 2048, 6144));
+        assert_eq!((stats.util_perc, stats.memory_perc), (37, 25));
+    }
+}
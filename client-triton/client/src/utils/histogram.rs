@@ -0,0 +1,185 @@
+//! Lightweight lock-free latency histogram, used for per-interval percentiles
+//!
+//! Values are counted in logarithmic buckets - every power of two is split into
+//! 4 linear sub-buckets, giving a resolution of 25% of the bucket's power of two
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Linear sub-buckets per power of two
+const SUB_BUCKETS: u64 = 4;
+const SUB_BUCKET_BITS: u32 = 2;
+
+/// Amount of buckets, covering values up to 2^40 (~12 days in microseconds)
+const BUCKETS: usize = 160;
+
+/// Returns the bucket index of a value
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+
+    let msb = 63 - value.leading_zeros();
+    let sub = (value >> (msb - SUB_BUCKET_BITS)) & (SUB_BUCKETS - 1);
+    let index = (msb as u64 - 1) * SUB_BUCKETS + sub;
+
+    (index as usize).min(BUCKETS - 1)
+}
+
+/// Returns the highest value counted in a bucket
+fn bucket_upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+
+    let msb = index / SUB_BUCKETS + 1;
+    let sub = index % SUB_BUCKETS;
+    let width = 1u64 << (msb - SUB_BUCKET_BITS as u64);
+    let lower = (SUB_BUCKETS + sub) * width;
+
+    lower + width - 1
+}
+
+/// Histogram of values recorded concurrently from many tasks
+pub struct LatencyHistogram {
+    buckets: Vec<AtomicU64>,
+    sum: AtomicU64,
+    max: AtomicU64
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0)
+        }
+    }
+
+    /// Records a single value
+    pub fn record(&self, value: u64) {
+        self.buckets[bucket_index(value)].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.max.fetch_max(value, Ordering::Relaxed);
+    }
+
+    /// Takes all recorded values, leaving the histogram empty.
+    /// Values recorded while draining are kept for the next drain
+    pub fn drain(&self) -> HistogramSnapshot {
+        let buckets: Vec<u64> = self.buckets
+            .iter()
+            .map(|bucket| bucket.swap(0, Ordering::Relaxed))
+            .collect();
+
+        // Count is derived from the drained buckets, so it always matches them
+        let count = buckets.iter().sum();
+
+        HistogramSnapshot {
+            buckets,
+            count,
+            sum: self.sum.swap(0, Ordering::Relaxed),
+            max: self.max.swap(0, Ordering::Relaxed)
+        }
+    }
+}
+
+/// Values drained from a histogram over an interval
+pub struct HistogramSnapshot {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: u64,
+    max: u64
+}
+
+impl HistogramSnapshot {
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            return 0.00;
+        }
+
+        self.sum as f64 / self.count as f64
+    }
+
+    /// Returns the value at the given percentile (0-100), accurate up to bucket resolution
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+
+        let rank = ((percentile / 100.00) * self.count as f64).ceil().max(1.00) as u64;
+        let mut seen = 0;
+
+        for (index, &bucket_count) in self.buckets.iter().enumerate() {
+            seen += bucket_count;
+            if seen >= rank {
+                return bucket_upper_bound(index).min(self.max);
+            }
+        }
+
+        self.max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_bound_values_within_resolution() {
+        for value in (0..100_000).chain([u32::MAX as u64, 1 << 39]) {
+            let upper = bucket_upper_bound(bucket_index(value));
+
+            assert!(upper >= value, "{} above its bucket bound {}", value, upper);
+            assert!(upper - value <= value / SUB_BUCKETS, "{} too far from its bucket bound {}", value, upper);
+        }
+    }
+
+    #[test]
+    fn reports_percentiles_within_resolution() {
+        let histogram = LatencyHistogram::new();
+        for value in 1..=1000 {
+            histogram.record(value);
+        }
+
+        let snapshot = histogram.drain();
+
+        assert_eq!(snapshot.count(), 1000);
+        assert_eq!(snapshot.max(), 1000);
+        assert_eq!(snapshot.mean(), 500.5);
+        for (percentile, expected) in [(50.0, 500), (95.0, 950), (99.0, 990)] {
+            let value = snapshot.percentile(percentile);
+            assert!(value >= expected && value <= expected + expected / 4, "p{} = {}", percentile, value);
+        }
+        assert_eq!(snapshot.percentile(100.0), 1000);
+    }
+
+    #[test]
+    fn caps_percentiles_at_max() {
+        let histogram = LatencyHistogram::new();
+        histogram.record(100);
+
+        assert_eq!(histogram.drain().percentile(50.0), 100);
+    }
+
+    #[test]
+    fn drain_empties_histogram() {
+        let histogram = LatencyHistogram::new();
+        histogram.record(42);
+        histogram.drain();
+
+        let snapshot = histogram.drain();
+
+        assert_eq!(snapshot.count(), 0);
+        assert_eq!(snapshot.max(), 0);
+        assert_eq!(snapshot.mean(), 0.00);
+        assert_eq!(snapshot.percentile(99.0), 0);
+    }
+}
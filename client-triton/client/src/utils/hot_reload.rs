@@ -0,0 +1,213 @@
+//! Responsible for applying configuration changes at runtime, without restarting streams
+//!
+//! The configuration is reloaded when the file changes or on a control command. A reloaded
+//! configuration is validated and compared against the running one - changes to fields that
+//! require a restart reject the whole reload. Accepted configurations are published as the
+//! current configuration and passed to every registered apply hook.
+//! Failed reloads leave the running configuration untouched
+
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use anyhow::{Result, Context};
+use arc_swap::ArcSwap;
+use futures::future::BoxFuture;
+use once_cell::sync::{Lazy, OnceCell};
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+// Custom modules
+use crate::utils::config::{AppConfig, CONFIG_PATH};
+use crate::utils::metrics;
+
+/// Applies a reloaded configuration to a single subsystem
+pub type ApplyHook = Box<dyn Fn(Arc<AppConfig>) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// Configuration currently applied to the application
+pub static CURRENT_CONFIG: OnceCell<ArcSwap<AppConfig>> = OnceCell::new();
+
+/// Hooks called with every accepted configuration, by subsystem name
+static APPLY_HOOKS: Lazy<RwLock<Vec<(&'static str, ApplyHook)>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Serializes reloads from the file watcher and control commands
+static RELOAD_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+pub fn get_current_config() -> Result<Arc<AppConfig>> {
+    Ok(
+        CURRENT_CONFIG
+            .get()
+            .context("Current configuration is not set")?
+            .load_full()
+    )
+}
+
+/// Registers a hook applying accepted configurations to a subsystem, in order of registration
+pub fn register_apply_hook<F>(name: &'static str, hook: F)
+where
+    F: Fn(Arc<AppConfig>) -> BoxFuture<'static, Result<()>> + Send + Sync + 'static
+{
+    APPLY_HOOKS.write().unwrap().push((name, Box::new(hook)));
+}
+
+/// Sets the configuration the application started with, and watches the configuration file when enabled
+pub fn init_hot_reload(app_config: Arc<AppConfig>) -> Result<()> {
+    let hot_reload_config = app_config.hot_reload_config().clone();
+    let profile = app_config.profile().map(|profile| profile.to_string());
+
+    CURRENT_CONFIG.set(ArcSwap::new(app_config))
+        .map_err(|_| anyhow::anyhow!("Current configuration is already set"))?;
+
+    if !hot_reload_config.enabled {
+        return Ok(());
+    }
+
+    tokio::spawn(async move {
+        let mut interval = interval(Duration::from_secs(hot_reload_config.poll_interval_secs.max(1)));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut last_modified = modified_time().await;
+
+        loop {
+            interval.tick().await;
+
+            let modified = modified_time().await;
+            if modified.is_none() || modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            // Reloads are logged with their outcome
+            let _ = reload_config(profile.as_deref()).await;
+        }
+    });
+
+    tracing::info!(path=CONFIG_PATH, "Watching configuration file for changes");
+
+    Ok(())
+}
+
+/// Reloads the configuration file, publishing and applying it when valid
+pub async fn reload_config(profile: Option<&str>) -> Result<()> {
+    let _guard = RELOAD_LOCK.lock().await;
+
+    let result = reload_config_internal(profile).await;
+    match &result {
+        Ok(_) => tracing::info!(path=CONFIG_PATH, "Applied reloaded configuration"),
+        Err(e) => tracing::warn!(
+            path=CONFIG_PATH,
+            error=format!("{:#}", e),
+            "Configuration was not reloaded, keeping the running configuration"
+        )
+    }
+
+    result
+}
+
+async fn reload_config_internal(profile: Option<&str>) -> Result<()> {
+    let profile = profile.map(|profile| profile.to_string());
+    let app_config = tokio::task::spawn_blocking(move || AppConfig::reload(profile.as_deref()))
+        .await
+        .context("Error joining configuration reload")?
+        .inspect_err(|_| metrics::CONFIG_RELOADS.with_label_values(&["failed"]).inc())
+        .context("Error reloading configuration")?;
+
+    apply_config(app_config).await
+}
+
+/// Publishes a reloaded configuration and passes it to every apply hook,
+/// unless it changes fields that require a restart
+async fn apply_config(app_config: AppConfig) -> Result<()> {
+    let current_config = CURRENT_CONFIG
+        .get()
+        .context("Current configuration is not set")?;
+
+    let immutable_changes = current_config.load().immutable_changes(&app_config);
+    if !immutable_changes.is_empty() {
+        metrics::CONFIG_RELOADS.with_label_values(&["rejected"]).inc();
+        anyhow::bail!("Fields cannot change without a restart: {}", immutable_changes.join(", "));
+    }
+
+    let app_config = Arc::new(app_config);
+    current_config.store(Arc::clone(&app_config));
+    metrics::CONFIG_RELOADS.with_label_values(&["applied"]).inc();
+
+    // Hooks are started without holding the lock across awaits
+    let applies: Vec<(&'static str, BoxFuture<'static, Result<()>>)> = APPLY_HOOKS
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(name, hook)| (*name, hook(Arc::clone(&app_config))))
+        .collect();
+
+    let mut failed: Vec<&'static str> = Vec::new();
+    for (name, apply) in applies {
+        if let Err(e) = apply.await {
+            tracing::error!(
+                subsystem=name,
+                error=format!("{:#}", e),
+                "Error applying reloaded configuration"
+            );
+            failed.push(name);
+        }
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!("Reloaded configuration was not fully applied to: {}", failed.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Returns the last modification time of the configuration file, if readable
+async fn modified_time() -> Option<SystemTime> {
+    tokio::fs::metadata(CONFIG_PATH)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn app_config(yaml: &str) -> AppConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[tokio::test]
+    async fn applies_accepted_configs_and_keeps_rejected_ones_out() {
+        let base = "
+            sources_config: { ids: [1] }
+            triton_config: { url: 'http://localhost:8001' }
+            inference_config: { models: { YOLO: { name: yolo } } }
+        ";
+        init_hot_reload(Arc::new(app_config(base))).unwrap();
+
+        let applied = Arc::new(Mutex::new(Vec::new()));
+        for name in ["sources", "failing"] {
+            let applied = Arc::clone(&applied);
+            register_apply_hook(name, move |app_config| {
+                let applied = Arc::clone(&applied);
+                Box::pin(async move {
+                    applied.lock().unwrap().push((name, app_config.sources_config().ids.clone()));
+                    anyhow::ensure!(name != "failing", "failing hook");
+                    Ok(())
+                })
+            });
+        }
+
+        // Accepted configurations are published, even when a hook fails to apply them
+        let error = apply_config(app_config(&base.replace("ids: [1]", "ids: [1, 2]"))).await.unwrap_err();
+        assert!(error.to_string().contains("failing"));
+        assert_eq!(get_current_config().unwrap().sources_config().ids, vec!["1", "2"]);
+        assert_eq!(*applied.lock().unwrap(), vec![
+            ("sources", vec!["1".to_string(), "2".to_string()]),
+            ("failing", vec!["1".to_string(), "2".to_string()])
+        ]);
+
+        // Rejected configurations are neither published nor applied
+        let changed_model = base.replace("ids: [1]", "ids: [3]").replace("name: yolo", "name: yolo-v2");
+        let error = apply_config(app_config(&changed_model)).await.unwrap_err();
+        assert!(error.to_string().contains("inference_config.models.YOLO.name"));
+        assert_eq!(get_current_config().unwrap().sources_config().ids, vec!["1", "2"]);
+        assert_eq!(applied.lock().unwrap().len(), 2);
+    }
+}
@@ -1,18 +1,31 @@
+use rdkafka::client::ClientContext;
 use rdkafka::config::ClientConfig;
-use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use rdkafka::statistics::Statistics;
 use rdkafka::util::Timeout;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use anyhow::{Context, Result};
 use tokio::sync::OnceCell;
-use std::sync::Arc;
-use rdkafka::message::ToBytes;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::{HashMap, HashSet, VecDeque};
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::{ToBytes, Header, OwnedHeaders};
+use tokio::sync::Notify;
+use serde::Serialize;
 
 // Custom modules
-use crate::utils::config::{KafkaConfig, AppConfig};
-use crate::processing::{ResultBBOX, ResultEmbedding, RawFrame};
+use crate::utils::config::{KafkaConfig, AppConfig, Serialization, Partitioning, InferenceModelType, KafkaUnavailablePolicy};
+use crate::inference;
+use crate::processing::{ResultBBOX, ResultEmbedding, RawFrame, FrameResults};
+use crate::source::StreamInfo;
+use crate::utils::metrics::{self, KafkaErrorKind};
+use crate::utils::protobuf::{self, MessageKind};
 
 // Variables
 pub static KAFKA_PRODUCER: OnceCell<Arc<Kafka>> = OnceCell::const_new();
+/// Version of the payloads layout, reported in message headers
+pub const SCHEMA_VERSION: u32 = 1;
 
 /// Returns the inference model instance, if initiated
 pub fn get_kafka_producer() -> Result<&'static Arc<Kafka>> {
@@ -23,95 +36,1654 @@ pub fn get_kafka_producer() -> Result<&'static Arc<Kafka>> {
     )
 }
 
+/// Returns whether results are published to Kafka, i.e. the producer was initiated
+pub fn is_kafka_enabled() -> bool {
+    KAFKA_PRODUCER.get().is_some()
+}
+
 /// Initiates a single instance of a model for inference
 pub async fn init_kafka_producer(app_config: &AppConfig) -> Result<()> {
     if let Ok(_) = get_kafka_producer() {
         anyhow::bail!("Kafka producer already initiated!")
     }
 
+    let kafka_config = app_config.kafka_config();
+    if !kafka_config.enabled {
+        tracing::info!("Kafka is disabled, results are not published to Kafka");
+        return Ok(());
+    }
+
+    // Register protobuf schemas of the topics of all sources, so payloads reference their schema
+    let mut schema_ids = HashMap::new();
+    if let (Serialization::Protobuf, Some(schema_registry_url)) = (kafka_config.serialization, &kafka_config.schema_registry_url) {
+        let topics: HashSet<String> = app_config.sources_config().sources
+            .iter()
+            .map(|(source_id, source_config)| kafka_config.source_topics(source_id, source_config.topic_override.as_ref()))
+            .flat_map(|topics| [topics.bboxes, topics.embedding, topics.results])
+            .collect();
+
+        for topic in topics {
+            let schema_id = protobuf::register_schema(schema_registry_url, &topic)
+                .await
+                .with_context(|| format!("Error registering protobuf schema of topic '{}'", topic))?;
+
+            tracing::info!(topic=topic, schema_id=schema_id, "Registered protobuf schema");
+            schema_ids.insert(topic, schema_id);
+        }
+    }
+
     // Create new instance
     let kafka_instance = Kafka::new(
-        app_config.kafka_config().clone()
+        kafka_config.clone(),
+        schema_ids
     )
         .context("Error creating new Kafka producer")?;
 
+    // Authentication errors and unreachable brokers would otherwise only show on the first produce
+    tokio::task::block_in_place(|| kafka_instance.verify_connection())?;
+
     // Set global variable
-    KAFKA_PRODUCER.set(Arc::new(kafka_instance))
+    let kafka_instance = Arc::new(kafka_instance);
+    KAFKA_PRODUCER.set(Arc::clone(&kafka_instance))
         .map_err(|_| anyhow::anyhow!("Error setting Kafka producer"))?;
 
+    // Retry messages that failed to produce in the background
+    let retry_instance = Arc::clone(&kafka_instance);
+    tokio::spawn(async move {
+        retry_instance.retry_buffered().await;
+    });
+
+    // Publish batches of frame results once they are due
+    if let Some(batching) = kafka_config.results_batching.clone() {
+        let batching_instance = Arc::clone(&kafka_instance);
+        tokio::spawn(async move {
+            batching_instance.flush_expired_batches(Duration::from_millis(batching.max_batch_ms.max(1))).await;
+        });
+    }
+
+    // Summarize delivery statistics periodically
+    if kafka_config.stats_interval_secs > 0 {
+        tokio::spawn(async move {
+            kafka_instance.report_stats().await;
+        });
+    }
+
     Ok(())
 }
 
+/// Metadata of a message attached as Kafka headers, so consumers can route without parsing payloads
+pub struct MessageHeaders<'a> {
+    pub source_id: &'a str,
+    pub pts: u64,
+    pub result_type: &'static str,
+    pub model_name: Option<&'a str>,
+    pub model_version: Option<u32>
+}
+
+impl MessageHeaders<'_> {
+    /// Returns headers as key-value pairs, completed with the schema and client versions,
+    /// and the schema registry id of protobuf payloads when registered
+    fn pairs(&self, schema_id: Option<u32>) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![
+            ("source_id", self.source_id.to_string()),
+            ("pts", self.pts.to_string()),
+            ("result_type", self.result_type.to_string())
+        ];
+        if let Some(model_name) = self.model_name {
+            pairs.push(("model_name", model_name.to_string()));
+        }
+        if let Some(model_version) = self.model_version {
+            pairs.push(("model_version", model_version.to_string()));
+        }
+        pairs.push(("schema_version", SCHEMA_VERSION.to_string()));
+        if let Some(schema_id) = schema_id {
+            pairs.push(("schema_id", schema_id.to_string()));
+        }
+        pairs.push(("client_version", env!("CARGO_PKG_VERSION").to_string()));
+
+        pairs
+    }
+}
+
+/// A message that failed to produce, kept for retrying
+struct BufferedMessage {
+    id: u64,
+    topic: String,
+    key: String,
+    payload: Vec<u8>,
+    headers: Vec<(&'static str, String)>,
+    attempts: u32
+}
+
+impl BufferedMessage {
+    fn size(&self) -> usize {
+        let headers_size: usize = self.headers
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum();
+
+        self.topic.len() + self.key.len() + self.payload.len() + headers_size
+    }
+}
+
+/// Failed messages in produce order, capped by their total size
+struct RetryBuffer {
+    messages: VecDeque<BufferedMessage>,
+    bytes: usize,
+    next_id: u64
+}
+
+/// Publishes all batched frame results, e.g. on shutdown or when a source stops.
+/// Only batches of the given source are published, when given
+pub async fn flush_result_batches(source_id: Option<&str>) -> Result<()> {
+    if !is_kafka_enabled() {
+        return Ok(());
+    }
+
+    get_kafka_producer()?
+        .flush_batches(|_| true, source_id)
+        .await;
+
+    Ok(())
+}
+
+/// Returns the last published embedding version of each model, none when Kafka is disabled
+pub fn embedding_versions() -> HashMap<String, u32> {
+    match get_kafka_producer() {
+        Ok(producer) => producer.embedding_versions.lock().unwrap().clone(),
+        Err(_) => HashMap::new()
+    }
+}
+
+/// Restores embedding versions published before a restart, so a version changed meanwhile
+/// still marks the following embeddings as requiring migration
+pub fn restore_embedding_versions(versions: HashMap<String, u32>) {
+    if let Ok(producer) = get_kafka_producer() {
+        let mut embedding_versions = producer.embedding_versions.lock().unwrap();
+        for (model_name, version) in versions {
+            embedding_versions.entry(model_name).or_insert(version);
+        }
+    }
+}
+
+/// Waits for messages queued in the producer to be delivered, up to the given timeout
+pub async fn flush_producer(timeout: Duration) -> Result<()> {
+    if !is_kafka_enabled() {
+        return Ok(());
+    }
+
+    let producer = get_kafka_producer()?;
+    tokio::task::spawn_blocking(move || producer.producer.flush(Timeout::After(timeout)))
+        .await
+        .context("Flush producer task failed")?
+        .context("Error flushing Kafka producer")
+}
+
+/// Combined results of frames of a source waiting to be published as a single message,
+/// along with whether each requires embedding migration
+struct ResultBatch {
+    frames: Vec<(Arc<FrameResults>, bool)>,
+    started: Instant
+}
+
+/// Returns the key and explicit partition of a message, as decided by the partitioning strategy
+fn record_routing(partitioning: Partitioning, key: &str) -> (Option<&str>, Option<i32>) {
+    match partitioning {
+        Partitioning::BySource => (Some(key), None),
+        Partitioning::Fixed(partition) => (Some(key), Some(partition as i32)),
+        Partitioning::RoundRobin => (None, None)
+    }
+}
+
+/// Latencies of a broker from the latest rdkafka statistics, in microseconds
+#[derive(Clone, Debug, Serialize)]
+pub struct BrokerLatency {
+    pub broker: String,
+    pub rtt_avg: i64,
+    pub rtt_p99: i64,
+    pub int_latency_avg: i64
+}
+
+/// Extracts latencies of brokers with round-trips from rdkafka statistics,
+/// skipping bootstrap entries which never carry requests
+pub fn broker_latencies(statistics: &Statistics) -> Vec<BrokerLatency> {
+    let mut latencies: Vec<BrokerLatency> = statistics.brokers
+        .values()
+        .filter_map(|broker| {
+            let rtt = broker.rtt.as_ref().filter(|rtt| rtt.cnt > 0)?;
+            Some(BrokerLatency {
+                broker: broker.name.clone(),
+                rtt_avg: rtt.avg,
+                rtt_p99: rtt.p99,
+                int_latency_avg: broker.int_latency.as_ref().map(|latency| latency.avg).unwrap_or_default()
+            })
+        })
+        .collect();
+
+    latencies.sort_by(|a, b| a.broker.cmp(&b.broker));
+    latencies
+}
+
+/// Client context of the producer, keeping broker latencies of the latest rdkafka statistics
+pub struct KafkaContext {
+    broker_latencies: Arc<Mutex<Vec<BrokerLatency>>>
+}
+
+impl ClientContext for KafkaContext {
+    fn stats(&self, statistics: Statistics) {
+        let latencies = broker_latencies(&statistics);
+
+        for latency in latencies.iter() {
+            for (kind, value) in [
+                ("rtt_avg", latency.rtt_avg),
+                ("rtt_p99", latency.rtt_p99),
+                ("int_latency_avg", latency.int_latency_avg)
+            ] {
+                metrics::KAFKA_BROKER_LATENCY
+                    .with_label_values(&[latency.broker.as_str(), kind])
+                    .set(value as f64);
+            }
+        }
+
+        *self.broker_latencies.lock().unwrap() = latencies;
+    }
+}
+
+/// Counters of message delivery since start
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DeliveryStats {
+    pub produced: u64,
+    pub failed: HashMap<&'static str, u64>,
+    pub bytes_sent: u64
+}
+
+impl DeliveryStats {
+    /// Returns failed produce attempts of all kinds
+    pub fn failed_total(&self) -> u64 {
+        self.failed.values().sum()
+    }
+}
+
+/// Counters of the retry buffer since start
+pub struct RetryBufferStats {
+    pub buffered: u64,
+    pub retried: u64,
+    pub evicted: u64,
+    pub dead_lettered: u64,
+    pub pending: usize
+}
+
 pub struct Kafka {
     config: KafkaConfig,
-    producer: FutureProducer
+    producer: FutureProducer<KafkaContext>,
+    broker_latencies: Arc<Mutex<Vec<BrokerLatency>>>,
+    embedding_versions: Mutex<HashMap<String, u32>>,
+    /// Schema ids of protobuf payloads per topic, when registered
+    schema_ids: HashMap<String, u32>,
+    /// Topics already reported as missing, so they are reported once
+    unknown_topics: Mutex<HashSet<String>>,
+    /// Batches of frame results per topic and source
+    result_batches: Mutex<HashMap<(String, String), ResultBatch>>,
+    retry_buffer: Mutex<RetryBuffer>,
+    retry_notify: Notify,
+    buffered: AtomicU64,
+    retried: AtomicU64,
+    evicted: AtomicU64,
+    dead_lettered: AtomicU64,
+    produced: AtomicU64,
+    failed: Mutex<HashMap<&'static str, u64>>,
+    bytes_sent: AtomicU64
 }
 
 impl Kafka {
     /// Creates a new Kafka producer instance
-    pub fn new(config: KafkaConfig) -> Result<Self> {
-        let producer: FutureProducer = ClientConfig::new()
-            .set("bootstrap.servers", &config.brokers)
-            .set("message.timeout.ms", "5000")
-            .create()
+    pub fn new(config: KafkaConfig, schema_ids: HashMap<String, u32>) -> Result<Self> {
+        let broker_latencies = Arc::new(Mutex::new(Vec::new()));
+        let context = KafkaContext { broker_latencies: Arc::clone(&broker_latencies) };
+        let producer: FutureProducer<KafkaContext> = Kafka::client_config(&config)?
+            .set("statistics.interval.ms", (config.stats_interval_secs * 1000).to_string())
+            .create_with_context(context)
             .context("Failed to create Kafka producer")?;
 
         Ok(
             Kafka { 
                 config,
                 producer,
+                broker_latencies,
+                embedding_versions: Mutex::new(HashMap::new()),
+                schema_ids,
+                unknown_topics: Mutex::new(HashSet::new()),
+                result_batches: Mutex::new(HashMap::new()),
+                retry_buffer: Mutex::new(RetryBuffer { messages: VecDeque::new(), bytes: 0, next_id: 0 }),
+                retry_notify: Notify::new(),
+                buffered: AtomicU64::new(0),
+                retried: AtomicU64::new(0),
+                evicted: AtomicU64::new(0),
+                dead_lettered: AtomicU64::new(0),
+                produced: AtomicU64::new(0),
+                failed: Mutex::new(HashMap::new()),
+                bytes_sent: AtomicU64::new(0)
             }
         )
     }
 
+    /// Builds the producer configuration - extra properties are applied last, overriding the others
+    pub fn client_config(config: &KafkaConfig) -> Result<ClientConfig> {
+        let mut client_config = ClientConfig::new();
+        client_config
+            .set("bootstrap.servers", &config.brokers)
+            .set("message.timeout.ms", "5000")
+            .set("acks", &config.acks)
+            .set("enable.idempotence", config.enable_idempotence.to_string())
+            .set("compression.codec", config.compression.as_str())
+            .set("batch.num.messages", config.batch_num_messages.to_string())
+            .set("message.send.max.retries", config.send_max_retries.to_string())
+            .set("queue.buffering.max.messages", config.queue_buffering_max_messages.to_string())
+            .set("queue.buffering.max.kbytes", config.queue_buffering_max_kbytes.to_string())
+            .set("queue.buffering.max.ms", config.queue_buffering_max_ms.to_string())
+            .set("message.max.bytes", config.message_max_bytes.to_string());
+
+        let security_properties = config.security_properties()
+            .context("Invalid Kafka security settings")?;
+        for (key, value) in security_properties {
+            client_config.set(key, value);
+        }
+
+        for (key, value) in config.extra_properties.iter() {
+            client_config.set(key, value);
+        }
+
+        Ok(client_config)
+    }
+
+    /// Fetches cluster metadata, so rejected credentials or certificates surface at startup.
+    /// Unreachable brokers are only reported, as failed messages are buffered until they are reachable
+    pub fn verify_connection(&self) -> Result<()> {
+        let result = self.producer
+            .client()
+            .fetch_metadata(None, Timeout::After(Duration::from_secs(10)));
+
+        let Err(err) = result else {
+            tracing::info!(security_protocol=self.config.security_protocol.as_str(), "Connected to Kafka brokers");
+            return Ok(());
+        };
+
+        let authentication_failed = matches!(
+            err.rdkafka_error_code(),
+            Some(
+                RDKafkaErrorCode::Authentication
+                | RDKafkaErrorCode::SaslAuthenticationFailed
+                | RDKafkaErrorCode::SSL
+                | RDKafkaErrorCode::TopicAuthorizationFailed
+                | RDKafkaErrorCode::ClusterAuthorizationFailed
+            )
+        );
+        if authentication_failed {
+            return Err(err).context("Kafka brokers rejected the security settings");
+        }
+
+        match self.config.on_unavailable {
+            KafkaUnavailablePolicy::Fail => Err(err).context("Kafka brokers are unreachable"),
+            KafkaUnavailablePolicy::Degrade => {
+                tracing::warn!(
+                    error=err.to_string(),
+                    "Could not verify connection to Kafka brokers, messages are retried once they are reachable"
+                );
+                Ok(())
+            }
+        }
+    }
+
     /// Produces a message to the specified topic
-    pub async fn produce<T: ToBytes>(&self, topic: &str, key: &str, message: &T) -> Result<()> {
-        let record = FutureRecord::to(topic)
-            .key(key)
-            .payload(message);
+    /// 
+    /// Messages failing to produce are buffered locally and retried in the background,
+    /// so a message is only lost when evicted by the buffer memory cap. While messages
+    /// are buffered, new messages are buffered behind them to keep their order.
+    /// Oversized messages and messages failing repeatedly are dead-lettered
+    pub async fn produce<T: ToBytes + ?Sized>(&self, topic: &str, key: &str, message: &T) -> Result<()> {
+        self.produce_with_headers(topic, key, message, None).await
+    }
+
+    /// Produces a message to the specified topic, attaching the given metadata as headers
+    ///
+    /// See `produce` for buffering of failed messages
+    pub async fn produce_with_headers<T: ToBytes + ?Sized>(
+        &self,
+        topic: &str,
+        key: &str,
+        message: &T,
+        headers: Option<&MessageHeaders<'_>>
+    ) -> Result<()> {
+        let headers = headers
+            .map(|headers| headers.pairs(self.schema_ids.get(topic).copied()))
+            .unwrap_or_default();
+
+        // Oversized messages fail permanently, dead-letter them instead of retrying
+        let payload = message.to_bytes();
+        if payload.len() > self.config.message_max_bytes {
+            return self.produce_oversized(topic, key, payload, headers).await;
+        }
+
+        self.produce_payload(topic, key, payload, headers).await
+    }
+
+    async fn produce_payload(
+        &self,
+        topic: &str,
+        key: &str,
+        payload: &[u8],
+        headers: Vec<(&'static str, String)>
+    ) -> Result<()> {
+        if self.config.retry_buffer_max_bytes > 0 && !self.retry_buffer.lock().unwrap().messages.is_empty() {
+            self.buffer(topic, key, payload, headers);
+            return Ok(());
+        }
+
+        let result = self.send(topic, key, payload, &headers).await;
+
+        match result {
+            Err(e) if self.config.retry_buffer_max_bytes > 0 => {
+                tracing::warn!(
+                    topic=topic,
+                    error=format!("{:#}", e),
+                    "Buffering Kafka message for retry"
+                );
+                self.buffer(topic, key, payload, headers);
+                Ok(())
+            },
+            result => result
+        }
+    }
+
+    /// Dead-letters an oversized message. When enabled, the message is still produced
+    /// without its embeddings, so its detections survive
+    async fn produce_oversized(
+        &self,
+        topic: &str,
+        key: &str,
+        payload: &[u8],
+        headers: Vec<(&'static str, String)>
+    ) -> Result<()> {
+        let error = format!(
+            "Message of {} bytes exceeds message_max_bytes of {}",
+            payload.len(), self.config.message_max_bytes
+        );
+        self.dead_letter(topic, key, payload, &headers, &error).await;
+
+        let stripped = self.config.dead_letter_strip_embeddings
+            .then(|| strip_embeddings(payload))
+            .flatten()
+            .filter(|stripped| stripped.len() <= self.config.message_max_bytes);
+
+        match stripped {
+            Some(stripped) => self.produce_payload(topic, key, &stripped, headers).await,
+            None => Ok(())
+        }
+    }
+
+    /// Writes a message given up on to the dead-letter topic, falling back to the spool directory
+    async fn dead_letter(&self, topic: &str, key: &str, payload: &[u8], headers: &[(&'static str, String)], error: &str) {
+        self.dead_lettered.fetch_add(1, Ordering::Relaxed);
+        metrics::KAFKA_DEAD_LETTERED.inc();
+        tracing::warn!(topic=topic, error=error, "Dead-lettering Kafka message");
+
+        if let Some(dead_letter_topic) = &self.config.dead_letter_topic {
+            let mut dead_letter_headers = headers.to_vec();
+            dead_letter_headers.push(("dead_letter_topic", topic.to_string()));
+            dead_letter_headers.push(("dead_letter_error", error.to_string()));
+
+            match self.send(dead_letter_topic, key, payload, &dead_letter_headers).await {
+                Ok(_) => return,
+                Err(e) => tracing::warn!(
+                    topic=dead_letter_topic,
+                    error=format!("{:#}", e),
+                    "Failed to produce to dead-letter topic"
+                )
+            }
+        }
+
+        if let Some(dead_letter_dir) = &self.config.dead_letter_dir {
+            if let Err(e) = spool_dead_letter(dead_letter_dir, topic, key, payload, headers, error) {
+                tracing::warn!(
+                    dir=dead_letter_dir,
+                    error=format!("{:#}", e),
+                    "Failed to spool dead-lettered message"
+                );
+            }
+        }
+    }
+
+    /// Sends a single message, awaiting its delivery
+    ///
+    /// The partitioning strategy decides whether the key or an explicit partition is set
+    async fn send(&self, topic: &str, key: &str, payload: &[u8], headers: &[(&'static str, String)]) -> Result<()> {
+        let mut record: FutureRecord<'_, str, [u8]> = FutureRecord::to(topic)
+            .payload(payload);
+
+        let (record_key, partition) = record_routing(self.config.partitioning, key);
+        if let Some(record_key) = record_key {
+            record = record.key(record_key);
+        }
+        if let Some(partition) = partition {
+            record = record.partition(partition);
+        }
+
+        if !headers.is_empty() {
+            let owned_headers = headers
+                .iter()
+                .fold(OwnedHeaders::new_with_capacity(headers.len()), |owned_headers, (key, value)| {
+                    owned_headers.insert(Header { key, value: Some(value.as_str()) })
+                });
+            record = record.headers(owned_headers);
+        }
 
         self.producer
             .send(record, Timeout::After(Duration::from_secs(5)))
             .await
-            .map(|_| ())
-            .map_err(|(err, _)| err)
+            .map(|_| self.count_delivered(payload.len()))
+            .map_err(|(err, _)| {
+                self.count_failed(&err);
+                self.report_unknown_topic(topic, &err);
+                err
+            })
             .context(format!("Failed to produce message to topic '{}'", topic))?;
 
         Ok(())
     }
 
-    pub async fn populate_bboxes(source_id: &str, frame: &RawFrame, bboxes: &[ResultBBOX]) -> Result<()>{
+    fn count_delivered(&self, bytes: usize) {
+        self.produced.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        metrics::KAFKA_PRODUCED.inc();
+        metrics::KAFKA_BYTES_SENT.inc_by(bytes as u64);
+    }
+
+    fn count_failed(&self, err: &KafkaError) {
+        let kind = KafkaErrorKind::from_kafka_error(err).to_string();
+        *self.failed.lock().unwrap().entry(kind).or_default() += 1;
+        metrics::KAFKA_PRODUCE_ERRORS.with_label_values(&[kind]).inc();
+    }
+
+    /// Reports a missing topic once with an actionable message, as topics are not assumed to be auto-created
+    fn report_unknown_topic(&self, topic: &str, err: &KafkaError) {
+        let unknown_topic = matches!(
+            err.rdkafka_error_code(),
+            Some(RDKafkaErrorCode::UnknownTopicOrPartition | RDKafkaErrorCode::UnknownTopic)
+        );
+        if !unknown_topic || !self.unknown_topics.lock().unwrap().insert(topic.to_string()) {
+            return;
+        }
+
+        tracing::error!(
+            topic=topic,
+            "Kafka topic '{}' does not exist - create it on the brokers or fix the configured topic (kafka_config topics / topic_override)",
+            topic
+        );
+    }
+
+    /// Adds a failed message to the retry buffer, evicting the oldest messages above the memory cap
+    fn buffer(&self, topic: &str, key: &str, payload: &[u8], headers: Vec<(&'static str, String)>) {
+        let mut evicted = 0;
+        {
+            let mut retry_buffer = self.retry_buffer.lock().unwrap();
+            let message = BufferedMessage {
+                id: retry_buffer.next_id,
+                topic: topic.to_string(),
+                key: key.to_string(),
+                payload: payload.to_vec(),
+                headers,
+                attempts: 0
+            };
+            retry_buffer.next_id += 1;
+            retry_buffer.bytes += message.size();
+            retry_buffer.messages.push_back(message);
+
+            while retry_buffer.bytes > self.config.retry_buffer_max_bytes {
+                match retry_buffer.messages.pop_front() {
+                    Some(oldest) => {
+                        retry_buffer.bytes -= oldest.size();
+                        evicted += 1;
+                    },
+                    None => break
+                }
+            }
+        }
+
+        self.buffered.fetch_add(1, Ordering::Relaxed);
+        metrics::KAFKA_BUFFERED.inc();
+        if evicted > 0 {
+            self.evicted.fetch_add(evicted, Ordering::Relaxed);
+            metrics::KAFKA_EVICTED.inc_by(evicted);
+        }
+
+        self.retry_notify.notify_one();
+    }
+
+    /// Retries buffered messages oldest-first, backing off while brokers are unreachable
+    async fn retry_buffered(&self) {
+        let initial_backoff = Duration::from_millis(self.config.retry_backoff_ms.max(1));
+        let max_backoff = Duration::from_millis(self.config.retry_backoff_max_ms).max(initial_backoff);
+        let mut backoff = initial_backoff;
+
+        loop {
+            // Wait for failed messages
+            let oldest = self.retry_buffer
+                .lock()
+                .unwrap()
+                .messages
+                .front()
+                .map(|message| (
+                    message.id,
+                    message.topic.clone(),
+                    message.key.clone(),
+                    message.payload.clone(),
+                    message.headers.clone()
+                ));
+
+            let Some((id, topic, key, payload, headers)) = oldest else {
+                self.retry_notify.notified().await;
+                continue;
+            };
+
+            match self.send(&topic, &key, &payload, &headers).await {
+                Ok(_) => {
+                    // Remove the message, unless it was evicted meanwhile
+                    let mut retry_buffer = self.retry_buffer.lock().unwrap();
+                    let delivered = retry_buffer.messages
+                        .front()
+                        .is_some_and(|message| message.id == id);
+                    if delivered {
+                        if let Some(message) = retry_buffer.messages.pop_front() {
+                            retry_buffer.bytes -= message.size();
+                        }
+                    }
+                    drop(retry_buffer);
+
+                    if backoff > initial_backoff {
+                        tracing::info!("Kafka is reachable again, producing buffered messages");
+                    }
+                    backoff = initial_backoff;
+                    self.retried.fetch_add(1, Ordering::Relaxed);
+                    metrics::KAFKA_RETRIED.inc();
+                },
+                Err(e) => {
+                    tracing::warn!(
+                        error=format!("{:#}", e),
+                        pending=self.retry_buffer.lock().unwrap().messages.len(),
+                        backoff_ms=backoff.as_millis() as u64,
+                        "Failed to produce buffered Kafka message"
+                    );
+
+                    // Give up on messages failing repeatedly, so they do not block the buffer forever
+                    if let Some(message) = self.count_failed_attempt(id) {
+                        self.dead_letter(&message.topic, &message.key, &message.payload, &message.headers, &format!("{:#}", e))
+                            .await;
+                        continue;
+                    }
+
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(max_backoff);
+                }
+            }
+        }
+    }
+
+    /// Counts a failed attempt of the oldest buffered message, unless evicted meanwhile.
+    /// Returns the message once removed for reaching the maximum attempts
+    fn count_failed_attempt(&self, id: u64) -> Option<BufferedMessage> {
+        if self.config.dead_letter_max_attempts == 0 {
+            return None;
+        }
+
+        let mut retry_buffer = self.retry_buffer.lock().unwrap();
+        let message = retry_buffer.messages
+            .front_mut()
+            .filter(|message| message.id == id)?;
+
+        message.attempts += 1;
+        if message.attempts < self.config.dead_letter_max_attempts {
+            return None;
+        }
+
+        let message = retry_buffer.messages.pop_front()?;
+        retry_buffer.bytes -= message.size();
+        Some(message)
+    }
+
+    /// Returns counters of the retry buffer since start
+    pub fn retry_buffer_stats(&self) -> RetryBufferStats {
+        RetryBufferStats {
+            buffered: self.buffered.load(Ordering::Relaxed),
+            retried: self.retried.load(Ordering::Relaxed),
+            evicted: self.evicted.load(Ordering::Relaxed),
+            dead_lettered: self.dead_lettered.load(Ordering::Relaxed),
+            pending: self.retry_buffer.lock().unwrap().messages.len()
+        }
+    }
+
+    /// Returns counters of message delivery since start
+    pub fn delivery_stats(&self) -> DeliveryStats {
+        DeliveryStats {
+            produced: self.produced.load(Ordering::Relaxed),
+            failed: self.failed.lock().unwrap().clone(),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Returns broker latencies of the latest rdkafka statistics
+    pub fn broker_latencies(&self) -> Vec<BrokerLatency> {
+        self.broker_latencies.lock().unwrap().clone()
+    }
+
+    /// Logs a summary of delivery every statistics interval, warning when the share
+    /// of failed produce attempts within the interval exceeds the configured threshold
+    async fn report_stats(&self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.stats_interval_secs));
+        let mut previous = DeliveryStats::default();
+
+        loop {
+            interval.tick().await;
+
+            let delivery = self.delivery_stats();
+            let retry_buffer = self.retry_buffer_stats();
+            metrics::KAFKA_RETRY_BUFFER_DEPTH.set(retry_buffer.pending as i64);
+
+            // Counters are cumulative, compare with the previous interval
+            let produced = delivery.produced - previous.produced;
+            let failed = delivery.failed_total() - previous.failed_total();
+            let bytes_sent = delivery.bytes_sent - previous.bytes_sent;
+            let failure_percent = failed as f32 / (produced + failed).max(1) as f32 * 100.00;
+
+            let latencies = self.broker_latencies();
+            let max_rtt_avg = latencies.iter().map(|latency| latency.rtt_avg).max();
+            let max_rtt_p99 = latencies.iter().map(|latency| latency.rtt_p99).max();
+
+            tracing::info!(
+                produced=produced,
+                failed=failed,
+                failed_by_kind=serde_json::to_string(&delivery.failed).unwrap_or_default(),
+                bytes_sent=bytes_sent,
+                retry_buffer_pending=retry_buffer.pending,
+                dead_lettered=retry_buffer.dead_lettered,
+                brokers=latencies.len(),
+                max_rtt_avg=max_rtt_avg,
+                max_rtt_p99=max_rtt_p99,
+                "kafka delivery statistics"
+            );
+
+            if failure_percent > self.config.failure_warn_percent {
+                tracing::warn!(
+                    produced=produced,
+                    failed=failed,
+                    failure_percent=failure_percent,
+                    failure_warn_percent=self.config.failure_warn_percent,
+                    "Kafka produce failure rate exceeds threshold"
+                );
+            }
+
+            previous = delivery;
+        }
+    }
+
+    /// Frames a protobuf payload with the schema of its topic, when registered
+    fn protobuf_payload(&self, topic: &str, kind: MessageKind, payload: Vec<u8>) -> Vec<u8> {
+        match self.schema_ids.get(topic) {
+            Some(&schema_id) => protobuf::frame_payload(schema_id, kind, &payload),
+            None => payload
+        }
+    }
+
+    /// Publishes bboxes of a frame, along with the resolution they are reported in and the fps of the stream,
+    /// so consumers can render them without looking up the source
+    pub async fn populate_bboxes(
+        topic: &str,
+        source_id: &str,
+        frame: &RawFrame,
+        bboxes: &[ResultBBOX],
+        stream_info: Option<&StreamInfo>
+    ) -> Result<()>{
         let producer = get_kafka_producer()?;
-        let data = serde_json::to_string(&bboxes)
-            .context("Error parsing bboxes to JSON")?;
+        let fps = stream_info.map(|stream_info| stream_info.fps);
+        let data = match producer.config.serialization {
+            Serialization::Json => bboxes_json(source_id, frame, bboxes, fps)?,
+            Serialization::Protobuf => producer.protobuf_payload(
+                topic,
+                MessageKind::BBoxes,
+                protobuf::encode_bboxes(source_id, frame, bboxes, fps)
+            )
+        };
+
+        // Bboxes come from the detection model
+        let model = inference::get_inference_model(InferenceModelType::YOLO).ok();
+        let headers = MessageHeaders {
+            source_id,
+            pts: frame.pts,
+            result_type: "bboxes",
+            model_name: model.as_ref().map(|model| model.model_config().name.as_str()),
+            model_version: model.as_ref().map(|model| model.model_config().version)
+        };
 
-        producer.produce(
-            &producer.config.topic_bboxes, 
-            &format!("{}-{}", source_id, frame.pts), 
-            &data
+        producer.produce_with_headers(
+            topic, 
+            source_id,
+            &data,
+            Some(&headers)
         ).await?;
 
         Ok(())
     }
 
-    pub async fn populate_embeddings(source_id: &str, frame: &RawFrame, embeddings: &[ResultEmbedding]) -> Result<()>{
+    /// Records the embedding version published for a model.
+    /// Returns whether it differs from the previously published version,
+    /// meaning consumers should migrate before mixing embeddings
+    fn update_embedding_version(&self, model_name: &str, version: u32) -> bool {
+        let mut versions = self.embedding_versions.lock().unwrap();
+        match versions.insert(model_name.to_string(), version) {
+            Some(previous) => previous != version,
+            None => false
+        }
+    }
+
+    pub async fn populate_embeddings(topic: &str, source_id: &str, frame: &RawFrame, embeddings: &[ResultEmbedding]) -> Result<()>{
         let producer = get_kafka_producer()?;
-        
-        let payload = serde_json::json!({
-            "source_id": source_id,
-            "embeddings": embeddings.iter().map(|e| &e.data).collect::<Vec<_>>(),
-            "frame": &frame.data
-        });
 
-        let data = serde_json::to_string(&payload)
-            .context("Error serializing embedding payload")?;
+        // All embeddings of a frame come from the same model
+        let (embedding_version, model_name) = embeddings
+            .first()
+            .map(|e| (e.embedding_version, e.model_name.as_str()))
+            .context("No embeddings to populate")?;
+        let migration_required = producer.update_embedding_version(model_name, embedding_version);
+
+        let data = match producer.config.serialization {
+            Serialization::Json => embeddings_json(source_id, frame, embeddings, migration_required)?,
+            Serialization::Protobuf => producer.protobuf_payload(
+                topic,
+                MessageKind::Embeddings,
+                protobuf::encode_embeddings(source_id, frame, embeddings, migration_required)
+            )
+        };
+
+        let headers = MessageHeaders {
+            source_id,
+            pts: frame.pts,
+            result_type: "embeddings",
+            model_name: Some(model_name),
+            model_version: Some(embedding_version)
+        };
 
-        producer.produce(
-            &producer.config.topic_embedding, 
-            &format!("{}-{}", source_id, frame.pts), 
-            &data
+        producer.produce_with_headers(
+            topic, 
+            source_id,
+            &data,
+            Some(&headers)
         ).await?;
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Publishes combined results of a frame, or adds them to the batch of the source when batching
+    pub async fn populate_results(topic: &str, results: Arc<FrameResults>) -> Result<()>{
+        let producer = get_kafka_producer()?;
+
+        let migration_required = match (&results.model_name, results.embedding_version) {
+            (Some(model_name), Some(version)) => producer.update_embedding_version(model_name, version),
+            _ => false
+        };
+
+        if let Some(batching) = &producer.config.results_batching {
+            if let Some(frames) = producer.add_to_batch(topic, results, migration_required, batching.max_batch_frames) {
+                producer.publish_results_batch(topic, frames).await?;
+            }
+            return Ok(());
+        }
+
+        let data = match producer.config.serialization {
+            Serialization::Json => serde_json::to_string(&results_json(&results, migration_required)?)
+                .context("Error serializing frame results payload")?
+                .into_bytes(),
+            Serialization::Protobuf => producer.protobuf_payload(
+                topic,
+                MessageKind::FrameResults,
+                protobuf::encode_results(&results, migration_required)
+            )
+        };
+
+        let headers = MessageHeaders {
+            source_id: &results.source_id,
+            pts: results.pts,
+            result_type: "results",
+            model_name: results.model_name.as_deref(),
+            model_version: results.embedding_version
+        };
+
+        producer.produce_with_headers(
+            topic, 
+            &results.source_id,
+            &data,
+            Some(&headers)
+        ).await?;
+
+        Ok(())
+    }
+
+    /// Adds frame results to the batch of their source, returning the frames of the batch once full
+    fn add_to_batch(
+        &self,
+        topic: &str,
+        results: Arc<FrameResults>,
+        migration_required: bool,
+        max_batch_frames: usize
+    ) -> Option<Vec<(Arc<FrameResults>, bool)>> {
+        let mut batches = self.result_batches.lock().unwrap();
+        let key = (topic.to_string(), results.source_id.clone());
+        let batch = batches
+            .entry(key.clone())
+            .or_insert_with(|| ResultBatch { frames: Vec::new(), started: Instant::now() });
+        batch.frames.push((results, migration_required));
+
+        if batch.frames.len() < max_batch_frames.max(1) {
+            return None;
+        }
+
+        batches.remove(&key).map(|batch| batch.frames)
+    }
+
+    /// Publishes batches matching the given condition, of a single source when given
+    async fn flush_batches<F>(&self, due: F, source_id: Option<&str>)
+    where
+        F: Fn(&ResultBatch) -> bool
+    {
+        for (topic, batch) in self.take_batches(due, source_id) {
+            if let Err(e) = self.publish_results_batch(&topic, batch.frames).await {
+                tracing::warn!(
+                    topic=topic,
+                    error=format!("{:#}", e),
+                    "Failed to publish batch of results to Kafka"
+                );
+            }
+        }
+    }
+
+    /// Removes batches matching the given condition, of a single source when given,
+    /// returning them with their topics
+    fn take_batches<F>(&self, due: F, source_id: Option<&str>) -> Vec<(String, ResultBatch)>
+    where
+        F: Fn(&ResultBatch) -> bool
+    {
+        let mut batches = self.result_batches.lock().unwrap();
+        let keys: Vec<(String, String)> = batches
+            .iter()
+            .filter(|((_, batch_source_id), batch)| {
+                source_id.is_none_or(|source_id| source_id == batch_source_id) && due(batch)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        keys.into_iter()
+            .filter_map(|key| batches.remove(&key).map(|batch| (key.0, batch)))
+            .collect()
+    }
+
+    /// Publishes batches older than the maximum batch duration, checking at that interval
+    async fn flush_expired_batches(&self, max_batch_duration: Duration) {
+        let mut interval = tokio::time::interval(max_batch_duration);
+
+        loop {
+            interval.tick().await;
+            self.flush_batches(|batch| batch.started.elapsed() >= max_batch_duration, None).await;
+        }
+    }
+
+    /// Publishes combined results of multiple frames of a source as a single message, in pts order.
+    /// Failures are buffered for retrying like any other message
+    async fn publish_results_batch(&self, topic: &str, mut frames: Vec<(Arc<FrameResults>, bool)>) -> Result<()> {
+        frames.sort_by_key(|(results, _)| results.pts);
+        let Some(first) = frames.first().map(|(results, _)| Arc::clone(results)) else {
+            return Ok(());
+        };
+
+        let data = match self.config.serialization {
+            Serialization::Json => results_batch_json(&first.source_id, &frames)?,
+            Serialization::Protobuf => self.protobuf_payload(
+                topic,
+                MessageKind::FrameResultsBatch,
+                protobuf::encode_results_batch(&first.source_id, &frames)
+            )
+        };
+
+        let headers = MessageHeaders {
+            source_id: &first.source_id,
+            pts: first.pts,
+            result_type: "results_batch",
+            model_name: first.model_name.as_deref(),
+            model_version: first.embedding_version
+        };
+
+        self.produce_with_headers(
+            topic,
+            &first.source_id,
+            &data,
+            Some(&headers)
+        ).await
+    }
+}
+
+/// Returns the JSON payload of bboxes of a frame
+fn bboxes_json(source_id: &str, frame: &RawFrame, bboxes: &[ResultBBOX], fps: Option<f64>) -> Result<Vec<u8>> {
+    let payload = serde_json::json!({
+        "source_id": source_id,
+        "pts": frame.pts,
+        "width": frame.original_width,
+        "height": frame.original_height,
+        "fps": fps,
+        "bboxes": bboxes
+    });
+
+    Ok(
+        serde_json::to_string(&payload)
+            .context("Error parsing bboxes to JSON")?
+            .into_bytes()
+    )
+}
+
+/// Returns the JSON payload of embeddings of a frame, along with the frame itself
+fn embeddings_json(
+    source_id: &str,
+    frame: &RawFrame,
+    embeddings: &[ResultEmbedding],
+    migration_required: bool
+) -> Result<Vec<u8>> {
+    let (embedding_version, model_name) = embeddings
+        .first()
+        .map(|e| (e.embedding_version, e.model_name.as_str()))
+        .context("No embeddings to populate")?;
+
+    let payload = serde_json::json!({
+        "source_id": source_id,
+        "embedding_version": embedding_version,
+        "model_name": model_name,
+        "migration_required": migration_required,
+        "embeddings": embeddings.iter().map(|e| &e.data).collect::<Vec<_>>(),
+        "frame": &frame.data
+    });
+
+    Ok(
+        serde_json::to_string(&payload)
+            .context("Error serializing embedding payload")?
+            .into_bytes()
+    )
+}
+
+/// Returns the JSON payload of combined results of multiple frames of a source
+fn results_batch_json(source_id: &str, frames: &[(Arc<FrameResults>, bool)]) -> Result<Vec<u8>> {
+    let frames_json = frames
+        .iter()
+        .map(|(results, migration_required)| results_json(results, *migration_required))
+        .collect::<Result<Vec<_>>>()?;
+    let payload = serde_json::json!({
+        "source_id": source_id,
+        "count": frames_json.len(),
+        "frames": frames_json
+    });
+
+    Ok(
+        serde_json::to_string(&payload)
+            .context("Error serializing frame results batch payload")?
+            .into_bytes()
+    )
+}
+
+/// Returns the JSON payload of combined results of a frame
+fn results_json(results: &FrameResults, migration_required: bool) -> Result<serde_json::Value> {
+    let mut payload = serde_json::to_value(results)
+        .context("Error serializing frame results")?;
+    payload["migration_required"] = serde_json::Value::Bool(migration_required);
+
+    Ok(payload)
+}
+
+/// Removes embeddings and frames of a JSON payload, returning None when there is nothing to remove.
+/// Frames of batched results are kept, only their embeddings are removed
+fn strip_embeddings(payload: &[u8]) -> Option<Vec<u8>> {
+    let mut value: serde_json::Value = serde_json::from_slice(payload).ok()?;
+    let object = value.as_object_mut()?;
+
+    let mut stripped = strip_object_embeddings(object);
+    if let Some(frames) = object.get_mut("frames").and_then(|frames| frames.as_array_mut()) {
+        for frame in frames.iter_mut().filter_map(|frame| frame.as_object_mut()) {
+            stripped |= strip_object_embeddings(frame);
+        }
+    }
+
+    stripped.then(|| value.to_string().into_bytes())
+}
+
+/// Removes embeddings and frames of a single payload object, returning whether any were removed
+fn strip_object_embeddings(object: &mut serde_json::Map<String, serde_json::Value>) -> bool {
+    let mut stripped = false;
+    for field in ["embeddings", "frame", "frame_embedding"] {
+        stripped |= object.remove(field).is_some();
+    }
+
+    if let Some(detections) = object.get_mut("detections").and_then(|detections| detections.as_array_mut()) {
+        for detection in detections.iter_mut().filter_map(|detection| detection.as_object_mut()) {
+            stripped |= detection.remove("embedding").is_some();
+        }
+    }
+
+    stripped
+}
+
+/// Appends a dead-lettered message to the spool file of the current day, as a single JSON line
+fn spool_dead_letter(
+    dir: &str,
+    topic: &str,
+    key: &str,
+    payload: &[u8],
+    headers: &[(&'static str, String)],
+    error: &str
+) -> Result<()> {
+    use std::io::Write;
+
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Error creating dead-letter directory {}", dir))?;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    // Binary payloads (e.g. protobuf) are kept as hex
+    let (payload, encoding) = match std::str::from_utf8(payload) {
+        Ok(payload) => (payload.to_string(), "utf8"),
+        Err(_) => (payload.iter().map(|byte| format!("{:02x}", byte)).collect(), "hex")
+    };
+
+    let record = serde_json::json!({
+        "timestamp": timestamp,
+        "topic": topic,
+        "key": key,
+        "headers": headers.iter().cloned().collect::<HashMap<_, _>>(),
+        "error": error,
+        "payload_encoding": encoding,
+        "payload": payload
+    });
+
+    let path = std::path::Path::new(dir).join(format!("dead_letters_{}.ndjson", timestamp / 86_400));
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Error opening {}", path.display()))?;
+
+    writeln!(file, "{}", record)
+        .with_context(|| format!("Error writing to {}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kafka() -> Kafka {
+        Kafka::new(KafkaConfig::default(), HashMap::new()).unwrap()
+    }
+
+    fn frame_results(source_id: &str, pts: u64) -> Arc<FrameResults> {
+        Arc::new(FrameResults {
+            pts,
+            source_id: source_id.to_string(),
+            model_name: None,
+            embedding_version: None,
+            frame_embedding: None,
+            detections: Vec::new()
+        })
+    }
+
+    fn pts_of(frames: &[(Arc<FrameResults>, bool)]) -> Vec<u64> {
+        frames.iter().map(|(results, _)| results.pts).collect()
+    }
+
+    #[test]
+    fn publishes_batch_once_full() {
+        let kafka = kafka();
+
+        assert!(kafka.add_to_batch("results", frame_results("1", 1), false, 2).is_none());
+        let frames = kafka.add_to_batch("results", frame_results("1", 2), false, 2).unwrap();
+
+        assert_eq!(pts_of(&frames), vec![1, 2]);
+        assert!(kafka.result_batches.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn takes_expired_batches_only() {
+        let kafka = kafka();
+        let max_batch_duration = Duration::from_millis(500);
+        kafka.add_to_batch("results", frame_results("1", 1), false, 10);
+        kafka.add_to_batch("results", frame_results("2", 1), false, 10);
+
+        let expired_key = ("results".to_string(), "1".to_string());
+        kafka.result_batches.lock().unwrap().get_mut(&expired_key).unwrap().started -= Duration::from_secs(1);
+
+        let taken = kafka.take_batches(|batch| batch.started.elapsed() >= max_batch_duration, None);
+
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].1.frames[0].0.source_id, "1");
+        assert_eq!(kafka.result_batches.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn shutdown_takes_all_batches() {
+        let kafka = kafka();
+        kafka.add_to_batch("results", frame_results("1", 2), false, 10);
+        kafka.add_to_batch("results", frame_results("1", 1), false, 10);
+        kafka.add_to_batch("results", frame_results("2", 1), false, 10);
+
+        // A stopped source only flushes its own batch
+        let taken = kafka.take_batches(|_| true, Some("2"));
+        assert_eq!(taken.len(), 1);
+
+        let taken = kafka.take_batches(|_| true, None);
+        assert_eq!(taken.len(), 1);
+        assert_eq!(pts_of(&taken[0].1.frames), vec![2, 1]);
+        assert!(kafka.result_batches.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn results_payload_carries_migration_flag() {
+        let payload = results_json(&frame_results("1", 5), true).unwrap();
+
+        assert_eq!(payload["source_id"], "1");
+        assert_eq!(payload["pts"], 5);
+        assert_eq!(payload["migration_required"], true);
+        assert_eq!(payload["detections"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn requires_migration_when_version_changes() {
+        let kafka = kafka();
+
+        assert!(!kafka.update_embedding_version("dino", 1));
+        assert!(!kafka.update_embedding_version("dino", 1));
+        assert!(kafka.update_embedding_version("dino", 2));
+        assert!(!kafka.update_embedding_version("yolo", 1));
+    }
+
+    #[test]
+    fn strips_embeddings_of_batched_frames() {
+        let payload = serde_json::json!({
+            "source_id": "1",
+            "count": 1,
+            "frames": [{
+                "pts": 1,
+                "frame_embedding": [0.1],
+                "detections": [{ "bbox": [0, 0, 1, 1], "embedding": [0.2] }]
+            }]
+        });
+
+        let stripped = strip_embeddings(payload.to_string().as_bytes()).unwrap();
+        let stripped: serde_json::Value = serde_json::from_slice(&stripped).unwrap();
+
+        assert_eq!(stripped, serde_json::json!({
+            "source_id": "1",
+            "count": 1,
+            "frames": [{ "pts": 1, "detections": [{ "bbox": [0, 0, 1, 1] }] }]
+        }));
+    }
+
+    #[test]
+    fn keeps_payloads_without_embeddings() {
+        let payload = serde_json::json!({ "source_id": "1", "detections": [{ "bbox": [0, 0, 1, 1] }] });
+
+        assert!(strip_embeddings(payload.to_string().as_bytes()).is_none());
+    }
+
+    fn raw_frame() -> RawFrame {
+        RawFrame {
+            data: vec![7, 8],
+            height: 360,
+            width: 640,
+            original_height: 1080,
+            original_width: 1920,
+            pts: 4200,
+            added: tokio::time::Instant::now()
+        }
+    }
+
+    fn embedding(data: Vec<f32>) -> ResultEmbedding {
+        ResultEmbedding { data, embedding_version: 2, model_name: "dino".to_string() }
+    }
+
+    // Payloads are compared byte for byte, as consumers parse them as is
+    #[test]
+    fn keeps_json_payloads_unchanged() {
+        let bboxes = [ResultBBOX { bbox: [0.5, 1.0, 10.0, 20.0], class: 2, score: 0.75 }];
+        let embeddings = [embedding(vec![0.25, 0.5]), embedding(vec![1.0, -1.0])];
+        let results = FrameResults::new("lobby", &raw_frame(), &bboxes, Some(&embeddings)).unwrap();
+
+        assert_eq!(
+            String::from_utf8(bboxes_json("lobby", &raw_frame(), &bboxes, Some(25.0)).unwrap()).unwrap(),
+            r#"{"bboxes":[{"bbox":[0.5,1.0,10.0,20.0],"class":2,"score":0.75}],"fps":25.0,"height":1080,"pts":4200,"source_id":"lobby","width":1920}"#
+        );
+        assert_eq!(
+            String::from_utf8(embeddings_json("lobby", &raw_frame(), &embeddings, true).unwrap()).unwrap(),
+            r#"{"embedding_version":2,"embeddings":[[0.25,0.5],[1.0,-1.0]],"frame":[7,8],"migration_required":true,"model_name":"dino","source_id":"lobby"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&results_json(&results, false).unwrap()).unwrap(),
+            r#"{"detections":[{"bbox":[0.5,1.0,10.0,20.0],"class":2,"class_name":"car","detection_index":0,"embedding":[1.0,-1.0],"embedding_index":1,"score":0.75}],"embedding_version":2,"frame_embedding":[0.25,0.5],"migration_required":false,"model_name":"dino","pts":4200,"source_id":"lobby"}"#
+        );
+        assert_eq!(
+            String::from_utf8(results_batch_json("lobby", &[(Arc::new(results), true)]).unwrap()).unwrap(),
+            r#"{"count":1,"frames":[{"detections":[{"bbox":[0.5,1.0,10.0,20.0],"class":2,"class_name":"car","detection_index":0,"embedding":[1.0,-1.0],"embedding_index":1,"score":0.75}],"embedding_version":2,"frame_embedding":[0.25,0.5],"migration_required":true,"model_name":"dino","pts":4200,"source_id":"lobby"}],"source_id":"lobby"}"#
+        );
+    }
+
+    #[test]
+    fn builds_producer_properties() {
+        let config: KafkaConfig = serde_yaml::from_str("
+            brokers: kafka-1:9092,kafka-2:9092
+            compression: zstd
+            linger_ms: 20
+            batch_num_messages: 500
+            acks: all
+            enable_idempotence: true
+            extra_properties:
+              client.id: detections
+              batch.num.messages: '1000'
+        ").unwrap();
+
+        let client_config = Kafka::client_config(&config).unwrap();
+
+        for (key, value) in [
+            ("bootstrap.servers", "kafka-1:9092,kafka-2:9092"),
+            ("compression.codec", "zstd"),
+            ("queue.buffering.max.ms", "20"),
+            ("acks", "all"),
+            ("enable.idempotence", "true"),
+            ("security.protocol", "plaintext"),
+            ("client.id", "detections"),
+            // Extra properties override the dedicated settings
+            ("batch.num.messages", "1000")
+        ] {
+            assert_eq!(client_config.get(key), Some(value), "{}", key);
+        }
+    }
+
+    #[test]
+    fn builds_default_producer_properties() {
+        let client_config = Kafka::client_config(&KafkaConfig::default()).unwrap();
+
+        for (key, value) in [
+            ("compression.codec", "none"),
+            ("queue.buffering.max.ms", "5"),
+            ("acks", "all"),
+            ("enable.idempotence", "false")
+        ] {
+            assert_eq!(client_config.get(key), Some(value), "{}", key);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_compression() {
+        assert!(serde_yaml::from_str::<KafkaConfig>("compression: brotli").is_err());
+    }
+
+    #[test]
+    fn routes_records_by_partitioning() {
+        for (partitioning, expected) in [
+            ("BySource", (Some("camera-1"), None)),
+            ("!Fixed 3", (Some("camera-1"), Some(3))),
+            ("RoundRobin", (None, None))
+        ] {
+            let partitioning: Partitioning = serde_yaml::from_str(partitioning).unwrap();
+            assert_eq!(record_routing(partitioning, "camera-1"), expected, "{:?}", partitioning);
+        }
+    }
+
+    #[test]
+    fn lists_message_headers() {
+        let headers = MessageHeaders {
+            source_id: "1",
+            pts: 42,
+            result_type: "bboxes",
+            model_name: Some("yolo"),
+            model_version: Some(3)
+        };
+
+        assert_eq!(headers.pairs(Some(17)), vec![
+            ("source_id", "1".to_string()),
+            ("pts", "42".to_string()),
+            ("result_type", "bboxes".to_string()),
+            ("model_name", "yolo".to_string()),
+            ("model_version", "3".to_string()),
+            ("schema_version", SCHEMA_VERSION.to_string()),
+            ("schema_id", "17".to_string()),
+            ("client_version", env!("CARGO_PKG_VERSION").to_string())
+        ]);
+    }
+
+    #[test]
+    fn skips_missing_message_headers() {
+        let headers = MessageHeaders {
+            source_id: "1",
+            pts: 42,
+            result_type: "embedding",
+            model_name: None,
+            model_version: None
+        };
+
+        let keys: Vec<&str> = headers.pairs(None).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["source_id", "pts", "result_type", "schema_version", "client_version"]);
+    }
+
+    fn retrying_kafka(retry_buffer_max_bytes: usize, dead_letter_max_attempts: u32) -> Kafka {
+        let config = KafkaConfig {
+            retry_buffer_max_bytes,
+            dead_letter_max_attempts,
+            ..KafkaConfig::default()
+        };
+        Kafka::new(config, HashMap::new()).unwrap()
+    }
+
+    fn buffered_keys(kafka: &Kafka) -> Vec<String> {
+        kafka.retry_buffer
+            .lock()
+            .unwrap()
+            .messages
+            .iter()
+            .map(|message| message.key.clone())
+            .collect()
+    }
+
+    #[test]
+    fn buffers_failed_messages_in_order() {
+        let kafka = retrying_kafka(1024, 3);
+
+        kafka.buffer("bboxes", "1", b"first", vec![("source_id", "1".to_string())]);
+        kafka.buffer("bboxes", "2", b"second", Vec::new());
+
+        assert_eq!(buffered_keys(&kafka), vec!["1", "2"]);
+        assert_eq!(kafka.retry_buffer.lock().unwrap().bytes, "bboxes1firstsource_id1".len() + "bboxes2second".len());
+
+        let stats = kafka.retry_buffer_stats();
+        assert_eq!((stats.buffered, stats.evicted, stats.pending), (2, 0, 2));
+    }
+
+    #[test]
+    fn evicts_oldest_messages_above_memory_cap() {
+        // Every message takes 6 + 1 + 10 bytes, so only two fit
+        let kafka = retrying_kafka(40, 3);
+
+        for key in ["1", "2", "3"] {
+            kafka.buffer("bboxes", key, &[0; 10], Vec::new());
+        }
+
+        assert_eq!(buffered_keys(&kafka), vec!["2", "3"]);
+        assert_eq!(kafka.retry_buffer.lock().unwrap().bytes, 34);
+
+        let stats = kafka.retry_buffer_stats();
+        assert_eq!((stats.buffered, stats.evicted, stats.pending), (3, 1, 2));
+    }
+
+    #[test]
+    fn gives_up_on_messages_after_max_attempts() {
+        let kafka = retrying_kafka(1024, 3);
+        kafka.buffer("bboxes", "1", b"first", Vec::new());
+        kafka.buffer("bboxes", "2", b"second", Vec::new());
+
+        assert!(kafka.count_failed_attempt(0).is_none());
+        assert!(kafka.count_failed_attempt(0).is_none());
+        let message = kafka.count_failed_attempt(0).unwrap();
+
+        assert_eq!((message.key.as_str(), message.attempts), ("1", 3));
+        assert_eq!(buffered_keys(&kafka), vec!["2"]);
+        assert_eq!(kafka.retry_buffer.lock().unwrap().bytes, "bboxes2second".len());
+
+        // Attempts of a message no longer buffered are not counted against the next one
+        assert!(kafka.count_failed_attempt(0).is_none());
+        assert_eq!(kafka.retry_buffer.lock().unwrap().messages[0].attempts, 0);
+    }
+
+    #[test]
+    fn retries_forever_without_max_attempts() {
+        let kafka = retrying_kafka(1024, 0);
+        kafka.buffer("bboxes", "1", b"first", Vec::new());
+
+        for _ in 0..10 {
+            assert!(kafka.count_failed_attempt(0).is_none());
+        }
+
+        assert_eq!(buffered_keys(&kafka), vec!["1"]);
+    }
+
+    #[tokio::test]
+    async fn dead_letters_to_topic_after_max_attempts() {
+        use rdkafka::consumer::{BaseConsumer, Consumer};
+        use rdkafka::message::{Headers, Message};
+        use rdkafka::mocking::MockCluster;
+        use rdkafka::types::RDKafkaRespErr;
+
+        let cluster = MockCluster::new(1).unwrap();
+        cluster.create_topic("dead_letters", 1, 1).unwrap();
+        cluster.topic_error("bboxes", RDKafkaRespErr::RD_KAFKA_RESP_ERR_TOPIC_AUTHORIZATION_FAILED).unwrap();
+
+        let config = KafkaConfig {
+            brokers: cluster.bootstrap_servers(),
+            retry_backoff_ms: 1,
+            retry_backoff_max_ms: 1,
+            dead_letter_max_attempts: 3,
+            dead_letter_topic: Some("dead_letters".to_string()),
+            ..KafkaConfig::default()
+        };
+        let kafka = Arc::new(Kafka::new(config, HashMap::new()).unwrap());
+        kafka.buffer("bboxes", "1", b"payload", vec![("source_id", "1".to_string())]);
+
+        let retry_instance = Arc::clone(&kafka);
+        let retry = tokio::spawn(async move { retry_instance.retry_buffered().await });
+        tokio::time::timeout(Duration::from_secs(20), async {
+            while kafka.retry_buffer_stats().dead_lettered == 0 || kafka.delivery_stats().produced == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+        retry.abort();
+
+        assert_eq!(kafka.delivery_stats().failed_total(), 3);
+        assert_eq!(kafka.retry_buffer_stats().pending, 0);
+
+        let consumer: BaseConsumer = ClientConfig::new()
+            .set("bootstrap.servers", cluster.bootstrap_servers())
+            .set("group.id", "dead_letters")
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .unwrap();
+        consumer.subscribe(&["dead_letters"]).unwrap();
+        let message = (0..100)
+            .find_map(|_| consumer.poll(Duration::from_millis(100)))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(message.key(), Some("1".as_bytes()));
+        assert_eq!(message.payload(), Some("payload".as_bytes()));
+        let headers: HashMap<&str, &[u8]> = message.headers()
+            .unwrap()
+            .iter()
+            .filter_map(|header| Some((header.key, header.value?)))
+            .collect();
+        assert_eq!(headers["source_id"], b"1");
+        assert_eq!(headers["dead_letter_topic"], b"bboxes");
+        assert!(headers.contains_key("dead_letter_error"));
+    }
+
+    /// Fills fields missing from the given rdkafka statistics JSON with their defaults,
+    /// as librdkafka always emits every field
+    fn with_defaults(defaults: serde_json::Value, overrides: serde_json::Value) -> serde_json::Value {
+        match (defaults, overrides) {
+            (serde_json::Value::Object(mut defaults), serde_json::Value::Object(overrides)) => {
+                for (key, value) in overrides {
+                    let default = defaults.remove(&key).unwrap_or(serde_json::Value::Null);
+                    defaults.insert(key, with_defaults(default, value));
+                }
+                serde_json::Value::Object(defaults)
+            },
+            (_, overrides) => overrides
+        }
+    }
+
+    fn statistics(brokers: serde_json::Value) -> Statistics {
+        let broker = serde_json::to_value(rdkafka::statistics::Broker::default()).unwrap();
+        let window = serde_json::to_value(rdkafka::statistics::Window::default()).unwrap();
+
+        let brokers = brokers
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(name, overrides)| {
+                let mut broker = with_defaults(broker.clone(), overrides.clone());
+                for field in ["rtt", "int_latency"] {
+                    if let Some(overrides) = overrides.get(field) {
+                        broker[field] = with_defaults(window.clone(), overrides.clone());
+                    }
+                }
+                (name.clone(), broker)
+            })
+            .collect();
+
+        let mut statistics = serde_json::to_value(Statistics::default()).unwrap();
+        statistics["brokers"] = serde_json::Value::Object(brokers);
+        serde_json::from_value(statistics).unwrap()
+    }
+
+    #[test]
+    fn extracts_latencies_of_brokers_with_round_trips() {
+        let statistics = statistics(serde_json::json!({
+            "localhost:9092/bootstrap": {
+                "name": "localhost:9092/bootstrap",
+                "rtt": { "avg": 0, "p99": 0, "cnt": 0 }
+            },
+            "kafka-2:9092/2": {
+                "name": "kafka-2:9092/2",
+                "rtt": { "avg": 2100, "p99": 5200, "cnt": 4 }
+            },
+            "kafka-1:9092/1": {
+                "name": "kafka-1:9092/1",
+                "rtt": { "avg": 1200, "p99": 4000, "cnt": 12 },
+                "int_latency": { "avg": 300, "cnt": 12 }
+            },
+            "kafka-3:9092/3": {
+                "name": "kafka-3:9092/3"
+            }
+        }));
+
+        let latencies: Vec<(String, i64, i64, i64)> = broker_latencies(&statistics)
+            .into_iter()
+            .map(|latency| (latency.broker, latency.rtt_avg, latency.rtt_p99, latency.int_latency_avg))
+            .collect();
+
+        assert_eq!(latencies, vec![
+            ("kafka-1:9092/1".to_string(), 1200, 4000, 300),
+            ("kafka-2:9092/2".to_string(), 2100, 5200, 0)
+        ]);
+    }
+}
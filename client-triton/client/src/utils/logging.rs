@@ -0,0 +1,297 @@
+//! Responsible for structured logging to the console and to rotated log files
+//!
+//! The log level is applied through a reloadable filter, so it can be changed at runtime
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use once_cell::sync::OnceCell;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, reload, EnvFilter, Layer, Registry, fmt};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+// Custom modules
+use crate::utils::config::{LoggingConfig, LogFormat, LogRotation};
+
+/// Level used when neither the configuration nor RUST_LOG set one
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// Handle of the filter of the global subscriber, for changing the level at runtime
+static LOG_FILTER: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
+/// Keeps the background file writer running, flushing buffered logs once dropped
+#[derive(Debug)]
+pub struct LoggingGuard {
+    _file_guard: Option<WorkerGuard>
+}
+
+/// Initiates the global subscriber, with the configured level, console format and log file
+pub fn init_logging(config: &LoggingConfig, console_format: LogFormat, file_enabled: bool) -> Result<LoggingGuard> {
+    let filter = match &config.level {
+        Some(level) => EnvFilter::try_new(level)
+            .with_context(|| format!("Invalid log level '{}'", level))?,
+        None => EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_LEVEL))
+    };
+    let (filter, filter_handle) = reload::Layer::new(filter);
+
+    // Console layer - format depends on configuration
+    let console_layer = config.console.then(|| match console_format {
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_timer(fmt::time::UtcTime::rfc_3339())
+            .with_writer(std::io::stdout)
+            .boxed(),
+        LogFormat::Pretty => fmt::layer()
+            .pretty()
+            .with_timer(fmt::time::UtcTime::rfc_3339())
+            .with_writer(std::io::stdout)
+            .boxed(),
+        LogFormat::Compact => fmt::layer()
+            .compact()
+            .with_timer(fmt::time::UtcTime::rfc_3339())
+            .with_writer(std::io::stdout)
+            .boxed()
+    });
+
+    // File layer - always JSON, for ingestion by log collectors
+    let (file_layer, file_guard) = if file_enabled {
+        let writer = file_writer(config)
+            .with_context(|| format!("Error opening log file {}", config.file_path))?;
+        let (non_blocking, guard) = tracing_appender::non_blocking(writer);
+
+        let layer = fmt::layer()
+            .json()
+            .with_timer(fmt::time::UtcTime::rfc_3339())
+            .with_writer(non_blocking);
+        (Some(layer), Some(guard))
+    } else {
+        (None, None)
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(console_layer)
+        .with(file_layer)
+        .try_init()
+        .context("Error setting global logger")?;
+
+    LOG_FILTER.set(filter_handle)
+        .map_err(|_| anyhow::anyhow!("Log filter is already set"))?;
+
+    Ok(LoggingGuard { _file_guard: file_guard })
+}
+
+/// Replaces the level of the global subscriber, accepting the same directives as RUST_LOG
+pub fn set_log_level(level: &str) -> Result<()> {
+    let filter = EnvFilter::try_new(level)
+        .with_context(|| format!("Invalid log level '{}'", level))?;
+
+    LOG_FILTER
+        .get()
+        .context("Logging is not initiated")?
+        .reload(filter)
+        .context("Error changing log level")?;
+
+    tracing::info!(level=level, "Changed log level");
+
+    Ok(())
+}
+
+/// Creates the writer of the log file, rotated by time or size
+fn file_writer(config: &LoggingConfig) -> Result<Box<dyn Write + Send>> {
+    let path = Path::new(&config.file_path);
+
+    let rotation = match config.rotation {
+        LogRotation::Size => {
+            return Ok(Box::new(SizeRotatingWriter::new(path, config.max_file_bytes, config.max_files)?));
+        },
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY
+    };
+
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let file_name = path.file_name()
+        .context("Log file path has no file name")?
+        .to_string_lossy()
+        .to_string();
+
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(file_name)
+        .max_log_files(config.max_files.max(1))
+        .build(directory)?;
+
+    Ok(Box::new(appender))
+}
+
+/// Writes to a file, renaming it once it reaches a size - `app.log` becomes `app.log.1`,
+/// previous files shift up and files beyond the retained count are deleted
+pub struct SizeRotatingWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64
+}
+
+impl SizeRotatingWriter {
+    pub fn new(path: &Path, max_bytes: u64, max_files: usize) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Error creating log directory")?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes: max_bytes.max(1),
+            max_files,
+            file,
+            written
+        })
+    }
+
+    /// Path of the rotated file with the given index
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        // Oldest file beyond the retained count is dropped, the others shift up
+        let _ = fs::remove_file(self.rotated_path(self.max_files));
+        for index in (1..self.max_files).rev() {
+            let _ = fs::rename(self.rotated_path(index), self.rotated_path(index + 1));
+        }
+
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.written = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty directory unique to the test
+    fn log_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("client-logs-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotates_past_size_keeping_max_files() {
+        let dir = log_dir("rotate");
+        let path = dir.join("app.log");
+        let mut writer = SizeRotatingWriter::new(&path, 10, 2).unwrap();
+
+        for line in 1..=5 {
+            writer.write_all(format!("line{}\n", line).as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(file_names(&dir), vec!["app.log", "app.log.1", "app.log.2"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line5\n");
+        assert_eq!(fs::read_to_string(dir.join("app.log.1")).unwrap(), "line4\n");
+        assert_eq!(fs::read_to_string(dir.join("app.log.2")).unwrap(), "line3\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_writes_within_size_in_one_file() {
+        let dir = log_dir("within");
+        let path = dir.join("app.log");
+        let mut writer = SizeRotatingWriter::new(&path, 12, 2).unwrap();
+
+        writer.write_all(b"line1\n").unwrap();
+        writer.write_all(b"line2\n").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(file_names(&dir), vec!["app.log"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line1\nline2\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncates_without_retained_files() {
+        let dir = log_dir("truncate");
+        let path = dir.join("app.log");
+        let mut writer = SizeRotatingWriter::new(&path, 10, 0).unwrap();
+
+        for line in 1..=3 {
+            writer.write_all(format!("line{}\n", line).as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(file_names(&dir), vec!["app.log"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line3\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn counts_size_of_existing_file() {
+        let dir = log_dir("existing");
+        let path = dir.join("app.log");
+        fs::write(&path, "previous\n").unwrap();
+
+        let mut writer = SizeRotatingWriter::new(&path, 10, 1).unwrap();
+        writer.write_all(b"line1\n").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("app.log.1")).unwrap(), "previous\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line1\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,455 @@
+//! Responsible for application metrics, collected under a single registry
+//! so they can be exported to Prometheus
+
+use once_cell::sync::Lazy;
+use prometheus::core::Collector;
+use prometheus::{GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry};
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+
+// Custom modules
+use crate::utils::config::InferenceModelType;
+
+/// Registry holding all application metrics
+pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+/// Inference errors, labeled by model type and kind of error
+pub static INFERENCE_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("inference_errors_total", "Inference errors by model type and error kind"),
+            &["model_type", "error_kind"]
+        ).expect("Invalid inference errors metric")
+    )
+});
+
+/// Failed model health checks, labeled by model type
+pub static HEALTH_CHECK_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("health_check_failures_total", "Failed model health check inferences by model type"),
+            &["model_type"]
+        ).expect("Invalid health check failures metric")
+    )
+});
+
+/// Frames received per source, including the ones skipped by inf_frame
+pub static FRAMES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("frames_total", "Frames received from the source"),
+            &["source_id"]
+        ).expect("Invalid frames total metric")
+    )
+});
+
+/// Frames successfully processed per source
+pub static FRAMES_SUCCESS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("frames_success_total", "Frames processed successfully"),
+            &["source_id"]
+        ).expect("Invalid frames success metric")
+    )
+});
+
+/// Frames failed or dropped per source
+pub static FRAMES_FAILED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("frames_failed_total", "Frames failed to process or dropped from queue"),
+            &["source_id"]
+        ).expect("Invalid frames failed metric")
+    )
+});
+
+/// Frames skipped by the motion gate per source
+pub static FRAMES_GATED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("frames_gated_total", "Frames skipped due to no motion in scene"),
+            &["source_id"]
+        ).expect("Invalid frames gated metric")
+    )
+});
+
+/// Frames skipped as duplicates of the last processed frame per source
+pub static FRAMES_DEDUPLICATED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("frames_deduplicated_total", "Frames skipped as near-identical to the last processed frame"),
+            &["source_id"]
+        ).expect("Invalid frames deduplicated metric")
+    )
+});
+
+/// Frame results missed by in-process subscribers that fell behind
+pub static RESULTS_LAGGED: Lazy<IntCounter> = Lazy::new(|| {
+    register(
+        IntCounter::new("results_subscriber_lagged_total", "Frame results dropped for lagging in-process subscribers")
+            .expect("Invalid results lagged metric")
+    )
+});
+
+/// Deprecated configuration fields found on startup
+pub static CONFIG_DEPRECATIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new("config_deprecation_warnings", "Deprecated configuration fields found on startup")
+            .expect("Invalid config deprecations metric")
+    )
+});
+
+/// Configuration reloads by result - applied, rejected or failed
+pub static CONFIG_RELOADS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("config_reloads_total", "Configuration reloads by result"),
+            &["result"]
+        ).expect("Invalid config reloads metric")
+    )
+});
+
+/// Kafka messages kept locally after failing to produce
+pub static KAFKA_BUFFERED: Lazy<IntCounter> = Lazy::new(|| {
+    register(
+        IntCounter::new("kafka_messages_buffered_total", "Kafka messages buffered locally for retrying")
+            .expect("Invalid Kafka buffered metric")
+    )
+});
+
+/// Buffered Kafka messages produced on retry
+pub static KAFKA_RETRIED: Lazy<IntCounter> = Lazy::new(|| {
+    register(
+        IntCounter::new("kafka_messages_retried_total", "Buffered Kafka messages produced on retry")
+            .expect("Invalid Kafka retried metric")
+    )
+});
+
+/// Buffered Kafka messages lost to the buffer memory cap
+pub static KAFKA_EVICTED: Lazy<IntCounter> = Lazy::new(|| {
+    register(
+        IntCounter::new("kafka_messages_evicted_total", "Buffered Kafka messages evicted due to the buffer memory cap")
+            .expect("Invalid Kafka evicted metric")
+    )
+});
+
+/// Kafka messages given up on, written to the dead-letter topic or spool directory
+pub static KAFKA_DEAD_LETTERED: Lazy<IntCounter> = Lazy::new(|| {
+    register(
+        IntCounter::new("kafka_dead_lettered", "Kafka messages dead-lettered after repeatedly failing or being oversized")
+            .expect("Invalid Kafka dead-lettered metric")
+    )
+});
+
+/// Kafka messages delivered to brokers, including retried and dead-lettered messages
+pub static KAFKA_PRODUCED: Lazy<IntCounter> = Lazy::new(|| {
+    register(
+        IntCounter::new("kafka_messages_produced_total", "Kafka messages acknowledged by brokers")
+            .expect("Invalid Kafka produced metric")
+    )
+});
+
+/// Failed attempts to produce Kafka messages, labeled by kind of error
+pub static KAFKA_PRODUCE_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("kafka_produce_errors_total", "Failed attempts to produce Kafka messages by error kind"),
+            &["error_kind"]
+        ).expect("Invalid Kafka produce errors metric")
+    )
+});
+
+/// Payload bytes of Kafka messages acknowledged by brokers
+pub static KAFKA_BYTES_SENT: Lazy<IntCounter> = Lazy::new(|| {
+    register(
+        IntCounter::new("kafka_bytes_sent_total", "Payload bytes of Kafka messages acknowledged by brokers")
+            .expect("Invalid Kafka bytes sent metric")
+    )
+});
+
+/// Kafka messages currently waiting in the retry buffer
+pub static KAFKA_RETRY_BUFFER_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new("kafka_retry_buffer_depth", "Kafka messages currently waiting in the retry buffer")
+            .expect("Invalid Kafka retry buffer depth metric")
+    )
+});
+
+/// Latencies of Kafka brokers from rdkafka statistics, labeled by broker and latency kind
+pub static KAFKA_BROKER_LATENCY: Lazy<GaugeVec> = Lazy::new(|| {
+    register(
+        GaugeVec::new(
+            Opts::new("kafka_broker_latency_microseconds", "Kafka broker round-trip and producer queue latencies"),
+            &["broker", "latency"]
+        ).expect("Invalid Kafka broker latency metric")
+    )
+});
+
+/// Frames dropped from a full queue per source
+pub static FRAMES_DROPPED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("frames_dropped_total", "Frames dropped from a full source queue, also counted as failed"),
+            &["source_id"]
+        ).expect("Invalid frames dropped metric")
+    )
+});
+
+/// Frames superseded by a newer frame in latest only queues per source
+pub static FRAMES_SUPERSEDED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("frames_superseded_total", "Frames skipped due to a newer frame arriving to a latest only queue"),
+            &["source_id"]
+        ).expect("Invalid frames superseded metric")
+    )
+});
+
+/// Frames skipped by the inference rate limit per source
+pub static FRAMES_RATE_LIMITED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("frames_rate_limited_total", "Frames skipped due to the max inferences per second of the source"),
+            &["source_id"]
+        ).expect("Invalid frames rate limited metric")
+    )
+});
+
+/// Detections suppressed as already published by a source with an overlapping view
+pub static CROSS_SOURCE_DUPLICATES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("cross_source_duplicate_total", "Detections suppressed as duplicates of detections published by any source"),
+            &["source_id"]
+        ).expect("Invalid cross source duplicates metric")
+    )
+});
+
+/// Latency percentiles of the last stats interval per source and processing stage
+pub static LATENCY_PERCENTILES: Lazy<GaugeVec> = Lazy::new(|| {
+    register(
+        GaugeVec::new(
+            Opts::new("source_latency_percentile_seconds", "Latency percentiles over the last stats interval"),
+            &["source_id", "stage", "quantile"]
+        ).expect("Invalid latency percentiles metric")
+    )
+});
+
+/// Whether the source is delivering frames - 1 from its first frame, 0 once silent for the readiness timeout
+pub static SOURCE_READY: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register(
+        IntGaugeVec::new(
+            Opts::new("source_ready", "Whether the source is delivering frames"),
+            &["source_id"]
+        ).expect("Invalid source ready metric")
+    )
+});
+
+/// Frames currently waiting in the source queue
+pub static QUEUE_DEPTH: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register(
+        IntGaugeVec::new(
+            Opts::new("queue_depth", "Frames waiting in the source queue"),
+            &["source_id"]
+        ).expect("Invalid queue depth metric")
+    )
+});
+
+/// Time frames spent waiting in queue
+pub static QUEUE_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    register(latency_histogram("queue_time_seconds", "Time frames spent in queue", &["source_id"]))
+});
+
+/// Time spent pre-processing frames, per model
+pub static PRE_PROC_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    register(latency_histogram("preprocess_time_seconds", "Time spent pre-processing frames", &["source_id", "model_type"]))
+});
+
+/// Time spent waiting for inference results, per model
+pub static INFERENCE_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    register(latency_histogram("inference_time_seconds", "Time spent on inference requests", &["source_id", "model_type"]))
+});
+
+/// Time spent post-processing inference results, per model
+pub static POST_PROC_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    register(latency_histogram("postprocess_time_seconds", "Time spent post-processing results", &["source_id", "model_type"]))
+});
+
+/// Total time frames took from being queued until results were populated
+pub static PROCESSING_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    register(latency_histogram("processing_time_seconds", "Total frame processing time", &["source_id"]))
+});
+
+/// GPU utilization as reported by the GPU driver
+pub static GPU_UTILIZATION: Lazy<GaugeVec> = Lazy::new(|| {
+    register(
+        GaugeVec::new(
+            Opts::new("gpu_utilization_percent", "GPU compute utilization"),
+            &["gpu_index", "gpu_name", "gpu_uuid"]
+        ).expect("Invalid GPU utilization metric")
+    )
+});
+
+/// GPU memory used as reported by the GPU driver
+pub static GPU_MEMORY_USED: Lazy<GaugeVec> = Lazy::new(|| {
+    register(
+        GaugeVec::new(
+            Opts::new("gpu_memory_used_megabytes", "GPU memory in use"),
+            &["gpu_index", "gpu_name", "gpu_uuid"]
+        ).expect("Invalid GPU memory metric")
+    )
+});
+
+/// Creates a histogram with buckets suited for latencies from ~100us to a few seconds
+fn latency_histogram(name: &str, help: &str, labels: &[&str]) -> HistogramVec {
+    let buckets = prometheus::exponential_buckets(0.0001, 2.0, 16)
+        .expect("Invalid latency buckets");
+
+    HistogramVec::new(
+        HistogramOpts::new(name, help).buckets(buckets),
+        labels
+    ).expect("Invalid latency histogram metric")
+}
+
+/// Converts a duration in microseconds to seconds, the unit used by Prometheus
+pub fn micros_to_secs(micros: u64) -> f64 {
+    micros as f64 / 1_000_000.0
+}
+
+/// Registers a metric in the application registry, returning it for use
+fn register<T: Collector + Clone + 'static>(collector: T) -> T {
+    REGISTRY
+        .register(Box::new(collector.clone()))
+        .expect("Metric is registered more than once");
+    collector
+}
+
+/// Represents the kind of failure that happened during inference
+#[derive(Clone, Copy, Debug)]
+pub enum InferenceErrorKind {
+    Timeout,
+    Transport,
+    ShapeMismatch,
+    PostprocessFailed,
+    Other
+}
+
+impl InferenceErrorKind {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            InferenceErrorKind::Timeout => "Timeout",
+            InferenceErrorKind::Transport => "Transport",
+            InferenceErrorKind::ShapeMismatch => "ShapeMismatch",
+            InferenceErrorKind::PostprocessFailed => "PostprocessFailed",
+            InferenceErrorKind::Other => "Other",
+        }
+    }
+
+    /// Classifies a failed request to Triton by its error message
+    pub fn from_request_error(error: &str) -> Self {
+        let error = error.to_lowercase();
+        if error.contains("timeout") || error.contains("timed out") || error.contains("deadline") {
+            InferenceErrorKind::Timeout
+        } else {
+            InferenceErrorKind::Transport
+        }
+    }
+}
+
+/// Represents the kind of failure that happened while producing to Kafka
+#[derive(Clone, Copy, Debug)]
+pub enum KafkaErrorKind {
+    Timeout,
+    QueueFull,
+    UnknownTopic,
+    MessageTooLarge,
+    Transport,
+    Other
+}
+
+impl KafkaErrorKind {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            KafkaErrorKind::Timeout => "Timeout",
+            KafkaErrorKind::QueueFull => "QueueFull",
+            KafkaErrorKind::UnknownTopic => "UnknownTopic",
+            KafkaErrorKind::MessageTooLarge => "MessageTooLarge",
+            KafkaErrorKind::Transport => "Transport",
+            KafkaErrorKind::Other => "Other",
+        }
+    }
+
+    /// Classifies a failed produce by its rdkafka error code
+    pub fn from_kafka_error(error: &KafkaError) -> Self {
+        match error.rdkafka_error_code() {
+            Some(RDKafkaErrorCode::MessageTimedOut | RDKafkaErrorCode::RequestTimedOut) => KafkaErrorKind::Timeout,
+            Some(RDKafkaErrorCode::QueueFull) => KafkaErrorKind::QueueFull,
+            Some(RDKafkaErrorCode::UnknownTopicOrPartition | RDKafkaErrorCode::UnknownTopic) => KafkaErrorKind::UnknownTopic,
+            Some(RDKafkaErrorCode::MessageSizeTooLarge) => KafkaErrorKind::MessageTooLarge,
+            Some(
+                RDKafkaErrorCode::AllBrokersDown
+                | RDKafkaErrorCode::BrokerTransportFailure
+                | RDKafkaErrorCode::BrokerNotAvailable
+                | RDKafkaErrorCode::NetworkException
+            ) => KafkaErrorKind::Transport,
+            _ => KafkaErrorKind::Other
+        }
+    }
+}
+
+/// Counts a single inference error for the given model type
+pub fn inc_inference_error(model_type: &InferenceModelType, kind: InferenceErrorKind) {
+    INFERENCE_ERRORS
+        .with_label_values(&[model_type.to_string(), kind.to_string()])
+        .inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{Encoder, TextEncoder};
+
+    /// Metrics in the Prometheus text format, as served by the admin server
+    fn exported_metrics() -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn exports_metrics_with_names_and_labels() {
+        FRAMES_TOTAL.with_label_values(&["metrics-test"]).inc_by(3);
+        inc_inference_error(&InferenceModelType::YOLO, InferenceErrorKind::Timeout);
+        GPU_UTILIZATION.with_label_values(&["1", "Test GPU", "GPU-1"]).set(42.0);
+
+        let exported = exported_metrics();
+
+        assert!(exported.contains("frames_total{source_id=\"metrics-test\"} 3"));
+        assert!(exported.contains("inference_errors_total{error_kind=\"Timeout\",model_type=\"YOLO\"}"));
+        assert!(exported.contains("gpu_utilization_percent{gpu_index=\"1\",gpu_name=\"Test GPU\",gpu_uuid=\"GPU-1\"} 42"));
+    }
+
+    #[test]
+    fn exports_latency_buckets_from_100us_to_seconds() {
+        PROCESSING_TIME.with_label_values(&["buckets-test"]).observe(micros_to_secs(1_500));
+
+        let exported = exported_metrics();
+        let bucket = |le: &str, count: u64| {
+            format!("processing_time_seconds_bucket{{source_id=\"buckets-test\",le=\"{}\"}} {}", le, count)
+        };
+
+        assert!(exported.contains(&bucket("0.0001", 0)));
+        assert!(exported.contains(&bucket("0.0008", 0)));
+        assert!(exported.contains(&bucket("0.0016", 1)));
+        assert!(exported.contains(&bucket("3.2768", 1)));
+        assert!(exported.contains(&bucket("+Inf", 1)));
+        assert!(exported.contains("processing_time_seconds_sum{source_id=\"buckets-test\"} 0.0015"));
+    }
+
+    #[test]
+    fn classifies_inference_request_errors() {
+        for error in ["Deadline Exceeded", "request timed out", "Timeout waiting for response"] {
+            assert_eq!(InferenceErrorKind::from_request_error(error).to_string(), "Timeout");
+        }
+        assert_eq!(InferenceErrorKind::from_request_error("connection refused").to_string(), "Transport");
+    }
+}
@@ -0,0 +1,242 @@
+//! Responsible for persisting per-source state across restarts
+//!
+//! Periodically saves the last known detections of each source to a pluggable
+//! store, and restores them on startup so restarts do not look like a fresh scene.
+//! Embedding versions published to Kafka are kept alongside, so version changes survive restarts
+
+use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::interval;
+
+// Custom modules
+use crate::source;
+use crate::utils::kafka;
+use crate::processing::ResultBBOX;
+use crate::utils::config::{AppConfig, StateBackend};
+use crate::utils::shutdown;
+
+/// Represents the last known state of a single source
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SourceState {
+    pub pts: u64,
+    pub detections: Vec<ResultBBOX>,
+    /// Unix timestamp (seconds) of when the state was captured
+    pub updated_at: u64
+}
+
+impl SourceState {
+    pub fn new(pts: u64, detections: Vec<ResultBBOX>) -> Self {
+        let updated_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            pts,
+            detections,
+            updated_at
+        }
+    }
+}
+
+/// Backing store for source states
+pub trait StateStore: Send + Sync {
+    /// Saves the states of all sources, replacing previously saved states
+    fn save(&self, states: &HashMap<String, SourceState>) -> Result<()>;
+
+    /// Loads previously saved states of all sources
+    fn load(&self) -> Result<HashMap<String, SourceState>>;
+
+    /// Saves the last published embedding version of each model, replacing previously saved versions
+    fn save_embedding_versions(&self, versions: &HashMap<String, u32>) -> Result<()>;
+
+    /// Loads previously saved embedding versions of each model
+    fn load_embedding_versions(&self) -> Result<HashMap<String, u32>>;
+}
+
+/// Stores source states as a JSON file on local disk, with embedding versions in a file next to it
+pub struct FileStateStore {
+    path: PathBuf,
+    embedding_versions_path: PathBuf
+}
+
+impl FileStateStore {
+    pub fn new(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        let embedding_versions_path = path.with_extension("embedding_versions.json");
+
+        Self {
+            path,
+            embedding_versions_path
+        }
+    }
+
+    /// Writes a JSON file, replacing the previous file only once fully written
+    fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context("Error creating state directory")?;
+        }
+
+        let data = serde_json::to_vec(value)
+            .context("Error serializing state")?;
+
+        // Write to a temporary file first, so a crash mid-write keeps the previous state intact
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, data)
+            .context("Error writing state file")?;
+        std::fs::rename(&temp_path, path)
+            .context("Error replacing state file")?;
+
+        Ok(())
+    }
+
+    /// Reads a JSON file, returning the default value when it does not exist yet
+    fn read_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+        if !path.exists() {
+            return Ok(T::default());
+        }
+
+        let data = std::fs::read(path)
+            .context("Error reading state file")?;
+
+        serde_json::from_slice(&data)
+            .context("Error parsing state file")
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn save(&self, states: &HashMap<String, SourceState>) -> Result<()> {
+        FileStateStore::write_json(&self.path, states)
+            .context("Error saving source states")
+    }
+
+    fn load(&self) -> Result<HashMap<String, SourceState>> {
+        FileStateStore::read_json(&self.path)
+            .context("Error loading source states")
+    }
+
+    fn save_embedding_versions(&self, versions: &HashMap<String, u32>) -> Result<()> {
+        FileStateStore::write_json(&self.embedding_versions_path, versions)
+            .context("Error saving embedding versions")
+    }
+
+    fn load_embedding_versions(&self) -> Result<HashMap<String, u32>> {
+        FileStateStore::read_json(&self.embedding_versions_path)
+            .context("Error loading embedding versions")
+    }
+}
+
+/// Restores source states and starts persisting them periodically and on shutdown.
+/// Must be called after source processors are initiated
+pub async fn init_state_persistence(app_config: &AppConfig) -> Result<()> {
+    let persistence_config = app_config.persistence_config();
+    if !persistence_config.enabled {
+        return Ok(())
+    }
+
+    let store: Arc<dyn StateStore> = match persistence_config.backend {
+        StateBackend::File => Arc::new(FileStateStore::new(&persistence_config.path))
+    };
+
+    // Restore previous states
+    let states = store.load()
+        .context("Error loading source states")?;
+    let restored = source::restore_source_states(states).await;
+    tracing::info!(restored=restored, "Restored source states");
+
+    // Versions changing across a restart still mark embeddings as requiring migration
+    let embedding_versions = store.load_embedding_versions()
+        .context("Error loading embedding versions")?;
+    kafka::restore_embedding_versions(embedding_versions);
+
+    // Save states periodically
+    let periodic_store = Arc::clone(&store);
+    let save_interval = Duration::from_secs(persistence_config.interval_secs.max(1));
+
+    tokio::spawn(async move {
+        let mut interval = interval(save_interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = save_states(&periodic_store).await {
+                tracing::warn!(
+                    error=e.to_string(),
+                    "Failed to persist source states"
+                );
+            }
+        }
+    });
+
+    // Save states on graceful shutdown
+    shutdown::register_shutdown_hook("source_states", move || {
+        let store = Arc::clone(&store);
+        Box::pin(async move {
+            save_states(&store)
+                .await
+                .context("Failed to persist source states before shutdown")
+        })
+    });
+
+    Ok(())
+}
+
+/// Saves the current states of all sources, and the published embedding versions, to the store
+async fn save_states(store: &Arc<dyn StateStore>) -> Result<()> {
+    let states = source::get_source_states().await;
+    let embedding_versions = kafka::embedding_versions();
+    let store = Arc::clone(store);
+
+    tokio::task::spawn_blocking(move || {
+        store.save(&states)?;
+        store.save_embedding_versions(&embedding_versions)
+    })
+        .await
+        .context("Save states task failed")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_store_restores_saved_states() {
+        let dir = std::env::temp_dir().join(format!("client-states-{}", std::process::id()));
+        let store = FileStateStore::new(dir.join("states.json").to_str().unwrap());
+        assert!(store.load().unwrap().is_empty());
+
+        let detection = ResultBBOX { bbox: [1.0, 2.0, 3.0, 4.0], class: 0, score: 0.9 };
+        let states = HashMap::from([("1".to_string(), SourceState::new(42, vec![detection]))]);
+        store.save(&states).unwrap();
+
+        let restored = store.load().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let state = &restored["1"];
+        assert_eq!(state.pts, 42);
+        assert_eq!(state.detections.len(), 1);
+        assert_eq!(state.detections[0].bbox, [1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn file_store_restores_embedding_versions() {
+        let dir = std::env::temp_dir().join(format!("client-versions-{}", std::process::id()));
+        let store = FileStateStore::new(dir.join("states.json").to_str().unwrap());
+        assert!(store.load_embedding_versions().unwrap().is_empty());
+
+        store.save_embedding_versions(&HashMap::from([("dino".to_string(), 2)])).unwrap();
+        let restored = store.load_embedding_versions().unwrap();
+
+        // Source states are kept apart
+        assert!(store.load().unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(restored, HashMap::from([("dino".to_string(), 2)]));
+    }
+}
@@ -0,0 +1,436 @@
+//! Responsible for protobuf serialization of detection messages published to Kafka
+//!
+//! Messages are defined with prost, mirroring `PROTO_SCHEMA` - the schema registered
+//! to a Confluent compatible schema registry when one is configured. Registered payloads
+//! are prefixed with the Confluent wire-format header, so consumers can resolve their schema
+
+use anyhow::{Result, Context};
+use prost::Message;
+use serde_json::json;
+use std::sync::Arc;
+
+// Custom modules
+use crate::processing::{ResultBBOX, ResultEmbedding, RawFrame, FrameResults};
+
+/// Protobuf schema of all detection messages, must match the message definitions below
+pub const PROTO_SCHEMA: &str = r#"syntax = "proto3";
+
+package detection;
+
+message BBox {
+  repeated float bbox = 1;
+  uint32 class = 2;
+  float score = 3;
+}
+
+message BBoxes {
+  repeated BBox bboxes = 1;
+  uint32 width = 2;
+  uint32 height = 3;
+  optional double fps = 4;
+  string source_id = 5;
+  uint64 pts = 6;
+}
+
+message Embeddings {
+  string source_id = 1;
+  uint32 embedding_version = 2;
+  string model_name = 3;
+  bool migration_required = 4;
+  repeated Embedding embeddings = 5;
+  bytes frame = 6;
+}
+
+message Embedding {
+  repeated float data = 1;
+}
+
+message Detection {
+  uint64 detection_index = 1;
+  repeated float bbox = 2;
+  uint32 class = 3;
+  string class_name = 4;
+  float score = 5;
+  optional uint64 embedding_index = 6;
+  optional Embedding embedding = 7;
+}
+
+message FrameResults {
+  uint64 pts = 1;
+  string source_id = 2;
+  optional string model_name = 3;
+  optional uint32 embedding_version = 4;
+  optional Embedding frame_embedding = 5;
+  repeated Detection detections = 6;
+  bool migration_required = 7;
+}
+
+message FrameResultsBatch {
+  string source_id = 1;
+  uint32 count = 2;
+  repeated FrameResults frames = 3;
+}
+"#;
+
+/// Kinds of messages published, with their index in `PROTO_SCHEMA`
+#[derive(Clone, Copy, Debug)]
+pub enum MessageKind {
+    BBoxes = 1,
+    Embeddings = 2,
+    FrameResults = 5,
+    FrameResultsBatch = 6
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BBoxMessage {
+    #[prost(float, repeated, tag = "1")]
+    pub bbox: Vec<f32>,
+    #[prost(uint32, tag = "2")]
+    pub class: u32,
+    #[prost(float, tag = "3")]
+    pub score: f32
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BBoxesMessage {
+    #[prost(message, repeated, tag = "1")]
+    pub bboxes: Vec<BBoxMessage>,
+    #[prost(uint32, tag = "2")]
+    pub width: u32,
+    #[prost(uint32, tag = "3")]
+    pub height: u32,
+    #[prost(double, optional, tag = "4")]
+    pub fps: Option<f64>,
+    #[prost(string, tag = "5")]
+    pub source_id: String,
+    #[prost(uint64, tag = "6")]
+    pub pts: u64
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct EmbeddingsMessage {
+    #[prost(string, tag = "1")]
+    pub source_id: String,
+    #[prost(uint32, tag = "2")]
+    pub embedding_version: u32,
+    #[prost(string, tag = "3")]
+    pub model_name: String,
+    #[prost(bool, tag = "4")]
+    pub migration_required: bool,
+    #[prost(message, repeated, tag = "5")]
+    pub embeddings: Vec<EmbeddingMessage>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub frame: Vec<u8>
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct EmbeddingMessage {
+    #[prost(float, repeated, tag = "1")]
+    pub data: Vec<f32>
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DetectionMessage {
+    #[prost(uint64, tag = "1")]
+    pub detection_index: u64,
+    #[prost(float, repeated, tag = "2")]
+    pub bbox: Vec<f32>,
+    #[prost(uint32, tag = "3")]
+    pub class: u32,
+    #[prost(string, tag = "4")]
+    pub class_name: String,
+    #[prost(float, tag = "5")]
+    pub score: f32,
+    #[prost(uint64, optional, tag = "6")]
+    pub embedding_index: Option<u64>,
+    #[prost(message, optional, tag = "7")]
+    pub embedding: Option<EmbeddingMessage>
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FrameResultsMessage {
+    #[prost(uint64, tag = "1")]
+    pub pts: u64,
+    #[prost(string, tag = "2")]
+    pub source_id: String,
+    #[prost(string, optional, tag = "3")]
+    pub model_name: Option<String>,
+    #[prost(uint32, optional, tag = "4")]
+    pub embedding_version: Option<u32>,
+    #[prost(message, optional, tag = "5")]
+    pub frame_embedding: Option<EmbeddingMessage>,
+    #[prost(message, repeated, tag = "6")]
+    pub detections: Vec<DetectionMessage>,
+    #[prost(bool, tag = "7")]
+    pub migration_required: bool
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FrameResultsBatchMessage {
+    #[prost(string, tag = "1")]
+    pub source_id: String,
+    #[prost(uint32, tag = "2")]
+    pub count: u32,
+    #[prost(message, repeated, tag = "3")]
+    pub frames: Vec<FrameResultsMessage>
+}
+
+impl From<&ResultBBOX> for BBoxMessage {
+    fn from(bbox: &ResultBBOX) -> Self {
+        Self {
+            bbox: bbox.bbox.to_vec(),
+            class: bbox.class,
+            score: bbox.score
+        }
+    }
+}
+
+/// Encodes bboxes of a frame, along with the resolution they are reported in, the fps of the stream
+/// and the source and pts of the frame
+pub fn encode_bboxes(source_id: &str, frame: &RawFrame, bboxes: &[ResultBBOX], fps: Option<f64>) -> Vec<u8> {
+    BBoxesMessage {
+        bboxes: bboxes.iter().map(BBoxMessage::from).collect(),
+        width: frame.original_width,
+        height: frame.original_height,
+        fps,
+        source_id: source_id.to_string(),
+        pts: frame.pts
+    }.encode_to_vec()
+}
+
+/// Encodes embeddings of a frame, along with the frame itself
+pub fn encode_embeddings(
+    source_id: &str,
+    frame: &RawFrame,
+    embeddings: &[ResultEmbedding],
+    migration_required: bool
+) -> Vec<u8> {
+    let (embedding_version, model_name) = embeddings
+        .first()
+        .map(|e| (e.embedding_version, e.model_name.clone()))
+        .unwrap_or_default();
+
+    EmbeddingsMessage {
+        source_id: source_id.to_string(),
+        embedding_version,
+        model_name,
+        migration_required,
+        embeddings: embeddings
+            .iter()
+            .map(|e| EmbeddingMessage { data: e.data.clone() })
+            .collect(),
+        frame: frame.data.clone()
+    }.encode_to_vec()
+}
+
+/// Encodes combined results of a frame
+pub fn encode_results(results: &FrameResults, migration_required: bool) -> Vec<u8> {
+    results_message(results, migration_required).encode_to_vec()
+}
+
+/// Encodes combined results of multiple frames of a source, along with whether each requires migration
+pub fn encode_results_batch(source_id: &str, frames: &[(Arc<FrameResults>, bool)]) -> Vec<u8> {
+    FrameResultsBatchMessage {
+        source_id: source_id.to_string(),
+        count: frames.len() as u32,
+        frames: frames
+            .iter()
+            .map(|(results, migration_required)| results_message(results, *migration_required))
+            .collect()
+    }.encode_to_vec()
+}
+
+fn results_message(results: &FrameResults, migration_required: bool) -> FrameResultsMessage {
+    FrameResultsMessage {
+        pts: results.pts,
+        source_id: results.source_id.clone(),
+        model_name: results.model_name.clone(),
+        embedding_version: results.embedding_version,
+        frame_embedding: results.frame_embedding
+            .as_ref()
+            .map(|data| EmbeddingMessage { data: data.clone() }),
+        detections: results.detections
+            .iter()
+            .map(|detection| DetectionMessage {
+                detection_index: detection.detection_index as u64,
+                bbox: detection.bbox.to_vec(),
+                class: detection.class,
+                class_name: detection.class_name.to_string(),
+                score: detection.score,
+                embedding_index: detection.embedding_index.map(|index| index as u64),
+                embedding: detection.embedding
+                    .as_ref()
+                    .map(|data| EmbeddingMessage { data: data.clone() })
+            })
+            .collect(),
+        migration_required
+    }
+}
+
+/// Prefixes a payload with the Confluent wire-format header:
+/// magic byte, big-endian schema id and the index of the message in the schema
+pub fn frame_payload(schema_id: u32, kind: MessageKind, payload: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(payload.len() + 7);
+    framed.push(0u8);
+    framed.extend_from_slice(&schema_id.to_be_bytes());
+
+    // Message indexes are zigzag varints - a count followed by the path of the message.
+    // Top-level messages have a single index, fitting in one byte each
+    framed.push(2);
+    framed.push((kind as u8) << 1);
+
+    framed.extend_from_slice(payload);
+    framed
+}
+
+/// Registers `PROTO_SCHEMA` for the values of a topic, returning its schema id
+///
+/// Registering an already registered schema returns its existing id
+pub async fn register_schema(schema_registry_url: &str, topic: &str) -> Result<u32> {
+    let url = format!(
+        "{}/subjects/{}-value/versions",
+        schema_registry_url.trim_end_matches('/'),
+        topic
+    );
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .header("Content-Type", "application/vnd.schemaregistry.v1+json")
+        .json(&json!({
+            "schemaType": "PROTOBUF",
+            "schema": PROTO_SCHEMA
+        }))
+        .send()
+        .await
+        .with_context(|| format!("Error sending schema registration to {}", url))?
+        .error_for_status()
+        .with_context(|| format!("Schema registry rejected schema of topic '{}'", topic))?;
+
+    let body: serde_json::Value = response.json()
+        .await
+        .context("Error reading schema registry response")?;
+
+    body["id"]
+        .as_u64()
+        .map(|id| id as u32)
+        .context("Schema registry response has no schema id")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> RawFrame {
+        RawFrame {
+            data: vec![1, 2, 3],
+            height: 720,
+            width: 1280,
+            original_height: 2160,
+            original_width: 3840,
+            pts: 4200,
+            added: tokio::time::Instant::now()
+        }
+    }
+
+    #[test]
+    fn bboxes_carry_source_and_pts() {
+        let bboxes = [ResultBBOX { bbox: [1.0, 2.0, 3.0, 4.0], class: 2, score: 0.75 }];
+
+        let message = BBoxesMessage::decode(encode_bboxes("lobby-east", &frame(), &bboxes, Some(25.0)).as_slice()).unwrap();
+
+        assert_eq!(message.source_id, "lobby-east");
+        assert_eq!(message.pts, 4200);
+        assert_eq!((message.width, message.height, message.fps), (3840, 2160, Some(25.0)));
+        assert_eq!(message.bboxes, vec![BBoxMessage { bbox: vec![1.0, 2.0, 3.0, 4.0], class: 2, score: 0.75 }]);
+    }
+
+    fn embedding(data: Vec<f32>) -> ResultEmbedding {
+        ResultEmbedding { data, embedding_version: 2, model_name: "dino".to_string() }
+    }
+
+    fn results() -> FrameResults {
+        let bboxes = [
+            ResultBBOX { bbox: [1.0, 2.0, 3.0, 4.0], class: 2, score: 0.75 },
+            ResultBBOX { bbox: [5.0, 6.0, 7.0, 8.0], class: 0, score: 0.5 }
+        ];
+        let embeddings = [embedding(vec![0.1, 0.2]), embedding(vec![0.3, 0.4]), embedding(vec![0.5, 0.6])];
+
+        FrameResults::new("lobby-east", &frame(), &bboxes, Some(&embeddings)).unwrap()
+    }
+
+    #[test]
+    fn embeddings_round_trip() {
+        let embeddings = [embedding(vec![0.1, 0.2]), embedding(vec![0.3, 0.4])];
+
+        let message = EmbeddingsMessage::decode(encode_embeddings("lobby-east", &frame(), &embeddings, true).as_slice()).unwrap();
+
+        assert_eq!(message, EmbeddingsMessage {
+            source_id: "lobby-east".to_string(),
+            embedding_version: 2,
+            model_name: "dino".to_string(),
+            migration_required: true,
+            embeddings: vec![EmbeddingMessage { data: vec![0.1, 0.2] }, EmbeddingMessage { data: vec![0.3, 0.4] }],
+            frame: vec![1, 2, 3]
+        });
+    }
+
+    #[test]
+    fn results_round_trip() {
+        let results = results();
+
+        let message = FrameResultsMessage::decode(encode_results(&results, true).as_slice()).unwrap();
+
+        assert_eq!((message.pts, message.source_id.as_str(), message.migration_required), (4200, "lobby-east", true));
+        assert_eq!((message.model_name.as_deref(), message.embedding_version), (Some("dino"), Some(2)));
+        assert_eq!(message.frame_embedding, Some(EmbeddingMessage { data: vec![0.1, 0.2] }));
+        assert_eq!(message.detections, vec![
+            DetectionMessage {
+                detection_index: 0,
+                bbox: vec![1.0, 2.0, 3.0, 4.0],
+                class: 2,
+                class_name: "car".to_string(),
+                score: 0.75,
+                embedding_index: Some(1),
+                embedding: Some(EmbeddingMessage { data: vec![0.3, 0.4] })
+            },
+            DetectionMessage {
+                detection_index: 1,
+                bbox: vec![5.0, 6.0, 7.0, 8.0],
+                class: 0,
+                class_name: "person".to_string(),
+                score: 0.5,
+                embedding_index: Some(2),
+                embedding: Some(EmbeddingMessage { data: vec![0.5, 0.6] })
+            }
+        ]);
+    }
+
+    #[test]
+    fn results_without_embeddings_round_trip() {
+        let bboxes = [ResultBBOX { bbox: [1.0, 2.0, 3.0, 4.0], class: 2, score: 0.75 }];
+        let results = FrameResults::new("lobby-east", &frame(), &bboxes, None).unwrap();
+
+        let message = FrameResultsMessage::decode(encode_results(&results, false).as_slice()).unwrap();
+
+        assert_eq!((message.model_name, message.embedding_version, message.frame_embedding), (None, None, None));
+        assert_eq!(message.detections.len(), 1);
+        assert_eq!((message.detections[0].embedding_index, &message.detections[0].embedding), (None, &None));
+    }
+
+    #[test]
+    fn results_batch_round_trip() {
+        let frames = [(Arc::new(results()), false), (Arc::new(results()), true)];
+
+        let message = FrameResultsBatchMessage::decode(encode_results_batch("lobby-east", &frames).as_slice()).unwrap();
+
+        assert_eq!((message.source_id.as_str(), message.count), ("lobby-east", 2));
+        assert_eq!(message.frames, vec![results_message(&frames[0].0, false), results_message(&frames[1].0, true)]);
+    }
+
+    #[test]
+    fn frames_payloads_with_wire_format_header() {
+        let framed = frame_payload(0x01020304, MessageKind::FrameResults, &[9, 9]);
+
+        assert_eq!(framed, vec![0, 1, 2, 3, 4, 2, 10, 9, 9]);
+    }
+}
@@ -1,7 +1,20 @@
 use std::collections::VecDeque;
 use std::sync::Arc;
-use tokio::sync::{Mutex, Notify};
+use tokio::sync::{Mutex, MutexGuard, Notify};
 use anyhow::{Result};
+use serde::{Deserialize, Serialize};
+
+/// Represents what happens to items sent to a full queue
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverflowPolicy {
+    /// Drops the oldest queued item to make room for the new one
+    #[default]
+    DropOldest,
+    /// Drops the new item, keeping the queued ones
+    DropNewest,
+    /// Waits for room in the queue. Non-blocking sends fail instead
+    Block
+}
 
 #[allow(dead_code)]
 pub struct FixedSizeQueue<T> {
@@ -14,24 +27,28 @@ pub struct FixedSizeQueue<T> {
 }
 
 impl<T> FixedSizeQueue<T> {
-    pub fn new<F>(capacity: usize, on_drop: Option<F>) -> Self 
+    pub fn new<F>(capacity: usize, overflow_policy: OverflowPolicy, on_drop: Option<F>) -> Self 
     where
         F: Fn(T) + Send + Sync + 'static
     {
         let queue = Arc::new(Mutex::new(VecDeque::with_capacity(capacity)));
         let notify = Arc::new(Notify::new());
+        let space_notify = Arc::new(Notify::new());
         let on_drop_arc = on_drop.map(|f| Arc::new(f) as Arc<dyn Fn(T) + Send + Sync>);
         
         let sender = FixedSizeQueueSender {
             queue: Arc::clone(&queue),
             notify: Arc::clone(&notify),
+            space_notify: Arc::clone(&space_notify),
             capacity,
+            overflow_policy,
             on_drop: on_drop_arc.clone(),
         };
         
         let receiver = FixedSizeQueueReceiver {
             queue: Arc::clone(&queue),
-            notify: Arc::clone(&notify)
+            notify: Arc::clone(&notify),
+            space_notify
         };
 
         Self {
@@ -43,12 +60,24 @@ impl<T> FixedSizeQueue<T> {
             receiver
         }
     }
+
+    /// Returns the amount of items currently waiting in queue
+    pub async fn len(&self) -> usize {
+        self.queue.lock().await.len()
+    }
+
+    /// Returns the maximum amount of items in queue
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
 }
 
 pub struct FixedSizeQueueSender<T> {
     queue: Arc<Mutex<VecDeque<T>>>,
     notify: Arc<Notify>,
+    space_notify: Arc<Notify>,
     capacity: usize,
+    overflow_policy: OverflowPolicy,
     on_drop: Option<Arc<dyn Fn(T) + Send + Sync>>,
 }
 
@@ -57,17 +86,14 @@ impl<T> FixedSizeQueueSender<T> {
         // Try to acquire the lock without blocking
         match self.queue.try_lock() {
             Ok(mut queue) => {
-                // If at capacity, remove the oldest item (front of queue)
-                if queue.len() >= self.capacity {
-                    if let Some(dropped_item) = queue.pop_front() {
-                        if let Some(ref callback) = self.on_drop {
-                            callback(dropped_item);
-                        }
-                    }
+                // Blocking is not possible here, the item is rejected instead
+                if queue.len() >= self.capacity && self.overflow_policy == OverflowPolicy::Block {
+                    anyhow::bail!("Queue is full")
                 }
-                
-                queue.push_back(item);
+
+                let dropped_item = self.push(&mut queue, item);
                 drop(queue); // Release lock before notify
+                self.dropped(dropped_item);
                 self.notify.notify_one();
                 Ok(())
             }
@@ -78,38 +104,243 @@ impl<T> FixedSizeQueueSender<T> {
     // Keep the async version too if you need it elsewhere
     pub async fn send_async(&self, item: T) {
         let mut queue = self.queue.lock().await;
-        
-        if queue.len() >= self.capacity {
-            if let Some(dropped_item) = queue.pop_front() {
-                if let Some(ref callback) = self.on_drop {
-                    callback(dropped_item);
-                }
+
+        // Wait for the receiver to make room
+        if self.overflow_policy == OverflowPolicy::Block {
+            while queue.len() >= self.capacity {
+                let space = self.space_notify.notified();
+                drop(queue);
+                space.await;
+                queue = self.queue.lock().await;
             }
         }
-        
-        queue.push_back(item);
+
+        let dropped_item = self.push(&mut queue, item);
         drop(queue);
+        self.dropped(dropped_item);
         self.notify.notify_one();
     }
+
+    /// Pushes an item according to the overflow policy, returning the item dropped to fit it, if any.
+    /// When the new item itself is dropped, nothing is pushed
+    fn push(&self, queue: &mut VecDeque<T>, item: T) -> Option<T> {
+        if queue.len() < self.capacity {
+            queue.push_back(item);
+            return None;
+        }
+
+        match self.overflow_policy {
+            OverflowPolicy::DropNewest => Some(item),
+            OverflowPolicy::DropOldest | OverflowPolicy::Block => {
+                let dropped_item = queue.pop_front();
+                queue.push_back(item);
+                dropped_item
+            }
+        }
+    }
+
+    fn dropped(&self, dropped_item: Option<T>) {
+        if let (Some(dropped_item), Some(callback)) = (dropped_item, &self.on_drop) {
+            callback(dropped_item);
+        }
+    }
 }
 
 pub struct FixedSizeQueueReceiver<T> {
     queue: Arc<Mutex<VecDeque<T>>>,
     notify: Arc<Notify>,
+    space_notify: Arc<Notify>
 }
 
 impl<T> FixedSizeQueueReceiver<T> {
+    /// Waits for the next item of the queue
+    ///
+    /// Cancellation-safe - the notification is registered before checking the queue,
+    /// so items sent in between are never missed and no item is lost when cancelled
     pub async fn recv(&self) -> Option<T> {
         loop {
-            let mut queue = self.queue.lock().await;
-            if let Some(item) = queue.pop_front() {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(item) = self.pop(self.queue.lock().await) {
                 return Some(item);
             }
-            
+
             // Queue is empty, wait for notification
-            let notified = self.notify.notified();
-            drop(queue); // Release lock before waiting
             notified.await;
         }
     }
-}
\ No newline at end of file
+
+    /// Returns the next item of the queue without waiting, None when empty or currently locked
+    pub fn try_recv(&self) -> Option<T> {
+        let queue = self.queue.try_lock().ok()?;
+        self.pop(queue)
+    }
+
+    fn pop(&self, mut queue: MutexGuard<'_, VecDeque<T>>) -> Option<T> {
+        let item = queue.pop_front();
+        drop(queue); // Release lock before notify
+
+        if item.is_some() {
+            self.space_notify.notify_one();
+        }
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    /// Queue of the given capacity and policy, along with the items it dropped
+    fn queue(capacity: usize, overflow_policy: OverflowPolicy) -> (FixedSizeQueue<u32>, Arc<StdMutex<Vec<u32>>>) {
+        let dropped = Arc::new(StdMutex::new(Vec::new()));
+        let on_drop = {
+            let dropped = Arc::clone(&dropped);
+            move |item: u32| dropped.lock().unwrap().push(item)
+        };
+
+        (FixedSizeQueue::new(capacity, overflow_policy, Some(on_drop)), dropped)
+    }
+
+    fn drain(queue: &FixedSizeQueue<u32>) -> Vec<u32> {
+        std::iter::from_fn(|| queue.receiver.try_recv()).collect()
+    }
+
+    #[tokio::test]
+    async fn drops_oldest_items_when_full() {
+        let (queue, dropped) = queue(2, OverflowPolicy::DropOldest);
+
+        for item in 1..=4 {
+            queue.sender.send_sync(item).unwrap();
+        }
+
+        assert_eq!(queue.len().await, 2);
+        assert_eq!(drain(&queue), vec![3, 4]);
+        assert_eq!(*dropped.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn drops_newest_items_when_full() {
+        let (queue, dropped) = queue(2, OverflowPolicy::DropNewest);
+
+        for item in 1..=4 {
+            queue.sender.send_async(item).await;
+        }
+
+        assert_eq!(drain(&queue), vec![1, 2]);
+        assert_eq!(*dropped.lock().unwrap(), vec![3, 4]);
+    }
+
+    #[tokio::test]
+    async fn blocks_senders_until_room() {
+        let (queue, dropped) = queue(1, OverflowPolicy::Block);
+        let queue = Arc::new(queue);
+        queue.sender.send_sync(1).unwrap();
+
+        assert!(queue.sender.send_sync(2).is_err(), "non-blocking sends fail when full");
+
+        let sender_queue = Arc::clone(&queue);
+        let send = tokio::spawn(async move { sender_queue.sender.send_async(3).await });
+        tokio::task::yield_now().await;
+        assert!(!send.is_finished());
+
+        assert_eq!(queue.receiver.recv().await, Some(1));
+        send.await.unwrap();
+        assert_eq!(drain(&queue), vec![3]);
+        assert!(dropped.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancelled_receives_lose_no_items() {
+        let (queue, _) = queue(4, OverflowPolicy::DropOldest);
+        let queue = Arc::new(queue);
+
+        let cancelled = tokio::time::timeout(Duration::from_millis(10), queue.receiver.recv()).await;
+        assert!(cancelled.is_err());
+
+        let receiver_queue = Arc::clone(&queue);
+        let receive = tokio::spawn(async move { receiver_queue.receiver.recv().await });
+        tokio::task::yield_now().await;
+        queue.sender.send_sync(1).unwrap();
+
+        assert_eq!(receive.await.unwrap(), Some(1));
+        assert_eq!(queue.receiver.try_recv(), None);
+    }
+
+    /// Sends items from many tasks while many tasks receive them, returning the received and dropped items
+    async fn send_concurrently(overflow_policy: OverflowPolicy, senders: u32, receivers: usize, items: u32) -> (Vec<u32>, Vec<u32>) {
+        let (queue, dropped) = queue(4, overflow_policy);
+        let queue = Arc::new(queue);
+        let senders_done = Arc::new(AtomicBool::new(false));
+
+        let receive_tasks: Vec<_> = (0..receivers)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let senders_done = Arc::clone(&senders_done);
+                tokio::spawn(async move {
+                    let mut received = Vec::new();
+                    loop {
+                        match tokio::time::timeout(Duration::from_millis(20), queue.receiver.recv()).await {
+                            Ok(Some(item)) => received.push(item),
+                            Ok(None) => break,
+                            Err(_) if senders_done.load(Ordering::SeqCst) => break,
+                            Err(_) => continue
+                        }
+                    }
+                    received
+                })
+            })
+            .collect();
+
+        let send_tasks: Vec<_> = (0..senders)
+            .map(|sender| {
+                let queue = Arc::clone(&queue);
+                tokio::spawn(async move {
+                    for item in (sender * items)..((sender + 1) * items) {
+                        queue.sender.send_async(item).await;
+                        if item % 16 == 0 {
+                            tokio::task::yield_now().await;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for send in send_tasks {
+            send.await.unwrap();
+        }
+        senders_done.store(true, Ordering::SeqCst);
+
+        let mut received = Vec::new();
+        for receive in receive_tasks {
+            received.extend(receive.await.unwrap());
+        }
+        received.extend(drain(&queue));
+
+        let dropped = dropped.lock().unwrap().clone();
+        (received, dropped)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn delivers_or_drops_every_item_exactly_once_under_contention() {
+        const SENDERS: u32 = 8;
+        const ITEMS: u32 = 2000;
+
+        for overflow_policy in [OverflowPolicy::DropOldest, OverflowPolicy::DropNewest, OverflowPolicy::Block] {
+            let (received, dropped) = send_concurrently(overflow_policy, SENDERS, 4, ITEMS).await;
+
+            let mut items: Vec<u32> = received.iter().chain(dropped.iter()).copied().collect();
+            items.sort_unstable();
+            assert_eq!(items, (0..SENDERS * ITEMS).collect::<Vec<_>>(), "{:?} lost or duplicated items", overflow_policy);
+
+            if overflow_policy == OverflowPolicy::Block {
+                assert!(dropped.is_empty(), "blocking senders never drop items");
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Lock-free rate limiter, used to cap inferences per second of a source
+//!
+//! Implemented as a token bucket holding a single token, tracked by the
+//! theoretical arrival time of the next permitted event (GCRA). The whole state
+//! is a single atomic timestamp, updated with compare-exchange
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub struct RateLimiter {
+    start: Instant,
+    /// Nanoseconds between permitted events
+    interval: u64,
+    /// Nanoseconds since start at which the next event is permitted
+    next_allowed: AtomicU64
+}
+
+impl RateLimiter {
+    /// Creates a limiter permitting at most the given amount of events per second
+    pub fn new(max_per_sec: f64) -> Self {
+        // Rates too low for a Duration never permit more than the first event
+        let interval = Duration::try_from_secs_f64(1.00 / max_per_sec.max(f64::MIN_POSITIVE))
+            .unwrap_or(Duration::MAX);
+
+        Self {
+            start: Instant::now(),
+            interval: interval.as_nanos().min(u64::MAX as u128) as u64,
+            next_allowed: AtomicU64::new(0)
+        }
+    }
+
+    /// Returns whether an event is permitted now, consuming the permit
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    /// Returns whether an event is permitted at the given time, consuming the permit
+    pub fn try_acquire_at(&self, now: Instant) -> bool {
+        let now = now.saturating_duration_since(self.start).as_nanos() as u64;
+        let mut next_allowed = self.next_allowed.load(Ordering::Relaxed);
+
+        loop {
+            if next_allowed > now {
+                return false;
+            }
+
+            // Schedule from now, so idle time does not accumulate into bursts
+            match self.next_allowed.compare_exchange_weak(
+                next_allowed,
+                now.saturating_add(self.interval),
+                Ordering::Relaxed,
+                Ordering::Relaxed
+            ) {
+                Ok(_) => return true,
+                Err(current) => next_allowed = current
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permits_single_event_per_interval() {
+        let limiter = RateLimiter::new(1.00);
+
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+    }
+
+    #[test]
+    fn permits_events_once_interval_passes() {
+        let limiter = RateLimiter::new(50.00);
+        assert!(limiter.try_acquire());
+
+        std::thread::sleep(Duration::from_millis(25));
+
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+    }
+
+    #[test]
+    fn idle_time_does_not_accumulate_bursts() {
+        let limiter = RateLimiter::new(50.00);
+        std::thread::sleep(Duration::from_millis(100));
+
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+    }
+
+    #[test]
+    fn handles_tiny_rates() {
+        let limiter = RateLimiter::new(1e-300);
+
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_60_fps_arrivals() {
+        let frame_interval = Duration::from_secs_f64(1.00 / 60.00);
+
+        // Frames are permitted on the first arrival after each interval, so rates
+        // not dividing 60 are rounded down to the next divisor
+        let cases = [
+            (1.00, 10),
+            (10.00, 100),
+            (20.00, 200),
+            (25.00, 200),
+            (30.00, 300),
+            (60.00, 600),
+            (120.00, 600)
+        ];
+        for (max_per_sec, expected) in cases {
+            let limiter = RateLimiter::new(max_per_sec);
+            let start = Instant::now();
+
+            let permitted = (0..600)
+                .filter(|&frame| limiter.try_acquire_at(start + frame_interval * frame))
+                .count();
+
+            assert_eq!(permitted, expected, "max_per_sec={}", max_per_sec);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn follows_paused_clock() {
+        let limiter = RateLimiter::new(60.00);
+        let frame_interval = Duration::from_secs_f64(1.00 / 60.00);
+
+        let mut permitted = 0;
+        for _ in 0..60 {
+            permitted += limiter.try_acquire() as u32;
+            permitted += limiter.try_acquire() as u32;
+            tokio::time::advance(frame_interval).await;
+        }
+
+        assert_eq!(permitted, 60);
+    }
+
+    #[test]
+    fn permits_single_concurrent_event() {
+        let limiter = RateLimiter::new(1.00);
+
+        let permitted = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| limiter.try_acquire()))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .filter(|permitted| *permitted)
+                .count()
+        });
+
+        assert_eq!(permitted, 1);
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::RwLock;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use tokio::sync::OnceCell;
+
+/// Runs a single step of the graceful shutdown
+pub type ShutdownHook = Box<dyn Fn() -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// Steps of the graceful shutdown, run in registration order
+static SHUTDOWN_HOOKS: Lazy<RwLock<Vec<(&'static str, ShutdownHook)>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Set once the shutdown hooks ran, so they run a single time whichever path triggers them
+static SHUTDOWN: OnceCell<()> = OnceCell::const_new();
+
+/// Registers a step of the graceful shutdown, after the previously registered steps
+pub fn register_shutdown_hook<F>(name: &'static str, hook: F)
+where
+    F: Fn() -> BoxFuture<'static, Result<()>> + Send + Sync + 'static
+{
+    SHUTDOWN_HOOKS.write().unwrap().push((name, Box::new(hook)));
+}
+
+/// Runs all shutdown hooks in registration order, a failed step does not prevent the following ones.
+/// Hooks run once - concurrent and later calls wait for the first run to finish
+pub async fn run_shutdown_hooks() {
+    SHUTDOWN.get_or_init(|| async {
+        // Hooks are started without holding the lock across awaits
+        let steps: Vec<(&'static str, BoxFuture<'static, Result<()>>)> = SHUTDOWN_HOOKS
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, hook)| (*name, hook()))
+            .collect();
+
+        for (name, step) in steps {
+            match step.await {
+                Ok(_) => tracing::info!(step=name, "Completed shutdown step"),
+                Err(e) => tracing::error!(
+                    step=name,
+                    error=format!("{:#}", e),
+                    "Error running shutdown step"
+                )
+            }
+        }
+    }).await;
+}
+
+/// Runs the shutdown hooks and exits once the application is interrupted or terminated
+pub fn init_shutdown_handler() {
+    tokio::spawn(async move {
+        if let Err(e) = shutdown_signal().await {
+            tracing::error!(error=e.to_string(), "Error listening for shutdown signals");
+            return;
+        }
+
+        tracing::info!("Received shutdown signal, shutting down gracefully");
+        run_shutdown_hooks().await;
+        std::process::exit(0);
+    });
+}
+
+/// Waits for SIGINT, or SIGTERM on unix
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn runs_hooks_once_in_registration_order() {
+        let steps = Arc::new(Mutex::new(Vec::new()));
+
+        for name in ["result_batches", "kafka"] {
+            let steps = Arc::clone(&steps);
+            register_shutdown_hook(name, move || {
+                let steps = Arc::clone(&steps);
+                Box::pin(async move {
+                    steps.lock().unwrap().push(name);
+                    anyhow::ensure!(name != "result_batches", "failing step");
+                    Ok(())
+                })
+            });
+        }
+
+        run_shutdown_hooks().await;
+        run_shutdown_hooks().await;
+
+        assert_eq!(*steps.lock().unwrap(), vec!["result_batches", "kafka"]);
+    }
+}
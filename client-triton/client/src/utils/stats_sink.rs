@@ -0,0 +1,237 @@
+//! Responsible for persisting source statistics to local files for offline analysis
+//!
+//! Statistics are appended as JSON lines to a size-capped file, rotated into a
+//! fixed amount of retained files. Writing happens on a dedicated thread, so
+//! reporting statistics never blocks on disk
+
+use anyhow::{Result, Context};
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use tokio::sync::OnceCell;
+
+// Custom modules
+use crate::utils::config::{AppConfig, StatsSinkConfig};
+
+// Variables
+pub static STATS_SINK: OnceCell<StatsSink> = OnceCell::const_new();
+const STATS_FILE_NAME: &str = "stats.jsonl";
+const MAX_PENDING_RECORDS: usize = 1024;
+
+/// Returns the stats sink, if enabled
+pub fn get_stats_sink() -> Option<&'static StatsSink> {
+    STATS_SINK.get()
+}
+
+/// Initiates the stats sink, when enabled in config
+pub fn init_stats_sink(app_config: &AppConfig) -> Result<()> {
+    let stats_sink_config = app_config.stats_sink_config();
+    if !stats_sink_config.enabled {
+        return Ok(())
+    }
+
+    let sink = StatsSink::new(stats_sink_config)
+        .context("Error creating stats sink")?;
+
+    STATS_SINK.set(sink)
+        .map_err(|_| anyhow::anyhow!("Stats sink is already initiated"))?;
+
+    Ok(())
+}
+
+/// Sends statistics records to a dedicated writer thread
+pub struct StatsSink {
+    sender: SyncSender<Value>
+}
+
+impl StatsSink {
+    pub fn new(config: &StatsSinkConfig) -> Result<Self> {
+        let mut writer = RotatingWriter::new(
+            Path::new(&config.directory),
+            config.max_file_bytes,
+            config.max_files
+        )
+            .context("Error opening stats file")?;
+
+        let (sender, receiver) = mpsc::sync_channel::<Value>(MAX_PENDING_RECORDS);
+
+        std::thread::spawn(move || {
+            for record in receiver {
+                if let Err(e) = writer.write_record(&record) {
+                    tracing::warn!(
+                        error=e.to_string(),
+                        "Failed to write statistics record"
+                    );
+                }
+            }
+        });
+
+        Ok(Self { sender })
+    }
+
+    /// Queues a record for writing, dropping it when the writer falls behind
+    pub fn record(&self, record: Value) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(record) {
+            tracing::warn!("Stats sink is full, dropping statistics record");
+        }
+    }
+}
+
+/// Appends JSON lines to a file, rotating it by size
+///
+/// The active file is `stats.jsonl`, rotated files are `stats.jsonl.1` (newest)
+/// up to `stats.jsonl.<max_files>` (oldest)
+struct RotatingWriter {
+    directory: PathBuf,
+    max_file_bytes: u64,
+    max_files: usize,
+    file: File,
+    size: u64
+}
+
+impl RotatingWriter {
+    fn new(directory: &Path, max_file_bytes: u64, max_files: usize) -> Result<Self> {
+        std::fs::create_dir_all(directory)
+            .context("Error creating stats directory")?;
+
+        let (file, size) = RotatingWriter::open(&directory.join(STATS_FILE_NAME))?;
+
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            max_file_bytes,
+            max_files,
+            file,
+            size
+        })
+    }
+
+    /// Opens the active file for appending.
+    /// A partial last line, left by a crash mid-write, is terminated so new lines stay well-formed
+    fn open(path: &Path) -> Result<(File, u64)> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .context("Error opening stats file")?;
+
+        let mut size = file.metadata()
+            .context("Error reading stats file metadata")?
+            .len();
+
+        if size > 0 {
+            let mut last_byte = [0u8; 1];
+            file.seek(SeekFrom::End(-1))
+                .and_then(|_| file.read_exact(&mut last_byte))
+                .context("Error reading stats file")?;
+
+            if last_byte[0] != b'\n' {
+                file.write_all(b"\n")
+                    .context("Error terminating partial stats line")?;
+                size += 1;
+            }
+        }
+
+        Ok((file, size))
+    }
+
+    fn file_path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.directory.join(STATS_FILE_NAME),
+            _ => self.directory.join(format!("{}.{}", STATS_FILE_NAME, index))
+        }
+    }
+
+    /// Shifts rotated files by one, dropping the oldest, and starts a new active file
+    fn rotate(&mut self) -> Result<()> {
+        if self.max_files == 0 {
+            std::fs::remove_file(self.file_path(0))
+                .context("Error removing stats file")?;
+        } else {
+            for index in (0..self.max_files).rev() {
+                let from = self.file_path(index);
+                if from.exists() {
+                    std::fs::rename(&from, self.file_path(index + 1))
+                        .context("Error rotating stats file")?;
+                }
+            }
+        }
+
+        let (file, size) = RotatingWriter::open(&self.file_path(0))?;
+        self.file = file;
+        self.size = size;
+
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        let mut line = serde_json::to_vec(record)
+            .context("Error serializing statistics record")?;
+        line.push(b'\n');
+
+        if self.size > 0 && self.size + line.len() as u64 > self.max_file_bytes {
+            self.rotate()?;
+        }
+
+        // Records of all sources are written by a single thread, a whole line at a time
+        self.file.write_all(&line)
+            .context("Error writing stats file")?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Empty directory unique to the test
+    fn stats_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("client-stats-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn read_lines(path: &Path) -> Vec<Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rotates_files_by_size() {
+        let directory = stats_directory("rotate");
+        let record = json!({"source_id": "1", "fps": 25});
+        let record_bytes = serde_json::to_vec(&record).unwrap().len() as u64 + 1;
+        let mut writer = RotatingWriter::new(&directory, record_bytes * 2, 2).unwrap();
+
+        for _ in 0..7 {
+            writer.write_record(&record).unwrap();
+        }
+
+        assert_eq!(read_lines(&writer.file_path(0)).len(), 1);
+        assert_eq!(read_lines(&writer.file_path(1)).len(), 2);
+        assert_eq!(read_lines(&writer.file_path(2)).len(), 2);
+        assert!(!writer.file_path(3).exists(), "oldest file is dropped");
+    }
+
+    #[test]
+    fn terminates_partial_last_line() {
+        let directory = stats_directory("partial");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join(STATS_FILE_NAME), b"{\"source_id\":\"1\"}\n{\"sour").unwrap();
+
+        let mut writer = RotatingWriter::new(&directory, 1024, 1).unwrap();
+        writer.write_record(&json!({"source_id": "2"})).unwrap();
+
+        let contents = std::fs::read_to_string(writer.file_path(0)).unwrap();
+        assert_eq!(contents.lines().last(), Some("{\"source_id\":\"2\"}"));
+        assert_eq!(contents.lines().count(), 3);
+    }
+}
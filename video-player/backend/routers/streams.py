@@ -1,4 +1,4 @@
-from fastapi import APIRouter
+from fastapi import APIRouter, HTTPException, Query
 
 # Custom modules
 from models import StreamConfig
@@ -16,6 +16,23 @@ async def stop_stream(video_id: int):
     """Stop streaming a video"""
     return await StreamManager.stop_stream(video_id)
 
+@router.get("/status")
+def get_streams_status(video_ids: str = Query(..., description="Comma separated video ids")):
+    """Get stream status of multiple videos, videos that do not exist are left out"""
+    try:
+        ids = [int(video_id) for video_id in video_ids.split(",") if video_id.strip()]
+    except ValueError:
+        raise HTTPException(status_code=422, detail="video_ids must be comma separated integers")
+
+    statuses = []
+    for video_id in ids:
+        try:
+            statuses.append(StreamManager.get_stream_status(video_id))
+        except HTTPException as e:
+            if e.status_code != 404:
+                raise
+    return statuses
+
 @router.get("/status/{video_id}")
 def get_stream_status(video_id: int):
     """Get stream status"""
@@ -0,0 +1,207 @@
+use ffmpeg_next as ffmpeg;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Results older than this are no longer drawn, so boxes do not linger after detections stop
+const RESULTS_MAX_AGE: Duration = Duration::from_secs(1);
+// Glyphs are drawn scaled up by this factor
+const TEXT_SCALE: usize = 2;
+const BOX_THICKNESS: usize = 2;
+const BOX_COLOR: [u8; 3] = [0, 255, 0];
+const TEXT_COLOR: [u8; 3] = [255, 255, 255];
+const TEXT_BACKGROUND: [u8; 3] = [0, 0, 0];
+
+// Latest detections posted back for each source, with the time they were posted
+static LATEST_RESULTS: OnceLock<Mutex<HashMap<i32, (Instant, Vec<ResultBox>)>>> = OnceLock::new();
+
+fn get_latest_results() -> &'static Mutex<HashMap<i32, (Instant, Vec<ResultBox>)>> {
+    LATEST_RESULTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Bounding box of a detection, as posted back by the inference side
+#[derive(Debug, Clone, Copy)]
+enum ResultBox {
+    // Corners as indexes of pixels in the frame, as if it was a 1d array
+    Flat { top_left: u64, bottom_right: u64 },
+    // COCO [x, y, width, height] in pixels
+    Coco { x: f64, y: f64, width: f64, height: f64 },
+}
+
+impl ResultBox {
+    // Returns the box corners (x1, y1, x2, y2) in a frame of the given width
+    fn corners(&self, frame_width: u64) -> (i64, i64, i64, i64) {
+        match *self {
+            ResultBox::Flat { top_left, bottom_right } => {
+                let frame_width = frame_width.max(1);
+                (
+                    (top_left % frame_width) as i64,
+                    (top_left / frame_width) as i64,
+                    (bottom_right % frame_width) as i64,
+                    (bottom_right / frame_width) as i64,
+                )
+            }
+            ResultBox::Coco { x, y, width, height } => {
+                (x as i64, y as i64, (x + width) as i64, (y + height) as i64)
+            }
+        }
+    }
+}
+
+/// Records the detections of results posted for a source, drawn on its following frames.
+/// Accepts both the bboxes and the COCO results formats, results without boxes are ignored
+pub fn record_results(source_id: i32, results: &Value) {
+    let boxes: Vec<ResultBox> = match results {
+        Value::Object(object) => object
+            .get("bboxes")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|bbox| Some(ResultBox::Flat {
+                top_left: bbox.get("top_left_corner")?.as_u64()?,
+                bottom_right: bbox.get("bottom_right_corner")?.as_u64()?,
+            }))
+            .collect(),
+        Value::Array(detections) => detections
+            .iter()
+            .filter_map(|detection| {
+                let bbox = detection.get("bbox")?.as_array()?;
+                let value = |index: usize| bbox.get(index).and_then(Value::as_f64);
+                Some(ResultBox::Coco { x: value(0)?, y: value(1)?, width: value(2)?, height: value(3)? })
+            })
+            .collect(),
+        _ => return,
+    };
+
+    get_latest_results()
+        .lock()
+        .unwrap()
+        .insert(source_id, (Instant::now(), boxes));
+}
+
+/// Draws the latest detections of a source and a label with its id, the current time and the
+/// detection count onto a packed RGB/BGR frame. Detections lag the frame by the inference latency
+pub fn annotate_frame(source_id: i32, frame: &mut ffmpeg::util::frame::video::Video) {
+    let boxes = get_latest_results()
+        .lock()
+        .unwrap()
+        .get(&source_id)
+        .filter(|(posted_at, _)| posted_at.elapsed() <= RESULTS_MAX_AGE)
+        .map(|(_, boxes)| boxes.clone())
+        .unwrap_or_default();
+
+    let mut canvas = Canvas {
+        width: frame.width() as i64,
+        height: frame.height() as i64,
+        stride: frame.stride(0),
+        data: frame.data_mut(0),
+    };
+
+    for result_box in boxes.iter() {
+        let (x1, y1, x2, y2) = result_box.corners(canvas.width as u64);
+        canvas.draw_rect_outline(x1, y1, x2, y2, BOX_COLOR);
+    }
+
+    let label = format!("ID {} {} DET {}", source_id, wall_clock_time(), boxes.len());
+    canvas.draw_text(4, 4, &label);
+}
+
+// Current UTC time of day as HH:MM:SS
+fn wall_clock_time() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+        % 86_400;
+
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+// Packed 3 bytes per pixel image, writes outside of it are clipped
+struct Canvas<'a> {
+    width: i64,
+    height: i64,
+    stride: usize,
+    data: &'a mut [u8],
+}
+
+impl Canvas<'_> {
+    fn fill_rect(&mut self, x1: i64, y1: i64, x2: i64, y2: i64, color: [u8; 3]) {
+        let (x1, x2) = (x1.max(0), x2.min(self.width));
+        let (y1, y2) = (y1.max(0), y2.min(self.height));
+
+        for y in y1..y2 {
+            for x in x1..x2 {
+                let offset = y as usize * self.stride + x as usize * 3;
+                if let Some(pixel) = self.data.get_mut(offset..offset + 3) {
+                    pixel.copy_from_slice(&color);
+                }
+            }
+        }
+    }
+
+    fn draw_rect_outline(&mut self, x1: i64, y1: i64, x2: i64, y2: i64, color: [u8; 3]) {
+        let thickness = BOX_THICKNESS as i64;
+        self.fill_rect(x1, y1, x2, y1 + thickness, color);
+        self.fill_rect(x1, y2 - thickness, x2, y2, color);
+        self.fill_rect(x1, y1, x1 + thickness, y2, color);
+        self.fill_rect(x2 - thickness, y1, x2, y2, color);
+    }
+
+    // Draws text on a background box, characters without a glyph are left blank
+    fn draw_text(&mut self, x: i64, y: i64, text: &str) {
+        let scale = TEXT_SCALE as i64;
+        let advance = (GLYPH_WIDTH as i64 + 1) * scale;
+        let text_width = text.chars().count() as i64 * advance;
+        let text_height = GLYPH_HEIGHT as i64 * scale;
+        self.fill_rect(x - scale, y - scale, x + text_width, y + text_height + scale, TEXT_BACKGROUND);
+
+        for (index, character) in text.chars().enumerate() {
+            let Some(rows) = glyph(character) else {
+                continue;
+            };
+
+            let glyph_x = x + index as i64 * advance;
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                        continue;
+                    }
+
+                    let pixel_x = glyph_x + column as i64 * scale;
+                    let pixel_y = y + row as i64 * scale;
+                    self.fill_rect(pixel_x, pixel_y, pixel_x + scale, pixel_y + scale, TEXT_COLOR);
+                }
+            }
+        }
+    }
+}
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+
+// 3x5 bitmap glyphs of the characters used by annotation labels, one row per entry
+fn glyph(character: char) -> Option<[u8; GLYPH_HEIGHT]> {
+    let rows = match character {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b111, 0b100, 0b111],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        _ => return None,
+    };
+
+    Some(rows)
+}
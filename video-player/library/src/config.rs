@@ -0,0 +1,190 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::log_info;
+
+// Global stream configuration
+pub static STREAM_CONFIG: OnceLock<StreamConfig> = OnceLock::new();
+
+/// Returns the stream configuration, falling back to defaults if never set
+pub fn get_stream_config() -> &'static StreamConfig {
+    STREAM_CONFIG.get_or_init(StreamConfig::default)
+}
+
+/// Sets the stream configuration from a JSON document. Can only be done once,
+/// before sources are initialized
+pub fn set_stream_config(config_json: &str) -> Result<()> {
+    let config: StreamConfig = serde_json::from_str(config_json)
+        .context("Invalid stream config JSON")?;
+
+    STREAM_CONFIG.set(config)
+        .map_err(|_| anyhow::anyhow!("Stream config is already set"))?;
+
+    log_info!("Stream config set: {:?}", get_stream_config());
+    Ok(())
+}
+
+// Settings controlling how streams are monitored and decoded
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StreamConfig {
+    /// Consecutive failures of the same kind before a source stops retrying
+    pub circuit_breaker_threshold: u32,
+    /// Seconds between checks of the backend that a consumed stream is still active
+    pub keepalive_interval_secs: u64,
+    /// Seconds without decoded frames after which a connected stream is considered frozen
+    /// and reconnected, regardless of FFmpeg's read timeout. 0 disables the watchdog
+    pub frame_timeout_secs: u64,
+    /// Decode every video stream in the container instead of only the best one.
+    /// Frames are emitted through the sub-stream frames callback
+    pub decode_all_streams: bool,
+    /// Channel order of frames passed to the frames callbacks
+    pub color_order: ColorOrder,
+    /// Frames larger than this are downscaled while decoding, keeping their aspect ratio.
+    /// Bounds memory and CPU per frame when a source unexpectedly switches to a huge resolution
+    pub max_decode_resolution: Option<Resolution>,
+    /// Draw the source id, time and latest posted detections onto frames before they are delivered,
+    /// producing a ready-to-display stream. Sub-stream frames are not annotated
+    pub annotate_frames: bool,
+}
+
+// Width downscaled frames are aligned to. Delivered frames are read as packed rows,
+// so the scaled width must not make FFmpeg pad rows of RGB frames
+pub const FRAME_WIDTH_ALIGNMENT: u32 = 32;
+
+// Frame dimensions in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    /// Returns the dimensions a frame is scaled to - unchanged when it fits, otherwise
+    /// the largest dimensions within the cap with the same aspect ratio.
+    /// Scaled widths are rounded down to `FRAME_WIDTH_ALIGNMENT`, unless the cap is narrower
+    pub fn fit(&self, width: u32, height: u32) -> (u32, u32) {
+        if width <= self.width && height <= self.height {
+            return (width, height);
+        }
+
+        let ratio = f64::min(
+            self.width as f64 / width as f64,
+            self.height as f64 / height as f64,
+        );
+        let fitted_width = ((width as f64 * ratio).floor() as u32).clamp(1, self.width.max(1));
+        let aligned_width = match fitted_width / FRAME_WIDTH_ALIGNMENT * FRAME_WIDTH_ALIGNMENT {
+            0 => fitted_width,
+            aligned_width => aligned_width,
+        };
+
+        // Height follows the aligned width, keeping the aspect ratio
+        let fitted_height = ((height as f64 * aligned_width as f64 / width as f64).round() as u32)
+            .clamp(1, self.height.max(1));
+
+        (aligned_width, fitted_height)
+    }
+}
+
+// Channel order of delivered frames, matching the order the consuming models were trained on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub enum ColorOrder {
+    #[default]
+    Rgb,
+    Bgr,
+}
+
+impl ColorOrder {
+    /// Returns the packed pixel format frames are scaled to
+    pub fn pixel(&self) -> ffmpeg_next::format::Pixel {
+        match self {
+            ColorOrder::Rgb => ffmpeg_next::format::Pixel::RGB24,
+            ColorOrder::Bgr => ffmpeg_next::format::Pixel::BGR24,
+        }
+    }
+}
+
+// Per-source delivery options, adjustable while the source is streaming
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StreamOptions {
+    /// Deliver only every Nth decoded frame to the frames callback, 1 delivers all frames
+    pub deliver_every_n: u32,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            deliver_every_n: 1,
+        }
+    }
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            circuit_breaker_threshold: 5,
+            keepalive_interval_secs: 2,
+            frame_timeout_secs: 10,
+            decode_all_streams: false,
+            color_order: ColorOrder::Rgb,
+            max_decode_resolution: None,
+            annotate_frames: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_defaults_of_unset_fields() {
+        let config: StreamConfig = serde_json::from_str(r#"{"circuit_breaker_threshold": 0}"#).unwrap();
+
+        assert_eq!(config.circuit_breaker_threshold, 0);
+        assert_eq!(config.frame_timeout_secs, StreamConfig::default().frame_timeout_secs);
+    }
+
+    const MAX_RESOLUTION: Resolution = Resolution { width: 1920, height: 1080 };
+
+    #[test]
+    fn keeps_frames_that_fit() {
+        assert_eq!(MAX_RESOLUTION.fit(1920, 1080), (1920, 1080));
+        assert_eq!(MAX_RESOLUTION.fit(1437, 801), (1437, 801));
+        assert_eq!(MAX_RESOLUTION.fit(640, 1080), (640, 1080));
+    }
+
+    #[test]
+    fn downscales_keeping_aspect_ratio() {
+        for (width, height) in [(3840, 2160), (4096, 2160), (2160, 3840), (2874, 1602), (7680, 1080)] {
+            let (fitted_width, fitted_height) = MAX_RESOLUTION.fit(width, height);
+
+            assert!(fitted_width <= MAX_RESOLUTION.width && fitted_height <= MAX_RESOLUTION.height);
+            let ratio = width as f64 / height as f64;
+            let fitted_ratio = fitted_width as f64 / fitted_height as f64;
+            assert!((ratio - fitted_ratio).abs() / ratio < 0.01, "{}x{} fitted to {}x{}", width, height, fitted_width, fitted_height);
+        }
+
+        assert_eq!(MAX_RESOLUTION.fit(3840, 2160), (1920, 1080));
+    }
+
+    #[test]
+    fn aligns_downscaled_widths() {
+        // Unaligned 1437 px wide fit is rounded down
+        let (width, height) = MAX_RESOLUTION.fit(2874, 2160);
+        assert_eq!((width, height), (1408, 1058));
+
+        for (width, height) in [(3840, 2160), (2160, 3840), (2874, 1602), (5000, 3333), (1921, 7000)] {
+            assert_eq!(MAX_RESOLUTION.fit(width, height).0 % FRAME_WIDTH_ALIGNMENT, 0);
+        }
+    }
+
+    #[test]
+    fn keeps_widths_of_caps_narrower_than_alignment() {
+        let tiny = Resolution { width: 16, height: 16 };
+
+        assert_eq!(tiny.fit(64, 32), (16, 8));
+    }
+}
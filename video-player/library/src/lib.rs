@@ -5,6 +5,8 @@ use std::sync::{Mutex, OnceLock};
 use tokio::runtime::Runtime;
 
 // Custom modules
+pub mod annotate;
+pub mod config;
 pub mod player_proxy;
 pub mod stream;
 
@@ -60,6 +62,8 @@ pub type SourceFramesCallback = extern "C" fn(source_id: c_int, frame: *const u8
 pub type SourceStoppedCallback = extern "C" fn(source_id: c_int);
 pub type SourceNameCallback = extern "C" fn(source_id: c_int, source_name: *const c_char);
 pub type SourceStatusCallback = extern "C" fn(source_id: c_int, source_status: c_int);
+pub type SourceSubFramesCallback = extern "C" fn(source_id: c_int, sub_stream: c_int, frame: *const u8, width: c_int, height: c_int, pts: c_ulonglong);
+pub type SourceInfoCallback = extern "C" fn(source_id: c_int, info_json: *const c_char);
 
 #[no_mangle]
 pub extern "C" fn SetCallbacks(
@@ -72,6 +76,111 @@ pub extern "C" fn SetCallbacks(
     stream::get_stream_manager().set_callbacks(source_frames, source_stopped, source_name, source_status);
 }
 
+#[no_mangle]
+pub extern "C" fn SetSubStreamFramesCallback(source_sub_frames: SourceSubFramesCallback) -> c_int {
+    log_info!("SetSubStreamFramesCallback called");
+
+    if !stream::get_stream_manager().are_callbacks_set() {
+        log_error!("Callbacks not set. Call SetCallbacks before SetSubStreamFramesCallback");
+        return -1;
+    }
+
+    stream::get_stream_manager().set_sub_frames_callback(source_sub_frames);
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn SetSourceInfoCallback(source_info: SourceInfoCallback) -> c_int {
+    log_info!("SetSourceInfoCallback called");
+
+    if !stream::get_stream_manager().are_callbacks_set() {
+        log_error!("Callbacks not set. Call SetCallbacks before SetSourceInfoCallback");
+        return -1;
+    }
+
+    stream::get_stream_manager().set_source_info_callback(source_info);
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn SetStreamConfig(config_json: *const c_char) -> c_int {
+    if config_json.is_null() {
+        log_error!("SetStreamConfig: null JSON pointer");
+        return -1;
+    }
+
+    let json_str = unsafe {
+        match CStr::from_ptr(config_json).to_str() {
+            Ok(s) => s,
+            Err(e) => {
+                log_error!("SetStreamConfig: invalid UTF-8 in JSON: {}", e);
+                return -1;
+            }
+        }
+    };
+
+    match config::set_stream_config(json_str) {
+        Ok(_) => 0,
+        Err(e) => {
+            log_error!("SetStreamConfig: {:#}", e);
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn SetStreamOptions(source_id: c_int, options_json: *const c_char) -> c_int {
+    if options_json.is_null() {
+        log_error!("SetStreamOptions: null JSON pointer");
+        return -1;
+    }
+
+    let json_str = unsafe {
+        match CStr::from_ptr(options_json).to_str() {
+            Ok(s) => s,
+            Err(e) => {
+                log_error!("SetStreamOptions: invalid UTF-8 in JSON: {}", e);
+                return -1;
+            }
+        }
+    };
+
+    let options: config::StreamOptions = match serde_json::from_str(json_str) {
+        Ok(options) => options,
+        Err(e) => {
+            log_error!("SetStreamOptions: invalid stream options JSON: {}", e);
+            return -1;
+        }
+    };
+
+    stream::get_stream_manager().set_stream_options(source_id, options);
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn ReinitSource(source_id: c_int) -> c_int {
+    log_info!("ReinitSource called for source {}", source_id);
+
+    if !stream::get_stream_manager().are_callbacks_set() {
+        log_error!("Callbacks not set. Call SetCallbacks before ReinitSource");
+        return -1;
+    }
+
+    stream::get_stream_manager().reinit_source(source_id);
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn StopSource(source_id: c_int) -> c_int {
+    log_info!("StopSource called for source {}", source_id);
+
+    if !stream::get_stream_manager().stop_source(source_id) {
+        log_error!("StopSource: source {} is not running", source_id);
+        return -1;
+    }
+    0
+}
+
 #[no_mangle]
 pub extern "C" fn InitMultipleSources(source_ids: *const c_int, size: c_int, log_level: c_int) {
     log_info!("InitMultipleSources called with {} sources, log_level: {}", size, log_level);
@@ -129,7 +238,6 @@ pub extern "C" fn InitMultipleSources(source_ids: *const c_int, size: c_int, log
 }
 
 #[no_mangle]
-#[allow(unused_variables)]
 pub extern "C" fn PostResults(source_id: c_int, result_json: *const c_char) -> c_int {
     if result_json.is_null() {
         log_error!("PostResults: null JSON pointer");
@@ -146,6 +254,14 @@ pub extern "C" fn PostResults(source_id: c_int, result_json: *const c_char) -> c
         }
     };
     
+    // Keep detections to draw on the following frames of the source
+    if config::get_stream_config().annotate_frames {
+        match serde_json::from_str(json_str) {
+            Ok(results) => annotate::record_results(source_id, &results),
+            Err(e) => log_error!("PostResults: invalid results JSON for annotation: {}", e),
+        }
+    }
+
     // Spawn async task to post results
     get_runtime().spawn(async move {
         match post_results_async(json_str.to_string()).await {
@@ -200,4 +316,53 @@ pub extern "C" fn FreeCPtr(ptr: *const c_void) {
         // Reconstruct the CString and drop it
         let _ = std::ffi::CString::from_raw(ptr as *mut c_char);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::sync::atomic::Ordering;
+    use std::sync::Once;
+
+    // The stream manager is global and needs a backend URL to be created, no request is sent by these tests
+    fn init_stream_manager() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            std::env::set_var("PLAYER_BACKEND_URL", "http://127.0.0.1:9");
+            stream::get_stream_manager();
+        });
+    }
+
+    fn deliver_every_n(source_id: i32) -> u32 {
+        stream::get_stream_manager().get_deliver_every_n(source_id).load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn set_stream_options_changes_deliver_every_n() {
+        init_stream_manager();
+
+        let options = CString::new(r#"{"deliver_every_n": 3}"#).unwrap();
+        assert_eq!(SetStreamOptions(9001, options.as_ptr()), 0);
+        assert_eq!(deliver_every_n(9001), 3);
+
+        // Zero would stop delivery, so it is raised to every frame
+        let options = CString::new(r#"{"deliver_every_n": 0}"#).unwrap();
+        assert_eq!(SetStreamOptions(9001, options.as_ptr()), 0);
+        assert_eq!(deliver_every_n(9001), 1);
+    }
+
+    #[test]
+    fn set_stream_options_rejects_invalid_input() {
+        init_stream_manager();
+
+        let options = CString::new(r#"{"deliver_every_n": 4}"#).unwrap();
+        assert_eq!(SetStreamOptions(9002, options.as_ptr()), 0);
+
+        let malformed = CString::new(r#"{"deliver_every_n": -2}"#).unwrap();
+        assert_eq!(SetStreamOptions(9002, malformed.as_ptr()), -1);
+        assert_eq!(SetStreamOptions(9002, std::ptr::null()), -1);
+
+        // Rejected calls keep the previous options
+        assert_eq!(deliver_every_n(9002), 4);
+    }
+}
@@ -1,4 +1,4 @@
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::env;
 use anyhow::{Context, Result};
@@ -13,7 +13,7 @@ pub struct DashInfo {
 }
 
 // Response models matching the backend
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct StreamStatus {
     pub video_id: i32,
     pub is_streaming: bool,
@@ -80,4 +80,44 @@ impl PlayerSession {
 
         Ok(status)
     }
+
+    /// Get stream status for multiple videos in a single request.
+    /// Returns nothing when the backend does not support batched status requests
+    pub async fn get_streams_status(&self, video_ids: &[i32]) -> Result<Option<Vec<StreamStatus>>> {
+        let url = format!("{}/streams/status", self.base_url);
+        let ids = video_ids
+            .iter()
+            .map(|video_id| video_id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let response = self.client
+            .get(&url)
+            .query(&[("video_ids", ids)])
+            .send()
+            .await
+            .context("Failed to send batch stream status request")?;
+
+        // Backends without the batch endpoint
+        if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED) {
+            return Ok(None);
+        }
+
+        // Check if request was successful
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            anyhow::bail!("Backend returned error {}: {}", status, error_text);
+        }
+
+        let statuses: Vec<StreamStatus> = response
+            .json()
+            .await
+            .context("Failed to parse batch stream status response")?;
+
+        Ok(Some(statuses))
+    }
 }
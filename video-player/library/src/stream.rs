@@ -2,20 +2,26 @@ use anyhow::{Context, Result};
 use ffmpeg_next as ffmpeg;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex, OnceLock};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::time::Duration;
 use tokio::task::JoinHandle;
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 
-use crate::player_proxy::PlayerSession;
+use crate::player_proxy::{PlayerSession, StreamStatus};
+use crate::annotate;
+use crate::config::{get_stream_config, StreamOptions};
 use crate::get_runtime;
-use crate::{SourceFramesCallback, SourceStoppedCallback, SourceNameCallback, SourceStatusCallback};
+use crate::{SourceFramesCallback, SourceStoppedCallback, SourceNameCallback, SourceStatusCallback, SourceSubFramesCallback, SourceInfoCallback};
 use crate::{log_info, log_error, log_debug};
 
 // Stream timeout constant
 const STREAM_TIMEOUT: Duration = Duration::from_secs(10);
+// Interval of the frame watchdog checking for decoded frames
+const FRAME_WATCHDOG_INTERVAL: Duration = Duration::from_millis(500);
+// Age beyond the poll interval for which a batch polled stream status is still used
+const STATUS_POLL_GRACE: Duration = Duration::from_secs(1);
 
 // Info for the raw video stream
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -33,19 +39,131 @@ pub struct RawStreamInfo {
 
 // Source status codes for C FFI
 #[repr(i32)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SourceStatus {
     Ok = 0,
     NotStreaming = 1,
     NotFound = 2,
     ConnectionError = 3,
     DecodeError = 4,
+    CircuitOpen = 5,
+    Stalled = 6,
+}
+
+// Failure of a stream attempt, classified by the status reported for it
+#[derive(Debug)]
+pub struct StreamFailure {
+    pub status: SourceStatus,
+    pub error: anyhow::Error,
+}
+
+impl StreamFailure {
+    fn new(status: SourceStatus, error: anyhow::Error) -> Self {
+        Self { status, error }
+    }
+}
+
+impl std::fmt::Display for StreamFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {:#}", self.status, self.error)
+    }
+}
+
+// Stops retrying a source after too many consecutive failures of the same kind
+struct CircuitBreaker {
+    threshold: u32,
+    last_failure: Option<SourceStatus>,
+    failures: u32,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: u32) -> Self {
+        Self {
+            threshold,
+            last_failure: None,
+            failures: 0,
+        }
+    }
+
+    // Records a failure, returns true once the breaker should open.
+    // A threshold of 0 disables the breaker
+    fn record_failure(&mut self, status: SourceStatus) -> bool {
+        if self.last_failure == Some(status) {
+            self.failures += 1;
+        } else {
+            self.last_failure = Some(status);
+            self.failures = 1;
+        }
+
+        self.threshold > 0 && self.failures >= self.threshold
+    }
+
+    fn record_success(&mut self) {
+        self.last_failure = None;
+        self.failures = 0;
+    }
+}
+
+// Keeps emitted PTS of a source monotonic across reconnects.
+// Every connection starts from its own PTS base, so the first PTS of a new
+// connection is rebased to continue right after the last emitted PTS.
+// Sub-streams of a multi-stream container keep their own timelines
+struct PtsTimeline {
+    offset: i64,
+    last_emitted: Option<u64>,
+    rebase: bool,
+    sub_streams: HashMap<i32, PtsTimeline>,
+}
+
+impl PtsTimeline {
+    fn new() -> Self {
+        Self {
+            offset: 0,
+            last_emitted: None,
+            rebase: false,
+            sub_streams: HashMap::new(),
+        }
+    }
+
+    // Marks the start of a new connection, rebasing its PTS on the next frame
+    fn start_connection(&mut self) {
+        self.rebase = true;
+        for timeline in self.sub_streams.values_mut() {
+            timeline.start_connection();
+        }
+    }
+
+    // Timeline of a sub-stream, created on its first frame
+    fn sub_stream(&mut self, sub_stream: i32) -> &mut PtsTimeline {
+        self.sub_streams.entry(sub_stream).or_insert_with(PtsTimeline::new)
+    }
+
+    // Maps a raw PTS of the current connection onto the source timeline
+    fn map(&mut self, pts: i64) -> u64 {
+        let pts = pts.max(0);
+
+        if self.rebase {
+            self.rebase = false;
+            if let Some(last) = self.last_emitted {
+                self.offset = last as i64 + 1 - pts;
+            }
+        }
+
+        let mapped = (pts + self.offset).max(0) as u64;
+        self.last_emitted = Some(mapped);
+        mapped
+    }
 }
 
 // Global state for managing streams
 pub struct StreamManager {
     streams: Mutex<HashMap<i32, JoinHandle<()>>>,
     callbacks: Mutex<Option<Callbacks>>,
+    // Every Nth decoded frame delivered per source, read by the decode loop on every frame
+    deliver_every_n: Mutex<HashMap<i32, Arc<AtomicU32>>>,
+    // Stream status of all sources, polled in a single backend request and the time it was polled
+    stream_statuses: Mutex<HashMap<i32, (Instant, StreamStatus)>>,
+    status_poller: Mutex<Option<JoinHandle<()>>>,
     player_session: PlayerSession,
 }
 
@@ -55,6 +173,8 @@ struct Callbacks {
     source_stopped: SourceStoppedCallback,
     source_name: SourceNameCallback,
     source_status: SourceStatusCallback,
+    source_sub_frames: Option<SourceSubFramesCallback>,
+    source_info: Option<SourceInfoCallback>,
 }
 
 // Function pointers are Send and Sync by nature
@@ -80,6 +200,9 @@ impl StreamManager {
         Ok(Self {
             streams: Mutex::new(HashMap::new()),
             callbacks: Mutex::new(None),
+            deliver_every_n: Mutex::new(HashMap::new()),
+            stream_statuses: Mutex::new(HashMap::new()),
+            status_poller: Mutex::new(None),
             player_session: PlayerSession::new()?,
         })
     }
@@ -96,11 +219,46 @@ impl StreamManager {
             source_stopped,
             source_name,
             source_status,
+            source_sub_frames: None,
+            source_info: None,
         };
         *self.callbacks.lock().unwrap() = Some(callbacks);
         log_info!("Callbacks registered");
     }
 
+    /// Registers the callback used for frames of containers decoded with `decode_all_streams`
+    pub fn set_sub_frames_callback(&self, source_sub_frames: SourceSubFramesCallback) {
+        if let Some(callbacks) = self.callbacks.lock().unwrap().as_mut() {
+            callbacks.source_sub_frames = Some(source_sub_frames);
+            log_info!("Sub-stream frames callback registered");
+        }
+    }
+
+    /// Registers the callback receiving stream information of a source (resolution, fps) once its stream is active
+    pub fn set_source_info_callback(&self, source_info: SourceInfoCallback) {
+        if let Some(callbacks) = self.callbacks.lock().unwrap().as_mut() {
+            callbacks.source_info = Some(source_info);
+            log_info!("Source info callback registered");
+        }
+    }
+
+    /// Applies delivery options of a source, taking effect on the next decoded frame
+    pub fn set_stream_options(&self, source_id: i32, options: StreamOptions) {
+        let deliver_every_n = options.deliver_every_n.max(1);
+        self.get_deliver_every_n(source_id).store(deliver_every_n, Ordering::Relaxed);
+        log_info!("[Source {}] Delivering every {} frames", source_id, deliver_every_n);
+    }
+
+    // Returns the shared delivery divisor of a source, created on first use
+    pub(crate) fn get_deliver_every_n(&self, source_id: i32) -> Arc<AtomicU32> {
+        self.deliver_every_n
+            .lock()
+            .unwrap()
+            .entry(source_id)
+            .or_insert_with(|| Arc::new(AtomicU32::new(1)))
+            .clone()
+    }
+
     pub fn are_callbacks_set(&self) -> bool {
         self.callbacks.lock().unwrap().is_some()
     }
@@ -110,6 +268,84 @@ impl StreamManager {
             self.start_source_monitor(source_id);
             log_info!("[Source {}] Initialized!", source_id);
         }
+
+        self.start_status_poller();
+    }
+
+    // Polls the stream status of all sources in a single request, distributing results through `get_stream_status`.
+    // Stops when the backend does not support batched requests, leaving sources to poll individually
+    fn start_status_poller(&self) {
+        let mut poller = self.status_poller.lock().unwrap();
+        if poller.is_some() {
+            return;
+        }
+
+        let manager = get_stream_manager().clone();
+        *poller = Some(get_runtime().spawn(async move {
+            loop {
+                let poll_interval = status_poll_interval();
+                let source_ids: Vec<i32> = manager.streams.lock().unwrap().keys().copied().collect();
+
+                if !source_ids.is_empty() {
+                    match manager.player_session.get_streams_status(&source_ids).await {
+                        Ok(Some(statuses)) => {
+                            let polled_at = Instant::now();
+                            let mut stream_statuses = manager.stream_statuses.lock().unwrap();
+                            stream_statuses.clear();
+                            for status in statuses {
+                                stream_statuses.insert(status.video_id, (polled_at, status));
+                            }
+                        }
+                        Ok(None) => {
+                            log_info!("Batch stream status unavailable on backend, polling sources individually");
+                            return;
+                        }
+                        Err(e) => {
+                            log_error!("Failed to poll stream status of {} sources: {}", source_ids.len(), e);
+                        }
+                    }
+                }
+
+                sleep(poll_interval).await;
+            }
+        }));
+    }
+
+    /// Returns the stream status of a source, from the latest batch poll when recent, requested individually otherwise
+    async fn get_stream_status(&self, source_id: i32) -> Result<StreamStatus> {
+        let polled = self.stream_statuses
+            .lock()
+            .unwrap()
+            .get(&source_id)
+            .filter(|(polled_at, _)| polled_at.elapsed() <= status_poll_interval() + STATUS_POLL_GRACE)
+            .map(|(_, status)| status.clone());
+
+        match polled {
+            Some(status) => Ok(status),
+            None => self.player_session.get_stream_status(source_id).await,
+        }
+    }
+
+    /// Restarts the monitor of a single source, closing its circuit breaker
+    pub fn reinit_source(&self, source_id: i32) {
+        if let Some(handle) = self.streams.lock().unwrap().remove(&source_id) {
+            handle.abort();
+        }
+
+        self.start_source_monitor(source_id);
+        log_info!("[Source {}] Re-initialized!", source_id);
+    }
+
+    /// Stops the monitor of a single source, returns whether it was running
+    pub fn stop_source(&self, source_id: i32) -> bool {
+        match self.streams.lock().unwrap().remove(&source_id) {
+            Some(handle) => {
+                handle.abort();
+                log_info!("[Source {}] Stopped!", source_id);
+                true
+            }
+            None => false,
+        }
     }
 
     fn start_source_monitor(&self, source_id: i32) {
@@ -128,6 +364,11 @@ impl StreamManager {
             };
             
             log_debug!("[Source {}] Using backend host: {}", source_id, host);
+
+            let mut breaker = CircuitBreaker::new(get_stream_config().circuit_breaker_threshold);
+
+            // Kept across reconnects, reset only when the source monitor is restarted
+            let pts_timeline = Arc::new(Mutex::new(PtsTimeline::new()));
             
             loop {
                 // Check if we have callbacks registered
@@ -151,7 +392,7 @@ impl StreamManager {
                 };
 
                 // Check stream status
-                match manager.player_session.get_stream_status(source_id).await {
+                match manager.get_stream_status(source_id).await {
                     Ok(status) => {
                         if !status.is_streaming {
                             log_error!("[Source {}] Not streaming, waiting...", source_id);
@@ -167,6 +408,9 @@ impl StreamManager {
                                 // UPDATED: Log message
                                 log_error!("[Source {}] No raw stream info ('relay' block) available from backend", source_id);
                                 (callbacks.source_status)(source_id, SourceStatus::ConnectionError as i32);
+                                if breaker.record_failure(SourceStatus::ConnectionError) {
+                                    break;
+                                }
                                 sleep(STREAM_TIMEOUT).await;
                                 continue;
                             }
@@ -179,20 +423,37 @@ impl StreamManager {
                             (callbacks.source_name)(source_id, name_cstr.into_raw());
                         }
 
+                        // Share stream information, so results can be reported along with it
+                        if let Some(source_info) = callbacks.source_info {
+                            if let Ok(info_cstr) = std::ffi::CString::new(serde_json::to_string(&raw_stream_info).unwrap_or_default()) {
+                                source_info(source_id, info_cstr.into_raw());
+                            }
+                        }
+
                         // UPDATED: Log for TCP
                         log_info!("[Source {}] Stream active, connecting to tcp://{}:{}", 
                                  source_id, host, raw_stream_info.port);
                         (callbacks.source_status)(source_id, SourceStatus::Ok as i32);
 
                         // Start consuming stream
-                        if let Err(e) = manager.consume_stream(source_id, raw_stream_info.clone(), host.clone(), callbacks, status.pid).await {
-                            log_error!("[Source {}] Stream error: {}", source_id, e);
-                            (callbacks.source_stopped)(source_id);
+                        match manager.consume_stream(source_id, raw_stream_info.clone(), host.clone(), callbacks, status.pid, pts_timeline.clone()).await {
+                            Ok(_) => breaker.record_success(),
+                            Err(e) => {
+                                log_error!("[Source {}] Stream error: {}", source_id, e);
+                                (callbacks.source_status)(source_id, e.status as i32);
+                                (callbacks.source_stopped)(source_id);
+                                if breaker.record_failure(e.status) {
+                                    break;
+                                }
+                            }
                         }
                     }
                     Err(e) => {
                         log_error!("[Source {}] Failed to get status: {}", source_id, e);
                         (callbacks.source_status)(source_id, SourceStatus::ConnectionError as i32);
+                        if breaker.record_failure(SourceStatus::ConnectionError) {
+                            break;
+                        }
                     }
                 }
 
@@ -200,6 +461,15 @@ impl StreamManager {
                 log_debug!("[Source {}] Retrying in {:?}...", source_id, STREAM_TIMEOUT);
                 sleep(STREAM_TIMEOUT).await;
             }
+
+            // Breaker is open - stop retrying until the source is re-initialized
+            log_error!(
+                "[Source {}] Circuit breaker open after {} consecutive {:?} failures, giving up until re-init",
+                source_id, breaker.failures, breaker.last_failure
+            );
+            if let Some(callbacks) = *manager.callbacks.lock().unwrap() {
+                (callbacks.source_status)(source_id, SourceStatus::CircuitOpen as i32);
+            }
         });
 
         self.streams.lock().unwrap().insert(source_id, handle);
@@ -222,17 +492,19 @@ impl StreamManager {
         host: String,
         callbacks: Callbacks,
         stream_pid: Option<i32>,
-    ) -> Result<()> {
-        let session = self.player_session.clone();
+        pts_timeline: Arc<Mutex<PtsTimeline>>,
+    ) -> std::result::Result<(), StreamFailure> {
+        let manager = get_stream_manager().clone();
         let stop_signal = Arc::new(AtomicBool::new(false));
         let stop_signal_decode = stop_signal.clone();
         
         // Spawn a task to periodically check if stream is still active on backend
+        let keepalive_interval = status_poll_interval();
         let mut keepalive_handle = tokio::spawn(async move {
             loop {
-                sleep(Duration::from_secs(2)).await;
+                sleep(keepalive_interval).await;
                 
-                match session.get_stream_status(source_id).await {
+                match manager.get_stream_status(source_id).await {
                     Ok(status) => {
                         if !status.is_streaming {
                             log_info!("[Source {}] Backend reports stream stopped, triggering reconnect", source_id);
@@ -251,46 +523,95 @@ impl StreamManager {
         });
         
         // Spawn blocking task for FFmpeg operations
+        let deliver_every_n = self.get_deliver_every_n(source_id);
+        let frames_decoded = Arc::new(AtomicU64::new(0));
+        let frames_decoded_decode = frames_decoded.clone();
         let mut decode_handle = tokio::task::spawn_blocking(move || {
-            if let Err(e) = decode_stream(source_id, stream_info, host, callbacks, stop_signal_decode) {
-                log_error!("[Source {}] Decode error: {}", source_id, e);
-                (callbacks.source_status)(source_id, SourceStatus::DecodeError as i32);
+            decode_stream(source_id, stream_info, host, callbacks, stop_signal_decode, pts_timeline, deliver_every_n, frames_decoded_decode)
+        });
+
+        // Spawn a task detecting streams that stay connected but stop delivering frames
+        let frame_timeout = Duration::from_secs(get_stream_config().frame_timeout_secs);
+        let mut watchdog_handle = tokio::spawn(async move {
+            if frame_timeout.is_zero() {
+                return std::future::pending().await;
+            }
+
+            // Armed by the first frame, failing to connect or decode the first frame is reported by the decode task
+            let mut last_count = 0;
+            let mut last_frame = Instant::now();
+            loop {
+                sleep(FRAME_WATCHDOG_INTERVAL).await;
+
+                let count = frames_decoded.load(Ordering::Relaxed);
+                if count != last_count || count == 0 {
+                    last_count = count;
+                    last_frame = Instant::now();
+                } else if last_frame.elapsed() >= frame_timeout {
+                    log_error!("[Source {}] No frames decoded for {:?}, triggering reconnect", source_id, frame_timeout);
+                    return;
+                }
             }
         });
         
-        // Wait for either decode to finish or keepalive to detect stream stopped
-        tokio::select! {
-            _ = &mut decode_handle => {
+        // Wait for either decode to finish, keepalive to detect stream stopped or the watchdog to detect frozen frames
+        let result = tokio::select! {
+            decode_result = &mut decode_handle => {
                 keepalive_handle.abort();
+                watchdog_handle.abort();
+
+                match decode_result {
+                    Ok(result) => return result,
+                    Err(e) => return Err(StreamFailure::new(
+                        SourceStatus::DecodeError,
+                        anyhow::anyhow!("Decode task failed: {}", e)
+                    )),
+                }
             }
             _ = &mut keepalive_handle => {
                 // Keepalive detected stream stopped
-                // Signal decode to stop
-                stop_signal.store(true, Ordering::Relaxed);
-                
-                // Wait for decode task to finish with timeout to ensure cleanup
-                log_info!("[Source {}] Stream stopped, waiting for decode task to cleanup...", source_id);
-                
-                let timeout_result = tokio::time::timeout(
-                    Duration::from_secs(5),
-                    decode_handle
-                ).await;
-                
-                match timeout_result {
-                    Ok(_) => {
-                        log_debug!("[Source {}] Decode task completed cleanup successfully", source_id);
-                    }
-                    Err(_) => {
-                        log_error!("[Source {}] Decode task cleanup timed out after 5s", source_id);
-                    }
-                }
+                watchdog_handle.abort();
+                Ok(())
+            }
+            _ = &mut watchdog_handle => {
+                // Watchdog detected a frozen stream
+                keepalive_handle.abort();
+                Err(StreamFailure::new(
+                    SourceStatus::Stalled,
+                    anyhow::anyhow!("No frames decoded for {:?}", frame_timeout)
+                ))
+            }
+        };
+
+        // Signal decode to stop
+        stop_signal.store(true, Ordering::Relaxed);
+        
+        // Wait for decode task to finish with timeout to ensure cleanup
+        log_info!("[Source {}] Stream stopped, waiting for decode task to cleanup...", source_id);
+        
+        let timeout_result = tokio::time::timeout(
+            Duration::from_secs(5),
+            decode_handle
+        ).await;
+        
+        match timeout_result {
+            Ok(_) => {
+                log_debug!("[Source {}] Decode task completed cleanup successfully", source_id);
+            }
+            Err(_) => {
+                log_error!("[Source {}] Decode task cleanup timed out after 5s", source_id);
             }
         }
 
-        Ok(())
+        result
     }
 }
 
+// Interval of stream status checks, shared by keepalive checks and batch polling
+fn status_poll_interval() -> Duration {
+    Duration::from_secs(get_stream_config().keepalive_interval_secs.max(1))
+}
+
 #[derive(serde::Deserialize)]
 struct VideoInfo {
     pub name: String,
@@ -302,7 +623,10 @@ fn decode_stream(
     host: String,
     callbacks: Callbacks, 
     stop_signal: Arc<AtomicBool>,
-) -> Result<()> {
+    pts_timeline: Arc<Mutex<PtsTimeline>>,
+    deliver_every_n: Arc<AtomicU32>,
+    frames_decoded: Arc<AtomicU64>,
+) -> std::result::Result<(), StreamFailure> {
     // UPDATED: Connect to TCP stream
     let connection_url = format!("tcp://{}:{}", host, stream_info.port);
 
@@ -325,14 +649,18 @@ fn decode_stream(
         match ffmpeg::format::input_with_dictionary(&connection_url, input_opts.clone()) {
             Ok(mut ictx) => {
                 log_info!("[Source {}] Successfully connected to TCP stream", source_id);
+                // New connection restarts the stream PTS base
+                let mut pts_timeline = pts_timeline.lock().unwrap();
+                pts_timeline.start_connection();
+
                 // process_stream will decode, scale to RGB24, and call callbacks
-                let result = process_stream(source_id, &mut ictx, callbacks, stop_signal.clone());
+                let result = process_stream(source_id, &mut ictx, callbacks, stop_signal.clone(), &mut pts_timeline, &deliver_every_n, &frames_decoded);
                 
                 // Explicitly drop the input context to ensure TCP socket is released
                 drop(ictx);
                 log_debug!("[Source {}] FFmpeg input context dropped, TCP connection closed", source_id);
                 
-                return result;
+                return result.map_err(|e| StreamFailure::new(SourceStatus::DecodeError, e));
             }
             Err(e) => {
                 last_error = Some(e);
@@ -345,7 +673,9 @@ fn decode_stream(
     }
     
     // UPDATED: Error message
-    Err(last_error.unwrap()).context(format!("Failed to open TCP stream after 3 attempts"))
+    let error = anyhow::Error::new(last_error.unwrap())
+        .context("Failed to open TCP stream after 3 attempts");
+    Err(StreamFailure::new(SourceStatus::ConnectionError, error))
 }
 
 // This function decodes the mpegts/h264 stream and scales it to RGB24
@@ -354,7 +684,21 @@ fn process_stream(
     ictx: &mut ffmpeg::format::context::Input,
     callbacks: Callbacks,
     stop_signal: Arc<AtomicBool>,
+    pts_timeline: &mut PtsTimeline,
+    deliver_every_n: &AtomicU32,
+    frames_decoded: &AtomicU64,
 ) -> Result<()> {
+    if get_stream_config().decode_all_streams {
+        match callbacks.source_sub_frames {
+            Some(source_sub_frames) => {
+                return process_all_streams(source_id, ictx, callbacks, source_sub_frames, stop_signal, pts_timeline, deliver_every_n, frames_decoded);
+            }
+            None => {
+                log_error!("[Source {}] decode_all_streams is set but no sub-stream callback registered, decoding best stream only", source_id);
+            }
+        }
+    }
+
     let input = ictx
         .streams()
         .best(ffmpeg::media::Type::Video)
@@ -418,36 +762,84 @@ fn process_stream(
         anyhow::bail!("Invalid frame dimensions from ffmpeg: {}x{}", width, height);
     }
 
-    // Create scaler to convert from stream format (e.g., YUV420P) to RGB24
-    let mut scaler = ffmpeg::software::scaling::context::Context::get(
-        format, // Input format from stream
-        width,
-        height,
-        ffmpeg::format::Pixel::RGB24,  // Output format: rgb24
-        width,
-        height,
-        ffmpeg::software::scaling::Flags::BILINEAR,
-    )
-    .context("Failed to create scaler")?;
+    // Scaler converting from stream format (e.g., YUV420P) to RGB24 (or BGR24 when configured),
+    // recreated when the stream changes resolution
+    let mut scaler = FrameScaler::default();
     
     // Process the first frame we already decoded
+    frames_decoded.fetch_add(1, Ordering::Relaxed);
     let mut rgb_frame = ffmpeg::util::frame::video::Video::empty();
-    if scaler.run(&first_frame, &mut rgb_frame).is_ok() {
-        let pts = first_frame.pts().unwrap_or(0);
+    let annotate_frames = get_stream_config().annotate_frames;
+    if scaler.scale(&first_frame, &mut rgb_frame).is_ok() {
+        let pts = pts_timeline.map(first_frame.pts().unwrap_or(0));
+        if annotate_frames {
+            annotate::annotate_frame(source_id, &mut rgb_frame);
+        }
         let data_ptr = rgb_frame.data(0).as_ptr();
         // Callback with RGB24 frame data
-        (callbacks.source_frames)(source_id, data_ptr, width as i32, height as i32, pts as u64);
+        (callbacks.source_frames)(source_id, data_ptr, rgb_frame.width() as i32, rgb_frame.height() as i32, pts);
         
         log_info!("[Source {}] Started receiving frames ({}x{}), PTS: {}", 
-                     source_id, width, height, pts);
+                     source_id, rgb_frame.width(), rgb_frame.height(), pts);
     }
 
     let mut last_pts: Option<i64> = first_frame.pts();
+    let mut decoded_frames: u64 = 0;
+
+    // Scales and emits every frame the decoder has ready
+    let mut emit_frames = |decoder: &mut ffmpeg::codec::decoder::Video| {
+        let mut decoded_frame = ffmpeg::util::frame::video::Video::empty();
+
+        while decoder.receive_frame(&mut decoded_frame).is_ok() {
+            frames_decoded.fetch_add(1, Ordering::Relaxed);
+
+            // Skip frames the consumer asked not to receive, before paying for scaling
+            decoded_frames += 1;
+            if decoded_frames % deliver_every_n.load(Ordering::Relaxed).max(1) as u64 != 0 {
+                continue;
+            }
+            
+            let mut rgb_frame = ffmpeg::util::frame::video::Video::empty();
+            
+            // Scale to RGB24
+            if let Err(e) = scaler.scale(&decoded_frame, &mut rgb_frame) {
+                log_error!("[Source {}] {:#}", source_id, e);
+                continue;
+            }
+
+            // Get PTS - raw value from stream
+            let pts = decoded_frame.pts().unwrap_or(0);
+            
+            if let Some(last) = last_pts {
+                if pts <= last && pts != 0 {
+                    log_debug!("[Source {}] PTS issue detected (last: {}, current: {})", 
+                            source_id, last, pts);
+                }
+            }
+            last_pts = Some(pts);
+
+            // Emitted PTS continues the source timeline across reconnects
+            let pts = pts_timeline.map(pts);
+
+            if annotate_frames {
+                annotate::annotate_frame(source_id, &mut rgb_frame);
+            }
+
+            let width = rgb_frame.width() as i32;
+            let height = rgb_frame.height() as i32;
+            let data_ptr = rgb_frame.data(0).as_ptr();
+
+            // Call frames callback with RGB24 data
+            (callbacks.source_frames)(source_id, data_ptr, width, height, pts);
+        }
+    };
 
     // Continue processing remaining frames
+    let mut stopped = false;
     for (stream, packet) in ictx.packets() {
         if stop_signal.load(Ordering::Relaxed) {
             log_info!("[Source {}] Stop signal received, exiting stream loop", source_id);
+            stopped = true;
             break;
         }
         if stream.index() == video_stream_index {
@@ -457,35 +849,199 @@ fn process_stream(
                 break;
             }
 
-            let mut decoded_frame = ffmpeg::util::frame::video::Video::empty();
-            
-            while decoder.receive_frame(&mut decoded_frame).is_ok() {
-                
-                let mut rgb_frame = ffmpeg::util::frame::video::Video::empty();
-                
-                // Scale to RGB24
-                if let Err(e) = scaler.run(&decoded_frame, &mut rgb_frame) {
-                    log_error!("[Source {}] Scaling error: {}", source_id, e);
-                    continue;
-                }
+            emit_frames(&mut decoder);
+        }
+    }
 
-                // Get PTS - raw value from stream
-                let pts = decoded_frame.pts().unwrap_or(0);
-                
-                if let Some(last) = last_pts {
-                    if pts <= last && pts != 0 {
-                        log_debug!("[Source {}] PTS issue detected (last: {}, current: {})", 
-                                source_id, last, pts);
-                    }
-                }
-                last_pts = Some(pts);
+    // Drain frames still buffered by the decoder, otherwise the last frames of finite inputs are lost
+    if !stopped {
+        match decoder.send_eof() {
+            Ok(_) => emit_frames(&mut decoder),
+            Err(e) => log_error!("[Source {}] Error flushing decoder: {}", source_id, e)
+        }
+    }
 
-                let width = rgb_frame.width() as i32;
-                let height = rgb_frame.height() as i32;
-                let data_ptr = rgb_frame.data(0).as_ptr();
+    // If we exit the loop, stream ended
+    log_info!("[Source {}] Stream ended", source_id);
+    (callbacks.source_stopped)(source_id);
+
+    Ok(())
+}
+
+// Converts decoded frames to RGB24 (or BGR24 when configured), downscaling frames above the maximum decode resolution
+#[derive(Default)]
+struct FrameScaler {
+    // Scaler along with the input dimensions it was created for
+    scaler: Option<(ffmpeg::software::scaling::context::Context, u32, u32)>,
+}
 
-                // Call frames callback with RGB24 data
-                (callbacks.source_frames)(source_id, data_ptr, width, height, pts as u64);
+impl FrameScaler {
+    // Scales a decoded frame, (re)creating the scaler when dimensions change
+    fn scale(
+        &mut self,
+        frame: &ffmpeg::util::frame::video::Video,
+        rgb_frame: &mut ffmpeg::util::frame::video::Video,
+    ) -> Result<()> {
+        let width = frame.width();
+        let height = frame.height();
+
+        let scaler_valid = matches!(&self.scaler, Some((_, w, h)) if *w == width && *h == height);
+        if !scaler_valid {
+            if width == 0 || height == 0 {
+                anyhow::bail!("Invalid frame dimensions from ffmpeg: {}x{}", width, height);
+            }
+
+            let (output_width, output_height) = match get_stream_config().max_decode_resolution {
+                Some(max_resolution) => max_resolution.fit(width, height),
+                None => (width, height),
+            };
+            if (output_width, output_height) != (width, height) {
+                log_info!("Downscaling frames of {}x{} to {}x{}", width, height, output_width, output_height);
+            }
+
+            let scaler = ffmpeg::software::scaling::context::Context::get(
+                frame.format(),
+                width,
+                height,
+                get_stream_config().color_order.pixel(),
+                output_width,
+                output_height,
+                ffmpeg::software::scaling::Flags::BILINEAR,
+            )
+            .context("Failed to create scaler")?;
+
+            self.scaler = Some((scaler, width, height));
+        }
+
+        let (scaler, _, _) = self.scaler.as_mut().unwrap();
+        scaler.run(frame, rgb_frame).context("Scaling error")?;
+        Ok(())
+    }
+}
+
+// Decoder state of a single video stream inside a multi-stream container
+struct SubStreamDecoder {
+    sub_stream: i32,
+    decoder: ffmpeg::codec::decoder::Video,
+    scaler: FrameScaler,
+    decoded_frames: u64,
+}
+
+impl SubStreamDecoder {
+
+    // Scales and emits every frame the decoder has ready
+    fn emit_frames(
+        &mut self,
+        source_id: i32,
+        decoded_frame: &mut ffmpeg::util::frame::video::Video,
+        source_sub_frames: SourceSubFramesCallback,
+        pts_timeline: &mut PtsTimeline,
+        deliver_every_n: &AtomicU32,
+        frames_decoded: &AtomicU64,
+    ) {
+        while self.decoder.receive_frame(decoded_frame).is_ok() {
+            frames_decoded.fetch_add(1, Ordering::Relaxed);
+
+            // Sub-streams honour the same frame skipping as the best stream
+            self.decoded_frames += 1;
+            if self.decoded_frames % deliver_every_n.load(Ordering::Relaxed).max(1) as u64 != 0 {
+                continue;
+            }
+
+            let mut rgb_frame = ffmpeg::util::frame::video::Video::empty();
+
+            if let Err(e) = self.scaler.scale(decoded_frame, &mut rgb_frame) {
+                log_error!("[Source {}][Sub-stream {}] {:#}", source_id, self.sub_stream, e);
+                continue;
+            }
+
+            // Emitted PTS continues the sub-stream timeline across reconnects
+            let pts = pts_timeline.map(decoded_frame.pts().unwrap_or(0));
+            let width = rgb_frame.width() as i32;
+            let height = rgb_frame.height() as i32;
+            let data_ptr = rgb_frame.data(0).as_ptr();
+
+            (source_sub_frames)(source_id, self.sub_stream, data_ptr, width, height, pts);
+        }
+    }
+}
+
+// Decodes every video stream of the container (e.g. MPEG-TS with several programs),
+// emitting RGB24 frames tagged with the sub-stream index - the order of the video stream in the container
+fn process_all_streams(
+    source_id: i32,
+    ictx: &mut ffmpeg::format::context::Input,
+    callbacks: Callbacks,
+    source_sub_frames: SourceSubFramesCallback,
+    stop_signal: Arc<AtomicBool>,
+    pts_timeline: &mut PtsTimeline,
+    deliver_every_n: &AtomicU32,
+    frames_decoded: &AtomicU64,
+) -> Result<()> {
+    // Create a decoder per video stream, keyed by container stream index
+    let mut decoders: HashMap<usize, SubStreamDecoder> = HashMap::new();
+    for stream in ictx.streams() {
+        if stream.parameters().medium() != ffmpeg::media::Type::Video {
+            continue;
+        }
+
+        let decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
+            .context("Failed to create codec context")?
+            .decoder()
+            .video()
+            .with_context(|| format!("Failed to create video decoder for stream {}", stream.index()))?;
+
+        let sub_stream = decoders.len() as i32;
+        log_debug!("[Source {}] Stream {} mapped to sub-stream {}", source_id, stream.index(), sub_stream);
+
+        decoders.insert(stream.index(), SubStreamDecoder {
+            sub_stream,
+            decoder,
+            scaler: FrameScaler::default(),
+            decoded_frames: 0,
+        });
+    }
+
+    if decoders.is_empty() {
+        anyhow::bail!("No video stream found");
+    }
+
+    log_info!("[Source {}] Decoding {} video streams from container", source_id, decoders.len());
+
+    let mut decoded_frame = ffmpeg::util::frame::video::Video::empty();
+
+    let mut stopped = false;
+    for (stream, packet) in ictx.packets() {
+        if stop_signal.load(Ordering::Relaxed) {
+            log_info!("[Source {}] Stop signal received, exiting stream loop", source_id);
+            stopped = true;
+            break;
+        }
+
+        let sub = match decoders.get_mut(&stream.index()) {
+            Some(sub) => sub,
+            None => continue,
+        };
+
+        // A broken sub-stream should not stop the other streams
+        if let Err(e) = sub.decoder.send_packet(&packet) {
+            log_error!("[Source {}][Sub-stream {}] Error sending packet: {}", source_id, sub.sub_stream, e);
+            continue;
+        }
+
+        let sub_timeline = pts_timeline.sub_stream(sub.sub_stream);
+        sub.emit_frames(source_id, &mut decoded_frame, source_sub_frames, sub_timeline, deliver_every_n, frames_decoded);
+    }
+
+    // Drain frames still buffered by every decoder, otherwise the last frames of finite inputs are lost
+    if !stopped {
+        for sub in decoders.values_mut() {
+            match sub.decoder.send_eof() {
+                Ok(_) => {
+                    let sub_timeline = pts_timeline.sub_stream(sub.sub_stream);
+                    sub.emit_frames(source_id, &mut decoded_frame, source_sub_frames, sub_timeline, deliver_every_n, frames_decoded)
+                }
+                Err(e) => log_error!("[Source {}][Sub-stream {}] Error flushing decoder: {}", source_id, sub.sub_stream, e)
             }
         }
     }
@@ -510,3 +1066,89 @@ pub fn init_ffmpeg() -> Result<()> {
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures_of_same_kind() {
+        let mut breaker = CircuitBreaker::new(3);
+
+        assert!(!breaker.record_failure(SourceStatus::ConnectionError));
+        assert!(!breaker.record_failure(SourceStatus::ConnectionError));
+        assert!(breaker.record_failure(SourceStatus::ConnectionError));
+    }
+
+    #[test]
+    fn restarts_count_on_different_failure_or_success() {
+        let mut breaker = CircuitBreaker::new(2);
+
+        assert!(!breaker.record_failure(SourceStatus::ConnectionError));
+        assert!(!breaker.record_failure(SourceStatus::DecodeError));
+        breaker.record_success();
+        assert!(!breaker.record_failure(SourceStatus::DecodeError));
+        assert!(breaker.record_failure(SourceStatus::DecodeError));
+    }
+
+    #[test]
+    fn starts_timeline_at_first_frame_pts() {
+        let mut timeline = PtsTimeline::new();
+        timeline.start_connection();
+
+        assert_eq!(timeline.map(9000), 9000);
+        assert_eq!(timeline.map(12000), 12000);
+    }
+
+    #[test]
+    fn continues_timeline_across_reconnects() {
+        let mut timeline = PtsTimeline::new();
+        timeline.start_connection();
+        timeline.map(1000);
+        assert_eq!(timeline.map(4000), 4000);
+
+        // The new connection restarts its PTS from a lower base
+        timeline.start_connection();
+        assert_eq!(timeline.map(500), 4001);
+        assert_eq!(timeline.map(3500), 7001);
+
+        // ... or jumps far ahead of the previous one
+        timeline.start_connection();
+        assert_eq!(timeline.map(1_000_000), 7002);
+    }
+
+    #[test]
+    fn clamps_negative_pts_and_passes_backwards_pts_within_connection() {
+        let mut timeline = PtsTimeline::new();
+        timeline.start_connection();
+
+        assert_eq!(timeline.map(-3000), 0);
+        assert_eq!(timeline.map(6000), 6000);
+        // Reordered frames of a connection keep their relative PTS
+        assert_eq!(timeline.map(3000), 3000);
+    }
+
+    #[test]
+    fn keeps_separate_timelines_per_sub_stream() {
+        let mut timeline = PtsTimeline::new();
+        timeline.start_connection();
+        timeline.map(100);
+        timeline.sub_stream(0).map(2000);
+        timeline.sub_stream(1).map(7000);
+
+        timeline.start_connection();
+        assert_eq!(timeline.map(0), 101);
+        assert_eq!(timeline.sub_stream(0).map(0), 2001);
+        assert_eq!(timeline.sub_stream(1).map(0), 7001);
+        // A sub-stream first seen after the reconnect starts at its own PTS
+        assert_eq!(timeline.sub_stream(2).map(50), 50);
+    }
+
+    #[test]
+    fn never_opens_with_zero_threshold() {
+        let mut breaker = CircuitBreaker::new(0);
+
+        for _ in 0..100 {
+            assert!(!breaker.record_failure(SourceStatus::NotFound));
+        }
+    }
+}